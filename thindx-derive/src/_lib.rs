@@ -0,0 +1,305 @@
+//! Derive macros for [thindx](https://docs.rs/thindx).  Use these via `thindx`'s `derive` feature rather than depending on this crate directly.
+#![forbid(unsafe_code)]
+#![warn(missing_docs)]
+
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+
+
+
+/// Implement `thindx::d3d11::ConstantBuffer` for a struct with named fields, and generate a padded `#[repr(C)]` `{Name}Hlsl` struct to upload.
+///
+/// See `thindx::d3d11::ConstantBuffer` for documentation of the supported `#[hlsl(...)]` attributes.
+/// Error cases are tested by `thindx` itself, as they require the real `thindx::d3d11` traits to be meaningful.
+#[proc_macro_derive(ConstantBuffer, attributes(hlsl))]
+pub fn derive_constant_buffer(input: TokenStream) -> TokenStream {
+    match constant_buffer(input) {
+        Ok(output)  => output,
+        Err(err)    => err.into_compile_error(),
+    }
+}
+
+fn constant_buffer(input: TokenStream) -> Result<TokenStream, SpannedError> {
+    let mut tokens = input.into_iter().peekable();
+
+    let mut hlsl_name = None;
+    while let Some(attr) = next_attribute(&mut tokens)? {
+        for option in attr.hlsl_options()? {
+            match option {
+                HlslOption::Rename(name, _) => hlsl_name = Some(name),
+                other                       => return Err(SpannedError::new(other.span(), "only #[hlsl(rename = \"...\")] is supported on structs")),
+            }
+        }
+    }
+
+    let mut struct_span = Span::call_site();
+    let mut vis = TokenStream::new();
+    loop {
+        match tokens.next() {
+            Some(TokenTree::Ident(i)) if i.to_string() == "struct" => { struct_span = i.span(); break }
+            Some(TokenTree::Ident(i)) if i.to_string() == "enum" || i.to_string() == "union" => return Err(SpannedError::new(i.span(), "#[derive(ConstantBuffer)] only supports structs")),
+            Some(other) => vis.extend(Some(other)),
+            None    => return Err(SpannedError::new(struct_span, "expected `struct`")),
+        }
+    }
+
+    let name = match tokens.next() {
+        Some(TokenTree::Ident(name))    => name,
+        other                           => return Err(SpannedError::new(span_of(other.as_ref(), struct_span), "expected struct name")),
+    };
+    let body = match tokens.next() {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Brace  => g,
+        Some(TokenTree::Punct(p)) if p.as_char() == '<'                 => return Err(SpannedError::new(p.span(), "#[derive(ConstantBuffer)] doesn't support generic structs")),
+        other                                                           => return Err(SpannedError::new(span_of(other.as_ref(), name.span()), "#[derive(ConstantBuffer)] requires a struct with named fields")),
+    };
+
+    let fields = parse_fields(body.stream())?;
+    let hlsl_name = hlsl_name.unwrap_or_else(|| name.to_string());
+
+    let hlsl = format!("{}Hlsl", name);
+    let cb = format!("<{} as ::thindx::d3d11::ConstantBuffer>", name);
+    let mut layouts = String::new();
+    let mut writes  = String::new();
+    let mut members = String::new();
+    let mut zeroed  = String::new();
+    let mut index   = 0;
+    for field in fields.iter().filter(|f| !f.skip) {
+        layouts += &format!("({}, <{} as ::thindx::d3d11::HlslType>::LAYOUT{}),\n", Literal::string(&field.hlsl_name), field.ty, field.conversion.unwrap_or(""));
+        writes  += &format!("fields[{}].write(&self.{}, ::core::convert::AsMut::<[u8]>::as_mut(&mut hlsl));\n", index, field.name);
+        members += &format!("_pad{i}: [u8; ::thindx::d3d11::ConstantBufferField::padding_before({cb}::FIELDS, {i}) as usize],\n", i = index, cb = cb);
+        members += &format!("#[doc = {}] pub {}: [u8; {}::FIELDS[{}].layout.size() as usize],\n", Literal::string(&format!("`{}`, packed according to HLSL's rules", field.hlsl_name)), field.name, cb, index);
+        zeroed  += &format!("_pad{}: ::core::array::from_fn(|_| 0), {}: ::core::array::from_fn(|_| 0),\n", index, field.name);
+        index += 1;
+    }
+    members += &format!("_pad{i}: [u8; ::thindx::d3d11::ConstantBufferField::padding_before({cb}::FIELDS, {i}) as usize],\n", i = index, cb = cb);
+    zeroed  += &format!("_pad{}: ::core::array::from_fn(|_| 0),\n", index);
+
+    let output = format!(r#"
+        impl ::thindx::d3d11::ConstantBuffer for {name} {{
+            const NAME : &'static str = {hlsl_name};
+            const FIELDS : &'static [::thindx::d3d11::ConstantBufferField] = &::thindx::d3d11::ConstantBufferField::pack([
+                {layouts}
+            ]);
+            type Hlsl = {hlsl};
+            fn to_hlsl(&self) -> Self::Hlsl {{
+                let fields = <Self as ::thindx::d3d11::ConstantBuffer>::FIELDS;
+                let mut hlsl = <Self::Hlsl as ::core::default::Default>::default();
+                {writes}
+                hlsl
+            }}
+        }}
+
+        #[doc = {hlsl_doc}]
+        #[derive(Clone, Copy, PartialEq, Eq)]
+        #[repr(C, align(16))] {vis} struct {hlsl} {{
+            {members}
+        }}
+        const _ : () = assert!(::core::mem::size_of::<{hlsl}>() == {cb}::SIZE as usize);
+
+        impl ::core::default::Default for {hlsl} {{
+            fn default() -> Self {{ Self {{ {zeroed} }} }}
+        }}
+
+        impl ::core::convert::AsRef<[u8]> for {hlsl} {{
+            fn as_ref(&self) -> &[u8] {{
+                // SAFETY: ✔️ `#[repr(C)]`, consisting solely of `[u8; N]`s, and asserted to have no tail padding
+                unsafe {{ ::core::slice::from_raw_parts((self as *const Self).cast::<u8>(), ::core::mem::size_of::<Self>()) }}
+            }}
+        }}
+
+        impl ::core::convert::AsMut<[u8]> for {hlsl} {{
+            fn as_mut(&mut self) -> &mut [u8] {{
+                // SAFETY: ✔️ `#[repr(C)]`, consisting solely of `[u8; N]`s, and asserted to have no tail padding
+                unsafe {{ ::core::slice::from_raw_parts_mut((self as *mut Self).cast::<u8>(), ::core::mem::size_of::<Self>()) }}
+            }}
+        }}
+    "#,
+        name = name, hlsl = hlsl, cb = cb, vis = vis, hlsl_name = Literal::string(&hlsl_name),
+        hlsl_doc = Literal::string(&format!("`cbuffer {}`, packed according to HLSL's rules.  Generated by `#[derive(ConstantBuffer)]` for [`{}`].", hlsl_name, name)),
+        layouts = layouts, writes = writes, members = members, zeroed = zeroed,
+    );
+    Ok(output.parse().expect("#[derive(ConstantBuffer)] generated invalid tokens"))
+}
+
+struct Field {
+    name:       String,
+    hlsl_name:  String,
+    ty:         String,
+    conversion: Option<&'static str>,
+    skip:       bool,
+}
+
+fn parse_fields(body: TokenStream) -> Result<Vec<Field>, SpannedError> {
+    let mut fields = Vec::new();
+    let mut tokens = body.into_iter().peekable();
+    loop {
+        let mut rename      = None;
+        let mut conversion  = None;
+        let mut skip        = false;
+        while let Some(attr) = next_attribute(&mut tokens)? {
+            for option in attr.hlsl_options()? {
+                match option {
+                    HlslOption::Rename(name, _)         => rename = Some(name),
+                    HlslOption::Skip(_)                 => skip = true,
+                    HlslOption::Conversion(c, span)     => {
+                        if conversion.is_some() { return Err(SpannedError::new(span, "only one of #[hlsl(array)], #[hlsl(row_major)], or #[hlsl(column_major)] may be specified")) }
+                        conversion = Some(c);
+                    },
+                }
+            }
+        }
+
+        // visibility
+        if let Some(TokenTree::Ident(i)) = tokens.peek() {
+            if i.to_string() == "pub" {
+                tokens.next();
+                if let Some(TokenTree::Group(g)) = tokens.peek() { if g.delimiter() == Delimiter::Parenthesis { tokens.next(); } }
+            }
+        }
+
+        let name = match tokens.next() {
+            None                            => break,
+            Some(TokenTree::Ident(name))    => name,
+            Some(other)                     => return Err(SpannedError::new(other.span(), "expected field name")),
+        };
+        match tokens.next() {
+            Some(TokenTree::Punct(p)) if p.as_char() == ':' => {},
+            other => return Err(SpannedError::new(span_of(other.as_ref(), name.span()), "expected `:`")),
+        }
+
+        let mut ty = TokenStream::new();
+        let mut angle_depth = 0;
+        let mut arrow = false; // `->` shouldn't close a `<`
+        for token in tokens.by_ref() {
+            if let TokenTree::Punct(p) = &token {
+                match p.as_char() {
+                    ',' if angle_depth == 0 => break,
+                    '<'                     => angle_depth += 1,
+                    '>' if !arrow           => angle_depth -= 1,
+                    _                       => {},
+                }
+                arrow = p.as_char() == '-' && p.spacing() == Spacing::Joint;
+            } else {
+                arrow = false;
+            }
+            ty.extend(Some(token));
+        }
+
+        let name = name.to_string();
+        let hlsl_name = rename.unwrap_or_else(|| name.strip_prefix("r#").unwrap_or(&name).to_string());
+        fields.push(Field { name, hlsl_name, ty: ty.to_string(), conversion, skip });
+    }
+    Ok(fields)
+}
+
+
+
+struct Attribute(Group);
+
+enum HlslOption {
+    Rename(String, Span),
+    Conversion(&'static str, Span),
+    Skip(Span),
+}
+
+impl HlslOption {
+    fn span(&self) -> Span {
+        match *self {
+            HlslOption::Rename(_, span) | HlslOption::Conversion(_, span) | HlslOption::Skip(span) => span,
+        }
+    }
+}
+
+/// Parse `#[...]` if it's next
+fn next_attribute(tokens: &mut std::iter::Peekable<impl Iterator<Item = TokenTree>>) -> Result<Option<Attribute>, SpannedError> {
+    match tokens.peek() {
+        Some(TokenTree::Punct(p)) if p.as_char() == '#' => {},
+        _ => return Ok(None),
+    }
+    let hash = tokens.next().unwrap();
+    match tokens.next() {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Bracket => Ok(Some(Attribute(g))),
+        other => Err(SpannedError::new(span_of(other.as_ref(), hash.span()), "expected `[` after `#`")),
+    }
+}
+
+impl Attribute {
+    /// Parse the comma separated options of `#[hlsl(...)]`, or return nothing for other attributes
+    fn hlsl_options(&self) -> Result<Vec<HlslOption>, SpannedError> {
+        let mut tokens = self.0.stream().into_iter();
+        let args = match (tokens.next(), tokens.next()) {
+            (Some(TokenTree::Ident(i)), Some(TokenTree::Group(g))) if i.to_string() == "hlsl" && g.delimiter() == Delimiter::Parenthesis => g,
+            (Some(TokenTree::Ident(i)), _) if i.to_string() == "hlsl" => return Err(SpannedError::new(i.span(), "expected #[hlsl(...)]")),
+            _ => return Ok(Vec::new()),
+        };
+
+        let mut options = Vec::new();
+        let mut tokens = args.stream().into_iter().peekable();
+        while let Some(token) = tokens.next() {
+            let ident = match token {
+                TokenTree::Ident(i) => i,
+                other               => return Err(SpannedError::new(other.span(), "expected hlsl option")),
+            };
+            let span = ident.span();
+            options.push(match ident.to_string().as_str() {
+                "array"         => HlslOption::Conversion(".array()", span),
+                "row_major"     => HlslOption::Conversion(".row_major()", span),
+                "column_major"  => HlslOption::Conversion(".column_major()", span),
+                "skip"          => HlslOption::Skip(span),
+                "rename"        => {
+                    match tokens.next() {
+                        Some(TokenTree::Punct(p)) if p.as_char() == '=' => {},
+                        other => return Err(SpannedError::new(span_of(other.as_ref(), span), "expected `rename = \"...\"`")),
+                    }
+                    match tokens.next() {
+                        Some(TokenTree::Literal(l)) => HlslOption::Rename(unquote(&l)?, l.span()),
+                        other => return Err(SpannedError::new(span_of(other.as_ref(), span), "expected `rename = \"...\"`")),
+                    }
+                },
+                _ => return Err(SpannedError::new(span, "unknown hlsl option: expected one of `array`, `row_major`, `column_major`, `rename`, or `skip`")),
+            });
+            match tokens.next() {
+                None                                            => break,
+                Some(TokenTree::Punct(p)) if p.as_char() == ',' => continue,
+                Some(other)                                     => return Err(SpannedError::new(other.span(), "expected `,`")),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Parse a plain string literal (no escapes, which are pointless for HLSL identifiers)
+fn unquote(l: &Literal) -> Result<String, SpannedError> {
+    let s = l.to_string();
+    match s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        Some(s) if !s.contains('\\') => Ok(s.to_string()),
+        _ => Err(SpannedError::new(l.span(), "expected a plain string literal")),
+    }
+}
+
+fn span_of(token: Option<&TokenTree>, fallback: Span) -> Span { token.map_or(fallback, |t| t.span()) }
+
+
+
+struct SpannedError { span: Span, message: &'static str }
+
+impl SpannedError {
+    fn new(span: Span, message: &'static str) -> Self { Self { span, message } }
+
+    /// `compile_error!("...")`, spanned to point at the problem
+    fn into_compile_error(self) -> TokenStream {
+        let mut bang = Punct::new('!', Spacing::Alone);
+        bang.set_span(self.span);
+        let mut message = Literal::string(self.message);
+        message.set_span(self.span);
+        let mut args = Group::new(Delimiter::Parenthesis, TokenStream::from(TokenTree::Literal(message)));
+        args.set_span(self.span);
+        let mut semi = Punct::new(';', Spacing::Alone);
+        semi.set_span(self.span);
+        [
+            TokenTree::Ident(Ident::new("compile_error", self.span)),
+            TokenTree::Punct(bang),
+            TokenTree::Group(args),
+            TokenTree::Punct(semi),
+        ].into_iter().collect()
+    }
+}
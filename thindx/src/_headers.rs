@@ -83,7 +83,7 @@ pub const unknwn_h : cxx_header = cxx_header;
 ///
 /// ### C++ Structs -> Rust Structs
 ///
-/// [`D3D_SHADER_MACRO`](https://learn.microsoft.com/en-us/windows/win32/api/d3dcommon/ns-d3dcommon-d3d_shader_macro)&nbsp;→ <code>trait [d3d::AsShaderMacros](d3d::AsShaderMacros)</code> <br>
+/// [`D3D_SHADER_MACRO`](https://learn.microsoft.com/en-us/windows/win32/api/d3dcommon/ns-d3dcommon-d3d_shader_macro)&nbsp;→ <code>trait [d3d::AsShaderMacros](d3d::AsShaderMacros)</code>, <code>struct [d3d::ShaderMacros](d3d::ShaderMacros)</code> <br>
 /// ### C++ Enums → Rust Structs
 ///
 /// [`D3D_CBUFFER_TYPE`](https://learn.microsoft.com/en-us/windows/win32/api/d3dcommon/ne-d3dcommon-d3d_cbuffer_type)&nbsp;→ [`d3d::CBufferType`] <br>
@@ -13,6 +13,7 @@
 
 pub extern crate abibool;
 pub extern crate abistr;
+#[cfg(all(test, feature = "derive"))] extern crate self as thindx; // for `#[derive(ConstantBuffer)]` tests
 
 #[doc(no_inline)] pub use thindx_xaudio2::{xaudio2_8, xaudio2_9};
 #[doc(hidden)] pub use abistr::cstr;
@@ -21,6 +22,7 @@ pub extern crate abistr;
 
 #[macro_use] mod macros;
 #[macro_use] mod error_macros;
+#[cfg(test)] mod test_fixtures;
 
 /// C ABI interop types
 #[path="ctypes/_ctypes.rs"] pub mod ctypes;
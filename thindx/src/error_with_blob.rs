@@ -16,6 +16,9 @@ impl ErrorWithBlob {
     /// Returns the corresponding [ErrorKind] for this error.
     pub fn kind(&self) -> ErrorKind { self.error.kind() }
 
+    /// Parse the error blob as structured compiler diagnostics.  See [Diagnostic::parse_all] for details.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> { self.errors.diagnostics() }
+
     pub(crate) fn method(&self) -> &'static str { self.error.method() }
 }
 
@@ -72,6 +72,11 @@ enumish! { ShVer => D3D11_SHADER_VERSION_TYPE; PixelShader, VertexShader, Geomet
 #[repr(transparent)] pub struct ShaderVersion(u32);
 
 impl ShaderVersion {
+    /// Construct a shader version from a [ShaderVersionType] and major/minor version numbers (e.g. `ps_4_1`)
+    pub const fn new(ty: ShaderVersionType, major: u32, minor: u32) -> Self {
+        Self(((ty.into_inner() & 0xFFFF) << 16) | ((major & 0xF) << 4) | ((minor & 0xF) << 0))
+    }
+
     /// D3D11_SHVER_GET_TYPE
     pub fn ty(&self) -> ShaderVersionType { ShaderVersionType((self.0 >> 16) & 0xFFFF) }
 
@@ -97,6 +102,8 @@ impl Debug for ShaderVersion {
     }
 }
 
+serde_transparent!(ShaderVersion);
+
 //#cpp2rust D3D11_SHVER_GET_TYPE            = d3d11::ShaderVersion::ty
 //#cpp2rust D3D11_SHVER_GET_MAJOR           = d3d11::ShaderVersion::major
 //#cpp2rust D3D11_SHVER_GET_MINOR           = d3d11::ShaderVersion::minor
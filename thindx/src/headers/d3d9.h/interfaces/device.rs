@@ -511,6 +511,7 @@ pub trait IDirect3DDevice9Ext : AsSafe<IDirect3DDevice9> + Sized {
         if mips.is_empty()  { return Err(fn_param_error!(mips, D3DERR::INVALIDCALL)); } // 0 levels = autogenerate mips, which is different from no levels
 
         let levels          = fn_param_try_len32!(mips)?;
+        if levels > 32 - size.leading_zeros() { return Err(fn_param_error!(mips, D3DERR::INVALIDCALL)); } // more mips than the full chain down to 1x1
         let usage           = usage.into();
         let pool            = pool.into();
         let texture         = self.create_cube_texture(size, levels, usage, format.format, pool, shared_handle)?;
@@ -521,8 +522,6 @@ pub trait IDirect3DDevice9Ext : AsSafe<IDirect3DDevice9> + Sized {
 
         let mut mip_pixels_size = size;
         for (mip_level, mip_ref) in mips.iter().enumerate() {
-            assert!(mip_pixels_size != 0, "too many mips"); // should this bail out instead perhaps?
-
             let mip_level           = mip_level as u32; // safe: mip_level < mips.len() == levels <= u32::MAX
             let mip_blocks_width    = (mip_pixels_size + block_width  - 1) / block_width;
             let mip_blocks_height   = (mip_pixels_size + block_height - 1) / block_height;
@@ -787,6 +786,7 @@ pub trait IDirect3DDevice9Ext : AsSafe<IDirect3DDevice9> + Sized {
         if mips.is_empty()  { return Err(fn_param_error!(mips,   D3DERR::INVALIDCALL)); } // 0 levels = autogenerate mips, which is different from no levels
 
         let levels          = fn_param_try_len32!(mips)?;
+        if levels > 32 - width.max(height).leading_zeros() { return Err(fn_param_error!(mips, D3DERR::INVALIDCALL)); } // more mips than the full chain down to 1x1
         let usage           = usage.into();
         let pool            = pool.into();
         let texture         = self.create_texture(width, height, levels, usage, format.format, pool, _shared_handle)?;
@@ -798,7 +798,6 @@ pub trait IDirect3DDevice9Ext : AsSafe<IDirect3DDevice9> + Sized {
         let mut mip_pixels_width    = width;
         let mut mip_pixels_height   = height;
         for (mip_level, mip_ref) in mips.iter().enumerate() {
-            assert!(mip_pixels_width != 0 || mip_pixels_height != 0, "too many mips"); // should this bail out instead perhaps?
             mip_pixels_width    = mip_pixels_width.max(1);
             mip_pixels_height   = mip_pixels_height.max(1);
 
@@ -1032,6 +1031,7 @@ pub trait IDirect3DDevice9Ext : AsSafe<IDirect3DDevice9> + Sized {
         if mips.is_empty()  { return Err(fn_param_error!(mips,   D3DERR::INVALIDCALL)); } // 0 levels = autogenerate mips, which is different from no levels
 
         let levels          = fn_param_try_len32!(mips)?;
+        if levels > 32 - width.max(height).max(depth).leading_zeros() { return Err(fn_param_error!(mips, D3DERR::INVALIDCALL)); } // more mips than the full chain down to 1x1
         let usage           = usage.into();
         let pool            = pool.into();
         let texture         = self.create_volume_texture(width, height, depth, levels, usage, format.format, pool, _shared_handle)?;
@@ -1044,7 +1044,6 @@ pub trait IDirect3DDevice9Ext : AsSafe<IDirect3DDevice9> + Sized {
         let mut mip_pixels_height   = height;
         let mut mip_pixels_depth    = depth;
         for (mip_level, mip_ref) in mips.iter().enumerate() {
-            assert!(mip_pixels_width != 0 || mip_pixels_height != 0 || mip_pixels_depth != 0, "too many mips"); // should this bail out instead perhaps?
             mip_pixels_width    = mip_pixels_width.max(1);
             mip_pixels_height   = mip_pixels_height.max(1);
             mip_pixels_depth    = mip_pixels_depth.max(1);
@@ -73,7 +73,13 @@ use std::fmt::{self, Debug, Formatter};
 /// | [`Format::DXT4`]          | 128  | 4x4    | BC3 | Gradient, premul
 /// | [`Format::DXT5`]          | 128  | 4x4    | BC3 | Gradient, non-premul
 ///
+/// | Vendor [FOURCC] Formats   | Bits/Block | Block  | BC# | Channels |
+/// | ------------------------- | ---- | ------ | --- | ----- |
+/// | [`Format::ATI1`]          |  64  | 4x4    | BC4 | R
+/// | [`Format::ATI2`]          | 128  | 4x4    | BC5 | RG
+///
 /// [X3TC]:             https://en.wikipedia.org/wiki/X3_Texture_Compression
+/// [FOURCC]:           https://en.wikipedia.org/wiki/FourCC
 ///
 /// <h3>Depth/Stencil Formats</h3>
 ///
@@ -259,6 +265,16 @@ impl Format {
     ///
     /// Direct3D 9Ex Only
     pub const BinaryBuffer          : Format = Format::BINARYBUFFER;
+
+    /// `MAKEFOURCC('A','T','I','1')` aka BC4:  a single channel, 4x4 block compressed format.
+    ///
+    /// Not part of `D3DFORMAT`, but a widely supported driver extension.  Check [IDirect3D9Ext::check_device_format](crate::d3d9::IDirect3D9Ext::check_device_format) before use.
+    pub const ATI1                  : Format = Format(u32::from_le_bytes(*b"ATI1"));
+
+    /// `MAKEFOURCC('A','T','I','2')` aka BC5 aka 3Dc:  a two channel, 4x4 block compressed format, commonly used for normal maps.
+    ///
+    /// Not part of `D3DFORMAT`, but a widely supported driver extension.  Check [IDirect3D9Ext::check_device_format](crate::d3d9::IDirect3D9Ext::check_device_format) before use.
+    pub const ATI2                  : Format = Format(u32::from_le_bytes(*b"ATI2"));
 }
 
 impl Format {
@@ -334,6 +350,9 @@ impl Format {
             Format::A2B10G10R10_XR_BIAS                         => "D3DFMT_A2B10G10R10_XR_BIAS",
             Format::BINARYBUFFER                                => "D3DFMT_BINARYBUFFER",
 
+            Format::ATI1                                        => "MAKEFOURCC('A','T','I','1')",
+            Format::ATI2                                        => "MAKEFOURCC('A','T','I','2')",
+
             _other                                              => "D3DFMT_???",
         }
     }
@@ -56,14 +56,14 @@ impl ShaderVersion {
     pub const PS_1_3 : ShaderVersion = ShaderVersion::ps(1, 3);
     pub const PS_1_4 : ShaderVersion = ShaderVersion::ps(1, 4);
     pub const PS_2_0 : ShaderVersion = ShaderVersion::ps(2, 0);
-    pub const PS_3_0 : ShaderVersion = ShaderVersion::ps(2, 0);
+    pub const PS_3_0 : ShaderVersion = ShaderVersion::ps(3, 0);
 
     // all valid vertex shader versions per
     // <https://learn.microsoft.com/en-us/windows/win32/direct3d9/d3dvs-version#remarks>
 
     pub const VS_1_1 : ShaderVersion = ShaderVersion::vs(1, 1);
-    pub const VS_2_0 : ShaderVersion = ShaderVersion::vs(1, 0);
-    pub const VS_3_0 : ShaderVersion = ShaderVersion::vs(1, 0);
+    pub const VS_2_0 : ShaderVersion = ShaderVersion::vs(2, 0);
+    pub const VS_3_0 : ShaderVersion = ShaderVersion::vs(3, 0);
 }
 
 impl Debug for ShaderVersion {
@@ -77,6 +77,12 @@ impl Debug for ShaderVersion {
     }
 }
 
+#[test] fn constants() {
+    assert_eq!(format!("{:?}", ShaderVersion::PS_3_0), "ShaderVersion::PS_3_0");
+    assert_eq!(format!("{:?}", ShaderVersion::VS_2_0), "ShaderVersion::VS_2_0");
+    assert_eq!(format!("{:?}", ShaderVersion::VS_3_0), "ShaderVersion::VS_3_0");
+}
+
 //#cpp2rust D3DPS_VERSION           = d3d9::ShaderVersion::ps
 //#cpp2rust D3DVS_VERSION           = d3d9::ShaderVersion::vs
 //#cpp2rust D3DSHADER_VERSION_MAJOR = d3d9::ShaderVersion::version_major
@@ -99,7 +99,8 @@ impl TextBlob {
     /// Treat the blob as a UTF8 string, returning a [std::str::Utf8Error] if it's not valid UTF8.
     pub fn to_utf8(&self) -> Result<&str, Utf8Error> { std::str::from_utf8(self.as_bytes()) }
 
-    // TODO: parsing diagnostics iterator?
+    /// Parse the blob as d3dcompiler errors/warnings.  See [d3d::Diagnostic::parse_all] for details.
+    pub fn diagnostics(&self) -> Vec<d3d::Diagnostic> { d3d::Diagnostic::parse_all(&self.to_utf8_lossy()) }
 }
 
 impl Debug   for TextBlob { fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { write!(fmt, "TextBlob({:?})", self.to_utf8_lossy()) } }
@@ -67,10 +67,10 @@ impl Bytecode {
     ///
     /// [Bytecode::from_unchecked] performs no validation.  Prefer [Bytecode::from] which at least performs some.
     //#allow_missing_argument_docs
-    pub unsafe fn from_unchecked(bytecode: &[u8]) -> &Self { unsafe { std::mem::transmute(bytecode) } }
+    pub const unsafe fn from_unchecked(bytecode: &[u8]) -> &Self { unsafe { std::mem::transmute(bytecode) } }
 
     /// Get the bytecode as a slice of bytes.
-    pub fn as_bytes(&self) -> &[u8] { self.0.as_ref() }
+    pub const fn as_bytes(&self) -> &[u8] { &self.0 }
 
     /// Get the bytecode as an iterator of bytes.
     pub fn bytes(&self) -> impl Iterator<Item = u8> + '_ { self.0.as_ref().iter().copied() }
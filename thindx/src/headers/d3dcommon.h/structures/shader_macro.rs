@@ -2,6 +2,8 @@ use crate::ErrorKind;
 
 use winapi::um::d3dcommon::D3D_SHADER_MACRO;
 
+use std::ffi::{CStr, CString};
+use std::fmt::{self, Debug, Formatter};
 use std::ptr::*;
 
 
@@ -30,4 +32,109 @@ unsafe impl AsShaderMacros for Option<std::convert::Infallible> {
     fn as_shader_macros(&self) -> Result<*const D3D_SHADER_MACRO, ErrorKind> { Ok(null()) }
 }
 
+unsafe impl<SM: AsShaderMacros> AsShaderMacros for &SM {
+    fn as_shader_macros(&self) -> Result<*const D3D_SHADER_MACRO, ErrorKind> { (**self).as_shader_macros() }
+}
+
+
+
+/// An owned, `\0`-terminated D3D_SHADER_MACRO\[\] array of `#define`s.
+///
+/// ### Examples
+/// ```rust
+/// # use thindx::d3d::*; let d3dc = Compiler::load_system(47).unwrap();
+/// let mut defines = ShaderMacros::new();
+/// defines.define("USE_FOG", "1").unwrap();
+/// defines.define("LIGHT_COUNT", "4").unwrap();
+/// assert_eq!(defines.len(), 2);
+///
+/// let basic_hlsl = std::fs::read(r"test\data\basic.hlsl").unwrap();
+/// let pixel_shader = d3dc.compile(
+///     &basic_hlsl, r"test\data\basic.hlsl", &defines, None, "ps_main", "ps_4_0",
+///     Compile::Debug, CompileEffect::None,
+/// ).unwrap();
+/// ```
+#[derive(Default)]
+pub struct ShaderMacros {
+    strings:    Vec<(CString, CString)>,
+    macros:     Vec<D3D_SHADER_MACRO>, // empty, or strings.len()+1 entries pointing into `strings`
+}
+
+// SAFETY: ✔️ `macros` only contains pointers into the heap allocations of `strings`, which are never mutated while shared.
+// Those allocations don't move when `strings` itself reallocates, so `macros` never needs re-pointing.
+unsafe impl Send for ShaderMacros {}
+unsafe impl Sync for ShaderMacros {}
+
+impl ShaderMacros {
+    /// Create an empty set of `#define`s.
+    pub fn new() -> Self { Self::default() }
+
+    /// Add `#define {name} {definition}`.
+    ///
+    /// ### Errors
+    /// *   [THINERR::STRING_CONTAINS_NULS](crate::THINERR::STRING_CONTAINS_NULS)    - if `name` or `definition` contain interior `\0`s
+    pub fn define(&mut self, name: impl Into<Vec<u8>>, definition: impl Into<Vec<u8>>) -> Result<(), ErrorKind> {
+        let name        = CString::new(name)?;
+        let definition  = CString::new(definition)?;
+        self.macros.pop(); // terminator
+        self.macros.push(entry(&name, &definition));
+        self.macros.push(TERMINATOR);
+        self.strings.push((name, definition));
+        Ok(())
+    }
+
+    /// The number of `#define`s.
+    pub fn len(&self) -> usize { self.strings.len() }
+
+    /// `true` if there are no `#define`s.
+    pub fn is_empty(&self) -> bool { self.strings.is_empty() }
+
+    /// Iterate over `(name, definition)` pairs.
+    pub fn iter(&self) -> impl Iterator<Item = (&CStr, &CStr)> { self.strings.iter().map(|(n, d)| (&**n, &**d)) }
+}
+
+impl Clone for ShaderMacros {
+    fn clone(&self) -> Self {
+        let strings = self.strings.clone();
+        let mut macros = Vec::with_capacity(self.macros.len());
+        macros.extend(strings.iter().map(|(n, d)| entry(n, d)));
+        if !strings.is_empty() { macros.push(TERMINATOR) }
+        Self { strings, macros }
+    }
+}
+
+impl Debug for ShaderMacros {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_map().entries(self.strings.iter().map(|(n, d)| (n.to_string_lossy(), d.to_string_lossy()))).finish()
+    }
+}
+
+unsafe impl AsShaderMacros for ShaderMacros {
+    fn as_shader_macros(&self) -> Result<*const D3D_SHADER_MACRO, ErrorKind> {
+        Ok(if self.macros.is_empty() { null() } else { self.macros.as_ptr() })
+    }
+}
+
+const TERMINATOR : D3D_SHADER_MACRO = D3D_SHADER_MACRO { Name: null(), Definition: null() };
+
+fn entry(name: &CStr, definition: &CStr) -> D3D_SHADER_MACRO { D3D_SHADER_MACRO { Name: name.as_ptr(), Definition: definition.as_ptr() } }
+
 //#cpp2rust D3D_SHADER_MACRO = trait d3d::AsShaderMacros
+//#cpp2rust D3D_SHADER_MACRO = struct d3d::ShaderMacros
+
+
+
+#[test] fn define_and_clone() {
+    let mut defines = ShaderMacros::new();
+    assert!(defines.as_shader_macros().unwrap().is_null());
+    for i in 0 .. 100 { defines.define(format!("M{i}"), i.to_string()).unwrap() }
+    let copy = defines.clone();
+    for m in [&defines, &copy] {
+        assert_eq!(m.macros.len(), 101);
+        for (i, (entry, (name, definition))) in m.macros.iter().zip(m.strings.iter()).enumerate() {
+            assert_eq!((entry.Name, entry.Definition), (name.as_ptr(), definition.as_ptr()), "macro {i}");
+        }
+        assert!(m.macros[100].Name.is_null() && m.macros[100].Definition.is_null());
+    }
+    assert_ne!(defines.macros[0].Name, copy.macros[0].Name);
+}
@@ -213,6 +213,8 @@ macro_rules! enumish {
             fn from(value: $d3d) -> Self { Self(value as _) }
         }
 
+        serde_transparent!($enumish);
+
         impl $enumish {
             /// Initialize to 0.
             pub const fn zeroed() -> Self { Self(0) }
@@ -306,9 +308,29 @@ macro_rules! flags {
             type Output = Self;
             fn bitor(self, other: Self) -> Self { Self(self.0 | other.0) }
         }
+
+        serde_transparent!($flagish);
     }
 }
 
+/// Implement `serde::{Serialize, Deserialize}` (if the `serde` feature is enabled) for a newtype, as its inner value.
+///
+/// ### Usage
+/// ```ignore
+/// serde_transparent!(RustyNewtype);
+/// ```
+macro_rules! serde_transparent {
+    ( $newtype:ty ) => {
+        #[cfg(feature = "serde")] impl serde::Serialize for $newtype {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> { serde::Serialize::serialize(&self.0, serializer) }
+        }
+
+        #[cfg(feature = "serde")] impl<'de> serde::Deserialize<'de> for $newtype {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> { serde::Deserialize::deserialize(deserializer).map(Self) }
+        }
+    };
+}
+
 /// COM conversion boilerplate
 ///
 /// ### ⚠️ Safety ⚠️
@@ -13,3 +13,16 @@ pub use crate::d3d9types_h::*; // TODO: exclude d3d9::ShaderVersion (conflicts w
 pub type Range = std::ops::Range<u32>; // TODO: make a Pod/Zeroable/??? struct ala other d3d9 structs?
 
 //#cpp2rust D3DRANGE = d3d::Range
+
+mods! {
+    inl mod diagnostic;
+    inl mod effect_binary;
+    inl mod format_info;
+    inl mod half;
+    inl mod hlsl_declarations;
+    inl mod include_bytecode;
+    inl mod shader_batch;
+    inl mod shader_target;
+    inl mod shader_variants;
+    inl mod shader_watcher;
+}
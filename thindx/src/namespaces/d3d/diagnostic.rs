@@ -0,0 +1,377 @@
+use std::fmt::{self, Debug, Display, Formatter};
+use std::ops::RangeInclusive;
+
+
+
+/// A single structured diagnostic parsed from d3dcompiler error/warning text.
+///
+/// d3dcompiler reports diagnostics as lines of text such as:
+/// ```text
+/// C:\local\thindx\test\data\basic.hlsl(12,5-9): error X3004: undeclared identifier 'colour'
+/// C:\local\thindx\test\data\basic.hlsl(17,15-27): warning X3206: implicit truncation of vector type
+/// error X3501: 'main': entrypoint not found
+/// compilation failed; no code produced
+/// ```
+///
+/// [Diagnostic::parse_all] splits such text into [Diagnostic]s, so editor integrations and CI annotations don't need to
+/// scrape the text themselves.  The text is typically sourced from:
+/// *   [d3d::CompileResult::errors](crate::d3d::CompileResult::errors) (warnings from successful compiles)
+/// *   [d3d::CompileError::errors](crate::d3d::CompileError::errors)
+/// *   [d3d::PreprocessError::errors](crate::d3d::PreprocessError::errors)
+/// *   [ErrorWithBlob](crate::ErrorWithBlob) (e.g. from [d3d11::Linker::link](crate::d3d11::Linker::link))
+///
+/// ### Examples
+/// ```rust
+/// # use thindx::d3d::*;
+/// let text = "shader.hlsl(12,5-9): error X3004: undeclared identifier 'colour'\n";
+/// let diags = Diagnostic::parse_all(text);
+/// assert_eq!(diags.len(), 1);
+///
+/// let d = &diags[0];
+/// assert_eq!(d.file.as_deref(),   Some("shader.hlsl"));
+/// assert_eq!(d.line,              Some(12));
+/// assert_eq!(d.columns,           Some(5 ..= 9));
+/// assert_eq!(d.severity,          DiagnosticSeverity::Error);
+/// assert_eq!(d.code.as_deref(),   Some("X3004"));
+/// assert_eq!(d.message,           "undeclared identifier 'colour'");
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    /// The file (or `source_name`) the diagnostic refers to, if any.  Typically whatever path the compiler was given.
+    pub file:       Option<String>,
+
+    /// The 1-based line the diagnostic refers to, if any.
+    pub line:       Option<u32>,
+
+    /// The 1-based, inclusive column range the diagnostic refers to, if any.
+    ///
+    /// A single column (e.g. `(12,5)`) is reported as `5 ..= 5`.
+    pub columns:    Option<RangeInclusive<u32>>,
+
+    /// How serious the diagnostic is.
+    pub severity:   DiagnosticSeverity,
+
+    /// The compiler's diagnostic code (e.g. `"X3004"`), if any.
+    pub code:       Option<String>,
+
+    /// The human readable message (e.g. `"undeclared identifier 'colour'"`).
+    pub message:    String,
+}
+
+/// The severity of a [Diagnostic].
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum DiagnosticSeverity {
+    /// Informational text that isn't an error or warning (e.g. `"compilation failed; no code produced"`).
+    Note,
+
+    /// A warning (e.g. `"warning X3206: implicit truncation of vector type"`).
+    Warning,
+
+    /// An error (e.g. `"error X3004: undeclared identifier"`).
+    Error,
+}
+
+impl Diagnostic {
+    /// Parse every line of d3dcompiler error/warning `text` into [Diagnostic]s.
+    ///
+    /// Blank lines are skipped.  Lines that don't match the `file(line,col-col): severity code: message` pattern are
+    /// still returned, as [DiagnosticSeverity::Note]s with only [Diagnostic::message] populated.
+    //#allow_missing_argument_docs
+    pub fn parse_all(text: &str) -> Vec<Self> {
+        text.lines().filter_map(Self::parse_line).collect()
+    }
+
+    /// Parse a single line of d3dcompiler error/warning text into a [Diagnostic].
+    ///
+    /// ### Returns
+    /// *   [None]  - if `line` is blank
+    /// *   [Some]  - otherwise, even if `line` doesn't match the expected pattern (see [parse_all](Self::parse_all))
+    //#allow_missing_argument_docs
+    pub fn parse_line(line: &str) -> Option<Self> {
+        let line = line.trim_end_matches(['\r', '\n', '\0']);
+        if line.trim().is_empty() { return None }
+
+        let (file, location, rest) = match split_location(line) {
+            Some((file, location, rest))    => (Some(file), Some(location), rest),
+            None                            => (None, None, line),
+        };
+
+        let (severity, code, message) = match split_severity(rest.trim_start()) {
+            Some(scm)   => scm,
+            None        => (DiagnosticSeverity::Note, None, rest.trim()),
+        };
+
+        let (line, columns) = location.unwrap_or((None, None));
+        Some(Self {
+            file:       file.map(String::from),
+            line,
+            columns,
+            severity,
+            code:       code.map(String::from),
+            message:    message.into(),
+        })
+    }
+
+    /// `true` if this is a [DiagnosticSeverity::Error].
+    pub fn is_error(&self) -> bool { self.severity == DiagnosticSeverity::Error }
+
+    /// `true` if this is a [DiagnosticSeverity::Warning].
+    pub fn is_warning(&self) -> bool { self.severity == DiagnosticSeverity::Warning }
+
+    /// Render this diagnostic with an excerpt of `source` and `^^^` carets underlining [Diagnostic::columns].
+    ///
+    /// ### Arguments
+    /// *   `source`    - The source text of [Diagnostic::file].  If the diagnostic has no [line](Self::line), or the
+    ///                   line is out of bounds, only the header is rendered.
+    ///
+    /// ### Examples
+    /// ```rust
+    /// # use thindx::d3d::*;
+    /// let source = "float4 main() : SV_TARGET {\n    return colour;\n}\n";
+    /// let diag = Diagnostic::parse_line("shader.hlsl(2,12-17): error X3004: undeclared identifier 'colour'").unwrap();
+    /// println!("{}", diag.with_source(source));
+    /// ```
+    ///
+    /// ### Output
+    /// ```text
+    /// error X3004: undeclared identifier 'colour'
+    ///  --> shader.hlsl:2:12
+    ///   |
+    /// 2 |     return colour;
+    ///   |            ^^^^^^
+    /// ```
+    pub fn with_source<'a>(&'a self, source: &'a str) -> DiagnosticSnippet<'a> {
+        DiagnosticSnippet { diagnostic: self, source }
+    }
+}
+
+impl Debug for Diagnostic {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let mut ds = fmt.debug_struct("Diagnostic");
+        if let Some(file)       = self.file.as_ref()    { ds.field("file",      file); }
+        if let Some(line)       = self.line             { ds.field("line",      &line); }
+        if let Some(columns)    = self.columns.as_ref() { ds.field("columns",   columns); }
+        ds.field("severity", &self.severity);
+        if let Some(code)       = self.code.as_ref()    { ds.field("code",      code); }
+        ds.field("message", &self.message);
+        ds.finish()
+    }
+}
+
+/// Formats as d3dcompiler would (e.g. `file.hlsl(12,5-9): error X3004: message`)
+impl Display for Diagnostic {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        if let Some(file) = self.file.as_ref() {
+            write!(fmt, "{file}")?;
+            if let Some(line) = self.line {
+                write!(fmt, "({line}")?;
+                if let Some(columns) = self.columns.as_ref() {
+                    write!(fmt, ",{}", columns.start())?;
+                    if columns.end() != columns.start() { write!(fmt, "-{}", columns.end())?; }
+                }
+                write!(fmt, ")")?;
+            }
+            write!(fmt, ": ")?;
+        }
+        self.fmt_header(fmt)
+    }
+}
+
+impl Diagnostic {
+    fn fmt_header(&self, fmt: &mut Formatter) -> fmt::Result {
+        if self.severity != DiagnosticSeverity::Note || self.code.is_some() {
+            write!(fmt, "{}", self.severity)?;
+            if let Some(code) = self.code.as_ref() { write!(fmt, " {code}")?; }
+            write!(fmt, ": ")?;
+        }
+        write!(fmt, "{}", self.message)
+    }
+}
+
+impl Display for DiagnosticSeverity {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.write_str(match self {
+            DiagnosticSeverity::Note    => "note",
+            DiagnosticSeverity::Warning => "warning",
+            DiagnosticSeverity::Error   => "error",
+        })
+    }
+}
+
+
+
+/// A [Diagnostic] + source text, [Display]ed as a source excerpt with carets.  See [Diagnostic::with_source].
+#[derive(Clone, Copy)]
+pub struct DiagnosticSnippet<'a> {
+    diagnostic: &'a Diagnostic,
+    source:     &'a str,
+}
+
+impl Debug for DiagnosticSnippet<'_> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { Debug::fmt(self.diagnostic, fmt) }
+}
+
+impl Display for DiagnosticSnippet<'_> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let d = self.diagnostic;
+        d.fmt_header(fmt)?;
+        writeln!(fmt)?;
+
+        let line_no = match d.line { Some(l) => l, None => return Ok(()) };
+        let gutter  = line_no.to_string().len();
+        if let Some(file) = d.file.as_ref() {
+            write!(fmt, "{:gutter$}--> {file}:{line_no}", "")?;
+            if let Some(columns) = d.columns.as_ref() { write!(fmt, ":{}", columns.start())?; }
+            writeln!(fmt)?;
+        }
+
+        let text = match self.source.lines().nth((line_no as usize).wrapping_sub(1)) {
+            Some(text)  => text.trim_end_matches('\r'),
+            None        => return Ok(()),
+        };
+
+        writeln!(fmt, "{:gutter$} |", "")?;
+        writeln!(fmt, "{line_no} | {text}")?;
+        if let Some(columns) = d.columns.as_ref() {
+            // clamp to the line (plus one past its end, e.g. for "unexpected end of line") - the compiler's range may be bogus
+            let len     = text.chars().count();
+            let start   = ((*columns.start()).max(1) as usize).min(len + 1);
+            let end     = ((*columns.end()).max(1) as usize).clamp(start, len.max(start));
+            // preserve tabs so carets line up with the excerpt above them
+            let indent  = text.chars().take(start-1).map(|ch| if ch == '\t' { '\t' } else { ' ' }).collect::<String>();
+            let carets  = "^".repeat(end - start + 1);
+            writeln!(fmt, "{:gutter$} | {indent}{carets}", "")?;
+        }
+        Ok(())
+    }
+}
+
+
+
+type Location = (Option<u32>, Option<RangeInclusive<u32>>);
+
+/// Split `file(line,col-col): rest` into `(file, (line, cols), rest)`
+fn split_location(line: &str) -> Option<(&str, Location, &str)> {
+    // Search for "): " rather than "(" - paths may contain parens (e.g. "C:\Program Files (x86)\...")
+    let close = line.find("): ")?;
+    let open  = line[..close].rfind('(')?;
+    let file  = &line[..open];
+    if !is_path_like(file) { return None }
+    let loc   = parse_location(&line[open+1 .. close])?;
+    Some((file, loc, &line[close+3..]))
+}
+
+/// `true` if `file` could be a source path or name (e.g. `C:\Program Files (x86)\a.hlsl` or `memory`) rather than the start of a location-less message
+fn is_path_like(file: &str) -> bool {
+    !file.trim().is_empty() && file.trim() == file && !file.contains(": ") && !file.contains(['\'', '"'])
+}
+
+/// Parse `line`, `line,col`, `line,col-col`, or `line,col-line,col`
+fn parse_location(loc: &str) -> Option<Location> {
+    let mut parts = loc.splitn(2, ',');
+    let line = parts.next()?.trim().parse::<u32>().ok()?;
+    let columns = match parts.next() {
+        None        => None,
+        Some(cols)  => {
+            let mut cols = cols.splitn(2, '-');
+            let start = cols.next()?.trim().parse::<u32>().ok()?;
+            let end = match cols.next() {
+                None        => start,
+                Some(end)   => match end.split_once(',') {
+                    // multi-line range: the end column refers to a different line, so just underline the start column
+                    Some(_) => start,
+                    None    => end.trim().parse::<u32>().ok()?.max(start),
+                },
+            };
+            Some(start ..= end)
+        },
+    };
+    Some((Some(line), columns))
+}
+
+/// Split `error X3004: message` into `(severity, code, message)`
+fn split_severity(rest: &str) -> Option<(DiagnosticSeverity, Option<&str>, &str)> {
+    let (severity, rest) = if let Some(rest) = rest.strip_prefix("error") {
+        (DiagnosticSeverity::Error, rest)
+    } else if let Some(rest) = rest.strip_prefix("warning") {
+        (DiagnosticSeverity::Warning, rest)
+    } else if let Some(rest) = rest.strip_prefix("internal error") {
+        (DiagnosticSeverity::Error, rest)
+    } else {
+        return None;
+    };
+
+    if let Some(message) = rest.strip_prefix(':') { return Some((severity, None, message.trim())) }
+
+    let rest = rest.strip_prefix(' ')?;
+    let (code, message) = rest.split_once(':')?;
+    let code = code.trim();
+    if code.is_empty() || code.contains(char::is_whitespace) { return None }
+    Some((severity, Some(code), message.trim()))
+}
+
+
+
+#[test] fn parse_lines() {
+    use DiagnosticSeverity::*;
+
+    let d = Diagnostic::parse_line(r"C:\Program Files (x86)\shaders\basic.hlsl(12,5-9): error X3004: undeclared identifier 'colour'").unwrap();
+    assert_eq!(d.file.as_deref(),   Some(r"C:\Program Files (x86)\shaders\basic.hlsl"));
+    assert_eq!(d.line,              Some(12));
+    assert_eq!(d.columns,           Some(5 ..= 9));
+    assert_eq!(d.severity,          Error);
+    assert_eq!(d.code.as_deref(),   Some("X3004"));
+    assert_eq!(d.message,           "undeclared identifier 'colour'");
+
+    let d = Diagnostic::parse_line("basic.hlsl(17,15): warning X3206: implicit truncation of vector type\r\n").unwrap();
+    assert_eq!((d.line, d.columns.clone(), d.severity), (Some(17), Some(15 ..= 15), Warning));
+    assert_eq!(d.to_string(), "basic.hlsl(17,15): warning X3206: implicit truncation of vector type");
+
+    let d = Diagnostic::parse_line("error X3501: 'main': entrypoint not found").unwrap();
+    assert_eq!((d.file, d.line, d.columns, d.severity), (None, None, None, Error));
+    assert_eq!(d.code.as_deref(), Some("X3501"));
+    assert_eq!(d.message, "'main': entrypoint not found");
+
+    let d = Diagnostic::parse_line("memory(3): error X1507: failed to open source file: 'missing.hlsl'").unwrap();
+    assert_eq!((d.file.as_deref(), d.line, d.columns), (Some("memory"), Some(3), None));
+    assert_eq!(d.message, "failed to open source file: 'missing.hlsl'");
+
+    let d = Diagnostic::parse_line("error X3000: syntax error: unexpected token 'f(1): '").unwrap();
+    assert_eq!((d.file, d.line, d.code.as_deref()), (None, None, Some("X3000")));
+    assert_eq!(d.message, "syntax error: unexpected token 'f(1): '");
+
+    let d = Diagnostic::parse_line("compilation failed; no code produced").unwrap();
+    assert_eq!((d.severity, d.code, d.message.as_str()), (Note, None, "compilation failed; no code produced"));
+
+    assert_eq!(Diagnostic::parse_line("  \0"), None);
+}
+
+#[test] fn parse_all() {
+    let text = "a.hlsl(1,1-2): warning X3571: pow(f, e) will not work for negative f\na.hlsl(2,3): error X3000: syntax error: unexpected token '}'\n\ncompilation failed; no code produced\n\0";
+    let diags = Diagnostic::parse_all(text);
+    assert_eq!(diags.len(), 3);
+    assert!(diags[0].is_warning());
+    assert!(diags[1].is_error());
+    assert_eq!(diags[1].message, "syntax error: unexpected token '}'");
+    assert_eq!(diags[2].severity, DiagnosticSeverity::Note);
+}
+
+#[test] fn snippet() {
+    let source = "float4 main() : SV_TARGET {\n    return colour;\n}\n";
+    let d = Diagnostic::parse_line("shader.hlsl(2,12-17): error X3004: undeclared identifier 'colour'").unwrap();
+    assert_eq!(d.with_source(source).to_string(), concat!(
+        "error X3004: undeclared identifier 'colour'\n",
+        " --> shader.hlsl:2:12\n",
+        "  |\n",
+        "2 |     return colour;\n",
+        "  |            ^^^^^^\n",
+    ));
+
+    let d = Diagnostic::parse_line("shader.hlsl(3,1-4000000000): error X3000: syntax error").unwrap();
+    assert_eq!(d.with_source(source).to_string(), "error X3000: syntax error\n --> shader.hlsl:3:1\n  |\n3 | }\n  | ^\n");
+    let d = Diagnostic::parse_line("shader.hlsl(3,90-95): error X3000: unexpected end of line").unwrap();
+    assert!(d.with_source(source).to_string().ends_with("3 | }\n  |  ^\n"));
+
+    let d = Diagnostic::parse_line("error X3501: 'main': entrypoint not found").unwrap();
+    assert_eq!(d.with_source(source).to_string(), "error X3501: 'main': entrypoint not found\n");
+}
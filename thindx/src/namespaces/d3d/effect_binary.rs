@@ -0,0 +1,1038 @@
+use crate::*;
+use crate::d3d::*;
+
+use std::collections::BTreeMap;
+
+
+
+/// A compiled effect binary (`.fxo`), as output when compiling HLSL for an `fx_2_0`, `fx_4_0`, `fx_4_1`, or `fx_5_0` target.
+///
+/// Effect binaries aren't shaders:  they're a container of techniques, passes, variables, annotations, and state blocks, with the
+/// compiled [Bytecode] of each shader embedded inside.  Their layouts aren't documented by Microsoft - this follows the same reverse
+/// engineered layouts Wine's `d3dx9` / `d3d10` effect implementations use (and Microsoft's open source Effects11 for `fx_5_0`),
+/// so tools can inspect legacy effects and pull individual shaders out of them without `d3dx9_*.dll` / `d3d10.dll`.
+///
+/// `fx_4_*` / `fx_5_0` binaries are accepted either as raw effect data, or wrapped in a DXBC container's `FX10` / `FX11` chunk.
+///
+/// ### Examples
+/// ```rust
+/// # use thindx::{*, d3d::*};
+/// # let d3dc = Compiler::load_system(47).unwrap();
+/// let fx = d3dc.compile(b"
+///     float4 tint < string UIName = \"Tint\"; > = 1;
+///     float4 ps_main() : COLOR0 { return tint; }
+///     technique T { pass P0 { ZEnable = false; PixelShader = compile ps_2_0 ps_main(); } }
+/// ", "effect.fx", None, None, None, "fx_2_0", Compile::Debug, CompileEffect::None).unwrap();
+///
+/// let effect = EffectBinary::parse(&fx).unwrap();
+/// assert_eq!(effect.target, ShaderTarget::FX_2_0);
+/// assert_eq!(effect.variable("tint").unwrap().annotations[0].name, "UIName");
+///
+/// let pass = &effect.technique("T").unwrap().passes[0];
+/// assert_eq!(pass.states[0].name, "ZEnable");
+/// let ps = pass.shader("PixelShader").unwrap().bytecode();
+/// assert_eq!(ps.version().unwrap().stage, ShaderStage::Pixel);
+/// ```
+///
+/// ### See Also
+/// *   [d3d9::Effect](crate::d3d9::Effect) - for parsing `.fx` source instead
+#[derive(Clone, Debug, PartialEq)]
+pub struct EffectBinary {
+    /// [ShaderTarget::FX_2_0], [FX_4_0](ShaderTarget::FX_4_0), [FX_4_1](ShaderTarget::FX_4_1), or [FX_5_0](ShaderTarget::FX_5_0)
+    pub target:     ShaderTarget,
+
+    /// Parameters (`fx_2_0`) or constant buffer, object, and interface variables (`fx_4_*` / `fx_5_0`), in declaration order
+    pub variables:  Vec<EffectBinaryVariable>,
+
+    /// Techniques, in declaration order (flattened out of `fx_5_0` groups)
+    pub techniques: Vec<EffectBinaryTechnique>,
+}
+
+/// A variable of an [EffectBinary], such as `float4 tint < string UIName = "Tint"; > = 1;` or `BlendState blend { ... };`
+#[derive(Clone, Debug, PartialEq)]
+pub struct EffectBinaryVariable {
+    /// The variable's name, such as `"tint"`
+    pub name:           String,
+
+    /// The variable's type, such as `"float4"`, `"sampler2D"`, or `"BlendState"`
+    pub ty:             String,
+
+    /// The variable's semantic, if any
+    pub semantic:       Option<String>,
+
+    /// The `cbuffer` / `tbuffer` containing this variable (`fx_4_*` / `fx_5_0` numeric variables only)
+    pub buffer:         Option<String>,
+
+    /// The array length, or `0` if this isn't an array
+    pub elements:       u32,
+
+    /// `true` if this is a `shared` variable of an `fx_4_*` effect pool (which don't carry values or annotations)
+    pub shared:         bool,
+
+    /// `< ... >` annotations
+    pub annotations:    Vec<EffectBinaryAnnotation>,
+
+    /// The initial value:  [Constants](EffectBinaryValue::Constants), [Strings](EffectBinaryValue::Strings), [Shaders](EffectBinaryValue::Shaders),
+    /// [StateBlocks](EffectBinaryValue::StateBlocks) (one per element for samplers and `fx_4_*` state objects), or [None](EffectBinaryValue::None)
+    pub value:          EffectBinaryValue,
+}
+
+/// An annotation such as `string UIName = "Tint";`
+#[derive(Clone, Debug, PartialEq)]
+pub struct EffectBinaryAnnotation {
+    /// The annotation's name, such as `"UIName"`
+    pub name:   String,
+
+    /// The annotation's type, such as `"string"`
+    pub ty:     String,
+
+    /// The annotation's value
+    pub value:  EffectBinaryValue,
+}
+
+/// A `technique` (`technique10` / `technique11`) of an [EffectBinary]
+#[derive(Clone, Debug, PartialEq)]
+pub struct EffectBinaryTechnique {
+    /// The `fx_5_0` group this technique was declared in, if any
+    pub group:          Option<String>,
+
+    /// The technique's name
+    pub name:           String,
+
+    /// `< ... >` annotations
+    pub annotations:    Vec<EffectBinaryAnnotation>,
+
+    /// `pass` blocks, in declaration order
+    pub passes:         Vec<EffectBinaryPass>,
+}
+
+/// A `pass` of an [EffectBinaryTechnique]
+#[derive(Clone, Debug, PartialEq)]
+pub struct EffectBinaryPass {
+    /// The pass's name
+    pub name:           String,
+
+    /// `< ... >` annotations
+    pub annotations:    Vec<EffectBinaryAnnotation>,
+
+    /// State assignments, in declaration order
+    pub states:         Vec<EffectBinaryState>,
+}
+
+/// A state assignment such as `ZEnable = false;`, `MinFilter[0] = Linear;`, or `SetPixelShader(CompileShader(ps_4_0, ps_main()));`
+#[derive(Clone, Debug, PartialEq)]
+pub struct EffectBinaryState {
+    /// The state's id:  a `fx_2_0` state table operation (`0x00 ..= 0xB2`), or a `fx_4_*` / `fx_5_0` state id (`0 ..= 58`)
+    pub id:     u32,
+
+    /// The state's name as written in effect source, such as `"ZEnable"`, `"PixelShader"`, or `"SrcBlendAlpha"`
+    pub name:   &'static str,
+
+    /// The `[index]` of the state, such as the sampler of `MinFilter[1]`, or `0`
+    pub index:  u32,
+
+    /// The assigned value
+    pub value:  EffectBinaryValue,
+}
+
+/// The value of an [EffectBinaryVariable], [EffectBinaryAnnotation], or [EffectBinaryState]
+#[derive(Clone, Debug, PartialEq)]
+pub enum EffectBinaryValue {
+    /// No value (textures, uninitialized variables, ...)
+    None,
+
+    /// Numeric values, such as `float4(1, 0, 0, 1)`.  `fx_4_*` / `fx_5_0` variables are in constant buffer layout, including any padding.
+    Constants(Vec<EffectBinaryConstant>),
+
+    /// Raw bytes of a `struct` value (`fx_4_*` / `fx_5_0`)
+    Data(Vec<u8>),
+
+    /// One string per element
+    Strings(Vec<String>),
+
+    /// One shader per element of a shader variable ([None] for `NULL`)
+    Shaders(Vec<Option<EffectBinaryShader>>),
+
+    /// A shader assigned by a state ([None] for `NULL`)
+    Shader(Option<EffectBinaryShader>),
+
+    /// One state block per element of a sampler or state object variable
+    StateBlocks(Vec<Vec<EffectBinaryState>>),
+
+    /// A reference to another variable, such as `<tex>` or `blend`
+    Variable(String),
+
+    /// An element of an array variable, such as `shaders[1]` or `shaders[i]`
+    Index {
+        /// The array variable's name
+        array:  String,
+
+        /// The element
+        index:  EffectBinaryIndex,
+    },
+
+    /// A compiled expression (a `FXLC` preshader for `fx_2_0`, or an expression blob for `fx_4_*` / `fx_5_0`.)
+    Expression(Vec<u8>),
+}
+
+/// A typed numeric value of an [EffectBinaryValue::Constants]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EffectBinaryConstant {
+    /// A `float`
+    Float(f32),
+
+    /// An `int`
+    Int(i32),
+
+    /// A `uint` (or a numeric value of unknown type)
+    UInt(u32),
+
+    /// A `bool`
+    Bool(bool),
+}
+
+/// The `[index]` of an [EffectBinaryValue::Index]
+#[derive(Clone, Debug, PartialEq)]
+pub enum EffectBinaryIndex {
+    /// A constant index, such as `shaders[1]`
+    Constant(u32),
+
+    /// An index variable, such as `shaders[i]`
+    Variable(String),
+
+    /// A compiled index expression, such as `shaders[i+1]`
+    Expression(Vec<u8>),
+}
+
+/// A shader embedded in an [EffectBinary]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EffectBinaryShader {
+    bytecode:               Vec<u8>,
+
+    /// Stream output declarations of `ConstructGSWithSO` geometry shaders, such as `"SV_POSITION.xyz"`
+    pub stream_output:      Vec<String>,
+}
+
+impl EffectBinary {
+    /// Parse an `fx_*` effect binary, without `d3dx9_*.dll`, `d3d10.dll`, or Effects11.
+    ///
+    /// ### Errors
+    /// *   [THINERR::INVALID_BYTECODE] - if `bytecode` isn't an `fx_2_0`, `fx_4_0`, `fx_4_1`, or `fx_5_0` effect binary
+    /// *   [THINERR::INVALID_BYTECODE] - if the effect is truncated, contains out of bounds offsets, or unknown state ids
+    /// *   [THINERR::INVALID_BYTECODE] - if an embedded shader isn't structurally valid bytecode
+    ///
+    /// ### Examples
+    /// ```rust
+    /// # use thindx::{*, d3d::*};
+    /// let ps = unsafe { Bytecode::from(&[0x00, 0x02, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00]) }.unwrap(); // ps_2_0, end
+    /// assert_eq!(EffectBinary::parse(ps).unwrap_err().kind(), THINERR::INVALID_BYTECODE);
+    /// ```
+    pub fn parse(bytecode: &Bytecode) -> Result<Self, Error> {
+        fn_context!(d3d::EffectBinary::parse);
+        parse(bytecode.as_bytes()).ok_or_else(|| fn_param_error!(bytecode, THINERR::INVALID_BYTECODE))
+    }
+
+    /// Parse an `fx_*` effect binary from raw bytes, such as those embedded by [include_effect!](crate::include_effect).
+    ///
+    /// ### Errors
+    /// *   See [EffectBinary::parse]
+    ///
+    /// ### Examples
+    /// ```rust
+    /// # use thindx::{*, d3d::*};
+    /// assert_eq!(EffectBinary::from_bytes(b"not an effect").unwrap_err().kind(), THINERR::INVALID_BYTECODE);
+    /// ```
+    //#allow_missing_argument_docs
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        fn_context!(d3d::EffectBinary::from_bytes);
+        parse(bytes).ok_or_else(|| fn_param_error!(bytes, THINERR::INVALID_BYTECODE))
+    }
+
+    /// Get the first technique named `name`
+    //#allow_missing_argument_docs
+    pub fn technique(&self, name: &str) -> Option<&EffectBinaryTechnique> { self.techniques.iter().find(|t| t.name == name) }
+
+    /// Get the first variable named `name`
+    //#allow_missing_argument_docs
+    pub fn variable(&self, name: &str) -> Option<&EffectBinaryVariable> { self.variables.iter().find(|v| v.name == name) }
+
+    /// Get every shader embedded in the effect:  shader variables first, then the inline shaders of each pass.
+    pub fn shaders(&self) -> Vec<&EffectBinaryShader> {
+        let mut shaders = Vec::new();
+        for v in self.variables.iter() {
+            if let EffectBinaryValue::Shaders(s) = &v.value { shaders.extend(s.iter().flatten()) }
+        }
+        for pass in self.techniques.iter().flat_map(|t| t.passes.iter()) {
+            shaders.extend(pass.states.iter().filter_map(|s| match &s.value { EffectBinaryValue::Shader(s) => s.as_ref(), _ => None }));
+        }
+        shaders
+    }
+}
+
+impl EffectBinaryPass {
+    /// Get the inline shader assigned to the state named `name` (e.g. `"VertexShader"`, `"PixelShader"`, `"ComputeShader"`).
+    ///
+    /// ### Returns
+    /// *   [None] if the state isn't assigned, is assigned `NULL`, or references a variable instead of an inline shader.
+    //#allow_missing_argument_docs
+    pub fn shader(&self, name: &str) -> Option<&EffectBinaryShader> {
+        self.states.iter().find(|s| s.name == name).and_then(|s| match &s.value { EffectBinaryValue::Shader(s) => s.as_ref(), _ => None })
+    }
+}
+
+impl EffectBinaryShader {
+    /// Get the shader's [Bytecode] (a SM1-3 token stream for `fx_2_0`, or a DXBC container for `fx_4_*` / `fx_5_0`.)
+    pub fn bytecode(&self) -> &Bytecode {
+        // SAFETY: ⚠️ validated at least as strictly as Bytecode::from when parsed
+        unsafe { Bytecode::from_unchecked(&self.bytecode) }
+    }
+}
+
+fn parse(b: &[u8]) -> Option<EffectBinary> {
+    if b.get(0..4)? == b"DXBC" {
+        for i in 0 .. u32_at(b, 28, 0)? as usize {
+            let off     = u32_at(b, 32, i.checked_mul(4)?)? as usize;
+            let size    = u32_at(b, off, 4)? as usize;
+            let start   = off.checked_add(8)?;
+            if matches!(b.get(off .. start - 4)?, b"FX10" | b"FX11") { return fx4(b.get(start .. start.checked_add(size)?)?) }
+        }
+        None
+    } else if u32_at(b, 0, 0)? == 0xFEFF0901 {
+        fx2(b)
+    } else {
+        fx4(b)
+    }
+}
+
+fn shader(data: &[u8], stream_output: Vec<String>) -> Option<EffectBinaryShader> {
+    // SAFETY: ⚠️ only inspected by Bytecode::version, which structurally validates the bytecode
+    let _ = unsafe { Bytecode::from_unchecked(data) }.version()?;
+    Some(EffectBinaryShader { bytecode: data.to_vec(), stream_output })
+}
+
+fn constant(ty: u32, v: u32) -> Option<EffectBinaryConstant> {
+    match ty {
+        1 => Some(EffectBinaryConstant::Float(f32::from_bits(v))),
+        2 => Some(EffectBinaryConstant::Int(v as i32)),
+        3 => Some(EffectBinaryConstant::UInt(v)),
+        4 => Some(EffectBinaryConstant::Bool(v != 0)),
+        _ => None,
+    }
+}
+
+
+
+// fx_2_0 (d3dx9):  all offsets are relative to the data following the 8 byte { tag, structured data offset } header
+
+struct Fx2Type {
+    ty:         u32,
+    class:      u32,
+    name:       String,
+    semantic:   Option<String>,
+    elements:   u32,
+    rows:       u32,
+    columns:    u32,
+    members:    Vec<Fx2Type>,
+}
+
+#[derive(Default)] struct Fx2Values {
+    constants:  Vec<EffectBinaryConstant>,
+    objects:    Vec<u32>,
+    states:     Vec<Vec<EffectBinaryState>>,
+}
+
+struct Fx2<'a> {
+    b:          &'a [u8],
+    objects:    BTreeMap<u32, &'a [u8]>,
+}
+
+fn fx2(b: &[u8]) -> Option<EffectBinary> {
+    let start = u32_at(b, 4, 0)? as usize;
+    let mut fx = Fx2 { b: b.get(8..)?, objects: BTreeMap::new() };
+    let (mut variables, mut techniques, mut p) = fx.body(start)?;
+
+    // strings and global shaders follow the techniques referencing them by id, so reparse once they're known
+    let objects     = fx.u32(&mut p)?;
+    let resources   = fx.u32(&mut p)?;
+    for _ in 0 .. objects {
+        let id = fx.u32(&mut p)?;
+        let data = fx.blob(&mut p)?;
+        fx.objects.insert(id, data);
+    }
+    if !fx.objects.is_empty() { (variables, techniques, _) = fx.body(start)?; }
+
+    // inline shaders, expressions, and variable references of individual states
+    for _ in 0 .. resources {
+        let technique   = fx.u32(&mut p)?;
+        let index       = fx.u32(&mut p)? as usize;
+        let element     = fx.u32(&mut p)?;
+        let state       = fx.u32(&mut p)? as usize;
+        let usage       = fx.u32(&mut p)?;
+        let data        = fx.blob(&mut p)?;
+        let state = if technique == !0 {
+            let EffectBinaryValue::StateBlocks(blocks) = &mut variables.get_mut(index)?.value else { return None };
+            blocks.get_mut(if element == !0 { 0 } else { element as usize })?.get_mut(state)?
+        } else {
+            techniques.get_mut(technique as usize)?.passes.get_mut(index)?.states.get_mut(state)?
+        };
+        state.value = match usage {
+            0 if matches!(state.id, 0x92 | 0x93) => EffectBinaryValue::Shader(Some(shader(data, Vec::new())?)),
+            0 => EffectBinaryValue::Expression(data.to_vec()),
+            1 => EffectBinaryValue::Variable(cstr(data)),
+            2 => {
+                let len = u32_at(data, 0, 0)? as usize;
+                let array = cstr(data.get(4 .. 4usize.checked_add(len)?)?);
+                EffectBinaryValue::Index { array, index: EffectBinaryIndex::Expression(data[4 + len ..].to_vec()) }
+            },
+            _ => return None,
+        };
+    }
+
+    Some(EffectBinary { target: ShaderTarget::FX_2_0, variables, techniques })
+}
+
+impl<'a> Fx2<'a> {
+    fn u32(&self, p: &mut usize) -> Option<u32> { let v = u32_at(self.b, *p, 0)?; *p += 4; Some(v) }
+
+    /// `u32` size, then `size` bytes padded to a multiple of 4
+    fn blob(&self, p: &mut usize) -> Option<&'a [u8]> {
+        let len = self.u32(p)? as usize;
+        let b = self.b;
+        let data = b.get(*p .. p.checked_add(len)?)?;
+        *p += (len + 3) & !3;
+        Some(data)
+    }
+
+    fn name(&self, o: u32) -> Option<String> { let mut p = o as usize; self.blob(&mut p).map(cstr) }
+
+    fn body(&self, start: usize) -> Option<(Vec<EffectBinaryVariable>, Vec<EffectBinaryTechnique>, usize)> {
+        let mut p = start;
+        let parameters  = self.u32(&mut p)?;
+        let techniques  = self.u32(&mut p)?;
+        let _unknown    = self.u32(&mut p)?;
+        let _objects    = self.u32(&mut p)?;
+
+        let mut variables = Vec::new();
+        for _ in 0 .. parameters {
+            let ty          = self.u32(&mut p)?;
+            let value       = self.u32(&mut p)?;
+            let _flags      = self.u32(&mut p)?;
+            let annotations = self.u32(&mut p)?;
+            let annotations = self.annotations(&mut p, annotations)?;
+            let (ty, value) = self.init(ty, value, 0)?;
+            variables.push(EffectBinaryVariable {
+                ty: ty.type_name(), name: ty.name, semantic: ty.semantic, buffer: None, elements: ty.elements, shared: false, annotations, value,
+            });
+        }
+
+        let mut techniques_ = Vec::new();
+        for _ in 0 .. techniques {
+            let name        = self.u32(&mut p)?;
+            let annotations = self.u32(&mut p)?;
+            let passes      = self.u32(&mut p)?;
+            let mut technique = EffectBinaryTechnique { group: None, name: self.name(name)?, annotations: self.annotations(&mut p, annotations)?, passes: Vec::new() };
+            for _ in 0 .. passes {
+                let name        = self.u32(&mut p)?;
+                let annotations = self.u32(&mut p)?;
+                let states      = self.u32(&mut p)?;
+                let annotations = self.annotations(&mut p, annotations)?;
+                let states      = (0 .. states).map(|_| self.state(&mut p, 0)).collect::<Option<Vec<_>>>()?;
+                technique.passes.push(EffectBinaryPass { name: self.name(name)?, annotations, states });
+            }
+            techniques_.push(technique);
+        }
+
+        Some((variables, techniques_, p))
+    }
+
+    fn annotations(&self, p: &mut usize, n: u32) -> Option<Vec<EffectBinaryAnnotation>> {
+        (0 .. n).map(|_| {
+            let ty      = self.u32(p)?;
+            let value   = self.u32(p)?;
+            let (ty, value) = self.init(ty, value, 0)?;
+            Some(EffectBinaryAnnotation { ty: ty.type_name(), name: ty.name, value })
+        }).collect()
+    }
+
+    fn state(&self, p: &mut usize, depth: usize) -> Option<EffectBinaryState> {
+        let id      = self.u32(p)?;
+        let index   = self.u32(p)?;
+        let ty      = self.u32(p)?;
+        let value   = self.u32(p)?;
+        let value   = match self.init(ty, value, depth)?.1 {
+            EffectBinaryValue::Shaders(mut s) if s.len() == 1 => EffectBinaryValue::Shader(s.remove(0)),
+            other => other,
+        };
+        Some(EffectBinaryState { id, name: FX2_STATES.get(id as usize)?, index, value })
+    }
+
+    /// Parse the typedef at `ty` and the value at `value`
+    fn init(&self, ty: u32, value: u32, depth: usize) -> Option<(Fx2Type, EffectBinaryValue)> {
+        let ty = self.typedef(&mut (ty as usize), depth)?;
+        let mut values = Fx2Values::default();
+        self.value(&ty, &mut (value as usize), &mut values, depth)?;
+        let value = match (ty.class, ty.ty) {
+            (4, 4)          => EffectBinaryValue::Strings(values.objects.iter().map(|id| self.objects.get(id).map_or(String::new(), |s| cstr(s))).collect()),
+            (4, 15 | 16)    => EffectBinaryValue::Shaders(values.objects.iter().map(|id| match self.objects.get(id) {
+                None        => Some(None),
+                Some(data)  => shader(data, Vec::new()).map(Some),
+            }).collect::<Option<_>>()?),
+            (4, 10 ..= 14)  => EffectBinaryValue::StateBlocks(values.states),
+            (4, _)          => EffectBinaryValue::None,
+            _               => EffectBinaryValue::Constants(values.constants),
+        };
+        Some((ty, value))
+    }
+
+    fn typedef(&self, p: &mut usize, depth: usize) -> Option<Fx2Type> {
+        if depth > 32 { return None }
+        let mut ty = Fx2Type {
+            ty:         self.u32(p)?,
+            class:      self.u32(p)?,
+            name:       self.name(self.u32(p)?)?,
+            semantic:   Some(self.name(self.u32(p)?)?).filter(|s| !s.is_empty()),
+            elements:   self.u32(p)?,
+            rows:       0,
+            columns:    0,
+            members:    Vec::new(),
+        };
+        match ty.class {
+            0 | 2 | 3   => { ty.rows = self.u32(p)?; ty.columns = self.u32(p)?; },
+            1           => { ty.columns = self.u32(p)?; ty.rows = self.u32(p)?; },
+            4           => {},
+            5           => for _ in 0 .. self.u32(p)? { ty.members.push(self.typedef(p, depth + 1)?) },
+            _           => return None,
+        }
+        Some(ty)
+    }
+
+    fn value(&self, ty: &Fx2Type, p: &mut usize, out: &mut Fx2Values, depth: usize) -> Option<()> {
+        if depth > 32 { return None }
+        for _ in 0 .. ty.elements.max(1) {
+            let start = *p;
+            match (ty.class, ty.ty) {
+                (0 ..= 3, _) => for _ in 0 .. u64::from(ty.rows) * u64::from(ty.columns) {
+                    let v = self.u32(p)?;
+                    out.constants.push(match ty.ty {
+                        1 => EffectBinaryConstant::Bool(v != 0),
+                        2 => EffectBinaryConstant::Int(v as i32),
+                        3 => EffectBinaryConstant::Float(f32::from_bits(v)),
+                        _ => EffectBinaryConstant::UInt(v),
+                    });
+                },
+                (5, _) => for m in ty.members.iter() { self.value(m, p, out, depth + 1)? },
+                (4, 10 ..= 14) => {
+                    let states = self.u32(p)?;
+                    out.states.push((0 .. states).map(|_| self.state(p, depth + 1)).collect::<Option<_>>()?);
+                },
+                (4, _) => out.objects.push(self.u32(p)?),
+                _ => return None,
+            }
+            if *p == start { return None } // zero sized elements (e.g. `float0x0`, empty structs) would otherwise spin through up to 2^32 elements
+        }
+        Some(())
+    }
+}
+
+impl Fx2Type {
+    fn type_name(&self) -> String {
+        let base = match self.ty {
+            0 => "void", 1 => "bool", 2 => "int", 3 => "float", 4 => "string",
+            5 => "texture", 6 => "texture1D", 7 => "texture2D", 8 => "texture3D", 9 => "textureCUBE",
+            10 => "sampler", 11 => "sampler1D", 12 => "sampler2D", 13 => "sampler3D", 14 => "samplerCUBE",
+            15 => "pixelshader", 16 => "vertexshader",
+            _ => "unknown",
+        };
+        match self.class {
+            1       => format!("{}{}", base, self.columns),
+            2 | 3   => format!("{}{}x{}", base, self.rows, self.columns),
+            5       => "struct".into(),
+            _       => base.into(),
+        }
+    }
+}
+
+/// d3dx9's effect state table, indexed by operation
+const FX2_STATES : [&str; 0xB3] = [
+    // 0x00 ..= 0x66: render states
+    "ZEnable", "FillMode", "ShadeMode", "ZWriteEnable", "AlphaTestEnable", "LastPixel", "SrcBlend", "DestBlend",
+    "CullMode", "ZFunc", "AlphaRef", "AlphaFunc", "DitherEnable", "AlphaBlendEnable", "FogEnable", "SpecularEnable",
+    "FogColor", "FogTableMode", "FogStart", "FogEnd", "FogDensity", "RangeFogEnable", "StencilEnable", "StencilFail",
+    "StencilZFail", "StencilPass", "StencilFunc", "StencilRef", "StencilMask", "StencilWriteMask", "TextureFactor", "Wrap0",
+    "Wrap1", "Wrap2", "Wrap3", "Wrap4", "Wrap5", "Wrap6", "Wrap7", "Wrap8",
+    "Wrap9", "Wrap10", "Wrap11", "Wrap12", "Wrap13", "Wrap14", "Wrap15", "Clipping",
+    "Lighting", "Ambient", "FogVertexMode", "ColorVertex", "LocalViewer", "NormalizeNormals", "DiffuseMaterialSource", "SpecularMaterialSource",
+    "AmbientMaterialSource", "EmissiveMaterialSource", "VertexBlend", "ClipPlaneEnable", "PointSize", "PointSize_Min", "PointSpriteEnable", "PointScaleEnable",
+    "PointScale_A", "PointScale_B", "PointScale_C", "MultiSampleAntialias", "MultiSampleMask", "PatchEdgeStyle", "DebugMonitorToken", "PointSize_Max",
+    "IndexedVertexBlendEnable", "ColorWriteEnable", "TweenFactor", "BlendOp", "PositionDegree", "NormalDegree", "ScissorTestEnable", "SlopeScaleDepthBias",
+    "AntialiasedLineEnable", "MinTessellationLevel", "MaxTessellationLevel", "AdaptiveTess_X", "AdaptiveTess_Y", "AdaptiveTess_Z", "AdaptiveTess_W", "EnableAdaptiveTessellation",
+    "TwoSidedStencilMode", "CCW_StencilFail", "CCW_StencilZFail", "CCW_StencilPass", "CCW_StencilFunc", "ColorWriteEnable1", "ColorWriteEnable2", "ColorWriteEnable3",
+    "BlendFactor", "SRGBWriteEnable", "DepthBias", "SeparateAlphaBlendEnable", "SrcBlendAlpha", "DestBlendAlpha", "BlendOpAlpha",
+    // 0x67 ..= 0x78: texture stage states
+    "ColorOp", "ColorArg0", "ColorArg1", "ColorArg2", "AlphaOp", "AlphaArg0", "AlphaArg1", "AlphaArg2", "ResultArg",
+    "BumpEnvMat00", "BumpEnvMat01", "BumpEnvMat10", "BumpEnvMat11", "TexCoordIndex", "BumpEnvLScale", "BumpEnvLOffset", "TextureTransformFlags", "Constant",
+    // 0x79 ..= 0x93: fixed function
+    "NPatchMode", "FVF",
+    "ProjectionTransform", "ViewTransform", "WorldTransform", "TextureTransform",
+    "MaterialDiffuse", "MaterialAmbient", "MaterialSpecular", "MaterialEmissive", "MaterialPower",
+    "LightType", "LightDiffuse", "LightSpecular", "LightAmbient", "LightPosition", "LightDirection", "LightRange", "LightFalloff",
+    "LightAttenuation0", "LightAttenuation1", "LightAttenuation2", "LightTheta", "LightPhi", "LightEnable",
+    "VertexShader", "PixelShader",
+    // 0x94 ..= 0xA3: shader constants
+    "VertexShaderConstantF", "VertexShaderConstantB", "VertexShaderConstantI", "VertexShaderConstant",
+    "VertexShaderConstant1", "VertexShaderConstant2", "VertexShaderConstant3", "VertexShaderConstant4",
+    "PixelShaderConstantF", "PixelShaderConstantB", "PixelShaderConstantI", "PixelShaderConstant",
+    "PixelShaderConstant1", "PixelShaderConstant2", "PixelShaderConstant3", "PixelShaderConstant4",
+    // 0xA4 ..= 0xB2: samplers
+    "Texture", "AddressU", "AddressV", "AddressW", "BorderColor", "MagFilter", "MinFilter", "MipFilter",
+    "MipMapLodBias", "MaxMipLevel", "MaxAnisotropy", "SRGBTexture", "ElementIndex", "DMAPOffset", "Sampler",
+];
+
+
+
+// fx_4_0 / fx_4_1 / fx_5_0 (d3d10 / Effects11):  a header, then "unstructured" data (strings, types, default values, shaders)
+// referenced by offset, then "structured" data (buffers, variables, groups, techniques, passes) read sequentially
+
+struct Fx4Type {
+    name:       String,
+    class:      u32,
+    elements:   u32,
+    size:       u32,
+    info:       u32,    // numeric: packed layout / scalar type / rows / columns, object: object type
+}
+
+struct Fx4<'a> {
+    u:  &'a [u8],
+    s:  &'a [u8],
+    p:  usize,
+}
+
+fn fx4(b: &[u8]) -> Option<EffectBinary> {
+    let (target, header) : (_, usize) = match u32_at(b, 0, 0)? {
+        0xFEFF1001  => (ShaderTarget::FX_4_0, 19),
+        0xFEFF1011  => (ShaderTarget::FX_4_1, 19),
+        0xFEFF2001  => (ShaderTarget::FX_5_0, 24),
+        _           => return None,
+    };
+    let h = |i: usize| u32_at(b, 0, 4 * i);
+    let unstructured = h(8)? as usize;
+    let mut fx = Fx4 {
+        u: b.get(4 * header .. (4 * header).checked_add(unstructured)?)?,
+        s: b.get(4 * header + unstructured ..)?,
+        p: 0,
+    };
+
+    let mut variables = Vec::new();
+    for _ in 0 .. h(1)? { fx.buffer(&mut variables, true)? }
+    for _ in 0 .. h(3)? { variables.push(fx.object(true)?) }
+    for _ in 0 .. h(4)? { fx.buffer(&mut variables, false)? }
+    for _ in 0 .. h(6)? { variables.push(fx.object(false)?) }
+
+    let mut techniques = Vec::new();
+    if header == 24 {
+        for _ in 0 .. h(21)? {
+            let name        = fx.string()?;
+            let ty          = fx.ty()?;
+            let _default    = fx.u32()?;
+            let _flags      = fx.u32()?;
+            let annotations = fx.annotations()?;
+            variables.push(EffectBinaryVariable {
+                name, ty: ty.name, semantic: None, buffer: None, elements: ty.elements, shared: false, annotations, value: EffectBinaryValue::None,
+            });
+        }
+        for _ in 0 .. h(19)? {
+            let group       = fx.opt_string()?;
+            let n           = fx.u32()?;
+            let _annotations= fx.annotations()?;
+            for _ in 0 .. n { techniques.push(fx.technique(group.clone())?) }
+        }
+    } else {
+        for _ in 0 .. h(7)? { techniques.push(fx.technique(None)?) }
+    }
+
+    Some(EffectBinary { target, variables, techniques })
+}
+
+impl Fx4<'_> {
+    fn u32(&mut self) -> Option<u32> { let v = u32_at(self.s, self.p, 0)?; self.p += 4; Some(v) }
+    fn string(&mut self) -> Option<String> { let o = self.u32()?; cstr_at(self.u, o as usize) }
+    fn opt_string(&mut self) -> Option<Option<String>> { let o = self.u32()?; if o == 0 { Some(None) } else { cstr_at(self.u, o as usize).map(Some) } }
+
+    fn ty(&mut self) -> Option<Fx4Type> {
+        let o = self.u32()? as usize;
+        let class = u32_at(self.u, o, 4)?;
+        Some(Fx4Type {
+            name:       cstr_at(self.u, u32_at(self.u, o, 0)? as usize)?,
+            class,
+            elements:   u32_at(self.u, o, 8)?,
+            size:       u32_at(self.u, o, 12)?,
+            info:       if matches!(class, 1 | 2) { u32_at(self.u, o, 24)? } else { 0 },
+        })
+    }
+
+    /// Numeric data at unstructured offset `o` (`0` if none)
+    fn numeric(&self, ty: &Fx4Type, o: u32) -> Option<EffectBinaryValue> {
+        if o == 0 { return Some(EffectBinaryValue::None) }
+        let data = self.u.get(o as usize .. (o as usize).checked_add(ty.size as usize)?)?;
+        if ty.class != 1 { return Some(EffectBinaryValue::Data(data.to_vec())) }
+        let scalar = (ty.info >> 3) & 0x1F;
+        Some(EffectBinaryValue::Constants(data.chunks_exact(4).map(|v| constant(scalar, u32::from_le_bytes(v.try_into().unwrap()))).collect::<Option<_>>()?))
+    }
+
+    /// `u32` size, then `size` bytes of code, at unstructured offset `o`
+    fn code(&self, o: u32) -> Option<Vec<u8>> {
+        let len     = u32_at(self.u, o as usize, 0)? as usize;
+        let start   = (o as usize).checked_add(4)?;
+        Some(self.u.get(start .. start.checked_add(len)?)?.to_vec())
+    }
+
+    /// A shader (or [None] if `NULL`) at unstructured offset `o`
+    fn shader(&self, o: u32, stream_output: Vec<String>) -> Option<Option<EffectBinaryShader>> {
+        let len     = u32_at(self.u, o as usize, 0)? as usize;
+        if len == 0 { return Some(None) }
+        let start   = (o as usize).checked_add(4)?;
+        shader(self.u.get(start .. start.checked_add(len)?)?, stream_output).map(Some)
+    }
+
+    /// A `fx_5_0` `{ oShader, oSODecls[4], cSODecls, RasterizedStream, cInterfaceBindings, oInterfaceBindings }` in `b` at offset `o`
+    fn shader5(&self, b: &[u8], o: usize) -> Option<Option<EffectBinaryShader>> {
+        let decls = u32_at(b, o, 20)?.min(4) as usize;
+        let stream_output = (0 .. decls).map(|i| u32_at(b, o, 4 + 4 * i).and_then(|s| cstr_at(self.u, s as usize))).collect::<Option<Vec<_>>>()?;
+        self.shader(u32_at(b, o, 0)?, stream_output.into_iter().filter(|s| !s.is_empty()).collect())
+    }
+
+    fn annotations(&mut self) -> Option<Vec<EffectBinaryAnnotation>> {
+        (0 .. self.u32()?).map(|_| {
+            let name    = self.string()?;
+            let ty      = self.ty()?;
+            let value   = if ty.class == 2 && ty.info == 1 {
+                EffectBinaryValue::Strings((0 .. ty.elements.max(1)).map(|_| self.string()).collect::<Option<_>>()?)
+            } else {
+                let o = self.u32()?;
+                self.numeric(&ty, o)?
+            };
+            Some(EffectBinaryAnnotation { name, ty: ty.name, value })
+        }).collect()
+    }
+
+    fn buffer(&mut self, variables: &mut Vec<EffectBinaryVariable>, local: bool) -> Option<()> {
+        let buffer      = self.string()?;
+        let _size       = self.u32()?;
+        let _flags      = self.u32()?;
+        let n           = self.u32()?;
+        if local {
+            let _bind           = self.u32()?;
+            let _annotations    = self.annotations()?;
+        }
+        for _ in 0 .. n {
+            let name        = self.string()?;
+            let ty          = self.ty()?;
+            let semantic    = self.opt_string()?;
+            let _offset     = self.u32()?;
+            let (value, annotations) = if local {
+                let default = self.u32()?;
+                let _flags  = self.u32()?;
+                (self.numeric(&ty, default)?, self.annotations()?)
+            } else {
+                (EffectBinaryValue::None, Vec::new())
+            };
+            variables.push(EffectBinaryVariable {
+                name, ty: ty.name, semantic, buffer: Some(buffer.clone()), elements: ty.elements, shared: !local, annotations, value,
+            });
+        }
+        Some(())
+    }
+
+    fn object(&mut self, local: bool) -> Option<EffectBinaryVariable> {
+        let name        = self.string()?;
+        let ty          = self.ty()?;
+        let semantic    = self.opt_string()?;
+        let _bind       = self.u32()?;
+        if !local {
+            return Some(EffectBinaryVariable { name, ty: ty.name, semantic, buffer: None, elements: ty.elements, shared: true, annotations: Vec::new(), value: EffectBinaryValue::None });
+        }
+
+        let elements = 0 .. ty.elements.max(1);
+        let value = match ty.info {
+            1               => EffectBinaryValue::Strings(elements.map(|_| self.string()).collect::<Option<_>>()?),
+            2 | 3 | 4 | 21  => EffectBinaryValue::StateBlocks(elements.map(|_| {
+                (0 .. self.u32()?).map(|_| self.assignment()).collect::<Option<_>>()
+            }).collect::<Option<_>>()?),
+            5 ..= 7         => EffectBinaryValue::Shaders(elements.map(|_| { let o = self.u32()?; self.shader(o, Vec::new()) }).collect::<Option<_>>()?),
+            8               => EffectBinaryValue::Shaders(elements.map(|_| {
+                let shader  = self.u32()?;
+                let decl    = self.u32()?;
+                let decl    = cstr_at(self.u, decl as usize)?;
+                self.shader(shader, Some(decl).filter(|d| !d.is_empty()).into_iter().collect())
+            }).collect::<Option<_>>()?),
+            25 ..= 30       => EffectBinaryValue::Shaders(elements.map(|_| {
+                let shader = self.shader5(self.s, self.p)?;
+                self.p += 36;
+                Some(shader)
+            }).collect::<Option<_>>()?),
+            _               => EffectBinaryValue::None,
+        };
+        let annotations = self.annotations()?;
+        Some(EffectBinaryVariable { name, ty: ty.name, semantic, buffer: None, elements: ty.elements, shared: false, annotations, value })
+    }
+
+    fn technique(&mut self, group: Option<String>) -> Option<EffectBinaryTechnique> {
+        let name        = self.string()?;
+        let passes      = self.u32()?;
+        let annotations = self.annotations()?;
+        let passes      = (0 .. passes).map(|_| {
+            let name        = self.string()?;
+            let states      = self.u32()?;
+            let annotations = self.annotations()?;
+            let states      = (0 .. states).map(|_| self.assignment()).collect::<Option<_>>()?;
+            Some(EffectBinaryPass { name, annotations, states })
+        }).collect::<Option<_>>()?;
+        Some(EffectBinaryTechnique { group, name, annotations, passes })
+    }
+
+    fn assignment(&mut self) -> Option<EffectBinaryState> {
+        let id      = self.u32()?;
+        let index   = self.u32()?;
+        let kind    = self.u32()?;
+        let o       = self.u32()? as usize;
+        let u       = self.u;
+        let string  = |o: usize, delta: usize| u32_at(u, o, delta).and_then(|s| cstr_at(u, s as usize));
+        let value = match kind {
+            1 => EffectBinaryValue::Constants((0 .. u32_at(u, o, 0)? as usize).map(|i| { let d = i.checked_mul(8)?; constant(u32_at(u, o, d.checked_add(4)?)?, u32_at(u, o, d.checked_add(8)?)?) }).collect::<Option<_>>()?),
+            2 => EffectBinaryValue::Variable(cstr_at(u, o)?),
+            3 => EffectBinaryValue::Index { array: string(o, 0)?, index: EffectBinaryIndex::Constant(u32_at(u, o, 4)?) },
+            4 => EffectBinaryValue::Index { array: string(o, 0)?, index: EffectBinaryIndex::Variable(string(o, 4)?) },
+            5 => EffectBinaryValue::Index { array: string(o, 0)?, index: EffectBinaryIndex::Expression(self.code(u32_at(u, o, 4)?)?) },
+            6 => EffectBinaryValue::Expression(self.code(o as u32)?),
+            7 => {
+                let decl = string(o, 4)?;
+                EffectBinaryValue::Shader(self.shader(u32_at(u, o, 0)?, Some(decl).filter(|d| !d.is_empty()).into_iter().collect())?)
+            },
+            8 => EffectBinaryValue::Shader(self.shader5(u, o)?),
+            _ => return None,
+        };
+        Some(EffectBinaryState { id, name: FX4_STATES.get(id as usize)?, index, value })
+    }
+}
+
+/// Effects11's state table, indexed by state id
+const FX4_STATES : [&str; 59] = [
+    // 0 ..= 11: pass states
+    "RasterizerState", "DepthStencilState", "BlendState", "RenderTargetView", "DepthStencilView", "GenerateMips",
+    "VertexShader", "PixelShader", "GeometryShader", "StencilRef", "BlendFactor", "SampleMask",
+    // 12 ..= 21: RasterizerState
+    "FillMode", "CullMode", "FrontCounterClockwise", "DepthBias", "DepthBiasClamp", "SlopeScaledDepthBias",
+    "DepthClipEnable", "ScissorEnable", "MultisampleEnable", "AntialiasedLineEnable",
+    // 22 ..= 35: DepthStencilState
+    "DepthEnable", "DepthWriteMask", "DepthFunc", "StencilEnable", "StencilReadMask", "StencilWriteMask",
+    "FrontFaceStencilFail", "FrontFaceStencilDepthFail", "FrontFaceStencilPass", "FrontFaceStencilFunc",
+    "BackFaceStencilFail", "BackFaceStencilDepthFail", "BackFaceStencilPass", "BackFaceStencilFunc",
+    // 36 ..= 44: BlendState
+    "AlphaToCoverageEnable", "BlendEnable", "SrcBlend", "DestBlend", "BlendOp", "SrcBlendAlpha", "DestBlendAlpha", "BlendOpAlpha", "RenderTargetWriteMask",
+    // 45 ..= 55: SamplerState
+    "Filter", "AddressU", "AddressV", "AddressW", "MipLODBias", "MaxAnisotropy", "ComparisonFunc", "BorderColor", "MinLOD", "MaxLOD", "Texture",
+    // 56 ..= 58: fx_5_0 pass states
+    "HullShader", "DomainShader", "ComputeShader",
+];
+
+
+
+/// The `u32` at byte offset `base + delta` of `b`
+fn u32_at(b: &[u8], base: usize, delta: usize) -> Option<u32> {
+    let o = base.checked_add(delta)?;
+    Some(u32::from_le_bytes(b.get(o .. o.checked_add(4)?)?.try_into().ok()?))
+}
+
+fn cstr_at(b: &[u8], o: usize) -> Option<String> {
+    let b = b.get(o..)?;
+    let len = b.iter().position(|&ch| ch == 0)?;
+    Some(String::from_utf8_lossy(&b[..len]).into_owned())
+}
+
+fn cstr(b: &[u8]) -> String {
+    let len = b.iter().position(|&ch| ch == 0).unwrap_or(b.len());
+    String::from_utf8_lossy(&b[..len]).into_owned()
+}
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::test_fixtures::dxbc;
+
+    const PS_2_0 : [u8; 8] = [0x00, 0x02, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00];
+
+    #[derive(Default)] struct Writer(Vec<u8>);
+    impl Writer {
+        fn len(&self) -> u32 { self.0.len() as u32 }
+        fn u32s(&mut self, v: &[u32]) -> u32 { let o = self.len(); for v in v { self.0.extend_from_slice(&v.to_le_bytes()) } o }
+        fn blob(&mut self, b: &[u8]) -> u32 { let o = self.u32s(&[b.len() as u32]); self.0.extend_from_slice(b); while self.0.len() % 4 != 0 { self.0.push(0) } o }
+        fn cstr(&mut self, s: &str) -> u32 { let o = self.len(); self.0.extend_from_slice(s.as_bytes()); self.0.push(0); o }
+    }
+
+    #[test] fn fx_2_0() {
+        let mut d = Writer::default();
+        let blob = |d: &mut Writer, s: &str| d.blob(format!("{s}\0").as_bytes());
+        let (empty, tint, color, ui_name, s, t, p0) = (blob(&mut d, ""), blob(&mut d, "tint"), blob(&mut d, "COLOR"), blob(&mut d, "UIName"), blob(&mut d, "s"), blob(&mut d, "T"), blob(&mut d, "P0"));
+
+        let float4      = d.u32s(&[3, 1, tint, color, 0, 4, 1]);                // FLOAT VECTOR, columns, rows
+        let tint_value  = d.u32s(&[1f32.to_bits(), 0, 0, 1f32.to_bits()]);
+        let string      = d.u32s(&[4, 4, ui_name, empty, 0]);                   // STRING OBJECT
+        let string_id   = d.u32s(&[1]);
+        let bool_ty     = d.u32s(&[1, 0, empty, empty, 0, 1, 1]);               // BOOL SCALAR
+        let false_value = d.u32s(&[0]);
+        let dword_ty    = d.u32s(&[2, 0, empty, empty, 0, 1, 1]);               // INT SCALAR
+        let linear      = d.u32s(&[2]);
+        let texture_ty  = d.u32s(&[5, 4, empty, empty, 0]);                     // TEXTURE OBJECT
+        let texture_id  = d.u32s(&[3]);
+        let sampler     = d.u32s(&[10, 4, s, empty, 0]);                        // SAMPLER OBJECT
+        let sampler_val = d.u32s(&[2, 0xAA, 0, dword_ty, linear, 0xA4, 0, texture_ty, texture_id]);
+        let ps_ty       = d.u32s(&[15, 4, empty, empty, 0]);                    // PIXELSHADER OBJECT
+        let ps_id       = d.u32s(&[2]);
+
+        let start = d.u32s(&[2, 1, 0, 4]);
+        d.u32s(&[float4, tint_value, 0, 1, string, string_id]);
+        d.u32s(&[sampler, sampler_val, 0, 0]);
+        d.u32s(&[t, 0, 1]);
+        d.u32s(&[p0, 0, 2, 0x00, 0, bool_ty, false_value, 0x93, 0, ps_ty, ps_id]);
+        d.u32s(&[1, 2]);                                                        // objects, resources
+        d.u32s(&[1]); d.blob(b"Tint\0");
+        d.u32s(&[0, 0, 0, 1, 0]); d.blob(&PS_2_0);                              // T.P0.PixelShader = compile ...
+        d.u32s(&[!0, 1, !0, 1, 1]); d.blob(b"tex\0");                           // s.Texture = <tex>
+
+        let mut b = Writer::default();
+        b.u32s(&[0xFEFF0901, start]);
+        b.0.extend_from_slice(&d.0);
+        let fx = parse(&b.0).unwrap();
+        assert_eq!(fx.target, ShaderTarget::FX_2_0);
+
+        let tint = fx.variable("tint").unwrap();
+        assert_eq!(tint.ty, "float4");
+        assert_eq!(tint.semantic.as_deref(), Some("COLOR"));
+        assert_eq!(tint.value, EffectBinaryValue::Constants([1.0, 0.0, 0.0, 1.0].map(EffectBinaryConstant::Float).to_vec()));
+        assert_eq!(tint.annotations, [EffectBinaryAnnotation { name: "UIName".into(), ty: "string".into(), value: EffectBinaryValue::Strings(vec!["Tint".into()]) }]);
+
+        let EffectBinaryValue::StateBlocks(blocks) = &fx.variable("s").unwrap().value else { panic!("expected sampler states") };
+        assert_eq!(blocks[0][0].name, "MinFilter");
+        assert_eq!(blocks[0][0].value, EffectBinaryValue::Constants(vec![EffectBinaryConstant::Int(2)]));
+        assert_eq!(blocks[0][1].name, "Texture");
+        assert_eq!(blocks[0][1].value, EffectBinaryValue::Variable("tex".into()));
+
+        let pass = &fx.technique("T").unwrap().passes[0];
+        assert_eq!(pass.name, "P0");
+        assert_eq!(pass.states[0].name, "ZEnable");
+        assert_eq!(pass.states[0].value, EffectBinaryValue::Constants(vec![EffectBinaryConstant::Bool(false)]));
+        assert_eq!(pass.shader("PixelShader").unwrap().bytecode().as_bytes(), PS_2_0);
+        assert_eq!(fx.shaders().len(), 1);
+
+        assert!(parse(&b.0[.. b.0.len() - 4]).is_none(), "truncated");
+        let mut bad = b.0.clone();
+        let ps = bad.windows(8).position(|w| w == PS_2_0).unwrap();
+        bad[ps + 4 .. ps + 8].copy_from_slice(&[0; 4]);                        // missing end token
+        assert!(parse(&bad).is_none(), "invalid shader");
+    }
+
+    #[test] fn fx_2_0_zero_sized_elements() {
+        let fx = |ty: &[u32]| {
+            let mut d = Writer::default();
+            let empty   = d.blob(b"\0");
+            let ty      = d.u32s(&ty.iter().map(|&t| if t == !1 { empty } else { t }).collect::<Vec<_>>());
+            let value   = d.u32s(&[0; 16]);
+            let start   = d.u32s(&[1, 0, 0, 0, ty, value, 0, 0, 0, 0]);     // 1 parameter, then 0 objects, 0 resources
+            let mut b = Writer::default();
+            b.u32s(&[0xFEFF0901, start]);
+            b.0.extend_from_slice(&d.0);
+            parse(&b.0)
+        };
+        let (e, n) = (!1, !0);                                              // empty name, element count
+        assert!(fx(&[3, 1, e, e, 4, 4, 1]).is_some(), "float4[4]");
+        assert!(fx(&[3, 1, e, e, n, 0, 0]).is_none(), "float0[0xFFFFFFFF]");
+        assert!(fx(&[3, 5, e, e, n, 0]).is_none(), "struct {{}} [0xFFFFFFFF]");
+        assert!(fx(&[3, 5, e, e, n, 1, 3, 5, e, e, 2, 0]).is_none(), "struct {{ struct {{}} m[2]; }} [0xFFFFFFFF]");
+    }
+
+    #[test] fn fx_4_0() {
+        let ps = dxbc(&[(b"SHDR", &[0x40, 0, 0, 0, 2, 0, 0, 0])]);
+
+        let mut u = Writer::default();
+        u.u32s(&[0]);                                                           // empty string placeholder
+        let (cb0, tint, float4, ui_name, string, blend, blend_ty, ps_name, ps_ty, t, p0) = (
+            u.cstr("cb0"), u.cstr("tint"), u.cstr("float4"), u.cstr("UIName"), u.cstr("string"),
+            u.cstr("blend"), u.cstr("BlendState"), u.cstr("ps"), u.cstr("PixelShader"), u.cstr("T"), u.cstr("P0"),
+        );
+        let tint_str    = u.cstr("Tint");
+        while u.0.len() % 4 != 0 { u.0.push(0) }
+        let float4_ty   = u.u32s(&[float4, 1, 0, 16, 16, 16, 1 | (1 << 3) | (1 << 8) | (4 << 11)]);
+        let string_ty   = u.u32s(&[string, 2, 0, 0, 0, 0, 1]);
+        let blend_ty    = u.u32s(&[blend_ty, 2, 0, 0, 0, 0, 2]);
+        let ps_ty       = u.u32s(&[ps_ty, 2, 0, 0, 0, 0, 5]);
+        let tint_value  = u.u32s(&[0, 1f32.to_bits(), 0, 0]);
+        let enable      = u.u32s(&[1, 4, 1]);
+        let shader      = u.blob(&ps);
+        let inline      = u.u32s(&[shader, 0]);
+
+        let mut s = Writer::default();
+        s.u32s(&[cb0, 16, 0, 1, !0, 0]);
+        s.u32s(&[tint, float4_ty, 0, 0, tint_value, 0, 1, ui_name, string_ty, tint_str]);
+        s.u32s(&[blend, blend_ty, 0, 0, 1, 37, 0, 1, enable, 0]);
+        s.u32s(&[ps_name, ps_ty, 0, 0, shader, 0]);
+        s.u32s(&[t, 1, 0, p0, 2, 0, 2, 0, 2, blend, 7, 0, 7, inline]);
+
+        let mut b = Writer::default();
+        b.u32s(&[0xFEFF1001, 1, 1, 2, 0, 0, 0, 1, u.len(), 0, 0, 0, 1, 0, 0, 0, 0, 2, 1]);
+        b.0.extend_from_slice(&u.0);
+        b.0.extend_from_slice(&s.0);
+        let fx = parse(&b.0).unwrap();
+        assert_eq!(fx.target, ShaderTarget::FX_4_0);
+        assert_eq!(parse(&dxbc(&[(b"FX10", &b.0)])).unwrap(), fx);
+
+        let tint = fx.variable("tint").unwrap();
+        assert_eq!(tint.buffer.as_deref(), Some("cb0"));
+        assert_eq!(tint.ty, "float4");
+        assert_eq!(tint.value, EffectBinaryValue::Constants([0.0, 1.0, 0.0, 0.0].map(EffectBinaryConstant::Float).to_vec()));
+        assert_eq!(tint.annotations[0].value, EffectBinaryValue::Strings(vec!["Tint".into()]));
+
+        let EffectBinaryValue::StateBlocks(blocks) = &fx.variable("blend").unwrap().value else { panic!("expected blend states") };
+        assert_eq!(blocks[0][0].name, "BlendEnable");
+        assert_eq!(blocks[0][0].value, EffectBinaryValue::Constants(vec![EffectBinaryConstant::Bool(true)]));
+        assert!(matches!(&fx.variable("ps").unwrap().value, EffectBinaryValue::Shaders(s) if s[0].as_ref().unwrap().bytecode().as_bytes() == ps));
+
+        let pass = &fx.technique("T").unwrap().passes[0];
+        assert_eq!(pass.states[0].name, "BlendState");
+        assert_eq!(pass.states[0].value, EffectBinaryValue::Variable("blend".into()));
+        assert_eq!(pass.shader("PixelShader").unwrap().bytecode().as_bytes(), ps);
+        assert_eq!(fx.shaders().len(), 2);
+
+        assert!(parse(&b.0[.. b.0.len() - 4]).is_none(), "truncated");
+        assert!(parse(&dxbc(&[(b"SHDR", &[0x40, 0, 0, 0, 2, 0, 0, 0])])).is_none(), "no FX10 chunk");
+    }
+
+    #[test] fn fx_5_0_groups() {
+        let mut u = Writer::default();
+        u.u32s(&[0]);
+        let (g, t, p) = (u.cstr("G"), u.cstr("T"), u.cstr("P"));
+        let mut s = Writer::default();
+        s.u32s(&[0, 1, 0]);                                                     // default group, 1 technique
+        s.u32s(&[t, 1, 0, p, 0, 0]);
+        s.u32s(&[g, 1, 0]);
+        s.u32s(&[t, 0, 0]);
+
+        let mut b = Writer::default();
+        b.u32s(&[0xFEFF2001, 0, 0, 0, 0, 0, 0, 2, u.len(), 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0]);
+        b.0.extend_from_slice(&u.0);
+        b.0.extend_from_slice(&s.0);
+        let fx = parse(&b.0).unwrap();
+        assert_eq!(fx.target, ShaderTarget::FX_5_0);
+        assert_eq!(fx.techniques.iter().map(|t| t.group.as_deref()).collect::<Vec<_>>(), [None, Some("G")]);
+        assert_eq!(fx.techniques[0].passes[0].name, "P");
+    }
+
+    #[test] fn state_tables() {
+        assert_eq!(FX2_STATES[0x1F], "Wrap0");
+        assert_eq!(FX2_STATES[0x2F], "Clipping");
+        assert_eq!(FX2_STATES[0x67], "ColorOp");
+        assert_eq!(FX2_STATES[0x92], "VertexShader");
+        assert_eq!(FX2_STATES[0xA4], "Texture");
+        assert_eq!(FX4_STATES[45], "Filter");
+    }
+}
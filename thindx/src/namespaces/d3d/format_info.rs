@@ -0,0 +1,386 @@
+use crate::*;
+use crate::d3d::Format;
+
+
+
+/// Metadata describing the memory layout of a [d3d::Format]:  channel bits/offsets, numeric type, block dimensions, and flags.
+///
+/// Covers every [d3d::Format] constant, including the "odd" ones [d3d9::FixedTextureFormat] leaves out
+/// ([YUY2](Format::YUY2), [UYVY](Format::UYVY), [R8G8_B8G8](Format::R8G8_B8G8), [G8R8_G8B8](Format::G8R8_G8B8),
+/// [CxV8U8](Format::CxV8U8), [MULTI2_ARGB8](Format::MULTI2_ARGB8), ...)  Lookups are `const`:
+///
+/// ```rust
+/// # use thindx::d3d::*;
+/// const A8R8G8B8 : &FormatInfo = Format::A8R8G8B8.info().unwrap();
+/// assert_eq!(A8R8G8B8.bits_per_block, 32);
+/// assert_eq!(A8R8G8B8.channels.r, FormatChannel { bits: 8, offset: 16 });
+/// assert_eq!(A8R8G8B8.numeric, FormatNumeric::Unorm);
+///
+/// let dxt5 = FormatInfo::of(Format::DXT5).unwrap();
+/// assert!(dxt5.compressed && dxt5.fourcc);
+/// assert_eq!((dxt5.bits_per_block, dxt5.block_size), (128, (4, 4)));
+/// assert_eq!(dxt5.surface_bytes(13, 5), 4 * 2 * 16);
+///
+/// let d24s8 = FormatInfo::of(Format::D24S8).unwrap();
+/// assert!(d24s8.depth && d24s8.stencil && !d24s8.lockable);
+/// assert_eq!(d24s8.numeric, FormatNumeric::Mixed);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct FormatInfo {
+    /// The format this metadata describes
+    pub format:         Format,
+
+    /// Bits per block, or `0` if the format has no fixed size ([Format::UNKNOWN], [Format::VERTEXDATA], [Format::BINARYBUFFER], [Format::MULTI2_ARGB8])
+    pub bits_per_block: u16,
+
+    /// Block size in pixels:  `(1, 1)` for most formats, `(4, 4)` for DXT formats, and `(2, 1)` for the packed YUV / RGBG formats.
+    pub block_size:     (u8, u8),
+
+    /// The numeric type of the format's channels
+    pub numeric:        FormatNumeric,
+
+    /// Bits and offsets of each channel within a (native endian) block.  Zeroed for compressed and untyped formats.
+    pub channels:       FormatChannels,
+
+    /// Block compressed ([Format::DXT1] ..= [Format::DXT5], [Format::ATI1], [Format::ATI2])
+    pub compressed:     bool,
+
+    /// Has a depth channel
+    pub depth:          bool,
+
+    /// Has a stencil channel
+    pub stencil:        bool,
+
+    /// Surfaces of this format can be locked for CPU access (every color format, only `*_LOCKABLE` depth/stencil formats.)
+    pub lockable:       bool,
+
+    /// The [Format] value is a `MAKEFOURCC` code (such as `'DXT1'` or `'YUY2'`) rather than a small integer.
+    pub fourcc:         bool,
+
+    /// An 8-bit-per-channel color or DXT format that hardware commonly supports `SRGBTexture` / `SRGBWriteEnable` reads/writes of.
+    /// Check `D3DUSAGE_QUERY_SRGBREAD` / `D3DUSAGE_QUERY_SRGBWRITE` support before relying on this.
+    pub srgb:           bool,
+
+    /// Holds indices rather than colors:  index buffer formats ([Format::INDEX16], [Format::INDEX32]) and palettized formats ([Format::P8], [Format::A8P8].)
+    pub index:          bool,
+}
+
+/// The numeric type of a [FormatInfo]'s channels.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum FormatNumeric {
+    /// No inherent numeric type ([Format::UNKNOWN], [Format::VERTEXDATA], [Format::BINARYBUFFER], [Format::MULTI2_ARGB8])
+    None,
+
+    /// Unsigned normalized integers (`0 ..= 1`)
+    Unorm,
+
+    /// Signed normalized integers (`-1 ..= 1`), such as [Format::V8U8]
+    Snorm,
+
+    /// Unsigned integers, such as palette indices or [Format::INDEX16]
+    Uint,
+
+    /// Floating point, such as [Format::R16F] or [Format::D32F_LOCKABLE]
+    Float,
+
+    /// Different channels have different types, such as [Format::L6V5U5] (unorm + snorm) or [Format::D24S8] (unorm depth + uint stencil)
+    Mixed,
+
+    /// [Format::A2B10G10R10_XR_BIAS]'s 2.8-biased fixed point
+    XrBias,
+}
+
+/// The bit `offset` and width (`bits`) of a channel within a [FormatInfo]'s block.  `bits == 0` if the channel isn't present.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct FormatChannel {
+    /// The number of bits in the channel, or `0` if the channel isn't present
+    pub bits:   u8,
+
+    /// The offset of the channel's least significant bit within the (native endian) block
+    pub offset: u8,
+}
+
+/// The [FormatChannel]s of a [FormatInfo].
+///
+/// For the packed YUV / RGBG formats, only the first of each block's duplicated channels (`Y0` / `G0`) is listed.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct FormatChannels {
+    /// Red
+    pub r: FormatChannel,
+
+    /// Green
+    pub g: FormatChannel,
+
+    /// Blue
+    pub b: FormatChannel,
+
+    /// Alpha
+    pub a: FormatChannel,
+
+    /// Luminance (or `Y` for YUV formats)
+    pub l: FormatChannel,
+
+    /// `U` (signed bump/normal map formats, or YUV chroma)
+    pub u: FormatChannel,
+
+    /// `V` (signed bump/normal map formats, or YUV chroma)
+    pub v: FormatChannel,
+
+    /// `W` (signed normal map formats)
+    pub w: FormatChannel,
+
+    /// `Q` (4th component of signed formats such as [Format::Q8W8V8U8])
+    pub q: FormatChannel,
+
+    /// Depth
+    pub d: FormatChannel,
+
+    /// Stencil
+    pub s: FormatChannel,
+}
+
+impl FormatChannel {
+    /// The absent channel (`bits == 0`)
+    pub const NONE : FormatChannel = FormatChannel { bits: 0, offset: 0 };
+
+    /// `true` if `bits != 0`
+    pub const fn is_present(&self) -> bool { self.bits != 0 }
+}
+
+impl FormatChannels {
+    /// No channels
+    pub const NONE : FormatChannels = FormatChannels {
+        r: FormatChannel::NONE, g: FormatChannel::NONE, b: FormatChannel::NONE, a: FormatChannel::NONE,
+        l: FormatChannel::NONE, u: FormatChannel::NONE, v: FormatChannel::NONE, w: FormatChannel::NONE,
+        q: FormatChannel::NONE, d: FormatChannel::NONE, s: FormatChannel::NONE,
+    };
+}
+
+impl FormatInfo {
+    /// Get the metadata for `format`, or [None] if `format` isn't a known [d3d::Format].
+    //#allow_missing_argument_docs
+    pub const fn of(format: Format) -> Option<&'static FormatInfo> {
+        let mut i = 0;
+        while i < ALL.len() {
+            if ALL[i].format.into() == format.into() { return Some(&ALL[i]) }
+            i += 1;
+        }
+        None
+    }
+
+    /// Metadata for every known [d3d::Format], in [d3d::Format] declaration order.
+    pub fn all() -> &'static [FormatInfo] { ALL }
+
+    /// `true` if the format has an alpha channel
+    pub const fn has_alpha(&self) -> bool { self.channels.a.is_present() }
+
+    /// The number of bytes per row of blocks for a `width` pixel wide surface, or `0` if the format has no fixed size.
+    //#allow_missing_argument_docs
+    pub const fn row_bytes(&self, width: u32) -> usize {
+        let blocks = (width as usize + self.block_size.0 as usize - 1) / self.block_size.0 as usize;
+        (blocks * self.bits_per_block as usize + 7) / 8
+    }
+
+    /// The number of bytes in a tightly packed `width` x `height` pixel surface, or `0` if the format has no fixed size.
+    //#allow_missing_argument_docs
+    pub const fn surface_bytes(&self, width: u32, height: u32) -> usize {
+        let rows = (height as usize + self.block_size.1 as usize - 1) / self.block_size.1 as usize;
+        self.row_bytes(width) * rows
+    }
+
+    /// [row_bytes](Self::row_bytes), or [None] if the result would overflow `usize`.
+    //#allow_missing_argument_docs
+    pub const fn checked_row_bytes(&self, width: u32) -> Option<usize> {
+        let (width, bw) = (width as usize, self.block_size.0 as usize);
+        let blocks = width / bw + (width % bw != 0) as usize;
+        match blocks.checked_mul(self.bits_per_block as usize) {
+            Some(bits)  => Some(bits / 8 + (bits % 8 != 0) as usize),
+            None        => None,
+        }
+    }
+
+    /// [surface_bytes](Self::surface_bytes), or [None] if the result would overflow `usize`.
+    //#allow_missing_argument_docs
+    pub const fn checked_surface_bytes(&self, width: u32, height: u32) -> Option<usize> {
+        let (height, bh) = (height as usize, self.block_size.1 as usize);
+        let rows = height / bh + (height % bh != 0) as usize;
+        match self.checked_row_bytes(width) {
+            Some(row_bytes) => row_bytes.checked_mul(rows),
+            None            => None,
+        }
+    }
+}
+
+impl d3d::Format {
+    /// Get the [FormatInfo] metadata for this format, or [None] if this isn't a known format.
+    pub const fn info(self) -> Option<&'static FormatInfo> { FormatInfo::of(self) }
+}
+
+
+
+const BLANK : FormatInfo = FormatInfo {
+    format:         Format::UNKNOWN,
+    bits_per_block: 0,
+    block_size:     (1, 1),
+    numeric:        FormatNumeric::None,
+    channels:       FormatChannels::NONE,
+    compressed:     false,
+    depth:          false,
+    stencil:        false,
+    lockable:       false,
+    fourcc:         false,
+    srgb:           false,
+    index:          false,
+};
+
+macro_rules! formats {
+    ( $( $format:ident = ($bits:literal, ($bw:literal, $bh:literal), $numeric:ident, [$( $ch:ident $cbits:literal @ $coff:literal ),* $(,)?], [$( $flag:ident ),* $(,)?]) ),* $(,)? ) => {
+        const ALL : &[FormatInfo] = &[$(
+            FormatInfo {
+                format:         Format::$format,
+                bits_per_block: $bits,
+                block_size:     ($bw, $bh),
+                numeric:        FormatNumeric::$numeric,
+                channels:       FormatChannels { $( $ch: FormatChannel { bits: $cbits, offset: $coff }, )* ..FormatChannels::NONE },
+                $( $flag: true, )*
+                ..BLANK
+            },
+        )*];
+    };
+}
+
+formats! {
+    // format                   bits  block  numeric  channels (bits @ offset)                                 flags
+    UNKNOWN                 = (   0, (1,1), None,    [],                                                       []),
+
+    R8G8B8                  = (  24, (1,1), Unorm,   [b 8 @ 0, g 8 @ 8, r 8 @ 16],                             [lockable, srgb]),
+    A8R8G8B8                = (  32, (1,1), Unorm,   [b 8 @ 0, g 8 @ 8, r 8 @ 16, a 8 @ 24],                   [lockable, srgb]),
+    X8R8G8B8                = (  32, (1,1), Unorm,   [b 8 @ 0, g 8 @ 8, r 8 @ 16],                             [lockable, srgb]),
+    R5G6B5                  = (  16, (1,1), Unorm,   [b 5 @ 0, g 6 @ 5, r 5 @ 11],                             [lockable]),
+    X1R5G5B5                = (  16, (1,1), Unorm,   [b 5 @ 0, g 5 @ 5, r 5 @ 10],                             [lockable]),
+    A1R5G5B5                = (  16, (1,1), Unorm,   [b 5 @ 0, g 5 @ 5, r 5 @ 10, a 1 @ 15],                   [lockable]),
+    A4R4G4B4                = (  16, (1,1), Unorm,   [b 4 @ 0, g 4 @ 4, r 4 @ 8, a 4 @ 12],                    [lockable]),
+    R3G3B2                  = (   8, (1,1), Unorm,   [b 2 @ 0, g 3 @ 2, r 3 @ 5],                              [lockable]),
+    A8                      = (   8, (1,1), Unorm,   [a 8 @ 0],                                                [lockable]),
+    A8R3G3B2                = (  16, (1,1), Unorm,   [b 2 @ 0, g 3 @ 2, r 3 @ 5, a 8 @ 8],                     [lockable]),
+    X4R4G4B4                = (  16, (1,1), Unorm,   [b 4 @ 0, g 4 @ 4, r 4 @ 8],                              [lockable]),
+    A2B10G10R10             = (  32, (1,1), Unorm,   [r 10 @ 0, g 10 @ 10, b 10 @ 20, a 2 @ 30],               [lockable]),
+    A8B8G8R8                = (  32, (1,1), Unorm,   [r 8 @ 0, g 8 @ 8, b 8 @ 16, a 8 @ 24],                   [lockable, srgb]),
+    X8B8G8R8                = (  32, (1,1), Unorm,   [r 8 @ 0, g 8 @ 8, b 8 @ 16],                             [lockable, srgb]),
+    G16R16                  = (  32, (1,1), Unorm,   [r 16 @ 0, g 16 @ 16],                                    [lockable]),
+    A2R10G10B10             = (  32, (1,1), Unorm,   [b 10 @ 0, g 10 @ 10, r 10 @ 20, a 2 @ 30],               [lockable]),
+    A16B16G16R16            = (  64, (1,1), Unorm,   [r 16 @ 0, g 16 @ 16, b 16 @ 32, a 16 @ 48],              [lockable]),
+
+    A8P8                    = (  16, (1,1), Uint,    [a 8 @ 8],                                                [lockable, index]),
+    P8                      = (   8, (1,1), Uint,    [],                                                       [lockable, index]),
+
+    L8                      = (   8, (1,1), Unorm,   [l 8 @ 0],                                                [lockable, srgb]),
+    A8L8                    = (  16, (1,1), Unorm,   [l 8 @ 0, a 8 @ 8],                                       [lockable, srgb]),
+    A4L4                    = (   8, (1,1), Unorm,   [l 4 @ 0, a 4 @ 4],                                       [lockable]),
+
+    V8U8                    = (  16, (1,1), Snorm,   [u 8 @ 0, v 8 @ 8],                                       [lockable]),
+    L6V5U5                  = (  16, (1,1), Mixed,   [u 5 @ 0, v 5 @ 5, l 6 @ 10],                             [lockable]),
+    X8L8V8U8                = (  32, (1,1), Mixed,   [u 8 @ 0, v 8 @ 8, l 8 @ 16],                             [lockable]),
+    Q8W8V8U8                = (  32, (1,1), Snorm,   [u 8 @ 0, v 8 @ 8, w 8 @ 16, q 8 @ 24],                   [lockable]),
+    V16U16                  = (  32, (1,1), Snorm,   [u 16 @ 0, v 16 @ 16],                                    [lockable]),
+    A2W10V10U10             = (  32, (1,1), Mixed,   [u 10 @ 0, v 10 @ 10, w 10 @ 20, a 2 @ 30],               [lockable]),
+
+    UYVY                    = (  32, (2,1), Unorm,   [u 8 @ 0, l 8 @ 8, v 8 @ 16],                             [lockable, fourcc]),
+    R8G8_B8G8               = (  32, (2,1), Unorm,   [g 8 @ 0, r 8 @ 8, b 8 @ 24],                             [lockable, fourcc]),
+    YUY2                    = (  32, (2,1), Unorm,   [l 8 @ 0, u 8 @ 8, v 8 @ 24],                             [lockable, fourcc]),
+    G8R8_G8B8               = (  32, (2,1), Unorm,   [r 8 @ 0, g 8 @ 8, b 8 @ 16],                             [lockable, fourcc]),
+    DXT1                    = (  64, (4,4), Unorm,   [],                                                       [compressed, lockable, fourcc, srgb]),
+    DXT2                    = ( 128, (4,4), Unorm,   [],                                                       [compressed, lockable, fourcc, srgb]),
+    DXT3                    = ( 128, (4,4), Unorm,   [],                                                       [compressed, lockable, fourcc, srgb]),
+    DXT4                    = ( 128, (4,4), Unorm,   [],                                                       [compressed, lockable, fourcc, srgb]),
+    DXT5                    = ( 128, (4,4), Unorm,   [],                                                       [compressed, lockable, fourcc, srgb]),
+
+    D16_LOCKABLE            = (  16, (1,1), Unorm,   [d 16 @ 0],                                               [depth, lockable]),
+    D32                     = (  32, (1,1), Unorm,   [d 32 @ 0],                                               [depth]),
+    D15S1                   = (  16, (1,1), Mixed,   [s 1 @ 0, d 15 @ 1],                                      [depth, stencil]),
+    D24S8                   = (  32, (1,1), Mixed,   [s 8 @ 0, d 24 @ 8],                                      [depth, stencil]),
+    D24X8                   = (  32, (1,1), Unorm,   [d 24 @ 8],                                               [depth]),
+    D24X4S4                 = (  32, (1,1), Mixed,   [s 4 @ 0, d 24 @ 8],                                      [depth, stencil]),
+    D16                     = (  16, (1,1), Unorm,   [d 16 @ 0],                                               [depth]),
+
+    D32F_LOCKABLE           = (  32, (1,1), Float,   [d 32 @ 0],                                               [depth, lockable]),
+    D24FS8                  = (  32, (1,1), Mixed,   [s 8 @ 0, d 24 @ 8],                                      [depth, stencil]),
+
+    D32_LOCKABLE            = (  32, (1,1), Unorm,   [d 32 @ 0],                                               [depth, lockable]),
+    X8_LOCKABLE             = (   8, (1,1), Uint,    [s 8 @ 0],                                                [stencil, lockable]),
+
+    L16                     = (  16, (1,1), Unorm,   [l 16 @ 0],                                               [lockable]),
+
+    VERTEXDATA              = (   0, (1,1), None,    [],                                                       [lockable]),
+    INDEX16                 = (  16, (1,1), Uint,    [],                                                       [lockable, index]),
+    INDEX32                 = (  32, (1,1), Uint,    [],                                                       [lockable, index]),
+
+    Q16W16V16U16            = (  64, (1,1), Snorm,   [u 16 @ 0, v 16 @ 16, w 16 @ 32, q 16 @ 48],              [lockable]),
+
+    MULTI2_ARGB8            = (   0, (1,1), None,    [],                                                       [fourcc]),
+
+    R16F                    = (  16, (1,1), Float,   [r 16 @ 0],                                               [lockable]),
+    G16R16F                 = (  32, (1,1), Float,   [r 16 @ 0, g 16 @ 16],                                    [lockable]),
+    A16B16G16R16F           = (  64, (1,1), Float,   [r 16 @ 0, g 16 @ 16, b 16 @ 32, a 16 @ 48],              [lockable]),
+
+    R32F                    = (  32, (1,1), Float,   [r 32 @ 0],                                               [lockable]),
+    G32R32F                 = (  64, (1,1), Float,   [r 32 @ 0, g 32 @ 32],                                    [lockable]),
+    A32B32G32R32F           = ( 128, (1,1), Float,   [r 32 @ 0, g 32 @ 32, b 32 @ 64, a 32 @ 96],              [lockable]),
+
+    CxV8U8                  = (  16, (1,1), Snorm,   [u 8 @ 0, v 8 @ 8],                                       [lockable]),
+
+    A1                      = (   1, (1,1), Unorm,   [a 1 @ 0],                                                [lockable]),
+    A2B10G10R10_XR_BIAS     = (  32, (1,1), XrBias,  [r 10 @ 0, g 10 @ 10, b 10 @ 20, a 2 @ 30],               [lockable]),
+    BINARYBUFFER            = (   0, (1,1), None,    [],                                                       [lockable]),
+
+    ATI1                    = (  64, (4,4), Unorm,   [],                                                       [compressed, lockable, fourcc]),
+    ATI2                    = ( 128, (4,4), Unorm,   [],                                                       [compressed, lockable, fourcc]),
+}
+
+
+
+#[test] fn lookup() {
+    const L8 : Option<&FormatInfo> = FormatInfo::of(Format::L8);
+    assert_eq!(L8.map(|i| i.channels.l), Some(FormatChannel { bits: 8, offset: 0 }));
+    assert_eq!(Format::from_unchecked(0x12345678).info(), None);
+    for info in FormatInfo::all() { assert_eq!(info.format.info(), Some(info)); }
+}
+
+#[test] fn channels_fit() {
+    for info in FormatInfo::all() {
+        let c = &info.channels;
+        let mut used = 0u128;
+        for ch in [c.r, c.g, c.b, c.a, c.l, c.u, c.v, c.w, c.q, c.d, c.s].into_iter().filter(|c| c.is_present()) {
+            assert!(u16::from(ch.offset) + u16::from(ch.bits) <= info.bits_per_block, "{:?} channel out of block", info.format);
+            let mask = ((1u128 << ch.bits) - 1) << ch.offset;
+            assert_eq!(used & mask, 0, "{:?} channels overlap", info.format);
+            used |= mask;
+        }
+        assert_eq!(info.depth,   c.d.is_present(), "{:?}", info.format);
+        assert_eq!(info.stencil, c.s.is_present(), "{:?}", info.format);
+        assert_eq!(info.fourcc,  info.format.into() > 0xFF, "{:?}", info.format);
+        if info.compressed { assert_eq!(info.block_size, (4, 4)); }
+    }
+}
+
+#[test] fn sizes() {
+    let yuy2 = Format::YUY2.info().unwrap();
+    assert_eq!((yuy2.row_bytes(3), yuy2.surface_bytes(3, 2)), (8, 16));
+    let a1 = Format::A1.info().unwrap();
+    assert_eq!((a1.row_bytes(9), a1.surface_bytes(9, 2)), (2, 4));
+    assert_eq!(Format::A32B32G32R32F.info().unwrap().row_bytes(2), 32);
+    assert_eq!(Format::MULTI2_ARGB8.info().unwrap().surface_bytes(4, 4), 0);
+}
+
+#[test] fn checked_sizes() {
+    for info in FormatInfo::all() {
+        for (w, h) in [(0, 0), (1, 1), (3, 2), (9, 7), (256, 128)] {
+            assert_eq!(info.checked_row_bytes(w),        Some(info.row_bytes(w)),        "{:?}", info.format);
+            assert_eq!(info.checked_surface_bytes(w, h), Some(info.surface_bytes(w, h)), "{:?}", info.format);
+        }
+    }
+    let rgba32f = Format::A32B32G32R32F.info().unwrap();
+    assert_eq!(rgba32f.checked_row_bytes(u32::MAX), usize::try_from(16 * u64::from(u32::MAX)).ok());
+    assert_eq!(rgba32f.checked_surface_bytes(u32::MAX, u32::MAX), None);
+    assert_eq!(Format::DXT1.info().unwrap().checked_surface_bytes(u32::MAX, 4), usize::try_from(8 * (u64::from(u32::MAX) / 4 + 1)).ok());
+}
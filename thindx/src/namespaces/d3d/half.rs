@@ -0,0 +1,127 @@
+use bytemuck::*;
+
+use std::fmt::{self, Debug, Display, Formatter};
+
+
+
+/// A 16-bit IEEE 754 half precision float, as used by [Format::R16F](crate::d3d::Format::R16F), [Format::A16B16G16R16F](crate::d3d::Format::A16B16G16R16F),
+/// `D3DDECLTYPE_FLOAT16_2`, etc.
+///
+/// This is a storage type:  convert to/from [f32] to do math.  Conversion from [f32] rounds to nearest (ties to even),
+/// overflows to infinity, and preserves NaN-ness.
+///
+/// ```rust
+/// # use thindx::d3d::Half;
+/// assert_eq!(Half::from_f32(1.0).to_bits(), 0x3C00);
+/// assert_eq!(Half::from_bits(0xC000).to_f32(), -2.0);
+/// assert_eq!(Half::from_f32(65536.0).to_f32(), f32::INFINITY);
+/// assert_eq!(f32::from(Half::from_f32(0.333)), 0.33300781);
+/// assert!(Half::from_f32(f32::NAN).is_nan());
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[derive(Pod, Zeroable)]
+#[repr(transparent)] pub struct Half(u16);
+
+impl Half {
+    /// `0.0`
+    pub const ZERO      : Half = Half(0x0000);
+
+    /// `1.0`
+    pub const ONE       : Half = Half(0x3C00);
+
+    /// `65504.0`, the largest finite half
+    pub const MAX       : Half = Half(0x7BFF);
+
+    /// `+∞`
+    pub const INFINITY  : Half = Half(0x7C00);
+
+    /// Reinterpret raw IEEE 754 binary16 bits as a half.
+    //#allow_missing_argument_docs
+    pub const fn from_bits(bits: u16) -> Self { Self(bits) }
+
+    /// The raw IEEE 754 binary16 bits of this half.
+    pub const fn to_bits(self) -> u16 { self.0 }
+
+    /// `true` if this half is NaN.
+    pub const fn is_nan(self) -> bool { (self.0 & 0x7C00) == 0x7C00 && (self.0 & 0x03FF) != 0 }
+
+    /// Convert an [f32] to the nearest half.
+    //#allow_missing_argument_docs
+    pub fn from_f32(value: f32) -> Self {
+        let bits = value.to_bits();
+        let sign = ((bits >> 16) & 0x8000) as u16;
+        let exp  = ((bits >> 23) & 0xFF) as i32;
+        let man  = bits & 0x007F_FFFF;
+
+        if exp == 0xFF { // inf / nan
+            let nan = if man != 0 { 0x0200 | (man >> 13) as u16 } else { 0 };
+            return Self(sign | 0x7C00 | nan);
+        }
+
+        let exp = exp - 127 + 15;
+        if exp >= 0x1F { return Self(sign | 0x7C00) } // overflow
+
+        if exp <= 0 { // subnormal or zero
+            if exp < -10 { return Self(sign) }
+            let man     = man | 0x0080_0000;
+            let shift   = (14 - exp) as u32;
+            let half    = man >> shift;
+            let rem     = man & ((1 << shift) - 1);
+            let mid     = 1 << (shift - 1);
+            let round   = (rem > mid || (rem == mid && (half & 1) != 0)) as u32;
+            return Self(sign | (half + round) as u16);
+        }
+
+        let half    = ((exp as u32) << 10) | (man >> 13);
+        let rem     = man & 0x1FFF;
+        let round   = (rem > 0x1000 || (rem == 0x1000 && (half & 1) != 0)) as u32;
+        Self(sign | (half + round) as u16) // a mantissa carry correctly bumps the exponent (and can round up to infinity)
+    }
+
+    /// Convert this half to an [f32] (lossless.)
+    pub fn to_f32(self) -> f32 {
+        let sign = ((self.0 & 0x8000) as u32) << 16;
+        let exp  = ((self.0 >> 10) & 0x1F) as u32;
+        let man  = (self.0 & 0x03FF) as u32;
+
+        let bits = if exp == 0x1F {
+            sign | 0x7F80_0000 | (man << 13)
+        } else if exp != 0 {
+            sign | ((exp + 127 - 15) << 23) | (man << 13)
+        } else if man == 0 {
+            sign
+        } else { // subnormal:  normalize
+            let shift = man.leading_zeros() - 21; // bring the leading 1 to bit 10
+            let man = (man << shift) & 0x03FF;
+            sign | ((127 - 15 + 1 - shift) << 23) | (man << 13)
+        };
+        f32::from_bits(bits)
+    }
+}
+
+impl From<Half> for f32 { fn from(value: Half) -> Self { value.to_f32() } }
+impl From<f32> for Half { fn from(value: f32) -> Self { Half::from_f32(value) } }
+
+impl Debug   for Half { fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { Debug  ::fmt(&self.to_f32(), fmt) } }
+impl Display for Half { fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { Display::fmt(&self.to_f32(), fmt) } }
+
+
+
+#[test] fn round_trip() {
+    for bits in 0 ..= u16::MAX {
+        let h = Half::from_bits(bits);
+        if h.is_nan() { assert!(Half::from_f32(h.to_f32()).is_nan()); continue }
+        assert_eq!(Half::from_f32(h.to_f32()).to_bits(), bits, "0x{bits:04X}");
+    }
+}
+
+#[test] fn rounding() {
+    assert_eq!(Half::from_f32(65504.0), Half::MAX);
+    assert_eq!(Half::from_f32(65519.0), Half::MAX);
+    assert_eq!(Half::from_f32(65520.0), Half::INFINITY);
+    assert_eq!(Half::from_f32(1.0 + 1.0/2048.0).to_bits(), 0x3C00); // tie => even
+    assert_eq!(Half::from_f32(1.0 + 3.0/2048.0).to_bits(), 0x3C02); // tie => even
+    assert_eq!(Half::from_f32(5.960_464_5e-8).to_bits(), 0x0001); // smallest subnormal
+    assert_eq!(Half::from_f32(2.0e-8).to_bits(), 0x0000);
+    assert_eq!(Half::from_f32(-0.0).to_bits(), 0x8000);
+}
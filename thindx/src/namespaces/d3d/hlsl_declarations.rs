@@ -0,0 +1,804 @@
+use crate::d3d::*;
+
+
+
+/// The top-level declarations of HLSL source, parsed without compiling.
+///
+/// This is a lightweight, declaration-level parser: it collects `struct`s, `cbuffer`s / `tbuffer`s, global variables
+/// (textures, samplers, buffers, ...), and function signatures - including semantics, `register(...)` /
+/// `packoffset(...)` annotations, and attributes such as `[numthreads(8, 8, 1)]`.  Function bodies, initializers, and
+/// effect (`technique`) blocks are skipped without being parsed.
+///
+/// No preprocessing is performed: run the source through [Compiler::preprocess] (or any other C preprocessor) first
+/// if it uses `#include`s or macros.  `#line` directives in the preprocessed output are honored, so [HlslLocation]s
+/// refer to the original files.
+///
+/// This doesn't require `d3dcompiler_*.dll`, so it's usable from material editors and linting tools on any platform.
+///
+/// ### Errors
+/// *   [Diagnostic] - if a declaration is malformed, or brackets are unbalanced
+///
+/// ### Examples
+/// ```rust
+/// # use thindx::d3d::*;
+/// let decls = HlslDeclarations::parse(r#"
+///     cbuffer Camera : register(b1) { float4x4 view_proj; float3 eye : packoffset(c4); };
+///     Texture2D<float4>   diffuse : register(t0);
+///     SamplerState        linear_clamp : register(s0);
+///     RWTexture2D<float4> output;
+///
+///     [numthreads(8, 8, 1)]
+///     void cs_main(uint3 id : SV_DispatchThreadID) { output[id.xy] = diffuse.SampleLevel(linear_clamp, id.xy / 64.0, 0); }
+/// "#).unwrap();
+///
+/// let camera = decls.cbuffer("Camera").unwrap();
+/// assert_eq!(camera.register.as_ref().unwrap().to_string(), "b1");
+/// assert_eq!(camera.members[1].packoffset.as_deref(), Some("c4"));
+///
+/// let diffuse = decls.global("diffuse").unwrap();
+/// assert_eq!(diffuse.ty, "Texture2D<float4>");
+/// assert_eq!(diffuse.register_class(), Some('t'));
+///
+/// let cs = decls.function("cs_main").unwrap();
+/// assert_eq!(cs.numthreads(), Some([8, 8, 1]));
+/// assert_eq!(cs.parameters[0].semantic.as_deref(), Some("SV_DispatchThreadID"));
+///
+/// let err = HlslDeclarations::parse("cbuffer Broken {\n    float4 x : register(q3);\n};").unwrap_err();
+/// assert_eq!(err.to_string(), "error: invalid register \"q3\"");
+/// assert_eq!(err.line, Some(2));
+/// ```
+///
+/// ### See Also
+/// *   [d3d11::ReflectionData](crate::d3d11::ReflectionData) - for binding information from compiled bytecode
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HlslDeclarations {
+    /// `struct Name { ... };` declarations
+    pub structs:    Vec<HlslStruct>,
+
+    /// `cbuffer Name { ... }` / `tbuffer Name { ... }` declarations
+    pub cbuffers:   Vec<HlslCBuffer>,
+
+    /// Global variables: textures, samplers, buffers, UAVs, `groupshared` memory, and loose (`$Globals`) constants
+    pub globals:    Vec<HlslVariable>,
+
+    /// Function definitions and prototypes
+    pub functions:  Vec<HlslFunction>,
+}
+
+/// Where a declaration was found.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct HlslLocation {
+    /// The file, if a `#line` directive named one.
+    pub file:   Option<String>,
+
+    /// The 1-based line (adjusted by `#line` directives.)
+    pub line:   u32,
+
+    /// The 1-based column.
+    pub column: u32,
+}
+
+/// A `struct Name { ... };` declaration.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HlslStruct {
+    /// The struct's name.
+    pub name:       String,
+
+    /// The struct's fields (member functions are skipped.)
+    pub members:    Vec<HlslVariable>,
+
+    /// Where the struct was declared.
+    pub location:   HlslLocation,
+}
+
+/// A `cbuffer Name : register(b#) { ... }` / `tbuffer Name { ... }` declaration.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HlslCBuffer {
+    /// `true` for a `tbuffer`, `false` for a `cbuffer`.
+    pub tbuffer:    bool,
+
+    /// The buffer's name.
+    pub name:       String,
+
+    /// The explicit `register(...)`, if any.
+    pub register:   Option<HlslRegister>,
+
+    /// The buffer's variables.
+    pub members:    Vec<HlslVariable>,
+
+    /// Where the buffer was declared.
+    pub location:   HlslLocation,
+}
+
+/// A variable, struct member, or function parameter declaration, such as `Texture2D<float4> diffuse : register(t0)` or `in float4 color : COLOR0`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HlslVariable {
+    /// Storage class, interpolation, and parameter modifiers, such as `static`, `groupshared`, `row_major`, `nointerpolation`, `inout`, or `triangle`.
+    pub modifiers:  Vec<String>,
+
+    /// The type, including any template arguments: `float4`, `Texture2D<float4>`, `StructuredBuffer<Light>`, ...
+    pub ty:         String,
+
+    /// The variable's name (empty for unnamed prototype parameters.)
+    pub name:       String,
+
+    /// Array dimensions, as written (e.g. `["4", "MAX_LIGHTS"]` for `x[4][MAX_LIGHTS]`, or `[""]` for `x[]`.)
+    pub array:      Vec<String>,
+
+    /// The semantic, if any (e.g. `"SV_Position"`, `"TEXCOORD0"`.)
+    pub semantic:   Option<String>,
+
+    /// The explicit `register(...)`, if any.
+    pub register:   Option<HlslRegister>,
+
+    /// The explicit `packoffset(...)`, if any (e.g. `"c4.y"`.)
+    pub packoffset: Option<String>,
+
+    /// Where the variable was declared.
+    pub location:   HlslLocation,
+}
+
+/// A function definition or prototype, such as `[numthreads(8, 8, 1)] void main(uint3 id : SV_DispatchThreadID) { ... }`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HlslFunction {
+    /// Attributes preceding the function, such as `[numthreads(8, 8, 1)]` or `[domain("tri")]`.
+    pub attributes: Vec<HlslAttribute>,
+
+    /// Modifiers preceding the return type, such as `export` or `inline`.
+    pub modifiers:  Vec<String>,
+
+    /// The return type (`void` if none.)
+    pub ty:         String,
+
+    /// The function's name.
+    pub name:       String,
+
+    /// The function's parameters.
+    pub parameters: Vec<HlslVariable>,
+
+    /// The semantic of the return value, if any (e.g. `"SV_Target"`.)
+    pub semantic:   Option<String>,
+
+    /// `true` if this is a definition (has a body), `false` if it's only a prototype.
+    pub defined:    bool,
+
+    /// Where the function was declared.
+    pub location:   HlslLocation,
+}
+
+/// An attribute such as `[numthreads(8, 8, 1)]`, `[domain("tri")]`, or `[earlydepthstencil]`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HlslAttribute {
+    /// The attribute's name (e.g. `"numthreads"`.)
+    pub name:       String,
+
+    /// The attribute's arguments, as written (string literals are unquoted.)
+    pub arguments:  Vec<String>,
+}
+
+/// An explicit `register([profile,] b#|t#|c#|s#|u#|i#[, space#])` annotation.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct HlslRegister {
+    /// The shader profile this register applies to (e.g. `"ps_5_0"`), or [None] for all profiles.
+    pub profile:    Option<String>,
+
+    /// The register type: `'b'`, `'t'`, `'c'`, `'s'`, `'u'`, or `'i'`.
+    pub class:      char,
+
+    /// The register index.
+    pub index:      u32,
+
+    /// The register space (SM5.1+), if specified.
+    pub space:      Option<u32>,
+}
+
+
+
+impl HlslDeclarations {
+    /// Parse the top-level declarations of (preprocessed) HLSL `source`.
+    ///
+    /// ### Errors
+    /// *   [Diagnostic] - if a declaration is malformed, or brackets are unbalanced
+    //#allow_missing_argument_docs
+    pub fn parse(source: &str) -> Result<Self, Diagnostic> {
+        let mut parser = Parser { tokens: tokenize(source)?, pos: 0 };
+        let mut decls = Self::default();
+        let mut attributes = Vec::new();
+
+        while let Some(tok) = parser.peek() {
+            match tok {
+                Tok::Punct(';')                                 => { parser.pos += 1; },
+                Tok::Punct('[')                                 => attributes.push(parser.attribute()?),
+                Tok::Ident(kw) if kw == "cbuffer" || kw == "tbuffer" => {
+                    attributes.clear();
+                    decls.cbuffers.push(parser.cbuffer()?);
+                },
+                Tok::Ident(kw) if kw == "struct" && matches!(parser.peek_at(2), Some(Tok::Punct('{'))) => {
+                    attributes.clear();
+                    decls.structs.push(parser.structure()?);
+                },
+                Tok::Ident(kw) if kw == "typedef"               => { attributes.clear(); parser.skip_statement()?; },
+                Tok::Ident(kw) if SKIPPED_BLOCKS.contains(&kw.as_str()) => { attributes.clear(); parser.skip_block()?; },
+                _                                               => match parser.declaration()? {
+                    Declaration::Variables(vars)    => { attributes.clear(); decls.globals.extend(vars) },
+                    Declaration::Function(mut f)    => { f.attributes = std::mem::take(&mut attributes); decls.functions.push(f) },
+                },
+            }
+        }
+        Ok(decls)
+    }
+
+    /// Find a `struct` by name.
+    pub fn structure(&self, name: &str) -> Option<&HlslStruct> { self.structs.iter().find(|s| s.name == name) }
+
+    /// Find a `cbuffer` / `tbuffer` by name.
+    pub fn cbuffer(&self, name: &str) -> Option<&HlslCBuffer> { self.cbuffers.iter().find(|cb| cb.name == name) }
+
+    /// Find a global variable by name.
+    pub fn global(&self, name: &str) -> Option<&HlslVariable> { self.globals.iter().find(|v| v.name == name) }
+
+    /// Find a function by name (the first overload, if there are several.)
+    pub fn function(&self, name: &str) -> Option<&HlslFunction> { self.functions.iter().find(|f| f.name == name) }
+
+    /// Every explicit `register(...)` assignment of a `cbuffer`, `tbuffer`, or global variable, as `(name, register)`.
+    pub fn registers(&self) -> impl Iterator<Item = (&str, &HlslRegister)> {
+        let cbuffers    = self.cbuffers.iter().filter_map(|cb| Some((cb.name.as_str(), cb.register.as_ref()?)));
+        let globals     = self.globals .iter().filter_map(|v|  Some((v .name.as_str(), v .register.as_ref()?)));
+        cbuffers.chain(globals)
+    }
+}
+
+impl HlslVariable {
+    /// The register type this variable would be bound to, based on its type:
+    /// `'t'` for textures and read-only buffers, `'s'` for samplers, `'u'` for UAVs, or [None] for everything else.
+    pub fn register_class(&self) -> Option<char> {
+        let base = self.ty.split('<').next().unwrap_or("").trim();
+        let lower = base.to_ascii_lowercase();
+        if lower.starts_with("sampler") {
+            Some('s')
+        } else if base.starts_with("RW") || base.starts_with("RasterizerOrdered") || base == "AppendStructuredBuffer" || base == "ConsumeStructuredBuffer" {
+            Some('u')
+        } else if lower.starts_with("texture") || matches!(base, "Buffer" | "StructuredBuffer" | "ByteAddressBuffer" | "RaytracingAccelerationStructure") {
+            Some('t')
+        } else {
+            None
+        }
+    }
+}
+
+impl HlslFunction {
+    /// Find an attribute by name (case insensitive, as HLSL attributes are.)
+    pub fn attribute(&self, name: &str) -> Option<&HlslAttribute> { self.attributes.iter().find(|a| a.name.eq_ignore_ascii_case(name)) }
+
+    /// The `[numthreads(x, y, z)]` of a compute shader entry point, if present and made of integer literals.
+    pub fn numthreads(&self) -> Option<[u32; 3]> {
+        let a = self.attribute("numthreads")?;
+        match a.arguments.iter().map(|a| a.parse::<u32>().ok()).collect::<Option<Vec<_>>>()?[..] {
+            [x, y, z]   => Some([x, y, z]),
+            _           => None,
+        }
+    }
+}
+
+/// Formats as written inside `register(...)`, e.g. `t0`, `ps_5_0, c4`, or `u1, space2`
+impl std::fmt::Display for HlslRegister {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(profile) = self.profile.as_ref() { write!(fmt, "{}, ", profile)?; }
+        write!(fmt, "{}{}", self.class, self.index)?;
+        if let Some(space) = self.space { write!(fmt, ", space{}", space)?; }
+        Ok(())
+    }
+}
+
+
+
+/// Modifiers that may precede a type
+const MODIFIERS : &[&str] = &[
+    "static", "const", "uniform", "extern", "volatile", "shared", "groupshared", "precise", "export", "inline",
+    "row_major", "column_major", "snorm", "unorm", "globallycoherent",
+    "nointerpolation", "linear", "centroid", "noperspective", "sample",
+    "in", "out", "inout", "point", "line", "triangle", "lineadj", "triangleadj", "vertices", "primitives", "indices", "payload",
+];
+
+/// Top-level blocks that are skipped entirely
+const SKIPPED_BLOCKS : &[&str] = &["namespace", "technique", "technique10", "technique11", "interface", "class"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Tok { Ident(String), Number(String), Str(String), Punct(char) }
+
+pub(crate) struct Token { pub(crate) tok: Tok, pub(crate) location: HlslLocation }
+
+enum Declaration { Variables(Vec<HlslVariable>), Function(HlslFunction) }
+
+pub(crate) struct Parser { pub(crate) tokens: Vec<Token>, pub(crate) pos: usize }
+
+impl Parser {
+    pub(crate) fn peek(&self) -> Option<&Tok> { self.peek_at(0) }
+    pub(crate) fn peek_at(&self, n: usize) -> Option<&Tok> { self.tokens.get(self.pos + n).map(|t| &t.tok) }
+    pub(crate) fn is_punct(&self, ch: char) -> bool { self.peek() == Some(&Tok::Punct(ch)) }
+
+    pub(crate) fn location(&self) -> HlslLocation {
+        self.tokens.get(self.pos).or(self.tokens.last()).map_or_else(HlslLocation::default, |t| t.location.clone())
+    }
+
+    pub(crate) fn error(&self, message: impl Into<String>) -> Diagnostic {
+        let location = self.location();
+        let line = Some(location.line).filter(|l| *l != 0);
+        Diagnostic { file: location.file, line, columns: line.map(|_| location.column ..= location.column), severity: DiagnosticSeverity::Error, code: None, message: message.into() }
+    }
+
+    pub(crate) fn next(&mut self) -> Result<Tok, Diagnostic> {
+        let tok = self.peek().cloned().ok_or_else(|| self.error("unexpected end of file"))?;
+        self.pos += 1;
+        Ok(tok)
+    }
+
+    pub(crate) fn punct(&mut self, ch: char) -> Result<(), Diagnostic> {
+        if self.is_punct(ch) { self.pos += 1; Ok(()) } else { Err(self.error(format!("expected '{}'", ch))) }
+    }
+
+    pub(crate) fn ident(&mut self) -> Result<String, Diagnostic> {
+        match self.peek() {
+            Some(Tok::Ident(i)) => { let i = i.clone(); self.pos += 1; Ok(i) },
+            _                   => Err(self.error("expected an identifier")),
+        }
+    }
+
+    /// Skip a balanced `(...)`, `[...]`, or `{...}` group starting at the current token
+    pub(crate) fn skip_group(&mut self) -> Result<(), Diagnostic> {
+        let mut stack = Vec::new();
+        loop {
+            let location = self.pos;
+            match self.next()? {
+                Tok::Punct(open @ ('(' | '[' | '{'))    => stack.push(open),
+                Tok::Punct(close @ (')' | ']' | '}'))   => {
+                    let expected = match stack.pop() { Some('(') => ')', Some('[') => ']', _ => '}' };
+                    if close != expected { self.pos = location; return Err(self.error(format!("mismatched '{}'", close))) }
+                },
+                _                                       => {},
+            }
+            if stack.is_empty() { return Ok(()) }
+        }
+    }
+
+    /// Skip tokens until one of `ends` is found outside of any brackets (the end token isn't consumed)
+    pub(crate) fn skip_until(&mut self, ends: &[char]) -> Result<(), Diagnostic> {
+        loop {
+            match self.peek() {
+                Some(Tok::Punct(ch)) if ends.contains(ch)   => return Ok(()),
+                Some(Tok::Punct('(' | '[' | '{'))           => self.skip_group()?,
+                Some(Tok::Punct(close @ (')' | ']' | '}'))) => return Err(self.error(format!("unexpected '{}'", close))),
+                Some(_)                                     => self.pos += 1,
+                None                                        => return Err(self.error("unexpected end of file")),
+            }
+        }
+    }
+
+    /// Skip a statement through its terminating `;`
+    pub(crate) fn skip_statement(&mut self) -> Result<(), Diagnostic> {
+        self.skip_until(&[';'])?;
+        self.punct(';')
+    }
+
+    /// Skip `keyword ... { ... }` and an optional trailing `;`
+    pub(crate) fn skip_block(&mut self) -> Result<(), Diagnostic> {
+        self.skip_until(&['{'])?;
+        self.skip_group()?;
+        if self.is_punct(';') { self.pos += 1 }
+        Ok(())
+    }
+
+    /// Render tokens `start .. self.pos` as source text
+    pub(crate) fn text(&self, start: usize) -> String {
+        let mut out = String::new();
+        let mut prev_word = false;
+        for t in self.tokens[start .. self.pos].iter() {
+            let word = matches!(t.tok, Tok::Ident(_) | Tok::Number(_));
+            if word && prev_word { out.push(' ') }
+            match &t.tok {
+                Tok::Ident(s) | Tok::Number(s)  => out.push_str(s),
+                Tok::Str(s)                     => out.push_str(s),
+                Tok::Punct(',')                 => out.push_str(", "),
+                Tok::Punct(ch)                  => out.push(*ch),
+            }
+            prev_word = word;
+        }
+        out
+    }
+
+    /// Parse comma separated arguments up to (and including) the closing `)` or `]`
+    pub(crate) fn arguments(&mut self, close: char) -> Result<Vec<String>, Diagnostic> {
+        let mut args = Vec::new();
+        if self.is_punct(close) { self.pos += 1; return Ok(args) }
+        loop {
+            let start = self.pos;
+            self.skip_until(&[',', close])?;
+            args.push(self.text(start));
+            if self.next()? == Tok::Punct(close) { return Ok(args) }
+        }
+    }
+
+    /// `[name]` or `[name(args...)]`
+    pub(crate) fn attribute(&mut self) -> Result<HlslAttribute, Diagnostic> {
+        self.punct('[')?;
+        let name = self.ident()?;
+        let arguments = if self.is_punct('(') { self.pos += 1; self.arguments(')')? } else { Vec::new() };
+        self.punct(']')?;
+        Ok(HlslAttribute { name, arguments })
+    }
+
+    /// `cbuffer Name [: register(...)] { members } [;]`
+    fn cbuffer(&mut self) -> Result<HlslCBuffer, Diagnostic> {
+        let location = self.location();
+        let tbuffer = self.ident()? == "tbuffer";
+        let name = self.ident()?;
+        let mut register = None;
+        while self.is_punct(':') {
+            self.pos += 1;
+            match self.ident()?.as_str() {
+                "register"  => register = Some(self.register()?),
+                _           => return Err(self.error("expected 'register'")),
+            }
+        }
+        let members = self.members()?;
+        if self.is_punct(';') { self.pos += 1 }
+        Ok(HlslCBuffer { tbuffer, name, register, members, location })
+    }
+
+    /// `struct Name { members } [declarators];`
+    fn structure(&mut self) -> Result<HlslStruct, Diagnostic> {
+        let location = self.location();
+        self.ident()?; // "struct"
+        let name = self.ident()?;
+        let members = self.members()?;
+        self.skip_statement()?;
+        Ok(HlslStruct { name, members, location })
+    }
+
+    /// `{ members }`
+    fn members(&mut self) -> Result<Vec<HlslVariable>, Diagnostic> {
+        self.punct('{')?;
+        let mut members = Vec::new();
+        loop {
+            match self.peek() {
+                Some(Tok::Punct('}'))   => { self.pos += 1; return Ok(members) },
+                Some(Tok::Punct(';'))   => self.pos += 1,
+                Some(Tok::Punct('['))   => { self.attribute()?; },
+                _                       => match self.declaration()? {
+                    Declaration::Variables(vars)    => members.extend(vars),
+                    Declaration::Function(_)        => {}, // member functions aren't interesting
+                },
+            }
+        }
+    }
+
+    /// `modifiers... type`
+    pub(crate) fn ty(&mut self) -> Result<(Vec<String>, String), Diagnostic> {
+        let mut modifiers = Vec::new();
+        while let Some(Tok::Ident(i)) = self.peek() {
+            // `line` etc. are also valid identifiers: only treat them as modifiers if a type + name follow
+            if !MODIFIERS.contains(&i.as_str()) || !matches!(self.peek_at(1), Some(Tok::Ident(_))) { break }
+            modifiers.push(i.clone());
+            self.pos += 1;
+        }
+        if self.peek() == Some(&Tok::Ident("struct".into())) { self.pos += 1 }
+
+        let start = self.pos;
+        let base = self.ident()?;
+        if base == "unsigned" && matches!(self.peek_at(1), Some(Tok::Ident(_))) { self.ident()?; }
+        if self.is_punct('<') {
+            let mut depth = 0;
+            loop {
+                match self.next()? {
+                    Tok::Punct('<')             => depth += 1,
+                    Tok::Punct('>')             => { depth -= 1; if depth == 0 { break } },
+                    Tok::Punct(';' | '{' | '}') => return Err(self.error("unterminated template arguments")),
+                    _                           => {},
+                }
+            }
+        }
+        Ok((modifiers, self.text(start)))
+    }
+
+    /// `: SEMANTIC`, `: register(...)`, and `: packoffset(...)` annotations
+    pub(crate) fn annotations(&mut self, semantic: &mut Option<String>, register: &mut Option<HlslRegister>, packoffset: &mut Option<String>) -> Result<(), Diagnostic> {
+        while self.is_punct(':') {
+            self.pos += 1;
+            match self.ident()?.as_str() {
+                "register"      => *register = Some(self.register()?),
+                "packoffset"    => {
+                    self.punct('(')?;
+                    let start = self.pos;
+                    self.skip_until(&[')'])?;
+                    *packoffset = Some(self.text(start));
+                    self.punct(')')?;
+                },
+                other           => *semantic = Some(other.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// `(args...)` of a `register(...)` annotation
+    fn register(&mut self) -> Result<HlslRegister, Diagnostic> {
+        self.punct('(')?;
+        let start = self.pos;
+        let args = self.arguments(')')?;
+        let mut register = HlslRegister::default();
+        for arg in args.iter() {
+            let digits = |s: &str| s.parse::<u32>().ok();
+            let reg = arg.split('[').next().unwrap_or("").trim();
+            if let Some(space) = reg.strip_prefix("space").and_then(digits) {
+                register.space = Some(space);
+            } else if let Some(index) = reg.get(1..).and_then(digits).filter(|_| reg.starts_with(['b', 't', 'c', 's', 'u', 'i'])) {
+                register.class = reg.chars().next().unwrap_or_default();
+                register.index = index;
+            } else if register.class == '\0' && register.profile.is_none() && arg.contains('_') {
+                register.profile = Some(arg.clone());
+            } else {
+                self.pos = start;
+                return Err(self.error(format!("invalid register {:?}", arg)));
+            }
+        }
+        if register.class == '\0' { self.pos = start; return Err(self.error("register(...) is missing a register")) }
+        Ok(register)
+    }
+
+    /// A variable declaration (possibly with multiple declarators), or a function declaration/definition
+    fn declaration(&mut self) -> Result<Declaration, Diagnostic> {
+        let location = self.location();
+        let (modifiers, ty) = self.ty()?;
+        let name = self.ident()?;
+
+        if self.is_punct('(') {
+            self.pos += 1;
+            let mut parameters = Vec::new();
+            if self.is_punct(')') { self.pos += 1 } else { loop {
+                let p = self.parameter()?;
+                if parameters.is_empty() && p.ty == "void" && p.name.is_empty() && self.is_punct(')') { self.pos += 1; break }
+                parameters.push(p);
+                match self.next()? {
+                    Tok::Punct(',') => {},
+                    Tok::Punct(')') => break,
+                    _               => { self.pos -= 1; return Err(self.error("expected ',' or ')'")) },
+                }
+            }}
+            let (mut semantic, mut register, mut packoffset) = (None, None, None);
+            self.annotations(&mut semantic, &mut register, &mut packoffset)?;
+            let defined = self.is_punct('{');
+            if defined { self.skip_group()? } else { self.punct(';')? }
+            return Ok(Declaration::Function(HlslFunction { attributes: Vec::new(), modifiers, ty, name, parameters, semantic, defined, location }));
+        }
+
+        let mut vars = Vec::new();
+        let mut var = HlslVariable { modifiers, ty, name, location, ..Default::default() };
+        loop {
+            self.declarator(&mut var)?;
+            if self.is_punct('=') { self.pos += 1; self.skip_until(&[',', ';'])?; }
+            else if self.is_punct('{') { self.skip_group()? } // effect state blocks: `SamplerState s { Filter = ...; };`
+            vars.push(var.clone());
+            match self.next()? {
+                Tok::Punct(',') => { var.location = self.location(); var.name = self.ident()?; },
+                Tok::Punct(';') => return Ok(Declaration::Variables(vars)),
+                _               => { self.pos -= 1; return Err(self.error("expected ';'")) },
+            }
+        }
+    }
+
+    /// A function parameter, up to (but excluding) the following `,` or `)`
+    fn parameter(&mut self) -> Result<HlslVariable, Diagnostic> {
+        let location = self.location();
+        let (modifiers, ty) = self.ty()?;
+        let name = if let Some(Tok::Ident(_)) = self.peek() { self.ident()? } else { String::new() };
+        let mut p = HlslVariable { modifiers, ty, name, location, ..Default::default() };
+        self.declarator(&mut p)?;
+        if self.is_punct('=') { self.pos += 1; self.skip_until(&[',', ')'])?; }
+        Ok(p)
+    }
+
+    /// Array dimensions, annotations, and `<...>` effect annotations following a variable name
+    fn declarator(&mut self, var: &mut HlslVariable) -> Result<(), Diagnostic> {
+        var.array.clear();
+        (var.semantic, var.register, var.packoffset) = (None, None, None);
+        while self.is_punct('[') {
+            self.pos += 1;
+            let start = self.pos;
+            self.skip_until(&[']'])?;
+            var.array.push(self.text(start));
+            self.punct(']')?;
+        }
+        self.annotations(&mut var.semantic, &mut var.register, &mut var.packoffset)?;
+        if self.is_punct('<') {
+            self.skip_until(&['>'])?;
+            self.punct('>')?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn tokenize(source: &str) -> Result<Vec<Token>, Diagnostic> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut file = None;
+    let (mut i, mut line, mut line_start, mut line_has_tokens) = (0, 1, 0, false);
+
+    while i < chars.len() {
+        let ch = chars[i];
+        let location = HlslLocation { file: file.clone(), line, column: (i - line_start + 1) as u32 };
+        let start = i;
+        match ch {
+            '\n'                                            => { i += 1; line = next_line(line); line_start = i; line_has_tokens = false; continue },
+            c if c.is_whitespace()                          => { i += 1; continue },
+            '/' if chars.get(i+1) == Some(&'/')             => { while i < chars.len() && chars[i] != '\n' { i += 1 } continue },
+            '/' if chars.get(i+1) == Some(&'*')             => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i+1) == Some(&'/')) {
+                    if chars[i] == '\n' { line = next_line(line); line_start = i+1 }
+                    i += 1;
+                }
+                if i >= chars.len() { return Err(error(location, "unterminated comment")) }
+                i += 2;
+                continue
+            },
+            '#' if !line_has_tokens                         => {
+                let mut directive = String::new();
+                while i < chars.len() && chars[i] != '\n' {
+                    if chars[i] == '\\' && chars.get(i+1) == Some(&'\n') { i += 2; line = next_line(line); line_start = i; continue }
+                    directive.push(chars[i]);
+                    i += 1;
+                }
+                // `#line 12 "file"` / `# 12 "file"`: the *next* line is line 12 of "file" (`#line 0` wraps back around to 0)
+                let rest = directive[1..].trim_start();
+                let rest = rest.strip_prefix("line").unwrap_or(rest).trim_start();
+                let (number, name) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                if let Ok(number) = number.parse::<u32>() {
+                    line = number.wrapping_sub(1);
+                    if let Some(name) = name.trim().strip_prefix('"').and_then(|n| n.strip_suffix('"')) { file = Some(unescape(name)) }
+                }
+                continue
+            },
+            '"'                                             => {
+                i += 1;
+                while i < chars.len() && chars[i] != '"' && chars[i] != '\n' { i += if chars[i] == '\\' { 2 } else { 1 } }
+                if chars.get(i) != Some(&'"') { return Err(error(location, "unterminated string")) }
+                i += 1;
+                tokens.push(Token { tok: Tok::Str(unescape(&chars[start+1 .. i-1].iter().collect::<String>())), location });
+            },
+            c if c.is_ascii_alphabetic() || c == '_'        => {
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') { i += 1 }
+                tokens.push(Token { tok: Tok::Ident(chars[start..i].iter().collect()), location });
+            },
+            c if c.is_ascii_digit() || (c == '.' && chars.get(i+1).is_some_and(|c| c.is_ascii_digit())) => {
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.' || chars[i] == '_' || (matches!(chars[i], '+' | '-') && matches!(chars[i-1], 'e' | 'E'))) { i += 1 }
+                tokens.push(Token { tok: Tok::Number(chars[start..i].iter().collect()), location });
+            },
+            c                                               => {
+                i += 1;
+                tokens.push(Token { tok: Tok::Punct(c), location });
+            },
+        }
+        line_has_tokens = true;
+    }
+    Ok(tokens)
+}
+
+/// Advance a line number, wrapping like `#line` arithmetic (line 0 means "unknown" in diagnostics.)
+fn next_line(line: u32) -> u32 { line.wrapping_add(1) }
+
+fn unescape(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\'    => if let Some(next) = chars.next() { out.push(next) },
+            ch      => out.push(ch),
+        }
+    }
+    out
+}
+
+fn error(location: HlslLocation, message: &str) -> Diagnostic {
+    Diagnostic { file: location.file, line: Some(location.line), columns: Some(location.column ..= location.column), severity: DiagnosticSeverity::Error, code: None, message: message.into() }
+}
+
+
+
+#[test] fn declarations() {
+    let source = concat!(
+        "#line 1 \"C:\\\\shaders\\\\common.hlsl\"\n",
+        "struct Light { float3 position; float range; float4 color : COLOR0; };\n",
+        "/* multi\n   line */ cbuffer Lights : register(b2) {\n",
+        "    row_major float4x4 view_proj : packoffset(c0);\n",
+        "    Light lights[MAX_LIGHTS], sun;\n",
+        "}\n",
+        "#line 20 \"C:\\\\shaders\\\\main.hlsl\"\n",
+        "Texture2D<float4> albedo : register(ps_5_0, t3), normals;\n",
+        "SamplerState s { Filter = MIN_MAG_MIP_LINEAR; };\n",
+        "RWStructuredBuffer<Light> out_lights : register(u1, space2);\n",
+        "static const float PI = 3.14159, TAU = PI * 2;\n",
+        "groupshared uint counts[64];\n",
+        "float4 shade(in Light l, float3 n = float3(0, 1, 0));\n",
+        "[domain(\"tri\")] [numthreads(8, 8, 1)]\n",
+        "void main(uint3 id : SV_DispatchThreadID, triangle float4 tri[3] : POSITION, inout TriangleStream<Light> stream) { if (id.x < 2) { return; } }\n",
+        "float4 ps(float4 pos : SV_Position) : SV_Target0 { return pos; }\n",
+        "technique11 T { pass P { SetPixelShader(CompileShader(ps_5_0, ps())); } }\n",
+    );
+    let decls = HlslDeclarations::parse(source).unwrap();
+
+    assert_eq!(decls.structs.len(), 1);
+    let light = decls.structure("Light").unwrap();
+    assert_eq!(light.members.iter().map(|m| m.name.as_str()).collect::<Vec<_>>(), ["position", "range", "color"]);
+    assert_eq!(light.members[2].semantic.as_deref(), Some("COLOR0"));
+    assert_eq!(light.location, HlslLocation { file: Some(r"C:\shaders\common.hlsl".into()), line: 1, column: 1 });
+
+    let lights = decls.cbuffer("Lights").unwrap();
+    assert!(!lights.tbuffer);
+    assert_eq!(lights.register, Some(HlslRegister { profile: None, class: 'b', index: 2, space: None }));
+    assert_eq!(lights.location.line, 3);
+    assert_eq!(lights.members[0].modifiers, ["row_major"]);
+    assert_eq!(lights.members[0].packoffset.as_deref(), Some("c0"));
+    assert_eq!((lights.members[1].ty.as_str(), lights.members[1].array.clone()), ("Light", vec!["MAX_LIGHTS".to_string()]));
+    assert_eq!((lights.members[2].name.as_str(), lights.members[2].array.len()), ("sun", 0));
+
+    let names = decls.globals.iter().map(|g| g.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["albedo", "normals", "s", "out_lights", "PI", "TAU", "counts"]);
+    let albedo = decls.global("albedo").unwrap();
+    assert_eq!(albedo.register.as_ref().unwrap().to_string(), "ps_5_0, t3");
+    assert_eq!(albedo.location, HlslLocation { file: Some(r"C:\shaders\main.hlsl".into()), line: 20, column: 1 });
+    assert_eq!(decls.global("normals").unwrap().register, None);
+    assert_eq!(decls.global("normals").unwrap().ty, "Texture2D<float4>");
+    assert_eq!(decls.global("s").unwrap().register_class(), Some('s'));
+    assert_eq!(decls.global("out_lights").unwrap().register_class(), Some('u'));
+    assert_eq!(decls.global("out_lights").unwrap().register.as_ref().unwrap().to_string(), "u1, space2");
+    assert_eq!(decls.global("TAU").unwrap().modifiers, ["static", "const"]);
+    assert_eq!(decls.global("counts").unwrap().register_class(), None);
+    assert_eq!(decls.registers().map(|(n, r)| format!("{} {}", n, r)).collect::<Vec<_>>(), ["Lights b2", "albedo ps_5_0, t3", "out_lights u1, space2"]);
+
+    let shade = decls.function("shade").unwrap();
+    assert!(!shade.defined);
+    assert_eq!(shade.parameters.iter().map(|p| (p.modifiers.clone(), p.ty.as_str(), p.name.as_str())).collect::<Vec<_>>(), [
+        (vec!["in".to_string()], "Light", "l"),
+        (vec![], "float3", "n"),
+    ]);
+
+    let main = decls.function("main").unwrap();
+    assert!(main.defined);
+    assert_eq!(main.attribute("domain").unwrap().arguments, ["tri"]);
+    assert_eq!(main.numthreads(), Some([8, 8, 1]));
+    assert_eq!(main.parameters[1].modifiers, ["triangle"]);
+    assert_eq!(main.parameters[1].array, ["3"]);
+    assert_eq!(main.parameters[2].ty, "TriangleStream<Light>");
+
+    let ps = decls.function("ps").unwrap();
+    assert_eq!(ps.semantic.as_deref(), Some("SV_Target0"));
+    assert!(ps.attributes.is_empty());
+    assert_eq!(decls.functions.len(), 3);
+}
+
+#[test] fn errors() {
+    let err = HlslDeclarations::parse("float4 f() { return 0; ").unwrap_err();
+    assert_eq!(err.message, "unexpected end of file");
+
+    let err = HlslDeclarations::parse("#line 7 \"a.hlsl\"\nfloat4 f() { return (0; }").unwrap_err();
+    assert_eq!(err.to_string(), "a.hlsl(7,25): error: mismatched '}'");
+
+    let err = HlslDeclarations::parse("Texture2D t : register(x0);").unwrap_err();
+    assert_eq!((err.line, err.message.as_str()), (Some(1), "invalid register \"x0\""));
+
+    let err = HlslDeclarations::parse("float a b;").unwrap_err();
+    assert_eq!(err.message, "expected ';'");
+
+    let err = HlslDeclarations::parse("#line 0\nfloat4 f() { return (0; }").unwrap_err();
+    assert_eq!((err.line, err.message.as_str()), (None, "mismatched '}'"));
+}
+
+#[test] fn line_overflow() {
+    let decls = HlslDeclarations::parse("#line 0\nfloat a;\nfloat b;").unwrap();
+    assert_eq!(decls.global("a").unwrap().location.line, 0);
+    assert_eq!(decls.global("b").unwrap().location.line, 1);
+
+    let decls = HlslDeclarations::parse("#line 4294967295\nfloat a;\n/* \n */ float b;\n\nfloat c;").unwrap();
+    assert_eq!(decls.global("a").unwrap().location.line, u32::MAX);
+    assert_eq!(decls.global("b").unwrap().location.line, 1);
+    assert_eq!(decls.global("c").unwrap().location.line, 3);
+}
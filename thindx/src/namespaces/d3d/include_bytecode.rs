@@ -0,0 +1,338 @@
+use crate::d3d::*;
+
+
+
+/// Embed a precompiled shader (e.g. a `.cso` file) as a <code>&'static [d3d::Bytecode](crate::d3d::Bytecode)</code>, validated at compile time.
+///
+/// The path is resolved like [include_bytes!]:  relative to the file containing the macro invocation.
+/// The file must contain a structurally valid DXBC container with a shader chunk (SM4+, including DXIL), or a SM1–3 token stream.
+/// `fx_*` effects aren't shaders, and are rejected:  embed them with [include_effect!] instead.
+/// Optionally, the shader stage or target may also be asserted:
+///
+/// *   `include_bytecode!("shader.cso")`
+/// *   `include_bytecode!("shader.cso", stage = d3d::ShaderStage::Pixel)`
+/// *   `include_bytecode!("shader.cso", target = d3d::ShaderTarget::PS_4_0)` (checks stage + shader model, but not `_level_9_*` variants)
+///
+/// Validation failures are compile errors, so no `unsafe` is needed at the use site.
+/// As with [Bytecode::from](crate::d3d::Bytecode::from), validation is structural only - it guards against truncated or mislabeled files, not malicious ones.
+///
+/// ### Examples
+/// ```rust
+/// # use thindx::*;
+/// const PIXEL_SHADER : &d3d::Bytecode = include_bytecode!("../../../test/data/ps_2_0-minimal.cso", stage = d3d::ShaderStage::Pixel);
+/// assert_eq!(PIXEL_SHADER.version().unwrap().major, 2);
+/// ```
+///
+/// ```rust,compile_fail
+/// # use thindx::*;
+/// let vs = include_bytecode!("../../../test/data/ps_2_0-minimal.cso", stage = d3d::ShaderStage::Vertex); // wrong stage
+/// ```
+///
+/// ```rust,compile_fail
+/// # use thindx::*;
+/// let text = include_bytecode!("../../../test/data/plain.txt"); // not bytecode
+/// ```
+///
+/// ```rust,compile_fail
+/// # use thindx::*;
+/// let fx = include_bytecode!("../../../test/data/fx_2_0-empty.fxo"); // an effect, not a shader
+/// ```
+#[macro_export]
+macro_rules! include_bytecode {
+    ( $path:expr $(,)? ) => {{
+        // ensure validation is evaluated at compile time
+        const BYTECODE : &'static $crate::d3d::Bytecode = $crate::d3d::Bytecode::_zzz_include(::core::include_bytes!($path));
+        BYTECODE
+    }};
+    ( $path:expr, stage = $stage:expr $(,)? ) => {{
+        const BYTECODE : &'static $crate::d3d::Bytecode = $crate::d3d::Bytecode::_zzz_include_stage(::core::include_bytes!($path), $stage);
+        BYTECODE
+    }};
+    ( $path:expr, target = $target:expr $(,)? ) => {{
+        const BYTECODE : &'static $crate::d3d::Bytecode = $crate::d3d::Bytecode::_zzz_include_target(::core::include_bytes!($path), $target);
+        BYTECODE
+    }};
+}
+
+
+
+/// Embed a precompiled `fx_*` effect (e.g. a `.fxo` file) as a `&'static [u8]`, validated at compile time.
+///
+/// The path is resolved like [include_bytes!]:  relative to the file containing the macro invocation.
+/// The file must contain a raw `fx_2_0`, `fx_4_0`, `fx_4_1`, or `fx_5_0` effect binary, or a DXBC container with an `FX10` / `FX11` chunk.
+/// Effects aren't shader [Bytecode](crate::d3d::Bytecode), so they're embedded as plain bytes - inspect them with
+/// [EffectBinary::from_bytes](crate::d3d::EffectBinary::from_bytes).
+///
+/// As with [include_bytecode!], validation is structural only - it guards against truncated or mislabeled files, not malicious ones.
+///
+/// ### Examples
+/// ```rust
+/// # use thindx::*;
+/// const EFFECT : &[u8] = include_effect!("../../../test/data/fx_2_0-empty.fxo");
+/// let effect = d3d::EffectBinary::from_bytes(EFFECT).unwrap();
+/// assert_eq!(effect.target, d3d::ShaderTarget::FX_2_0);
+/// ```
+///
+/// ```rust,compile_fail
+/// # use thindx::*;
+/// let ps = include_effect!("../../../test/data/ps_2_0-minimal.cso"); // a shader, not an effect
+/// ```
+#[macro_export]
+macro_rules! include_effect {
+    ( $path:expr $(,)? ) => {{
+        // ensure validation is evaluated at compile time
+        const EFFECT : &'static [u8] = $crate::d3d::EffectBinary::_zzz_include(::core::include_bytes!($path));
+        EFFECT
+    }};
+}
+
+
+
+/// { stage, major, minor } shader model of [Bytecode], as returned by [Bytecode::version].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BytecodeVersion {
+    /// The pipeline stage the bytecode was compiled for.
+    pub stage:  ShaderStage,
+
+    /// The major shader model version (e.g. `5` for `ps_5_0`.)
+    pub major:  u8,
+
+    /// The minor shader model version (e.g. `0` for `ps_5_0`.)
+    pub minor:  u8,
+}
+
+impl Bytecode {
+    /// Get the shader stage and model of this bytecode.
+    ///
+    /// ### Returns
+    /// *   [None] if this is valid bytecode without a code chunk (e.g. `fx_*` effects), or if the bytecode is malformed.
+    ///
+    /// ### Examples
+    /// ```rust
+    /// # use thindx::d3d::*;
+    /// let ps = unsafe { Bytecode::from(&[0x00, 0x02, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00]) }.unwrap(); // ps_2_0, end
+    /// assert_eq!(ps.version(), Some(BytecodeVersion { stage: ShaderStage::Pixel, major: 2, minor: 0 }));
+    /// ```
+    pub const fn version(&self) -> Option<BytecodeVersion> {
+        match parse(self.as_bytes()) {
+            Ok(v)   => v,
+            Err(_)  => None,
+        }
+    }
+
+    #[doc(hidden)] pub const fn _zzz_include(bytes: &'static [u8]) -> &'static Bytecode {
+        if expect_valid(bytes).is_none() { panic!("include_bytecode!: bytecode contains no shader code (embed effects with include_effect!)") }
+        // SAFETY: ⚠️ validated at least as strictly as Bytecode::from
+        unsafe { Bytecode::from_unchecked(bytes) }
+    }
+
+    #[doc(hidden)] pub const fn _zzz_include_stage(bytes: &'static [u8], stage: ShaderStage) -> &'static Bytecode {
+        match expect_valid(bytes) {
+            Some(v) if v.stage as u8 == stage as u8 => {},
+            Some(_) => panic!("include_bytecode!: shader stage doesn't match `stage = ...`"),
+            None    => panic!("include_bytecode!: bytecode contains no shader code to check the stage of"),
+        }
+        // SAFETY: ⚠️ validated at least as strictly as Bytecode::from
+        unsafe { Bytecode::from_unchecked(bytes) }
+    }
+
+    #[doc(hidden)] pub const fn _zzz_include_target(bytes: &'static [u8], target: ShaderTarget) -> &'static Bytecode {
+        match expect_valid(bytes) {
+            Some(v) if v.stage as u8 != target.stage() as u8    => panic!("include_bytecode!: shader stage doesn't match `target = ...`"),
+            Some(v) if v.major != target.major()                => panic!("include_bytecode!: shader model major version doesn't match `target = ...`"),
+            Some(v) if v.minor != target.minor()                => panic!("include_bytecode!: shader model minor version doesn't match `target = ...`"),
+            Some(_) => {},
+            None    => panic!("include_bytecode!: bytecode contains no shader code to check the target of"),
+        }
+        // SAFETY: ⚠️ validated at least as strictly as Bytecode::from
+        unsafe { Bytecode::from_unchecked(bytes) }
+    }
+}
+
+impl EffectBinary {
+    #[doc(hidden)] pub const fn _zzz_include(bytes: &'static [u8]) -> &'static [u8] {
+        match parse_effect(bytes) {
+            Ok(())      => bytes,
+            Err(msg)    => panic!("{}", msg),
+        }
+    }
+}
+
+const fn expect_valid(bytes: &[u8]) -> Option<BytecodeVersion> {
+    match parse(bytes) {
+        Ok(v)       => v,
+        Err(msg)    => panic!("{}", msg),
+    }
+}
+
+/// Structurally validate DXBC / SM1-3 bytecode, returning the version of the first code chunk (if any.)
+const fn parse(b: &[u8]) -> Result<Option<BytecodeVersion>, &'static str> {
+    // http://timjones.io/blog/archive/2015/09/02/parsing-direct3d-shader-bytecode
+    if b.len() >= 4 && b[0] == b'D' && b[1] == b'X' && b[2] == b'B' && b[3] == b'C' {
+        if b.len() < 32                         { return Err("include_bytecode!: DXBC header truncated") }
+        if u32_at(b, 24) as usize != b.len()    { return Err("include_bytecode!: DXBC header's total size doesn't match the file size") }
+        let chunks = u32_at(b, 28) as usize;
+        if chunks > (b.len() - 32) / 4          { return Err("include_bytecode!: DXBC chunk offsets truncated") }
+
+        let mut version = None;
+        let mut i = 0;
+        while i < chunks {
+            let off = u32_at(b, 32 + 4 * i) as usize;
+            if off > b.len() || b.len() - off < 8           { return Err("include_bytecode!: DXBC chunk header out of bounds") }
+            let size = u32_at(b, off + 4) as usize;
+            if size > b.len() - off - 8                     { return Err("include_bytecode!: DXBC chunk data out of bounds") }
+
+            let fourcc = [b[off], b[off+1], b[off+2], b[off+3]];
+            let code = matches!(&fourcc, b"SHDR" | b"SHEX" | b"DXIL");
+            if code && version.is_none() {
+                if size < 8 { return Err("include_bytecode!: DXBC shader chunk truncated") }
+                let token = u32_at(b, off + 8);
+                let stage = match token >> 16 {
+                    0 => ShaderStage::Pixel,
+                    1 => ShaderStage::Vertex,
+                    2 => ShaderStage::Geometry,
+                    3 => ShaderStage::Hull,
+                    4 => ShaderStage::Domain,
+                    5 => ShaderStage::Compute,
+                    6 => ShaderStage::Library,
+                    _ => return Err("include_bytecode!: DXBC shader chunk has an unrecognized program type"),
+                };
+                version = Some(BytecodeVersion { stage, major: ((token >> 4) & 0xF) as u8, minor: (token & 0xF) as u8 });
+            }
+            i += 1;
+        }
+        Ok(version)
+    } else {
+        if b.len() < 8 || b.len() % 4 != 0      { return Err("include_bytecode!: not DXBC, and not a valid SM1-3 token stream (bad length)") }
+        if u32_at(b, b.len() - 4) != 0x0000FFFF { return Err("include_bytecode!: not DXBC, and not a valid SM1-3 token stream (missing end token)") }
+        let token = u32_at(b, 0);
+        let stage = match token >> 16 {
+            0xFFFE  => ShaderStage::Vertex,
+            0xFFFF  => ShaderStage::Pixel,
+            _       => return Err("include_bytecode!: not DXBC, and not a valid SM1-3 token stream (bad version token)"),
+        };
+        let (major, minor) = ((token >> 8) as u8, token as u8);
+        if major < 1 || major > 3 { return Err("include_bytecode!: not DXBC, and not a valid SM1-3 token stream (bad shader model)") }
+        Ok(Some(BytecodeVersion { stage, major, minor }))
+    }
+}
+
+/// Structurally validate a raw `fx_*` effect binary, or a DXBC container's `FX10` / `FX11` effect chunk.
+const fn parse_effect(b: &[u8]) -> Result<(), &'static str> {
+    if b.len() >= 4 && b[0] == b'D' && b[1] == b'X' && b[2] == b'B' && b[3] == b'C' {
+        match parse(b) {
+            Ok(None)    => {},
+            Ok(Some(_)) => return Err("include_effect!: DXBC container holds shader code, not an effect (embed shaders with include_bytecode!)"),
+            Err(msg)    => return Err(msg),
+        }
+        // parse validated every chunk header and size
+        let mut i = 0;
+        while i < u32_at(b, 28) as usize {
+            let off = u32_at(b, 32 + 4 * i) as usize;
+            if matches!(&[b[off], b[off+1], b[off+2], b[off+3]], b"FX10" | b"FX11") {
+                let (_, chunk) = b.split_at(off + 8);
+                let (chunk, _) = chunk.split_at(u32_at(b, off + 4) as usize);
+                return parse_raw_effect(chunk);
+            }
+            i += 1;
+        }
+        Err("include_effect!: DXBC container has no FX10 / FX11 effect chunk")
+    } else {
+        parse_raw_effect(b)
+    }
+}
+
+/// Structurally validate a raw `fx_2_0` / `fx_4_0` / `fx_4_1` / `fx_5_0` effect binary (see [EffectBinary::from_bytes])
+const fn parse_raw_effect(b: &[u8]) -> Result<(), &'static str> {
+    if b.len() < 4 { return Err("include_effect!: not an effect (truncated magic)") }
+    let header = match u32_at(b, 0) {
+        0xFEFF0901              => 2,
+        0xFEFF1001 | 0xFEFF1011 => 19,
+        0xFEFF2001              => 24,
+        _                       => return Err("include_effect!: not an fx_2_0, fx_4_0, fx_4_1, or fx_5_0 effect"),
+    } * 4;
+    if b.len() < header                             { return Err("include_effect!: effect header truncated") }
+    if header == 8 {
+        let start = u32_at(b, 4) as usize;
+        if start > b.len() - 8 || b.len() - 8 - start < 16 { return Err("include_effect!: effect body out of bounds") }
+    } else {
+        let unstructured = u32_at(b, 32) as usize;
+        if unstructured > b.len() - header              { return Err("include_effect!: effect unstructured data out of bounds") }
+    }
+    Ok(())
+}
+
+const fn u32_at(b: &[u8], o: usize) -> u32 { u32::from_le_bytes([b[o], b[o+1], b[o+2], b[o+3]]) }
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::test_fixtures::dxbc;
+
+    #[test] fn dxbc_versions() {
+        let ps_5_0 = [0x50, 0, 0, 0, 2, 0, 0, 0];               // version token, dword count
+        let cs_4_1 = [0x41, 0, 5, 0, 2, 0, 0, 0];
+        assert_eq!(parse(&dxbc(&[(b"RDEF", &[0; 12][..]), (b"SHEX", &ps_5_0[..])])), Ok(Some(BytecodeVersion { stage: ShaderStage::Pixel,   major: 5, minor: 0 })));
+        assert_eq!(parse(&dxbc(&[(b"SHDR", &cs_4_1)])),                      Ok(Some(BytecodeVersion { stage: ShaderStage::Compute, major: 4, minor: 1 })));
+        assert_eq!(parse(&dxbc(&[(b"FX10", &[0; 4])])),                      Ok(None));
+
+        let mut truncated = dxbc(&[(b"SHEX", &ps_5_0)]);
+        truncated.pop();
+        assert!(parse(&truncated).is_err(), "total size mismatch");
+        let len = truncated.len() as u32;
+        truncated[24..28].copy_from_slice(&len.to_le_bytes());
+        assert!(parse(&truncated).is_err(), "chunk out of bounds");
+        assert!(parse(&dxbc(&[(b"SHEX", &[0; 4])])).is_err(), "chunk too small for version token");
+        assert!(parse(b"DXBC").is_err());
+    }
+
+    #[test] fn sm1_3_versions() {
+        assert_eq!(parse(&[0x01, 0x01, 0xFE, 0xFF, 0xFF, 0xFF, 0x00, 0x00]), Ok(Some(BytecodeVersion { stage: ShaderStage::Vertex, major: 1, minor: 1 })));
+        assert_eq!(parse(&[0x00, 0x03, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00]), Ok(Some(BytecodeVersion { stage: ShaderStage::Pixel,  major: 3, minor: 0 })));
+        assert!(parse(&[0x00, 0x03, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00]).is_err(), "missing end token");
+        assert!(parse(&[0x00, 0x04, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00]).is_err(), "not SM1-3");
+        assert!(parse(b"plain text!\n").is_err());
+    }
+
+    #[test] fn effects() {
+        let fx = |version: u32, header: &[u32], len: usize| {
+            let mut b = [version].iter().chain(header).flat_map(|d| d.to_le_bytes()).collect::<Vec<u8>>();
+            b.resize(len, 0);
+            b
+        };
+        let fx_4_0_header = |unstructured| { let mut h = [0; 18]; h[7] = unstructured; h };
+
+        let fx_2_0 = fx(0xFEFF0901, &[0], 24);                                                                  // start, then 4 body dwords
+        let fx_4_0 = fx(0xFEFF1001, &fx_4_0_header(8), 4 * 19 + 8);                                             // 19 dword header, then unstructured data
+        for effect in [&fx_2_0, &fx(0xFEFF0901, &[4], 28), &fx_4_0, &fx(0xFEFF2001, &[0; 23], 4 * 24)] {       // fx_5_0: 24 dword header
+            assert_eq!(parse_effect(effect), Ok(()));
+            assert!(parse(effect).is_err(), "effects aren't shader bytecode");
+        }
+        assert_eq!(parse_effect(&dxbc(&[(b"RDEF", &[0; 4][..]), (b"FX10", &fx_4_0[..])])), Ok(()));
+        assert_eq!(parse(&dxbc(&[(b"FX10", &fx_4_0)])), Ok(None)); // structurally valid, but no shader version for include_bytecode!
+
+        assert!(parse_effect(&fx(0xFEFF0901, &[],                4)).is_err(),          "truncated fx_2_0 header");
+        assert!(parse_effect(&fx(0xFEFF0901, &[4],               24)).is_err(),         "fx_2_0 body out of bounds");
+        assert!(parse_effect(&fx(0xFEFF0901, &[!0],              24)).is_err(),         "fx_2_0 start overflow");
+        assert!(parse_effect(&fx(0xFEFF1011, &[0; 18],           4 * 18)).is_err(),     "truncated fx_4_1 header");
+        assert!(parse_effect(&fx(0xFEFF1001, &fx_4_0_header(9),  4 * 19 + 8)).is_err(), "unstructured data out of bounds");
+        assert!(parse_effect(&fx(0xFEFF1001, &fx_4_0_header(!0), 4 * 19)).is_err(),     "unstructured data overflow");
+        assert!(parse_effect(&[0x01, 0x20, 0xFF, 0xFE]).is_err(), "magic only");
+        assert!(parse_effect(&[0x01, 0x30, 0xFF, 0xFE, 0x00, 0x00, 0x00, 0x00]).is_err(), "unknown effect version");
+        assert!(parse_effect(&dxbc(&[(b"FX10", &fx_4_0[..20])])).is_err(), "truncated FX10 chunk");
+        assert!(parse_effect(&dxbc(&[(b"RDEF", &[0; 4])])).is_err(), "no FX10 chunk");
+        assert!(parse_effect(&dxbc(&[(b"SHDR", &[0x40, 0, 0, 0, 2, 0, 0, 0])])).is_err(), "shader, not effect");
+        assert!(parse_effect(&[0x00, 0x02, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00]).is_err(), "ps_2_0, not effect");
+    }
+
+    #[test] fn include() {
+        const PS : &Bytecode = include_bytecode!("../../../test/data/ps_2_0-minimal.cso", target = ShaderTarget::PS_2_0);
+        assert_eq!(PS.version(), Some(BytecodeVersion { stage: ShaderStage::Pixel, major: 2, minor: 0 }));
+        let ps = include_bytecode!("../../../test/data/ps_2_0-minimal.cso", stage = ShaderStage::Pixel);
+        assert_eq!(ps.as_bytes(), PS.as_bytes());
+
+        const FX : &[u8] = include_effect!("../../../test/data/fx_2_0-empty.fxo");
+        assert_eq!(EffectBinary::from_bytes(FX).unwrap().target, ShaderTarget::FX_2_0);
+    }
+}
@@ -0,0 +1,396 @@
+use crate::*;
+use crate::d3d::*;
+
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::{self, JoinHandle};
+
+
+
+/// Identifies a job added to a [ShaderBatch].  Results are indexed by job, in submission order, regardless of completion order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ShaderJobId(usize);
+
+impl ShaderJobId {
+    /// The 0-based submission index of this job.
+    pub fn index(self) -> usize { self.0 }
+}
+
+type FilesFactory<'j> = Box<dyn FnOnce() -> Box<dyn ShaderFiles + 'j> + Send + 'j>;
+
+struct ShaderJob<'j> {
+    source: ShaderSource,
+    files:  FilesFactory<'j>,
+}
+
+/// A batch of shader compile jobs to be run on a pool of worker threads.
+///
+/// *   Jobs are started in submission order, and results are returned in submission order.
+/// *   Each job's [ShaderFiles] (which resolve `#include`s) are created *on the worker thread*, so they need not be [Send].
+/// *   Any [ShaderBackend] that is [Sync], with [Send] results, can be used.  Use [BatchCompiler] to compile with a [Compiler]
+///     (whose COM blob results are not [Send]), or stand-in compilers for testing.
+///
+/// ### Examples
+/// ```rust
+/// # use thindx::d3d::*;
+/// # return; // doc tests have wrong dir
+/// let d3dc = Compiler::load_system(47).unwrap();
+/// let mut keywords = ShaderKeywords::new();
+/// let fog = keywords.add_bool("USE_FOG");
+///
+/// let mut batch = ShaderBatch::new();
+/// let vs = batch.add(ShaderSource::new(r"thindx\test\data\basic.hlsl", "vs_main", ShaderTarget::VS_4_0));
+/// let ps = batch.add_permutations(&ShaderSource::new(r"thindx\test\data\basic.hlsl", "ps_main", ShaderTarget::PS_4_0), &keywords);
+///
+/// let mut results = batch.compile(&BatchCompiler(&d3dc), 0);
+/// let vs : Vec<u8> = results.take(vs).unwrap().unwrap();
+/// let ps = ps.into_iter().map(|(key, job)| (key, results.take(job).unwrap().unwrap())).collect::<std::collections::BTreeMap<_, _>>();
+/// let ps_fog = &ps[&fog.enabled(true)];
+/// ```
+pub struct ShaderBatch<'j> {
+    jobs: Vec<ShaderJob<'j>>,
+}
+
+impl<'j> ShaderBatch<'j> {
+    /// Create an empty batch.
+    pub fn new() -> Self { Self { jobs: Vec::new() } }
+
+    /// The number of jobs in this batch.
+    pub fn len(&self) -> usize { self.jobs.len() }
+
+    /// `true` if this batch has no jobs.
+    pub fn is_empty(&self) -> bool { self.jobs.is_empty() }
+
+    /// Add a job reading from the local file system.
+    pub fn add(&mut self, source: ShaderSource) -> ShaderJobId {
+        self.add_with_files(source, || StdShaderFiles)
+    }
+
+    /// Add a job reading through the [ShaderFiles] returned by `files`.
+    ///
+    /// `files` is called on the worker thread that compiles the job, so the [ShaderFiles] it returns need not be [Send].
+    pub fn add_with_files<F: ShaderFiles + 'j>(&mut self, source: ShaderSource, files: impl FnOnce() -> F + Send + 'j) -> ShaderJobId {
+        let id = ShaderJobId(self.jobs.len());
+        self.jobs.push(ShaderJob { source, files: Box::new(move || Box::new(files())) });
+        id
+    }
+
+    /// Add a job for every non-excluded permutation of `keywords`, appending each permutation's `#define`s to `source`'s.
+    ///
+    /// The returned `(key, job)` pairs are in [ShaderKeywords::permutations] order.
+    pub fn add_permutations(&mut self, source: &ShaderSource, keywords: &ShaderKeywords) -> Vec<(ShaderVariantKey, ShaderJobId)> {
+        self.add_permutations_with(source, keywords, || StdShaderFiles)
+    }
+
+    /// Add a job for every non-excluded permutation of `keywords`, each reading through the [ShaderFiles] returned by a clone of `files`.
+    ///
+    /// As with [add_with_files](Self::add_with_files), `files` is called on the worker thread that compiles each job.
+    /// The returned `(key, job)` pairs are in [ShaderKeywords::permutations] order.
+    pub fn add_permutations_with<F: ShaderFiles + 'j>(&mut self, source: &ShaderSource, keywords: &ShaderKeywords, files: impl Fn() -> F + Clone + Send + 'j) -> Vec<(ShaderVariantKey, ShaderJobId)> {
+        keywords.permutations().map(|p| {
+            let mut source = source.clone();
+            source.defines.extend(p.iter_defines().map(|(n, d)| (n.to_owned(), d.to_owned())));
+            (p.key(), self.add_with_files(source, files.clone()))
+        }).collect()
+    }
+
+    /// Compile every job on `threads` worker threads (or [std::thread::available_parallelism] threads if `0`), blocking until all jobs complete.
+    ///
+    /// ### Panics
+    /// *   If `backend` or a [ShaderFiles] factory panics.
+    pub fn compile<B>(self, backend: &B, threads: usize) -> ShaderBatchResults<B>
+    where
+        B:          ShaderBackend + Sync,
+        B::Output:  Send,
+        B::Error:   Send,
+    {
+        let queue = Queue::new(self.jobs);
+        thread::scope(|scope| {
+            for _ in 0 .. worker_count(threads, queue.len()) { scope.spawn(|| queue.work(backend)); }
+        });
+        ShaderBatchResults { results: queue.into_results() }
+    }
+}
+
+impl ShaderBatch<'static> {
+    /// Start compiling every job on `threads` worker threads (or [std::thread::available_parallelism] threads if `0`), without blocking.
+    pub fn spawn<B>(self, backend: Arc<B>, threads: usize) -> ShaderBatchHandle<B>
+    where
+        B:          ShaderBackend + Send + Sync + 'static,
+        B::Output:  Send + 'static,
+        B::Error:   Send + 'static,
+    {
+        let queue = Arc::new(Queue::new(self.jobs));
+        let workers = (0 .. worker_count(threads, queue.len())).map(|_| {
+            let queue   = Arc::clone(&queue);
+            let backend = Arc::clone(&backend);
+            thread::spawn(move || queue.work(&*backend))
+        }).collect();
+        ShaderBatchHandle { queue, workers }
+    }
+}
+
+impl Default for ShaderBatch<'_> {
+    fn default() -> Self { Self::new() }
+}
+
+impl Debug for ShaderBatch<'_> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("ShaderBatch").field("jobs", &self.jobs.iter().map(|j| &j.source).collect::<Vec<_>>()).finish()
+    }
+}
+
+
+
+/// A [ShaderBackend] for [ShaderBatch] that compiles with a [Compiler] (`&Compiler`, `Arc<Compiler>`, ...)
+///
+/// [Compiler]'s own results are COM blobs, which may not be sent between threads.
+/// This copies bytecode into an owned [Vec], and failures into a [BatchCompileError], before they leave the worker thread.
+#[derive(Clone, Copy, Debug)]
+pub struct BatchCompiler<C>(pub C);
+
+impl<C: Deref<Target = Compiler>> ShaderBackend for BatchCompiler<C> {
+    type Output = Vec<u8>;
+    type Error  = BatchCompileError;
+
+    fn compile(&self, source: &ShaderSource, read: &dyn Fn(&Path) -> io::Result<Vec<u8>>) -> Result<Vec<u8>, BatchCompileError> {
+        match ShaderBackend::compile(&*self.0, source, read) {
+            Ok(code)    => Ok(code.as_bytes().to_vec()),
+            Err(err)    => Err(BatchCompileError { diagnostics: err.diagnostics(), error: err.error }),
+        }
+    }
+}
+
+/// A failed [BatchCompiler] compile:  the owned, [Send]able equivalent of [ErrorWithBlob].
+#[derive(Clone, Debug)]
+pub struct BatchCompileError {
+    /// The underlying compile error.
+    pub error:          Error,
+
+    /// The compiler's diagnostics, parsed from its error blob.
+    pub diagnostics:    Vec<Diagnostic>,
+}
+
+impl BatchCompileError {
+    /// Returns the corresponding [ErrorKind] for this error.
+    pub fn kind(&self) -> ErrorKind { self.error.kind() }
+}
+
+
+
+/// The results of a [ShaderBatch], indexed by [ShaderJobId].
+pub struct ShaderBatchResults<B: ShaderBackend> {
+    results: Vec<Option<Result<B::Output, B::Error>>>,
+}
+
+impl<B: ShaderBackend> ShaderBatchResults<B> {
+    /// The number of jobs in the batch.
+    pub fn len(&self) -> usize { self.results.len() }
+
+    /// `true` if the batch had no jobs.
+    pub fn is_empty(&self) -> bool { self.results.is_empty() }
+
+    /// The result of `job`, or [None] if it was already [take](Self::take)n.
+    pub fn get(&self, job: ShaderJobId) -> Option<Result<&B::Output, &B::Error>> { Some(self.results.get(job.0)?.as_ref()?.as_ref()) }
+
+    /// Take ownership of the result of `job`, or [None] if it was already taken.
+    pub fn take(&mut self, job: ShaderJobId) -> Option<Result<B::Output, B::Error>> { self.results.get_mut(job.0)?.take() }
+
+    /// The number of jobs that failed to compile (and haven't been taken.)
+    pub fn errors(&self) -> usize { self.results.iter().filter(|r| matches!(r, Some(Err(_)))).count() }
+}
+
+impl<B: ShaderBackend> IntoIterator for ShaderBatchResults<B> {
+    type Item       = (ShaderJobId, Result<B::Output, B::Error>);
+    type IntoIter   = std::vec::IntoIter<Self::Item>;
+
+    /// Iterate over `(job, result)`s in submission order, skipping any results that were already taken.
+    fn into_iter(self) -> Self::IntoIter { self.results.into_iter().enumerate().filter_map(|(i, r)| Some((ShaderJobId(i), r?))).collect::<Vec<_>>().into_iter() }
+}
+
+impl<B: ShaderBackend> Debug for ShaderBatchResults<B> where B::Output: Debug, B::Error: Debug {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { fmt.debug_list().entries(self.results.iter()).finish() }
+}
+
+
+
+/// A running [ShaderBatch], returned by [ShaderBatch::spawn].
+pub struct ShaderBatchHandle<B: ShaderBackend> {
+    queue:      Arc<Queue<'static, B>>,
+    workers:    Vec<JoinHandle<()>>,
+}
+
+impl<B: ShaderBackend> ShaderBatchHandle<B> {
+    /// The number of jobs in the batch.
+    pub fn len(&self) -> usize { self.queue.len() }
+
+    /// `true` if the batch had no jobs.
+    pub fn is_empty(&self) -> bool { self.queue.len() == 0 }
+
+    /// The number of jobs that have finished compiling.
+    pub fn completed(&self) -> usize { self.queue.completed.load(Ordering::Acquire) }
+
+    /// `true` if every job has finished compiling.
+    pub fn is_finished(&self) -> bool { self.completed() == self.len() }
+
+    /// Take the result of `job` if it has finished compiling and hasn't already been taken.
+    pub fn try_take(&self, job: ShaderJobId) -> Option<Result<B::Output, B::Error>> {
+        self.queue.results.get(job.0)?.lock().unwrap_or_else(|p| p.into_inner()).take()
+    }
+
+    /// Block until every job has finished compiling.
+    ///
+    /// ### Panics
+    /// *   If the backend or a [ShaderFiles] factory panicked on any worker thread.
+    pub fn join(self) -> ShaderBatchResults<B> {
+        for worker in self.workers {
+            if let Err(panic) = worker.join() { std::panic::resume_unwind(panic) }
+        }
+        let queue = Arc::try_unwrap(self.queue).unwrap_or_else(|_| panic!("ShaderBatchHandle::join: workers still running"));
+        ShaderBatchResults { results: queue.into_results() }
+    }
+}
+
+impl<B: ShaderBackend> Debug for ShaderBatchHandle<B> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("ShaderBatchHandle")
+            .field("jobs",      &self.len())
+            .field("completed", &self.completed())
+            .finish_non_exhaustive()
+    }
+}
+
+
+
+struct Queue<'j, B: ShaderBackend> {
+    jobs:       Vec<Mutex<Option<ShaderJob<'j>>>>,
+    next:       AtomicUsize,
+    completed:  AtomicUsize,
+    results:    Vec<Mutex<Option<Result<B::Output, B::Error>>>>,
+}
+
+impl<'j, B: ShaderBackend> Queue<'j, B> {
+    fn new(jobs: Vec<ShaderJob<'j>>) -> Self {
+        let results = jobs.iter().map(|_| Mutex::new(None)).collect();
+        let jobs    = jobs.into_iter().map(|j| Mutex::new(Some(j))).collect();
+        Self { jobs, next: AtomicUsize::new(0), completed: AtomicUsize::new(0), results }
+    }
+
+    fn len(&self) -> usize { self.jobs.len() }
+
+    fn work(&self, backend: &B) {
+        loop {
+            let i = self.next.fetch_add(1, Ordering::Relaxed);
+            let Some(job) = self.jobs.get(i) else { return };
+            let job = job.lock().unwrap().take().expect("job already started");
+            let files = (job.files)();
+            let read = |path: &Path| -> io::Result<Vec<u8>> { files.read(path) };
+            let result = backend.compile(&job.source, &read);
+            *self.results[i].lock().unwrap() = Some(result);
+            self.completed.fetch_add(1, Ordering::Release);
+        }
+    }
+
+    fn into_results(self) -> Vec<Option<Result<B::Output, B::Error>>> {
+        self.results.into_iter().map(|r| r.into_inner().unwrap_or_else(|p| p.into_inner())).collect()
+    }
+}
+
+fn worker_count(threads: usize, jobs: usize) -> usize {
+    let threads = if threads != 0 { threads } else { thread::available_parallelism().map_or(1, |n| n.get()) };
+    threads.min(jobs)
+}
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    /// Stand-in compiler: "bytecode" is `{target}:{defines}:{contents}`.  Sleeps for `SLEEP_MS` ms (if defined), to shuffle completion order.
+    struct Echo;
+
+    impl ShaderBackend for Echo {
+        type Output = String;
+        type Error  = String;
+
+        fn compile(&self, source: &ShaderSource, read: &dyn Fn(&Path) -> io::Result<Vec<u8>>) -> Result<String, String> {
+            let text = read(&source.path).map_err(|e| e.to_string())?;
+            let defines = source.defines.iter().map(|(n, d)| format!("{}={}", n, d)).collect::<Vec<_>>().join(",");
+            if let Some((_, ms)) = source.defines.iter().find(|(n, _)| n == "SLEEP_MS") {
+                thread::sleep(Duration::from_millis(ms.parse().unwrap()));
+            }
+            Ok(format!("{}:{}:{}", source.target, defines, String::from_utf8(text).unwrap()))
+        }
+    }
+
+    /// Deliberately `!Send` in-memory [ShaderFiles].
+    struct RcFiles(Rc<HashMap<PathBuf, String>>);
+
+    impl ShaderFiles for RcFiles {
+        fn read(&self, path: &Path) -> io::Result<Vec<u8>> { self.0.get(path).map(|s| s.clone().into_bytes()).ok_or_else(|| io::ErrorKind::NotFound.into()) }
+        fn stamp(&self, _path: &Path) -> io::Result<ShaderFileStamp> { Err(io::ErrorKind::Unsupported.into()) }
+    }
+
+    fn rc_files() -> RcFiles { RcFiles(Rc::new([(PathBuf::from("a.hlsl"), String::from("a"))].into_iter().collect())) }
+
+    #[test] fn compile_in_order() {
+        let mut batch = ShaderBatch::new();
+        let jobs = (0 .. 16).map(|i| batch.add_with_files(
+            ShaderSource::new("a.hlsl", "main", "ps_4_0").define("SLEEP_MS", ((16 - i) % 5).to_string()),
+            rc_files,
+        )).collect::<Vec<_>>();
+        let missing = batch.add_with_files(ShaderSource::new("missing.hlsl", "main", "ps_4_0"), rc_files);
+
+        let mut results = batch.compile(&Echo, 4);
+        assert_eq!(results.len(), 17);
+        assert_eq!(results.errors(), 1);
+        for (i, &job) in jobs.iter().enumerate() {
+            assert_eq!(job.index(), i);
+            assert_eq!(results.get(job).unwrap().unwrap(), &format!("ps_4_0:SLEEP_MS={}:a", (16 - i) % 5));
+        }
+        assert!(results.take(missing).unwrap().is_err());
+        assert!(results.take(missing).is_none());
+        assert_eq!(results.into_iter().map(|(job, _)| job).collect::<Vec<_>>(), jobs);
+    }
+
+    #[test] fn permutations() {
+        let mut keywords = ShaderKeywords::new();
+        let fog     = keywords.add_bool("FOG");
+        let lights  = keywords.add_values("LIGHTS", ["0", "4"]);
+
+        let mut batch = ShaderBatch::new();
+        let jobs = batch.add_permutations_with(&ShaderSource::new("a.hlsl", "main", "vs_4_0").define("X", "1"), &keywords, rc_files);
+        let mut results = batch.compile(&Echo, 0);
+
+        let out = jobs.iter().map(|&(key, job)| (key, results.take(job).unwrap().unwrap())).collect::<HashMap<_, _>>();
+        assert_eq!(out.len(), 4);
+        assert_eq!(out[&(fog.enabled(false) | lights.value(0))], "vs_4_0:X=1,LIGHTS=0:a");
+        assert_eq!(out[&(fog.enabled(true)  | lights.value(1))], "vs_4_0:X=1,FOG=1,LIGHTS=4:a");
+    }
+
+    #[test] fn spawn() {
+        let mut batch = ShaderBatch::new();
+        let slow = batch.add_with_files(ShaderSource::new("a.hlsl", "main", "ps_4_0").define("SLEEP_MS", "50"), rc_files);
+        let fast = batch.add_with_files(ShaderSource::new("a.hlsl", "main", "ps_4_0"), rc_files);
+
+        let handle = batch.spawn(Arc::new(Echo), 2);
+        assert_eq!(handle.len(), 2);
+        let fast_result = loop {
+            if let Some(r) = handle.try_take(fast) { break r }
+            thread::yield_now();
+        };
+        assert_eq!(fast_result.unwrap(), "ps_4_0::a");
+
+        let mut results = handle.join();
+        assert!(results.take(fast).is_none(), "already taken");
+        assert_eq!(results.take(slow).unwrap().unwrap(), "ps_4_0:SLEEP_MS=50:a");
+    }
+}
@@ -0,0 +1,314 @@
+use crate::*;
+use crate::d3d::FeatureLevel;
+
+use std::fmt::{self, Debug, Display, Formatter};
+use std::str::FromStr;
+
+
+
+/// A typed HLSL compile target / profile such as `"ps_4_0"`, `"vs_4_0_level_9_1"`, `"lib_5_0"`, or `"fx_2_0"`.
+///
+/// Can be passed anywhere a `target` string is accepted, such as [d3d::Compiler::compile]:
+/// ```rust
+/// # use thindx::d3d::*; let d3dc = Compiler::load_system(47).unwrap();
+/// let basic_hlsl = std::fs::read(r"test\data\basic.hlsl").unwrap();
+/// let target : ShaderTarget = "ps_4_0".parse().unwrap();
+/// assert_eq!(target, ShaderTarget::PS_4_0);
+/// assert_eq!(target.min_feature_level(), Some(FeatureLevel::_10_0));
+///
+/// let pixel_shader = d3dc.compile(
+///     &basic_hlsl, r"test\data\basic.hlsl", None, None, "ps_main", target,
+///     Compile::Debug, CompileEffect::None,
+/// ).unwrap();
+/// ```
+///
+/// ### See Also
+/// *   [d3d9::ShaderVersion] - Direct3D 9 shader version tokens (vs_1_1 ..= ps_3_0)
+/// *   [d3d11::ShaderVersion] - Direct3D 10/11 shader versions as reported by reflection
+/// *   [d3d11::ShaderVersionType] - Direct3D 10/11 shader stages as reported by reflection
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShaderTarget {
+    name:       &'static str,
+    stage:      ShaderStage,
+    major:      u8,
+    minor:      u8,
+    level_9:    Option<FeatureLevel>,
+    feature:    Option<FeatureLevel>,
+}
+
+/// The pipeline stage (or other kind of output) a [ShaderTarget] compiles for.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum ShaderStage {
+    /// `vs_*` targets
+    Vertex,
+
+    /// `ps_*` targets
+    Pixel,
+
+    /// `gs_*` targets
+    Geometry,
+
+    /// `hs_*` targets
+    Hull,
+
+    /// `ds_*` targets
+    Domain,
+
+    /// `cs_*` targets
+    Compute,
+
+    /// `lib_*` targets (shader libraries for [d3d11::Linker] / [d3d11::FunctionLinkingGraph])
+    Library,
+
+    /// `fx_*` targets (effects)
+    Effect,
+}
+
+impl ShaderTarget {
+    /// The profile string passed to the compiler (e.g. `"ps_4_0"`)
+    pub const fn as_str(&self) -> &'static str { self.name }
+
+    /// The pipeline stage this target compiles for.
+    pub const fn stage(&self) -> ShaderStage { self.stage }
+
+    /// The major shader model version (e.g. `4` for `"ps_4_0_level_9_1"`, `2` for `"ps_2_a"`)
+    pub const fn major(&self) -> u8 { self.major }
+
+    /// The minor shader model version (e.g. `1` for `"vs_4_1"`, `0` for `"ps_2_a"`)
+    pub const fn minor(&self) -> u8 { self.minor }
+
+    /// The Direct3D 9 feature level targeted by `*_4_0_level_9_*` profiles, or [None] for other profiles.
+    pub const fn level_9(&self) -> Option<FeatureLevel> { self.level_9 }
+
+    /// The minimum [FeatureLevel] a Direct3D 10+ device must support to use this target.
+    ///
+    /// ### Returns
+    /// *   [None]          - for Direct3D 9 targets (`vs_1_1` ..= `ps_3_sw`, `fx_2_0`) which are not used with feature levels.
+    /// *   [Some]\(fl\)    - e.g. [FeatureLevel::_9_1] for `ps_4_0_level_9_1`, [FeatureLevel::_10_0] for `ps_4_0`.
+    ///
+    /// ### Remarks
+    /// *   `cs_4_x` requires [FeatureLevel::_10_0]/[FeatureLevel::_10_1] *and* optional compute shader support, which not all such devices have.
+    /// *   `*_5_1` targets require Direct3D 12.
+    pub const fn min_feature_level(&self) -> Option<FeatureLevel> { self.feature }
+
+    /// Every [ShaderTarget] known to thindx.
+    pub fn all() -> &'static [ShaderTarget] { ALL }
+
+    /// Convert into a [d3d11::ShaderVersion] as would be reported by [d3d11::ShaderReflection::get_desc].
+    ///
+    /// ### Returns
+    /// *   [None]  - for Direct3D 9 (`*_1_*` ..= `*_3_*`), `lib_*`, and `fx_*` targets
+    pub fn to_d3d11(&self) -> Option<d3d11::ShaderVersion> {
+        if self.major < 4 { return None }
+        Some(d3d11::ShaderVersion::new(self.stage.to_d3d11()?, self.major.into(), self.minor.into()))
+    }
+
+    /// Convert from a [d3d11::ShaderVersion] to the equivalent non-`level_9` [ShaderTarget].
+    ///
+    /// ### Returns
+    /// *   [None]  - for unrecognized shader versions
+    //#allow_missing_argument_docs
+    pub fn from_d3d11(version: d3d11::ShaderVersion) -> Option<Self> {
+        let stage = ShaderStage::from_d3d11(version.ty())?;
+        ALL.iter().copied().find(|t| t.stage == stage && t.level_9.is_none() && u32::from(t.major) == version.major() && u32::from(t.minor) == version.minor())
+    }
+
+    /// Convert into a [d3d9::ShaderVersion] token.
+    ///
+    /// ### Returns
+    /// *   [None]  - for non-Direct3D 9 targets, and `*_2_a`, `*_2_b`, `*_sw` targets which don't have an exact version token
+    pub fn to_d3d9(&self) -> Option<d3d9::ShaderVersion> {
+        if self.major > 3 || self.name.ends_with("_a") || self.name.ends_with("_b") || self.name.ends_with("_sw") { return None }
+        match self.stage {
+            ShaderStage::Vertex => Some(d3d9::ShaderVersion::vs(self.major, self.minor)),
+            ShaderStage::Pixel  => Some(d3d9::ShaderVersion::ps(self.major, self.minor)),
+            _other              => None,
+        }
+    }
+
+    /// Convert from a [d3d9::ShaderVersion] token (e.g. [d3d9::ShaderVersion::PS_3_0] → [ShaderTarget::PS_3_0])
+    ///
+    /// ### Returns
+    /// *   [None]  - for unrecognized shader versions
+    //#allow_missing_argument_docs
+    pub fn from_d3d9(version: d3d9::ShaderVersion) -> Option<Self> {
+        ALL.iter().copied().find(|t| t.to_d3d9() == Some(version))
+    }
+}
+
+impl ShaderStage {
+    /// Convert into a [d3d11::ShaderVersionType] ([None] for [ShaderStage::Library] and [ShaderStage::Effect])
+    pub fn to_d3d11(self) -> Option<d3d11::ShaderVersionType> {
+        use d3d11::ShVer;
+        match self {
+            ShaderStage::Vertex     => Some(ShVer::VertexShader),
+            ShaderStage::Pixel      => Some(ShVer::PixelShader),
+            ShaderStage::Geometry   => Some(ShVer::GeometryShader),
+            ShaderStage::Hull       => Some(ShVer::HullShader),
+            ShaderStage::Domain     => Some(ShVer::DomainShader),
+            ShaderStage::Compute    => Some(ShVer::ComputeShader),
+            ShaderStage::Library    => None,
+            ShaderStage::Effect     => None,
+        }
+    }
+
+    /// Convert from a [d3d11::ShaderVersionType] ([None] for unrecognized types)
+    //#allow_missing_argument_docs
+    pub fn from_d3d11(ty: d3d11::ShaderVersionType) -> Option<Self> {
+        use d3d11::ShVer;
+        match ty {
+            ShVer::VertexShader     => Some(ShaderStage::Vertex),
+            ShVer::PixelShader      => Some(ShaderStage::Pixel),
+            ShVer::GeometryShader   => Some(ShaderStage::Geometry),
+            ShVer::HullShader       => Some(ShaderStage::Hull),
+            ShVer::DomainShader     => Some(ShaderStage::Domain),
+            ShVer::ComputeShader    => Some(ShaderStage::Compute),
+            _other                  => None,
+        }
+    }
+}
+
+impl Debug   for ShaderTarget { fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { write!(fmt, "ShaderTarget({:?})", self.name) } }
+impl Display for ShaderTarget { fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { fmt.write_str(self.name) } }
+impl AsRef<str> for ShaderTarget { fn as_ref(&self) -> &str { self.name } }
+impl From<ShaderTarget> for &'static str { fn from(t: ShaderTarget) -> Self { t.name } }
+
+impl FromStr for ShaderTarget {
+    type Err = ErrorKind;
+
+    /// Parse a profile such as `"ps_4_0"` (case insensitive).
+    ///
+    /// ### Errors
+    /// *   [E::INVALIDARG] - if `s` isn't a known target profile
+    fn from_str(s: &str) -> Result<Self, ErrorKind> {
+        ALL.iter().copied().find(|t| t.name.eq_ignore_ascii_case(s)).ok_or(E::INVALIDARG.into())
+    }
+}
+
+impl TryFrom<d3d11::ShaderVersion> for ShaderTarget { type Error = ErrorKind; fn try_from(v: d3d11::ShaderVersion) -> Result<Self, ErrorKind> { Self::from_d3d11(v).ok_or(E::INVALIDARG.into()) } }
+impl TryFrom<d3d9::ShaderVersion>  for ShaderTarget { type Error = ErrorKind; fn try_from(v: d3d9::ShaderVersion ) -> Result<Self, ErrorKind> { Self::from_d3d9 (v).ok_or(E::INVALIDARG.into()) } }
+impl TryFrom<ShaderTarget> for d3d11::ShaderVersion { type Error = ErrorKind; fn try_from(t: ShaderTarget) -> Result<Self, ErrorKind> { t.to_d3d11().ok_or(E::INVALIDARG.into()) } }
+impl TryFrom<ShaderTarget> for d3d9::ShaderVersion  { type Error = ErrorKind; fn try_from(t: ShaderTarget) -> Result<Self, ErrorKind> { t.to_d3d9 ().ok_or(E::INVALIDARG.into()) } }
+
+impl abistr::TryIntoAsCStr for ShaderTarget {
+    type Target = <&'static str as abistr::TryIntoAsCStr>::Target;
+    fn try_into(self) -> Result<Self::Target, abistr::InteriorNulError> { abistr::TryIntoAsCStr::try_into(self.name) }
+}
+
+
+
+macro_rules! targets {
+    ( $( $ident:ident = ($name:literal, $stage:ident, $major:literal, $minor:literal, $level_9:tt, $feature:tt) ),* $(,)? ) => {
+        #[allow(missing_docs)] // self explanatory
+        impl ShaderTarget {
+            $(
+                pub const $ident : ShaderTarget = ShaderTarget {
+                    name:       $name,
+                    stage:      ShaderStage::$stage,
+                    major:      $major,
+                    minor:      $minor,
+                    level_9:    targets!(@fl $level_9),
+                    feature:    targets!(@fl $feature),
+                };
+            )*
+        }
+
+        const ALL : &[ShaderTarget] = &[ $( ShaderTarget::$ident ),* ];
+    };
+    ( @fl - ) => { None };
+    ( @fl $fl:ident ) => { Some(FeatureLevel::$fl) };
+}
+
+targets! {
+    // ident                        =  name                          stage     maj min level_9  min feature level
+    VS_1_1                          = ("vs_1_1",                     Vertex,   1,  1,  -,       -    ),
+    VS_2_0                          = ("vs_2_0",                     Vertex,   2,  0,  -,       -    ),
+    VS_2_A                          = ("vs_2_a",                     Vertex,   2,  0,  -,       -    ),
+    VS_2_SW                         = ("vs_2_sw",                    Vertex,   2,  0,  -,       -    ),
+    VS_3_0                          = ("vs_3_0",                     Vertex,   3,  0,  -,       -    ),
+    VS_3_SW                         = ("vs_3_sw",                    Vertex,   3,  0,  -,       -    ),
+    VS_4_0_LEVEL_9_1                = ("vs_4_0_level_9_1",           Vertex,   4,  0,  _9_1,    _9_1 ),
+    VS_4_0_LEVEL_9_3                = ("vs_4_0_level_9_3",           Vertex,   4,  0,  _9_3,    _9_3 ),
+    VS_4_0                          = ("vs_4_0",                     Vertex,   4,  0,  -,       _10_0),
+    VS_4_1                          = ("vs_4_1",                     Vertex,   4,  1,  -,       _10_1),
+    VS_5_0                          = ("vs_5_0",                     Vertex,   5,  0,  -,       _11_0),
+    VS_5_1                          = ("vs_5_1",                     Vertex,   5,  1,  -,       _11_0),
+
+    PS_1_1                          = ("ps_1_1",                     Pixel,    1,  1,  -,       -    ),
+    PS_1_2                          = ("ps_1_2",                     Pixel,    1,  2,  -,       -    ),
+    PS_1_3                          = ("ps_1_3",                     Pixel,    1,  3,  -,       -    ),
+    PS_1_4                          = ("ps_1_4",                     Pixel,    1,  4,  -,       -    ),
+    PS_2_0                          = ("ps_2_0",                     Pixel,    2,  0,  -,       -    ),
+    PS_2_A                          = ("ps_2_a",                     Pixel,    2,  0,  -,       -    ),
+    PS_2_B                          = ("ps_2_b",                     Pixel,    2,  0,  -,       -    ),
+    PS_2_SW                         = ("ps_2_sw",                    Pixel,    2,  0,  -,       -    ),
+    PS_3_0                          = ("ps_3_0",                     Pixel,    3,  0,  -,       -    ),
+    PS_3_SW                         = ("ps_3_sw",                    Pixel,    3,  0,  -,       -    ),
+    PS_4_0_LEVEL_9_1                = ("ps_4_0_level_9_1",           Pixel,    4,  0,  _9_1,    _9_1 ),
+    PS_4_0_LEVEL_9_3                = ("ps_4_0_level_9_3",           Pixel,    4,  0,  _9_3,    _9_3 ),
+    PS_4_0                          = ("ps_4_0",                     Pixel,    4,  0,  -,       _10_0),
+    PS_4_1                          = ("ps_4_1",                     Pixel,    4,  1,  -,       _10_1),
+    PS_5_0                          = ("ps_5_0",                     Pixel,    5,  0,  -,       _11_0),
+    PS_5_1                          = ("ps_5_1",                     Pixel,    5,  1,  -,       _11_0),
+
+    GS_4_0                          = ("gs_4_0",                     Geometry, 4,  0,  -,       _10_0),
+    GS_4_1                          = ("gs_4_1",                     Geometry, 4,  1,  -,       _10_1),
+    GS_5_0                          = ("gs_5_0",                     Geometry, 5,  0,  -,       _11_0),
+    GS_5_1                          = ("gs_5_1",                     Geometry, 5,  1,  -,       _11_0),
+
+    HS_5_0                          = ("hs_5_0",                     Hull,     5,  0,  -,       _11_0),
+    HS_5_1                          = ("hs_5_1",                     Hull,     5,  1,  -,       _11_0),
+
+    DS_5_0                          = ("ds_5_0",                     Domain,   5,  0,  -,       _11_0),
+    DS_5_1                          = ("ds_5_1",                     Domain,   5,  1,  -,       _11_0),
+
+    CS_4_0                          = ("cs_4_0",                     Compute,  4,  0,  -,       _10_0),
+    CS_4_1                          = ("cs_4_1",                     Compute,  4,  1,  -,       _10_1),
+    CS_5_0                          = ("cs_5_0",                     Compute,  5,  0,  -,       _11_0),
+    CS_5_1                          = ("cs_5_1",                     Compute,  5,  1,  -,       _11_0),
+
+    LIB_4_0_LEVEL_9_1               = ("lib_4_0_level_9_1",          Library,  4,  0,  _9_1,    _9_1 ),
+    LIB_4_0_LEVEL_9_1_VS_ONLY       = ("lib_4_0_level_9_1_vs_only",  Library,  4,  0,  _9_1,    _9_1 ),
+    LIB_4_0_LEVEL_9_1_PS_ONLY       = ("lib_4_0_level_9_1_ps_only",  Library,  4,  0,  _9_1,    _9_1 ),
+    LIB_4_0_LEVEL_9_3               = ("lib_4_0_level_9_3",          Library,  4,  0,  _9_3,    _9_3 ),
+    LIB_4_0_LEVEL_9_3_VS_ONLY       = ("lib_4_0_level_9_3_vs_only",  Library,  4,  0,  _9_3,    _9_3 ),
+    LIB_4_0_LEVEL_9_3_PS_ONLY       = ("lib_4_0_level_9_3_ps_only",  Library,  4,  0,  _9_3,    _9_3 ),
+    LIB_4_0                         = ("lib_4_0",                    Library,  4,  0,  -,       _10_0),
+    LIB_4_1                         = ("lib_4_1",                    Library,  4,  1,  -,       _10_1),
+    LIB_5_0                         = ("lib_5_0",                    Library,  5,  0,  -,       _11_0),
+
+    FX_2_0                          = ("fx_2_0",                     Effect,   2,  0,  -,       -    ),
+    FX_4_0                          = ("fx_4_0",                     Effect,   4,  0,  -,       _10_0),
+    FX_4_1                          = ("fx_4_1",                     Effect,   4,  1,  -,       _10_1),
+    FX_5_0                          = ("fx_5_0",                     Effect,   5,  0,  -,       _11_0),
+}
+
+
+
+#[test] fn round_trip() {
+    for &target in ShaderTarget::all() {
+        assert_eq!(target.to_string().parse::<ShaderTarget>(), Ok(target));
+        assert_eq!(target.as_str().to_uppercase().parse::<ShaderTarget>(), Ok(target));
+        if let Some(v) = target.to_d3d11() {
+            let rt = ShaderTarget::from_d3d11(v).unwrap();
+            assert_eq!((rt.stage(), rt.major(), rt.minor()), (target.stage(), target.major(), target.minor()));
+        }
+        if let Some(v) = target.to_d3d9() { assert_eq!(ShaderTarget::from_d3d9(v), Some(target)); }
+    }
+    assert_eq!("ps_9_9".parse::<ShaderTarget>(), Err(E::INVALIDARG.into()));
+}
+
+#[test] fn d3d9_versions() {
+    assert_eq!(ShaderTarget::PS_3_0.to_d3d9(), Some(d3d9::ShaderVersion::PS_3_0));
+    assert_eq!(ShaderTarget::VS_2_0.to_d3d9(), Some(d3d9::ShaderVersion::VS_2_0));
+    assert_eq!(ShaderTarget::PS_2_B.to_d3d9(), None);
+    assert_eq!(ShaderTarget::PS_4_0.to_d3d9(), None);
+}
+
+#[test] fn feature_levels() {
+    assert_eq!(ShaderTarget::PS_2_0          .min_feature_level(), None);
+    assert_eq!(ShaderTarget::VS_4_0_LEVEL_9_1.min_feature_level(), Some(FeatureLevel::_9_1));
+    assert_eq!(ShaderTarget::GS_4_1          .min_feature_level(), Some(FeatureLevel::_10_1));
+    assert_eq!(ShaderTarget::HS_5_0          .min_feature_level(), Some(FeatureLevel::_11_0));
+    assert_eq!(ShaderTarget::PS_4_0_LEVEL_9_3.to_d3d11().map(|v| format!("{v:?}")).as_deref(), Some("ps_4_0"));
+}
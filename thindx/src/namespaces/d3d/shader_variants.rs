@@ -0,0 +1,468 @@
+use crate::*;
+use crate::d3d::*;
+
+use abistr::{TryIntoAsCStr, TryIntoAsOptCStr};
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::ops::{BitOr, BitOrAssign};
+
+
+
+/// A compact, stable identifier for a single permutation of [ShaderKeywords].
+///
+/// Each keyword occupies a fixed bit range, allocated in declaration order.
+/// As long as keywords are declared in the same order with the same number of values, the same key refers to the same permutation -
+/// making it suitable for cache file names, or for selecting a shader at runtime.
+///
+/// ### Examples
+/// ```rust
+/// # use thindx::d3d::*;
+/// let mut keywords = ShaderKeywords::new();
+/// let fog     = keywords.add_bool("USE_FOG");
+/// let lights  = keywords.add_values("LIGHT_COUNT", ["0", "1", "2", "4"]);
+///
+/// let key = fog.value(1) | lights.value(2);
+/// assert_eq!(key.bits(), 0b10_1);
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)] pub struct ShaderVariantKey(u64);
+
+impl ShaderVariantKey {
+    /// The key with every keyword set to value `0`.
+    pub const DEFAULT : ShaderVariantKey = ShaderVariantKey(0);
+
+    /// Create a key from raw bits, such as those returned by [ShaderVariantKey::bits].
+    pub const fn from_bits(bits: u64) -> Self { Self(bits) }
+
+    /// The raw bits of this key.
+    pub const fn bits(self) -> u64 { self.0 }
+}
+
+impl BitOr for ShaderVariantKey {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self { Self(self.0 | rhs.0) }
+}
+
+impl BitOrAssign for ShaderVariantKey {
+    fn bitor_assign(&mut self, rhs: Self) { self.0 |= rhs.0 }
+}
+
+impl Debug   for ShaderVariantKey { fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { write!(fmt, "ShaderVariantKey(0x{:X})", self.0) } }
+impl Display for ShaderVariantKey { fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { write!(fmt, "{:016x}", self.0) } }
+
+
+
+/// A handle to a keyword declared by [ShaderKeywords::add_bool] or [ShaderKeywords::add_values].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ShaderKeyword {
+    index:  u32,
+    shift:  u8,
+    bits:   u8,
+    count:  u32,
+}
+
+impl ShaderKeyword {
+    /// The number of distinct values this keyword can take (`2` for boolean keywords.)
+    pub fn count(self) -> u32 { self.count }
+
+    /// The [ShaderVariantKey] bits corresponding to this keyword having `value`.
+    ///
+    /// ### Panics
+    /// *   If `value` >= [ShaderKeyword::count]
+    pub fn value(self, value: u32) -> ShaderVariantKey {
+        assert!(value < self.count, "ShaderKeyword::value: value {} out of range (keyword has {} values)", value, self.count);
+        ShaderVariantKey(u64::from(value) << self.shift)
+    }
+
+    /// Shorthand for <code>[value](Self::value)(enabled as u32)</code>.
+    pub fn enabled(self, enabled: bool) -> ShaderVariantKey { self.value(enabled as u32) }
+
+    fn mask(self) -> u64 { (1u64 << self.bits) - 1 } // bits <= 32
+
+    fn get(self, key: ShaderVariantKey) -> u32 { ((key.0 >> self.shift) & self.mask()) as u32 }
+}
+
+
+
+/// A set of shader keywords (`#define`s) whose combinations produce shader permutations.
+///
+/// *   Boolean keywords ([add_bool](Self::add_bool)) are either undefined, or `#define NAME 1`.
+/// *   Multi-value keywords ([add_values](Self::add_values)) are always `#define NAME value`.
+/// *   Exclusion rules ([exclude](Self::exclude), [exclude_if](Self::exclude_if)) prune invalid or unwanted combinations.
+///
+/// ### Examples
+/// ```rust
+/// # use thindx::d3d::*;
+/// let mut keywords = ShaderKeywords::new();
+/// let fog     = keywords.add_bool("USE_FOG");
+/// let shadows = keywords.add_bool("USE_SHADOWS");
+/// let lights  = keywords.add_values("LIGHT_COUNT", ["0", "1", "2", "4"]);
+/// keywords.exclude([(shadows, 1), (lights, 0)]); // shadows without lights are pointless
+///
+/// assert_eq!(keywords.permutations().count(), 2 * 2 * 4 - 2);
+/// for p in keywords.permutations() {
+///     let defines = p.defines().unwrap();
+///     // ...compile with `defines`...
+/// }
+/// ```
+///
+/// ### See Also
+/// *   [Compiler::compile_variants]
+#[derive(Default)]
+pub struct ShaderKeywords {
+    keywords:   Vec<KeywordDecl>,
+    bits:       u32,
+    exclusions: Vec<Exclusion>,
+}
+
+struct KeywordDecl {
+    name:   String,
+    values: Option<Vec<String>>, // None = bool
+    handle: ShaderKeyword,
+}
+
+enum Exclusion {
+    All(Vec<(ShaderKeyword, u32)>),
+    Fn(Box<dyn Fn(&ShaderPermutation) -> bool + Send + Sync>),
+}
+
+impl ShaderKeywords {
+    /// Create an empty keyword set (with a single, default permutation.)
+    pub fn new() -> Self { Self::default() }
+
+    /// Declare a boolean keyword: undefined when `0`, `#define {name} 1` when `1`.
+    ///
+    /// ### Panics
+    /// *   If this would require more than 64 bits of [ShaderVariantKey]
+    pub fn add_bool(&mut self, name: impl Into<String>) -> ShaderKeyword {
+        self.add(name.into(), None, 2)
+    }
+
+    /// Declare a multi-value keyword: `#define {name} {values[n]}`.
+    ///
+    /// ### Panics
+    /// *   If `values` is empty
+    /// *   If this would require more than 64 bits of [ShaderVariantKey]
+    pub fn add_values(&mut self, name: impl Into<String>, values: impl IntoIterator<Item = impl Into<String>>) -> ShaderKeyword {
+        let values = values.into_iter().map(|v| v.into()).collect::<Vec<_>>();
+        let count = u32::try_from(values.len()).expect("ShaderKeywords::add_values: too many values");
+        assert!(count > 0, "ShaderKeywords::add_values: keyword must have at least one value");
+        self.add(name.into(), Some(values), count)
+    }
+
+    fn add(&mut self, name: String, values: Option<Vec<String>>, count: u32) -> ShaderKeyword {
+        let bits = 32 - (count - 1).leading_zeros();
+        assert!(self.bits + bits <= 64, "ShaderKeywords: keyword {:?} doesn't fit in a 64-bit ShaderVariantKey", name);
+        let handle = ShaderKeyword { index: self.keywords.len() as u32, shift: self.bits as u8, bits: bits as u8, count };
+        self.bits += bits;
+        self.keywords.push(KeywordDecl { name, values, handle });
+        handle
+    }
+
+    /// Exclude any permutation where *all* of the given `(keyword, value)` pairs match.
+    pub fn exclude(&mut self, combination: impl IntoIterator<Item = (ShaderKeyword, u32)>) {
+        self.exclusions.push(Exclusion::All(combination.into_iter().collect()));
+    }
+
+    /// Exclude any permutation for which `rule` returns `true`.
+    pub fn exclude_if(&mut self, rule: impl Fn(&ShaderPermutation) -> bool + Send + Sync + 'static) {
+        self.exclusions.push(Exclusion::Fn(Box::new(rule)));
+    }
+
+    /// The number of bits used by [ShaderVariantKey]s of this keyword set.
+    pub fn key_bits(&self) -> u32 { self.bits }
+
+    /// The name of `keyword`.
+    pub fn name(&self, keyword: ShaderKeyword) -> &str { &self.decl(keyword).name }
+
+    /// Look up a previously declared keyword by `name`.
+    pub fn find(&self, name: &str) -> Option<ShaderKeyword> { self.keywords.iter().find(|k| k.name == name).map(|k| k.handle) }
+
+    /// Get the permutation identified by `key`, or [None] if `key` has out of range bits/values, or is excluded.
+    pub fn permutation(&self, key: ShaderVariantKey) -> Option<ShaderPermutation<'_>> {
+        let unused = if self.bits == 64 { 0 } else { !0u64 << self.bits };
+        if key.0 & unused != 0 { return None }
+        if self.keywords.iter().any(|k| k.handle.get(key) >= k.handle.count) { return None }
+        let p = ShaderPermutation { keywords: self, key };
+        if self.is_excluded(&p) { None } else { Some(p) }
+    }
+
+    /// Iterate over every non-excluded permutation, in ascending keyword-value order.
+    pub fn permutations(&self) -> impl Iterator<Item = ShaderPermutation<'_>> + '_ {
+        let mut values = vec![0u32; self.keywords.len()];
+        let mut done = false;
+        std::iter::from_fn(move || {
+            while !done {
+                let key = self.keywords.iter().zip(values.iter()).fold(0u64, |key, (k, &v)| key | u64::from(v) << k.handle.shift);
+                done = !self.keywords.iter().zip(values.iter_mut()).any(|(k, v)| {
+                    *v += 1;
+                    if *v < k.handle.count { true } else { *v = 0; false }
+                });
+                let p = ShaderPermutation { keywords: self, key: ShaderVariantKey(key) };
+                if !self.is_excluded(&p) { return Some(p) }
+            }
+            None
+        })
+    }
+
+    fn is_excluded(&self, p: &ShaderPermutation) -> bool {
+        self.exclusions.iter().any(|e| match e {
+            Exclusion::All(combination) => combination.iter().all(|&(k, v)| p.get(k) == v),
+            Exclusion::Fn(rule)         => rule(p),
+        })
+    }
+
+    fn decl(&self, keyword: ShaderKeyword) -> &KeywordDecl {
+        let decl = &self.keywords[keyword.index as usize];
+        assert!(decl.handle == keyword, "ShaderKeyword doesn't belong to this ShaderKeywords");
+        decl
+    }
+}
+
+impl Debug for ShaderKeywords {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("ShaderKeywords")
+            .field("keywords",      &self.keywords.iter().map(|k| (&k.name, k.handle.count)).collect::<Vec<_>>())
+            .field("exclusions",    &self.exclusions.len())
+            .finish()
+    }
+}
+
+
+
+/// A single permutation of [ShaderKeywords], returned by [ShaderKeywords::permutations] / [ShaderKeywords::permutation].
+#[derive(Clone, Copy)]
+pub struct ShaderPermutation<'k> {
+    keywords:   &'k ShaderKeywords,
+    key:        ShaderVariantKey,
+}
+
+impl<'k> ShaderPermutation<'k> {
+    /// The stable key identifying this permutation.
+    pub fn key(&self) -> ShaderVariantKey { self.key }
+
+    /// The value index of `keyword` in this permutation.
+    pub fn get(&self, keyword: ShaderKeyword) -> u32 { keyword.get(self.key) }
+
+    /// `true` if `keyword` has a nonzero value in this permutation.
+    pub fn is_enabled(&self, keyword: ShaderKeyword) -> bool { self.get(keyword) != 0 }
+
+    /// Iterate over the `(name, definition)` pairs this permutation `#define`s.
+    pub fn iter_defines(&self) -> impl Iterator<Item = (&'k str, &'k str)> + '_ {
+        self.keywords.keywords.iter().filter_map(move |k| {
+            let v = self.get(k.handle);
+            match k.values.as_ref() {
+                None if v == 0  => None,
+                None            => Some((k.name.as_str(), "1")),
+                Some(values)    => Some((k.name.as_str(), values[v as usize].as_str())),
+            }
+        })
+    }
+
+    /// The `#define`s for this permutation, suitable for passing to [Compiler::compile].
+    ///
+    /// ### Errors
+    /// *   [THINERR::STRING_CONTAINS_NULS]    - if a keyword name or value contains interior `\0`s
+    pub fn defines(&self) -> Result<ShaderMacros, ErrorKind> {
+        let mut defines = ShaderMacros::new();
+        for (name, value) in self.iter_defines() { defines.define(name, value)? }
+        Ok(defines)
+    }
+}
+
+impl Debug for ShaderPermutation<'_> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("ShaderPermutation")
+            .field("key",       &self.key)
+            .field("defines",   &format_args!("{}", self))
+            .finish()
+    }
+}
+
+impl Display for ShaderPermutation<'_> {
+    /// `NAME=VALUE NAME=VALUE ...` (or `(default)` if nothing is defined)
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let mut any = false;
+        for (name, value) in self.iter_defines() {
+            if any { write!(fmt, " ")? }
+            write!(fmt, "{}={}", name, value)?;
+            any = true;
+        }
+        if !any { write!(fmt, "(default)")? }
+        Ok(())
+    }
+}
+
+
+
+/// A set of compiled shader variants, selectable at runtime by [ShaderVariantKey].
+///
+/// ### See Also
+/// *   [Compiler::compile_variants]
+#[derive(Clone, Debug, Default)]
+pub struct ShaderVariants {
+    variants: BTreeMap<ShaderVariantKey, CodeBlob>,
+}
+
+impl ShaderVariants {
+    /// Create an empty set of variants.
+    pub fn new() -> Self { Self::default() }
+
+    /// Add (or replace) the bytecode for `key`.
+    pub fn insert(&mut self, key: ShaderVariantKey, shader: CodeBlob) -> Option<CodeBlob> { self.variants.insert(key, shader) }
+
+    /// Get the bytecode for `key`, or [None] if no such variant was compiled (e.g. it was excluded.)
+    pub fn get(&self, key: ShaderVariantKey) -> Option<&Bytecode> { self.variants.get(&key).map(|s| s.as_bytecode()) }
+
+    /// Get the bytecode blob for `key`.
+    pub fn get_blob(&self, key: ShaderVariantKey) -> Option<&CodeBlob> { self.variants.get(&key) }
+
+    /// The number of compiled variants.
+    pub fn len(&self) -> usize { self.variants.len() }
+
+    /// `true` if no variants have been compiled.
+    pub fn is_empty(&self) -> bool { self.variants.is_empty() }
+
+    /// Iterate over `(key, bytecode)` pairs in ascending key order.
+    pub fn iter(&self) -> impl Iterator<Item = (ShaderVariantKey, &Bytecode)> { self.variants.iter().map(|(k, s)| (*k, s.as_bytecode())) }
+}
+
+impl FromIterator<(ShaderVariantKey, CodeBlob)> for ShaderVariants {
+    fn from_iter<I: IntoIterator<Item = (ShaderVariantKey, CodeBlob)>>(iter: I) -> Self { Self { variants: iter.into_iter().collect() } }
+}
+
+
+
+/// { key: [ShaderVariantKey], defines: [String], error: [CompileError] } returned by [Compiler::compile_variants]
+#[derive(Clone, Debug)]
+pub struct ShaderVariantError {
+    /// The key of the permutation that failed to compile.
+    pub key:        ShaderVariantKey,
+
+    /// The `NAME=VALUE ...` defines of the permutation that failed to compile.
+    pub defines:    String,
+
+    /// The underlying compile error.
+    pub error:      CompileError,
+}
+
+impl std::error::Error for ShaderVariantError {}
+
+impl Display for ShaderVariantError {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "Error compiling shader variant {} ({}): {}", self.key, self.defines, self.error)
+    }
+}
+
+
+
+/// <h1 id="variants" class="section-header"><a href="#variants">Compile Shader Permutations</a></h1>
+impl Compiler {
+    /// Compile every non-excluded permutation of `keywords`.
+    ///
+    /// ### Arguments
+    /// *   `src_data`      - The shader source code
+    /// *   `source_name`   - An optional shader name such as `Some("myshader.hlsl")` for debug purpouses.
+    /// *   `keywords`      - The keywords to permute.  Each permutation's `#define`s are passed to [Compiler::compile].
+    /// *   `include`       - An optional interface for dispatching `#include`s.
+    /// *   `entrypoint`    - An optional entrypoint such as `Some("main")`.  Ignored if `target` is `fx_*`.
+    /// *   `target`        - A target shader profile such as `ps_3_0`, `vs_5_0`, `fx_4_0`, etc.
+    /// *   `flags1`        - [Compile]::\* constants.
+    /// *   `flags2`        - [CompileEffect]::\* constants.
+    ///
+    /// ### Errors
+    /// *   [ShaderVariantError]            - for the first permutation that failed to compile
+    ///
+    /// ### Examples
+    /// ```rust
+    /// # use thindx::d3d::*; let d3dc = Compiler::load_system(47).unwrap();
+    /// let mut keywords = ShaderKeywords::new();
+    /// let fog = keywords.add_bool("USE_FOG");
+    ///
+    /// let basic_hlsl = std::fs::read(r"test\data\basic.hlsl").unwrap();
+    /// let variants = d3dc.compile_variants(
+    ///     &basic_hlsl, r"test\data\basic.hlsl", &keywords, None, "ps_main", ShaderTarget::PS_4_0,
+    ///     Compile::Debug, CompileEffect::None,
+    /// ).unwrap();
+    /// assert_eq!(variants.len(), 2);
+    ///
+    /// let ps_fog = variants.get(fog.enabled(true)).unwrap();
+    /// ```
+    pub fn compile_variants(
+        &self,
+        src_data:       impl AsRef<[u8]>,
+        source_name:    impl TryIntoAsOptCStr + Clone,
+        keywords:       &ShaderKeywords,
+        include:        impl AsInclude,
+        entrypoint:     impl TryIntoAsOptCStr + Clone,
+        target:         impl TryIntoAsCStr + Clone,
+        flags1:         impl Into<Compile>,
+        flags2:         impl Into<CompileEffect>,
+    ) -> Result<ShaderVariants, ShaderVariantError> {
+        fn_context!(d3d::Compiler::compile_variants);
+        let src_data    = src_data.as_ref();
+        let flags1      = flags1.into();
+        let flags2      = flags2.into();
+
+        let mut variants = ShaderVariants::new();
+        for p in keywords.permutations() {
+            let err = |error: CompileError| ShaderVariantError { key: p.key(), defines: p.to_string(), error };
+            let defines = p.defines().map_err(|e| err(fn_param_error!(keywords, e).into()))?;
+            let shader = self.compile(src_data, source_name.clone(), &defines, &include, entrypoint.clone(), target.clone(), flags1, flags2).map_err(err)?;
+            variants.insert(p.key(), shader.shader);
+        }
+        Ok(variants)
+    }
+}
+
+
+
+#[test] fn permutations() {
+    let mut keywords = ShaderKeywords::new();
+    assert_eq!(keywords.permutations().map(|p| p.key()).collect::<Vec<_>>(), vec![ShaderVariantKey::DEFAULT]);
+
+    let fog     = keywords.add_bool("USE_FOG");
+    let lights  = keywords.add_values("LIGHT_COUNT", ["0", "1", "2", "4", "8"]);
+    let shadows = keywords.add_bool("USE_SHADOWS");
+    assert_eq!(keywords.key_bits(), 1 + 3 + 1);
+    assert_eq!(keywords.find("LIGHT_COUNT"), Some(lights));
+    assert_eq!(keywords.name(shadows), "USE_SHADOWS");
+    assert_eq!(keywords.permutations().count(), 2 * 5 * 2);
+
+    let key = fog.enabled(true) | lights.value(4) | shadows.enabled(true);
+    assert_eq!(key.bits(), 0b1_100_1);
+    let p = keywords.permutation(key).unwrap();
+    assert_eq!(p.get(lights), 4);
+    assert!(p.is_enabled(fog));
+    assert_eq!(p.to_string(), "USE_FOG=1 LIGHT_COUNT=8 USE_SHADOWS=1");
+    assert_eq!(keywords.permutation(ShaderVariantKey::DEFAULT).unwrap().to_string(), "LIGHT_COUNT=0");
+
+    assert!(keywords.permutation(ShaderVariantKey::from_bits(0b0_101_0)).is_none(), "LIGHT_COUNT value 5 is out of range");
+    assert!(keywords.permutation(ShaderVariantKey::from_bits(1 << 5)).is_none(), "unused bit set");
+
+    keywords.exclude([(shadows, 1), (lights, 0)]);
+    keywords.exclude_if(move |p| p.is_enabled(fog) && p.get(lights) > 2);
+    assert_eq!(keywords.permutations().count(), 2 * 5 * 2 - 2 - 2 * 2);
+    assert!(keywords.permutation(shadows.enabled(true)).is_none());
+    assert!(keywords.permutation(key).is_none());
+    assert!(keywords.permutations().all(|p| keywords.permutation(p.key()).is_some()));
+
+    let mut keys = keywords.permutations().map(|p| p.key()).collect::<Vec<_>>();
+    let n = keys.len();
+    keys.sort();
+    keys.dedup();
+    assert_eq!(keys.len(), n, "keys should be unique");
+}
+
+#[test] fn key_bits() {
+    let mut keywords = ShaderKeywords::new();
+    let one     = keywords.add_values("ONE", ["A"]);
+    let three   = keywords.add_values("THREE", ["A", "B", "C"]);
+    let four    = keywords.add_values("FOUR", ["A", "B", "C", "D"]);
+    assert_eq!(keywords.key_bits(), 0 + 2 + 2);
+    assert_eq!(one.value(0).bits(), 0);
+    assert_eq!(three.value(2).bits(), 0b10);
+    assert_eq!(four.value(3).bits(), 0b11_00);
+    assert_eq!(keywords.permutations().count(), 3 * 4);
+}
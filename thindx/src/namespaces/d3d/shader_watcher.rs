@@ -0,0 +1,415 @@
+use crate::*;
+use crate::d3d::*;
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+
+
+/// { path, entrypoint, target, defines, flags1, flags2 } describing a shader to be compiled from a file.
+#[derive(Clone, Debug)]
+pub struct ShaderSource {
+    /// The root HLSL file.  `#include`s are resolved relative to the including file's directory.
+    pub path:       PathBuf,
+
+    /// An optional entrypoint such as `Some("main")`.  Ignored if `target` is `fx_*`.
+    pub entrypoint: Option<String>,
+
+    /// A target shader profile such as `ps_3_0`, `vs_5_0`, `fx_4_0`, etc.
+    pub target:     String,
+
+    /// `#define {name} {definition}`s to compile with.
+    pub defines:    Vec<(String, String)>,
+
+    /// [Compile]::\* constants.
+    pub flags1:     Compile,
+
+    /// [CompileEffect]::\* constants.
+    pub flags2:     CompileEffect,
+}
+
+impl ShaderSource {
+    /// Describe `entrypoint` in `path`, compiled for `target`, with no defines or flags.
+    pub fn new(path: impl Into<PathBuf>, entrypoint: impl Into<String>, target: impl ToString) -> Self {
+        Self {
+            path:       path.into(),
+            entrypoint: Some(entrypoint.into()),
+            target:     target.to_string(),
+            defines:    Vec::new(),
+            flags1:     Compile::None,
+            flags2:     CompileEffect::None,
+        }
+    }
+
+    /// Add `#define {name} {definition}`.
+    pub fn define(mut self, name: impl Into<String>, definition: impl Into<String>) -> Self { self.defines.push((name.into(), definition.into())); self }
+
+    /// Replace [ShaderSource::flags1].
+    pub fn flags1(mut self, flags1: impl Into<Compile>) -> Self { self.flags1 = flags1.into(); self }
+
+    /// Replace [ShaderSource::flags2].
+    pub fn flags2(mut self, flags2: impl Into<CompileEffect>) -> Self { self.flags2 = flags2.into(); self }
+}
+
+
+
+/// A shader compiler that reads all of its input through a caller-provided callback.
+///
+/// Implemented by [Compiler] (via `D3DCompile`), but can be implemented by stand-in compilers for testing, or by other toolchains.
+pub trait ShaderBackend {
+    /// The result of a successful compile (e.g. [CodeBlob].)
+    type Output;
+
+    /// The result of a failed compile (e.g. [ErrorWithBlob], which includes [diagnostics](ErrorWithBlob::diagnostics).)
+    type Error;
+
+    /// Compile `source`, reading [ShaderSource::path] and every `#include`d file through `read`.
+    fn compile(&self, source: &ShaderSource, read: &dyn Fn(&Path) -> io::Result<Vec<u8>>) -> Result<Self::Output, Self::Error>;
+}
+
+impl ShaderBackend for Compiler {
+    type Output = CodeBlob;
+    type Error  = ErrorWithBlob;
+
+    fn compile(&self, source: &ShaderSource, read: &dyn Fn(&Path) -> io::Result<Vec<u8>>) -> Result<CodeBlob, ErrorWithBlob> {
+        fn_context!(d3d::ShaderBackend::compile);
+        let io_err = |err: io::Error| err.raw_os_error().map_or(ErrorKind::from(D3D11::ERROR_FILE_NOT_FOUND), |raw| ErrorKind::from_win32(raw as _));
+
+        let src = read(&source.path).map_err(|e| fn_param_error!(source, io_err(e)))?;
+        let root_dir = source.path.parent().unwrap_or(Path::new(""));
+
+        let mut defines = ShaderMacros::new();
+        for (name, definition) in source.defines.iter() { defines.define(name.as_str(), definition.as_str()).map_err(|e| fn_param_error!(source, e))? }
+
+        let include = Include::from_blob_meta_fn(|_include_type, file_name: abistr::CStrNonNull, parent: Option<&PathBuf>| {
+            let dir     = parent.map_or(root_dir, |p| &**p);
+            let path    = dir.join(file_name.to_str().map_err(|_| D3D11::ERROR_FILE_NOT_FOUND)?);
+            let data    = read(&path).map_err(io_err)?;
+            let dir     = path.parent().unwrap_or(Path::new("")).to_path_buf();
+            Ok((data, dir))
+        });
+
+        let source_name = source.path.to_string_lossy();
+        match Compiler::compile(self, &src, &*source_name, &defines, &include, source.entrypoint.as_deref(), source.target.as_str(), source.flags1, source.flags2) {
+            Ok(CompileResult { shader, .. })                => Ok(shader),
+            Err(CompileError { error, errors, .. })         => Err(ErrorWithBlob { error, errors }),
+        }
+    }
+}
+
+
+
+/// A cheap-to-query file version, used by [ShaderWatcher] to detect modified files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ShaderFileStamp {
+    /// The last modification time of the file, if available.
+    pub modified:   Option<SystemTime>,
+
+    /// The size of the file, in bytes.
+    pub len:        u64,
+}
+
+/// A file system that [ShaderWatcher] reads and polls shader sources through.
+///
+/// [StdShaderFiles] uses [std::fs], but in-memory implementations can be used for testing or embedded/virtual file systems.
+pub trait ShaderFiles {
+    /// Read the entire contents of `path`.
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Get the current [ShaderFileStamp] of `path`.
+    fn stamp(&self, path: &Path) -> io::Result<ShaderFileStamp>;
+}
+
+/// [ShaderFiles] implemented via [std::fs].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StdShaderFiles;
+
+impl ShaderFiles for StdShaderFiles {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> { std::fs::read(path) }
+
+    fn stamp(&self, path: &Path) -> io::Result<ShaderFileStamp> {
+        let meta = std::fs::metadata(path)?;
+        Ok(ShaderFileStamp { modified: meta.modified().ok(), len: meta.len() })
+    }
+}
+
+
+
+/// Identifies a shader added to a [ShaderWatcher].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ShaderId(usize);
+
+/// { id, source, result } passed to [ShaderWatcher::poll]'s callback whenever a shader was recompiled.
+pub struct ShaderReload<'w, B: ShaderBackend> {
+    /// The shader that was recompiled.
+    pub id:         ShaderId,
+
+    /// The description of the shader that was recompiled.
+    pub source:     &'w ShaderSource,
+
+    /// The new bytecode, or the error/diagnostics explaining why compilation failed.
+    pub result:     Result<&'w B::Output, &'w B::Error>,
+}
+
+/// Compiles shaders, tracks every file they read (including `#include`s), and recompiles them when those files change.
+///
+/// ### Examples
+/// ```rust
+/// # use thindx::d3d::*;
+/// # return; // doc tests have wrong dir
+/// let d3dc = Compiler::load_system(47).unwrap();
+/// let mut watcher = ShaderWatcher::new(d3dc);
+/// let ps = watcher.add(ShaderSource::new(r"thindx\test\data\include-chain-1.hlsl", "ps_main", ShaderTarget::PS_4_0));
+/// let bytecode = watcher.result(ps).unwrap().unwrap();
+///
+/// loop {
+///     watcher.poll(|reload| match reload.result {
+///         Ok(_shader) => println!("reloaded {}", reload.source.path.display()),
+///         Err(err)    => for d in err.diagnostics() { eprintln!("{}", d) },
+///     });
+///     # break;
+///     std::thread::sleep(std::time::Duration::from_millis(100));
+/// }
+/// ```
+pub struct ShaderWatcher<B: ShaderBackend, F: ShaderFiles = StdShaderFiles> {
+    backend:    B,
+    files:      F,
+    shaders:    Vec<Option<WatchedShader<B>>>,
+    tracked:    BTreeMap<PathBuf, TrackedFile>,
+}
+
+struct WatchedShader<B: ShaderBackend> {
+    source:         ShaderSource,
+    dependencies:   Vec<PathBuf>,
+    result:         Result<B::Output, B::Error>,
+}
+
+struct TrackedFile {
+    stamp:      Option<ShaderFileStamp>, // None if missing
+    dependents: BTreeSet<ShaderId>,
+}
+
+impl<B: ShaderBackend> ShaderWatcher<B> {
+    /// Create a watcher that compiles with `backend`, reading from the local file system.
+    pub fn new(backend: B) -> Self { Self::with_files(backend, StdShaderFiles) }
+}
+
+impl<B: ShaderBackend, F: ShaderFiles> ShaderWatcher<B, F> {
+    /// Create a watcher that compiles with `backend`, reading from `files`.
+    pub fn with_files(backend: B, files: F) -> Self {
+        Self { backend, files, shaders: Vec::new(), tracked: BTreeMap::new() }
+    }
+
+    /// The compiler backend.
+    pub fn backend(&self) -> &B { &self.backend }
+
+    /// The file system shaders are read from.
+    pub fn files(&self) -> &F { &self.files }
+
+    /// The file system shaders are read from.
+    pub fn files_mut(&mut self) -> &mut F { &mut self.files }
+
+    /// Compile and start watching `source`.  Check [ShaderWatcher::result] to see if compilation succeeded.
+    pub fn add(&mut self, source: ShaderSource) -> ShaderId {
+        let id = ShaderId(self.shaders.len());
+        let (result, dependencies) = self.compile_tracked(id, &source);
+        self.shaders.push(Some(WatchedShader { source, dependencies, result }));
+        id
+    }
+
+    /// Stop watching `id`, returning its source if it was being watched.
+    pub fn remove(&mut self, id: ShaderId) -> Option<ShaderSource> {
+        let shader = self.shaders.get_mut(id.0)?.take()?;
+        self.untrack(id, &shader.dependencies);
+        Some(shader.source)
+    }
+
+    /// The description of shader `id`.
+    pub fn source(&self, id: ShaderId) -> Option<&ShaderSource> { Some(&self.shader(id)?.source) }
+
+    /// The most recent compile result of shader `id`.
+    pub fn result(&self, id: ShaderId) -> Option<Result<&B::Output, &B::Error>> { Some(self.shader(id)?.result.as_ref()) }
+
+    /// Every file read by the most recent compile of shader `id` (the root file first, then `#include`s in the order they were read.)
+    pub fn dependencies(&self, id: ShaderId) -> impl Iterator<Item = &Path> { self.shader(id).into_iter().flat_map(|s| s.dependencies.iter().map(|p| &**p)) }
+
+    /// Every shader whose most recent compile read `path`.
+    pub fn dependents(&self, path: &Path) -> impl Iterator<Item = ShaderId> + '_ { self.tracked.get(path).into_iter().flat_map(|f| f.dependents.iter().copied()) }
+
+    /// Iterate over the ids of all watched shaders.
+    pub fn ids(&self) -> impl Iterator<Item = ShaderId> + '_ { self.shaders.iter().enumerate().filter(|(_, s)| s.is_some()).map(|(i, _)| ShaderId(i)) }
+
+    /// Check every tracked file for changes, recompile any shader that depends on a changed file, and report each recompile to `on_reload`.
+    ///
+    /// ### Returns
+    /// *   The number of shaders recompiled.
+    pub fn poll(&mut self, mut on_reload: impl FnMut(ShaderReload<B>)) -> usize {
+        let mut dirty = BTreeSet::new();
+        for (path, file) in self.tracked.iter() {
+            if self.files.stamp(path).ok() != file.stamp { dirty.extend(file.dependents.iter().copied()) }
+        }
+
+        for &id in dirty.iter() {
+            let shader = self.shaders[id.0].take().expect("dirty shader was removed without being untracked");
+            self.untrack(id, &shader.dependencies);
+            let (result, dependencies) = self.compile_tracked(id, &shader.source);
+            let shader = self.shaders[id.0].insert(WatchedShader { source: shader.source, dependencies, result });
+            on_reload(ShaderReload { id, source: &shader.source, result: shader.result.as_ref() });
+        }
+
+        dirty.len()
+    }
+
+    fn shader(&self, id: ShaderId) -> Option<&WatchedShader<B>> { self.shaders.get(id.0)?.as_ref() }
+
+    fn compile_tracked(&mut self, id: ShaderId, source: &ShaderSource) -> (Result<B::Output, B::Error>, Vec<PathBuf>) {
+        let files = &self.files;
+        let reads = RefCell::new(Vec::<(PathBuf, Option<ShaderFileStamp>)>::new());
+        let result = self.backend.compile(source, &|path| {
+            let stamp = files.stamp(path).ok(); // stamp *before* reading, so writes during compilation trigger another recompile
+            reads.borrow_mut().push((path.to_path_buf(), stamp));
+            files.read(path)
+        });
+
+        let mut dependencies = Vec::new();
+        for (path, stamp) in reads.into_inner() {
+            if dependencies.contains(&path) { continue }
+            let file = self.tracked.entry(path.clone()).or_insert_with(|| TrackedFile { stamp, dependents: BTreeSet::new() });
+            file.stamp = stamp;
+            file.dependents.insert(id);
+            dependencies.push(path);
+        }
+        (result, dependencies)
+    }
+
+    fn untrack(&mut self, id: ShaderId, dependencies: &[PathBuf]) {
+        for path in dependencies {
+            if let Some(file) = self.tracked.get_mut(path) {
+                file.dependents.remove(&id);
+                if file.dependents.is_empty() { self.tracked.remove(path); }
+            }
+        }
+    }
+}
+
+impl<B: ShaderBackend, F: ShaderFiles> Debug for ShaderWatcher<B, F> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("ShaderWatcher")
+            .field("shaders",   &self.ids().map(|id| &self.shaders[id.0].as_ref().unwrap().source.path).collect::<Vec<_>>())
+            .field("tracked",   &self.tracked.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    /// In-memory [ShaderFiles] where every write bumps the modification time.
+    #[derive(Default)] struct MemFiles { files: HashMap<PathBuf, (Vec<u8>, u64)>, writes: u64 }
+
+    impl MemFiles {
+        fn write(&mut self, path: &str, data: &str) { self.writes += 1; self.files.insert(PathBuf::from(path), (data.into(), self.writes)); }
+        fn delete(&mut self, path: &str) { self.files.remove(Path::new(path)); }
+    }
+
+    impl ShaderFiles for MemFiles {
+        fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+            self.files.get(path).map(|f| f.0.clone()).ok_or_else(|| io::ErrorKind::NotFound.into())
+        }
+
+        fn stamp(&self, path: &Path) -> io::Result<ShaderFileStamp> {
+            let (data, writes) = self.files.get(path).ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+            Ok(ShaderFileStamp { modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(*writes)), len: data.len() as u64 })
+        }
+    }
+
+    /// Stand-in compiler: "bytecode" is the source with `#include "..."` lines recursively expanded.
+    struct Expand;
+
+    impl ShaderBackend for Expand {
+        type Output = String;
+        type Error  = String;
+
+        fn compile(&self, source: &ShaderSource, read: &dyn Fn(&Path) -> io::Result<Vec<u8>>) -> Result<String, String> {
+            fn expand(path: &Path, read: &dyn Fn(&Path) -> io::Result<Vec<u8>>, out: &mut String) -> Result<(), String> {
+                let text = read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                for line in String::from_utf8(text).unwrap().lines() {
+                    match line.strip_prefix("#include \"").and_then(|l| l.strip_suffix('"')) {
+                        Some(inc)   => expand(&path.parent().unwrap().join(inc), read, out)?,
+                        None        => { out.push_str(line); out.push('\n'); },
+                    }
+                }
+                Ok(())
+            }
+            let mut out = String::new();
+            expand(&source.path, read, &mut out)?;
+            Ok(out)
+        }
+    }
+
+    fn p(path: &str) -> PathBuf { PathBuf::from(path) }
+
+    #[test] fn dependency_graph() {
+        let mut files = MemFiles::default();
+        files.write("s/a.hlsl",         "#include \"common.hlsl\"\na");
+        files.write("s/b.hlsl",         "#include \"common.hlsl\"\n#include \"inc/b.hlsl\"\nb");
+        files.write("s/common.hlsl",    "common");
+        files.write("s/inc/b.hlsl",     "b_inc");
+
+        let mut watcher = ShaderWatcher::with_files(Expand, files);
+        let a = watcher.add(ShaderSource::new("s/a.hlsl", "main", "ps_4_0"));
+        let b = watcher.add(ShaderSource::new("s/b.hlsl", "main", "ps_4_0"));
+        assert_eq!(watcher.result(a).unwrap().unwrap(), "common\na\n");
+        assert_eq!(watcher.result(b).unwrap().unwrap(), "common\nb_inc\nb\n");
+        assert_eq!(watcher.dependencies(a).collect::<Vec<_>>(), [Path::new("s/a.hlsl"), Path::new("s/common.hlsl")]);
+        assert_eq!(watcher.dependents(&p("s/common.hlsl")).collect::<Vec<_>>(), [a, b]);
+        assert_eq!(watcher.dependents(&p("s/inc/b.hlsl")).collect::<Vec<_>>(), [b]);
+        assert_eq!(watcher.poll(|_| panic!("nothing changed")), 0);
+
+        watcher.files_mut().write("s/inc/b.hlsl", "b_inc2");
+        let mut reloaded = Vec::new();
+        assert_eq!(watcher.poll(|r| reloaded.push((r.id, r.result.unwrap().clone()))), 1);
+        assert_eq!(reloaded, [(b, String::from("common\nb_inc2\nb\n"))]);
+        assert_eq!(watcher.poll(|_| panic!("nothing changed")), 0);
+
+        watcher.files_mut().write("s/b.hlsl", "b");
+        assert_eq!(watcher.poll(|r| assert_eq!(r.id, b)), 1);
+        assert_eq!(watcher.dependents(&p("s/common.hlsl")).collect::<Vec<_>>(), [a], "b no longer includes common");
+        assert_eq!(watcher.dependents(&p("s/inc/b.hlsl")).count(), 0, "b no longer includes inc/b");
+
+        assert_eq!(watcher.remove(a).unwrap().path, p("s/a.hlsl"));
+        assert_eq!(watcher.dependents(&p("s/common.hlsl")).count(), 0);
+        watcher.files_mut().write("s/common.hlsl", "common2");
+        assert_eq!(watcher.poll(|_| panic!("nobody depends on common.hlsl")), 0);
+        assert_eq!(watcher.ids().collect::<Vec<_>>(), [b]);
+    }
+
+    #[test] fn missing_files() {
+        let mut files = MemFiles::default();
+        files.write("a.hlsl", "#include \"missing.hlsl\"\na");
+
+        let mut watcher = ShaderWatcher::with_files(Expand, files);
+        let a = watcher.add(ShaderSource::new("a.hlsl", "main", "ps_4_0"));
+        assert!(watcher.result(a).unwrap().is_err());
+        assert_eq!(watcher.dependents(&p("missing.hlsl")).collect::<Vec<_>>(), [a]);
+
+        watcher.files_mut().write("missing.hlsl", "found");
+        let mut reloaded = Vec::new();
+        assert_eq!(watcher.poll(|r| reloaded.push(r.result.map(|s| s.clone()).map_err(|e| e.clone()))), 1);
+        assert_eq!(reloaded, [Ok(String::from("found\na\n"))]);
+
+        watcher.files_mut().delete("a.hlsl");
+        let mut reloaded = Vec::new();
+        assert_eq!(watcher.poll(|r| reloaded.push(r.result.is_err())), 1);
+        assert_eq!(reloaded, [true]);
+        assert_eq!(watcher.dependencies(a).collect::<Vec<_>>(), [Path::new("a.hlsl")]);
+    }
+}
@@ -1,4 +1,21 @@
 //! \[[microsoft.com](https://learn.microsoft.com/en-us/windows/win32/direct3d11/atoc-dx-graphics-direct3d-11)\]
 //! Direct3D 11 related types and APIs (including shader reflection APIs)
+//!
+//! ### Features
+//! | feature               | Description           |
+//! | --------------------- | --------------------- |
+//! | `derive`              | Enables `#[derive(ConstantBuffer)]`
+//! | `serde`               | Enables `serde::{Serialize, Deserialize}` for [ReflectionData] and friends
 
 pub use crate::d3d11shader_h::*;
+#[cfg(feature = "derive")] pub use thindx_derive::ConstantBuffer;
+
+mods! {
+    inl mod constant_buffer;
+    inl mod hlsl_codegen;
+    inl mod linking_graph;
+    inl mod pipeline_check;
+    inl mod reflection_data;
+    mod reflection_parse;
+    inl mod rust_codegen;
+}
@@ -0,0 +1,557 @@
+use crate::*;
+use crate::d3d::*;
+use crate::d3d11::*;
+
+use abibool::bool32;
+
+use std::fmt::{self, Display, Formatter};
+
+
+
+/// A Rust struct that can be packed into a HLSL `cbuffer` according to HLSL's packing rules.
+///
+/// Implement this with `#[derive(ConstantBuffer)]` (requires the `derive` feature) rather than by hand.
+/// HLSL packs `cbuffer` members quite differently from `#[repr(C)]`:
+/// *   Members are packed into 16 byte registers, and may not straddle a register boundary.
+/// *   Arrays and matrices always start on a new register, and every array element is padded to a full register.
+/// *   The tail of the last register of an array or matrix can still hold subsequent members.
+/// *   Matrices are `column_major` by default, one register per column.
+///
+/// Rather than asking you to hand-pad your structs, the derive computes the HLSL offset of every field at compile time,
+/// and generates a padded `#[repr(C, align(16))]` `{Name}Hlsl` struct for [to_hlsl](Self::to_hlsl) to copy fields into, suitable for uploading.
+/// `{Name}Hlsl` has a `pub {field}: [u8; N]` for every field, separated by explicit `_pad#` fields:  the original field types can't be reused,
+/// as `column_major` matrices are stored transposed, and every array element is padded out to a full register.
+///
+/// ### Field Attributes
+/// | Attribute                 | Description   |
+/// | ------------------------- | ------------- |
+/// | `#[hlsl(row_major)]`      | Treat a `[[T; C]; R]` field as a `row_major TRxC` matrix
+/// | `#[hlsl(column_major)]`   | Treat a `[[T; C]; R]` field as a `column_major TRxC` matrix
+/// | `#[hlsl(array)]`          | Treat a `[T; 1..=4]` field as `T name[N]` instead of a vector
+/// | `#[hlsl(rename = "x")]`   | Use a different HLSL name than the Rust field name
+/// | `#[hlsl(skip)]`           | Don't pack this field at all
+///
+/// `#[hlsl(rename = "x")]` may also be placed on the struct itself, to change the `cbuffer` name [check_reflection](Self::check_reflection) looks for.
+///
+/// ### Examples
+/// ```rust
+/// # #[cfg(feature = "derive")] fn main() {
+/// use thindx::d3d11::ConstantBuffer;
+///
+/// #[derive(Clone, Copy, ConstantBuffer)]
+/// #[hlsl(rename = "ExampleCBuffer")]
+/// struct Example {
+///     scale:      [f32; 2],               // float2   scale;      // offset  0
+///     direction:  [f32; 3],               // float3   direction;  // offset 16 (wouldn't fit in the first register)
+///     #[hlsl(array)]
+///     weights:    [f32; 3],               // float    weights[3]; // offset 32, 48, 64
+///     time:       f32,                    // float    time;       // offset 68
+///     #[hlsl(row_major)]
+///     world:      [[f32; 4]; 4],          // row_major float4x4 world; // offset 80
+/// }
+///
+/// let offsets = Example::FIELDS.iter().map(|f| f.offset).collect::<Vec<_>>();
+/// assert_eq!(offsets, [0, 16, 32, 68, 80]);
+/// assert_eq!(Example::SIZE, 144);
+///
+/// let hlsl : ExampleHlsl = Example {
+///     scale: [1.0, 2.0], direction: [0.0; 3], weights: [0.0; 3], time: 3.0, world: [[0.0; 4]; 4],
+/// }.to_hlsl();
+/// assert_eq!(std::mem::size_of::<ExampleHlsl>(), 144);
+/// assert_eq!(hlsl.time, 3.0f32.to_le_bytes());
+/// assert_eq!(hlsl.as_ref()[68..72], 3.0f32.to_le_bytes());
+///
+/// // Compare against what the compiler actually did:
+/// # use thindx::d3d::*;
+/// # let d3dc = Compiler::load_system(47).unwrap();
+/// let vs = d3dc.compile(b"
+///     cbuffer ExampleCBuffer {
+///         float2 scale; float3 direction; float weights[3]; float time; row_major float4x4 world;
+///     };
+///     float4 main() : SV_Position { return float4(scale * weights[2] * time, direction.x, world._11); }
+/// ", "example.hlsl", None, None, "main", "vs_4_0", Compile::Debug, CompileEffect::None).unwrap();
+/// Example::check_reflection(&d3dc.reflect11(&vs).unwrap()).unwrap();
+/// # }
+/// # #[cfg(not(feature = "derive"))] fn main() {}
+/// ```
+pub trait ConstantBuffer {
+    /// The HLSL `cbuffer` name.
+    const NAME : &'static str;
+
+    /// The HLSL packed fields, in declaration order.
+    const FIELDS : &'static [ConstantBufferField];
+
+    /// The HLSL packed size, rounded up to a multiple of 16 bytes (as required of constant buffer sizes.)
+    const SIZE : u32 = ConstantBufferField::buffer_size(Self::FIELDS);
+
+    /// A padding-correct, [Self::SIZE] byte image of this buffer, generally the `{Name}Hlsl` struct generated by `#[derive(ConstantBuffer)]`.
+    type Hlsl : Copy + Default + AsRef<[u8]> + AsMut<[u8]>;
+
+    /// Pack `self` according to HLSL's packing rules.
+    fn to_hlsl(&self) -> Self::Hlsl;
+
+    /// Compare [Self::FIELDS] and [Self::SIZE] against a reflected `cbuffer`, reporting every mismatch.
+    ///
+    /// ### Errors
+    /// *   [ConstantBufferProblem::BufferSize]     - if the `cbuffer` size doesn't match [Self::SIZE]
+    /// *   [ConstantBufferProblem::MissingField]   - if HLSL declares a variable the Rust struct doesn't
+    /// *   [ConstantBufferProblem::MissingVariable] - if the Rust struct declares a field HLSL doesn't
+    /// *   [ConstantBufferProblem::Offset] / [Size](ConstantBufferProblem::Size) / [Type](ConstantBufferProblem::Type) - if a field is laid out differently in HLSL
+    fn check(cb: &ReflectionConstantBuffer) -> Result<(), ConstantBufferMismatch> {
+        ConstantBufferField::check(Self::FIELDS, Self::SIZE, cb)
+    }
+
+    /// Compare [Self::FIELDS] and [Self::SIZE] against the `cbuffer` named [Self::NAME] in `reflection`.
+    ///
+    /// ### Errors
+    /// *   [ConstantBufferProblem::Reflection]     - if `reflection` has no `cbuffer` named [Self::NAME]
+    /// *   Anything [ConstantBuffer::check] can return
+    fn check_reflection(reflection: &ShaderReflection) -> Result<(), ConstantBufferMismatch> {
+        match ReflectionConstantBuffer::from_reflection(&reflection.get_constant_buffer_by_name(Self::NAME)) {
+            Ok(cb)      => Self::check(&cb),
+            Err(err)    => Err(ConstantBufferMismatch { buffer: Self::NAME.into(), problems: vec![ConstantBufferProblem::Reflection(err)] }),
+        }
+    }
+}
+
+/// A single HLSL packed field of a [ConstantBuffer].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConstantBufferField {
+    /// The HLSL variable name
+    pub name:   &'static str,
+
+    /// The HLSL offset of the variable, in bytes, from the start of the `cbuffer`
+    pub offset: u32,
+
+    /// The HLSL type of the variable
+    pub layout: HlslLayout,
+}
+
+impl ConstantBufferField {
+    /// Assign HLSL offsets to a list of fields, in declaration order.
+    pub const fn pack<const N: usize>(fields: [(&'static str, HlslLayout); N]) -> [Self; N] {
+        let mut packed = [Self { name: "", offset: 0, layout: HlslLayout::scalar(ShaderVariableType::Void) }; N];
+        let mut end = 0;
+        let mut i = 0;
+        while i < N {
+            let (name, layout) = fields[i];
+            let offset = layout.pack_after(end);
+            packed[i] = Self { name, offset, layout };
+            end = offset + layout.size();
+            i += 1;
+        }
+        packed
+    }
+
+    /// The HLSL size of a `cbuffer` containing `fields`, rounded up to a multiple of 16 bytes.
+    pub const fn buffer_size(fields: &[Self]) -> u32 {
+        let mut end = 0;
+        let mut i = 0;
+        while i < fields.len() {
+            let field_end = fields[i].offset + fields[i].layout.size();
+            if field_end > end { end = field_end }
+            i += 1;
+        }
+        (end + 15) / 16 * 16
+    }
+
+    /// The number of padding bytes between the end of `fields[i-1]` (or the start of the `cbuffer`) and the start of `fields[i]` (or the end of the `cbuffer`, if `i == fields.len()`.)
+    ///
+    /// ### Panics
+    /// *   If `i > fields.len()`
+    /// *   If `fields` overlap or are out of order (never the case for [Self::pack]ed fields)
+    pub const fn padding_before(fields: &[Self], i: usize) -> u32 {
+        let end     = if i == 0 { 0 } else { fields[i-1].offset + fields[i-1].layout.size() };
+        let start   = if i == fields.len() { Self::buffer_size(fields) } else { fields[i].offset };
+        start - end
+    }
+
+    /// Copy `value` into `cbuffer` (the entire packed buffer) at [Self::offset], according to [Self::layout].
+    ///
+    /// ### Panics
+    /// *   If `cbuffer` is too small
+    /// *   If `value` doesn't contain enough components for [Self::layout]
+    pub fn write<T: HlslType>(&self, value: &T, cbuffer: &mut [u8]) {
+        // SAFETY: ✔️ HlslType is an unsafe trait requiring `T` to be plain old data without padding
+        let src = unsafe { std::slice::from_raw_parts((value as *const T).cast::<u8>(), std::mem::size_of::<T>()) };
+        self.layout.write(src, &mut cbuffer[self.offset as usize ..]);
+    }
+
+    fn check(fields: &[Self], size: u32, cb: &ReflectionConstantBuffer) -> Result<(), ConstantBufferMismatch> {
+        let mut problems = Vec::new();
+        if cb.size != size { problems.push(ConstantBufferProblem::BufferSize { rust: size, hlsl: cb.size }) }
+        for var in cb.variables.iter().filter(|var| !fields.iter().any(|f| f.name == var.name)) {
+            problems.push(ConstantBufferProblem::MissingField { name: var.name.clone() });
+        }
+        for field in fields.iter() {
+            let Some(var) = cb.variable(field.name) else {
+                problems.push(ConstantBufferProblem::MissingVariable { name: field.name });
+                continue;
+            };
+            let (rust, hlsl) = (field.layout, HlslLayout { class: var.ty.class, ty: var.ty.ty, rows: var.ty.rows, columns: var.ty.columns, elements: var.ty.elements });
+            if field.offset != var.start_offset { problems.push(ConstantBufferProblem::Offset { name: field.name, rust: field.offset, hlsl: var.start_offset }) }
+            if rust.size() != var.size          { problems.push(ConstantBufferProblem::Size   { name: field.name, rust: rust.size(),  hlsl: var.size }) }
+            if rust != hlsl                     { problems.push(ConstantBufferProblem::Type   { name: field.name, rust, hlsl }) }
+        }
+        if problems.is_empty() { Ok(()) } else { Err(ConstantBufferMismatch { buffer: cb.name.clone(), problems }) }
+    }
+}
+
+
+
+/// The HLSL type of a [HlslType] or [ConstantBufferField], mirroring the relevant parts of [ShaderTypeDesc].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HlslLayout {
+    /// [SVC::Scalar], [SVC::Vector], [SVC::MatrixRows], or [SVC::MatrixColumns]
+    pub class:      ShaderVariableClass,
+
+    /// [SVT::Float], [SVT::Int], [SVT::UInt], or [SVT::Bool]
+    pub ty:         ShaderVariableType,
+
+    /// Number of matrix rows, or 1
+    pub rows:       u32,
+
+    /// Number of matrix/vector columns, or 1
+    pub columns:    u32,
+
+    /// Number of array elements, or 0 if not an array
+    pub elements:   u32,
+}
+
+impl HlslLayout {
+    /// A (non-array) scalar `ty`
+    pub const fn scalar(ty: ShaderVariableType) -> Self { Self { class: SVC::Scalar, ty, rows: 1, columns: 1, elements: 0 } }
+
+    /// `self[n]`, or a `n`-element vector if `self` is a non-array scalar and `n` is `1 ..= 4`.
+    ///
+    /// ### Panics
+    /// *   If `n` is 0
+    /// *   If `self` is already an array (HLSL supports arrays of arrays, but this doesn't yet)
+    pub const fn repeat(self, n: usize) -> Self {
+        if n == 0 { panic!("HlslLayout::repeat: zero length arrays can't be placed in a cbuffer") }
+        if self.elements != 0 { panic!("HlslLayout::repeat: arrays of arrays aren't supported") }
+        if matches!(self.class, SVC::Scalar) && n <= 4 {
+            Self { class: SVC::Vector, columns: n as u32, ..self }
+        } else {
+            Self { elements: n as u32, ..self }
+        }
+    }
+
+    /// Reinterpret a vector `T[N]` as an array `T name[N]`.
+    ///
+    /// ### Panics
+    /// *   If `self` isn't a vector or array
+    pub const fn array(self) -> Self {
+        match self.class {
+            SVC::Vector if self.elements == 0   => Self { class: SVC::Scalar, columns: 1, elements: self.columns, ..self },
+            _ if self.elements != 0             => self,
+            _                                   => panic!("#[hlsl(array)] requires a [T; N] array"),
+        }
+    }
+
+    /// Reinterpret an array of `R` `TC` vectors (`[[T; C]; R]`) as a `row_major TRxC` matrix.
+    ///
+    /// ### Panics
+    /// *   If `self` isn't an array of vectors
+    pub const fn row_major(self) -> Self {
+        if !matches!(self.class, SVC::Vector) || self.elements == 0 { panic!("#[hlsl(row_major)] requires a [[T; C]; R] array of vectors") }
+        Self { class: SVC::MatrixRows, rows: self.elements, elements: 0, ..self }
+    }
+
+    /// Reinterpret an array of `R` `TC` vectors (`[[T; C]; R]`) as a `column_major TRxC` matrix.
+    ///
+    /// ### Panics
+    /// *   If `self` isn't an array of vectors
+    pub const fn column_major(self) -> Self {
+        if !matches!(self.class, SVC::Vector) || self.elements == 0 { panic!("#[hlsl(column_major)] requires a [[T; C]; R] array of vectors") }
+        Self { class: SVC::MatrixColumns, rows: self.elements, elements: 0, ..self }
+    }
+
+    /// The number of 16 byte registers each element occupies.
+    pub const fn registers_per_element(&self) -> u32 {
+        match self.class {
+            SVC::MatrixRows     => self.rows,
+            SVC::MatrixColumns  => self.columns,
+            _                   => 1,
+        }
+    }
+
+    /// The number of 4 byte components used in each register.
+    pub const fn components_per_register(&self) -> u32 {
+        match self.class {
+            SVC::MatrixColumns  => self.rows,
+            _                   => self.columns,
+        }
+    }
+
+    /// The HLSL packed size in bytes.  The final register is *not* padded, as subsequent variables can be packed into it.
+    pub const fn size(&self) -> u32 {
+        let elements = if self.elements == 0 { 1 } else { self.elements };
+        (elements * self.registers_per_element() - 1) * 16 + self.components_per_register() * 4
+    }
+
+    /// The offset a variable of this type would be placed at, if the previous variable ended at `end`.
+    pub const fn pack_after(&self, end: u32) -> u32 {
+        let new_register = self.elements != 0 || matches!(self.class, SVC::MatrixRows | SVC::MatrixColumns) || (end % 16) + self.size() > 16;
+        if new_register { (end + 15) / 16 * 16 } else { end }
+    }
+
+    /// Copy tightly packed, row-by-row components from `src` into HLSL's padded register layout at the start of `dst`.
+    ///
+    /// ### Panics
+    /// *   If `src` is smaller than `elements * rows * columns * 4` bytes
+    /// *   If `dst` is smaller than [Self::size]
+    pub fn write(&self, src: &[u8], dst: &mut [u8]) {
+        let elements    = self.elements.max(1) as usize;
+        let (rows, cols) = (self.rows as usize, self.columns as usize);
+        let stride      = self.registers_per_element() as usize * 16;
+        let transpose   = matches!(self.class, SVC::MatrixColumns);
+        for e in 0 .. elements {
+            for r in 0 .. rows {
+                for c in 0 .. cols {
+                    let s = ((e * rows + r) * cols + c) * 4;
+                    let d = e * stride + if transpose { c * 16 + r * 4 } else { r * 16 + c * 4 };
+                    dst[d .. d + 4].copy_from_slice(&src[s .. s + 4]);
+                }
+            }
+        }
+    }
+}
+
+
+
+/// A Rust type with a HLSL equivalent that can be placed in a `cbuffer`.
+///
+/// ### ⚠️ Safety ⚠️
+/// Implementations must be plain old data without padding, consisting of [LAYOUT](Self::LAYOUT)`.elements * rows * columns` 4 byte components, row by row.
+pub unsafe trait HlslType : Copy + 'static {
+    /// The HLSL type of `Self`
+    const LAYOUT : HlslLayout;
+}
+
+unsafe impl HlslType for f32    { const LAYOUT : HlslLayout = HlslLayout::scalar(SVT::Float); }
+unsafe impl HlslType for i32    { const LAYOUT : HlslLayout = HlslLayout::scalar(SVT::Int);   }
+unsafe impl HlslType for u32    { const LAYOUT : HlslLayout = HlslLayout::scalar(SVT::UInt);  }
+unsafe impl HlslType for bool32 { const LAYOUT : HlslLayout = HlslLayout::scalar(SVT::Bool);  }
+unsafe impl<T: HlslType, const N: usize> HlslType for [T; N] { const LAYOUT : HlslLayout = T::LAYOUT.repeat(N); }
+unsafe impl<T: HlslType, const C: usize, const R: usize> HlslType for RowMajor   <[[T; C]; R]> { const LAYOUT : HlslLayout = <[[T; C]; R]>::LAYOUT.row_major(); }
+unsafe impl<T: HlslType, const C: usize, const R: usize> HlslType for ColumnMajor<[[T; C]; R]> { const LAYOUT : HlslLayout = <[[T; C]; R]>::LAYOUT.column_major(); }
+
+/// A `row_major` HLSL matrix.  Equivalent to `#[hlsl(row_major)]`, but also usable in arrays (e.g. `[RowMajor<[[f32; 4]; 4]>; 64]`.)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[repr(transparent)] pub struct RowMajor<M>(pub M);
+
+/// A `column_major` HLSL matrix.  Equivalent to `#[hlsl(column_major)]`, but also usable in arrays (e.g. `[ColumnMajor<[[f32; 4]; 4]>; 64]`.)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[repr(transparent)] pub struct ColumnMajor<M>(pub M);
+
+
+
+/// The Rust and HLSL layouts of a [ConstantBuffer] disagree.
+#[derive(Debug)]
+pub struct ConstantBufferMismatch {
+    /// The name of the HLSL `cbuffer`
+    pub buffer:     String,
+
+    /// Every detected problem
+    pub problems:   Vec<ConstantBufferProblem>,
+}
+
+/// A single [ConstantBufferMismatch] problem.
+#[allow(missing_docs)]
+#[derive(Debug)]
+pub enum ConstantBufferProblem {
+    /// The `cbuffer` couldn't be reflected (e.g. because a `cbuffer` by that name doesn't exist)
+    Reflection(Error),
+    BufferSize      { rust: u32, hlsl: u32 },
+    /// A HLSL variable has no matching Rust field
+    MissingField    { name: String },
+    /// A Rust field has no matching HLSL variable
+    MissingVariable { name: &'static str },
+    Offset          { name: &'static str, rust: u32, hlsl: u32 },
+    Size            { name: &'static str, rust: u32, hlsl: u32 },
+    Type            { name: &'static str, rust: HlslLayout, hlsl: HlslLayout },
+}
+
+impl std::error::Error for ConstantBufferMismatch {}
+
+impl Display for ConstantBufferMismatch {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "cbuffer {} doesn't match its Rust definition:", self.buffer)?;
+        for problem in self.problems.iter() { write!(fmt, "\n    {}", problem)?; }
+        Ok(())
+    }
+}
+
+impl Display for ConstantBufferProblem {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Reflection(err)                   => write!(fmt, "unable to reflect cbuffer: {}", err),
+            Self::BufferSize { rust, hlsl }         => write!(fmt, "size is {} bytes in Rust, but {} bytes in HLSL", rust, hlsl),
+            Self::MissingField { name }             => write!(fmt, "{}: missing from Rust", name),
+            Self::MissingVariable { name }          => write!(fmt, "{}: missing from HLSL", name),
+            Self::Offset { name, rust, hlsl }       => write!(fmt, "{}: offset is {} in Rust, but {} in HLSL", name, rust, hlsl),
+            Self::Size { name, rust, hlsl }         => write!(fmt, "{}: size is {} bytes in Rust, but {} bytes in HLSL", name, rust, hlsl),
+            Self::Type { name, rust, hlsl }         => write!(fmt, "{}: type is {:?} in Rust, but {:?} in HLSL", name, rust, hlsl),
+        }
+    }
+}
+
+
+
+/// `#[derive(ConstantBuffer)]` errors.
+///
+/// Only non-generic structs with named fields are supported:
+/// ```compile_fail
+/// #[derive(thindx::d3d11::ConstantBuffer)] enum NotAStruct { A }
+/// ```
+/// ```compile_fail
+/// #[derive(thindx::d3d11::ConstantBuffer)] struct Tuple(f32);
+/// ```
+/// ```compile_fail
+/// #[derive(thindx::d3d11::ConstantBuffer)] struct Generic<T> { t: T }
+/// ```
+/// A field may only have one of `array`, `row_major`, or `column_major`:
+/// ```compile_fail
+/// #[derive(thindx::d3d11::ConstantBuffer)] struct Conflict { #[hlsl(array, row_major)] m: [[f32; 4]; 4] }
+/// ```
+/// Which must also suit the field's type:
+/// ```compile_fail
+/// #[derive(thindx::d3d11::ConstantBuffer)] struct NotAMatrix { #[hlsl(row_major)] m: [f32; 4] }
+/// ```
+/// Every field must be a [HlslType]:
+/// ```compile_fail
+/// #[derive(thindx::d3d11::ConstantBuffer)] struct NotHlsl { f: f64 }
+/// ```
+#[cfg(all(doctest, feature = "derive"))] #[allow(dead_code)] struct DeriveErrors;
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    const fn layout<T: HlslType>() -> HlslLayout { T::LAYOUT }
+
+    #[test] fn packing() {
+        let fields = ConstantBufferField::pack([
+            ("a", layout::<f32>()),                                         // 0
+            ("b", layout::<[f32; 2]>()),                                    // 4
+            ("c", layout::<[f32; 3]>()),                                    // 16 (would straddle)
+            ("d", layout::<u32>()),                                         // 28
+            ("e", layout::<[f32; 2]>().array()),                            // 32, 48 (arrays start new registers, elements are padded)
+            ("f", layout::<f32>()),                                         // 52 (packed into the array's last register)
+            ("g", layout::<[[f32; 3]; 2]>().column_major()),                // 64 (float2x3: 3 registers of 2)
+            ("h", layout::<[[f32; 3]; 2]>().row_major()),                   // 112 (float2x3: 2 registers of 3)
+            ("i", layout::<[ColumnMajor<[[f32; 4]; 4]>; 2]>()),             // 144 (2x float4x4)
+            ("j", layout::<i32>()),                                         // 272
+        ]);
+        let offsets = fields.iter().map(|f| f.offset).collect::<Vec<_>>();
+        assert_eq!(offsets, [0, 4, 16, 28, 32, 52, 64, 112, 144, 272]);
+        assert_eq!(fields[4].layout.size(), 20);
+        assert_eq!(fields[6].layout.size(), 40);
+        assert_eq!(fields[7].layout.size(), 28);
+        assert_eq!(ConstantBufferField::buffer_size(&fields), 288);
+        assert_eq!(layout::<[f32; 8]>().elements, 8);
+        assert_eq!(layout::<[f32; 4]>().class, SVC::Vector);
+    }
+
+    #[test] fn write() {
+        let m = [[1.0f32, 2.0, 3.0], [4.0, 5.0, 6.0]];
+        let field = ConstantBufferField::pack([("m", layout::<[[f32; 3]; 2]>().column_major())])[0];
+        let mut cb = [0u8; 48];
+        field.write(&m, &mut cb);
+        let floats = cb.chunks(4).map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect::<Vec<_>>();
+        assert_eq!(floats, [1.0, 4.0, 0.0, 0.0, 2.0, 5.0, 0.0, 0.0, 3.0, 6.0, 0.0, 0.0]);
+
+        let field = ConstantBufferField::pack([("a", layout::<[u32; 2]>().array())])[0];
+        let mut cb = [0u8; 32];
+        field.write(&[1u32, 2], &mut cb);
+        assert_eq!(cb[0..4], 1u32.to_le_bytes());
+        assert_eq!(cb[16..20], 2u32.to_le_bytes());
+    }
+
+    #[test] fn check() {
+        let fields = ConstantBufferField::pack([("a", layout::<[f32; 2]>()), ("b", layout::<[f32; 3]>())]);
+        let var = |name: &str, start_offset, size, columns| ReflectionVariable {
+            name: name.into(), start_offset, size,
+            ty: ReflectionType { class: SVC::Vector, ty: SVT::Float, rows: 1, columns, ..Default::default() },
+            ..Default::default()
+        };
+        let mut cb = ReflectionConstantBuffer { name: "cb".into(), size: 32, variables: vec![var("a", 0, 8, 2), var("b", 16, 12, 3)], ..Default::default() };
+        ConstantBufferField::check(&fields, 32, &cb).unwrap();
+
+        cb.variables[1].start_offset = 8;
+        cb.variables.push(var("c", 28, 4, 1));
+        let err = ConstantBufferField::check(&fields, 32, &cb).unwrap_err();
+        assert!(matches!(err.problems[..], [ConstantBufferProblem::MissingField { .. }, ConstantBufferProblem::Offset { name: "b", rust: 16, hlsl: 8 }]), "{}", err);
+    }
+
+    #[cfg(feature = "derive")] fn floats(hlsl: &impl AsRef<[u8]>) -> Vec<f32> {
+        hlsl.as_ref().chunks(4).map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect()
+    }
+
+    #[cfg(feature = "derive")] #[test] fn derive_plain() {
+        #[derive(ConstantBuffer)]
+        #[allow(dead_code)]
+        struct Plain { a: f32, pub b: [f32; 3], pub(crate) r#type: f32 }
+
+        assert_eq!(Plain::NAME, "Plain");
+        assert_eq!(Plain::FIELDS.iter().map(|f| (f.name, f.offset)).collect::<Vec<_>>(), [("a", 0), ("b", 4), ("type", 16)]);
+        assert_eq!(Plain::SIZE, 32);
+        assert_eq!((std::mem::size_of::<PlainHlsl>(), std::mem::align_of::<PlainHlsl>()), (32, 16));
+
+        let hlsl = Plain { a: 1.0, b: [2.0, 3.0, 4.0], r#type: 5.0 }.to_hlsl();
+        assert_eq!((hlsl.a.len(), hlsl.b.len(), hlsl.r#type), (4, 12, 5.0f32.to_le_bytes()));
+        assert_eq!(floats(&hlsl), [1.0, 2.0, 3.0, 4.0, 5.0, 0.0, 0.0, 0.0]);
+        assert!(PlainHlsl { a: [0; 4], b: [0; 12], r#type: [0; 4], ..hlsl } == PlainHlsl::default() && hlsl != PlainHlsl::default());
+    }
+
+    #[cfg(feature = "derive")] #[test] fn derive_attributes() {
+        use std::collections::HashMap;
+
+        /// Doc comments and other attributes are ignored
+        #[derive(ConstantBuffer)]
+        #[hlsl(rename = "ExampleCBuffer")]
+        #[allow(dead_code)]
+        struct Example {
+            /// The first field
+            #[hlsl(rename = "Scale")]
+            scale:      f32,
+            #[hlsl(skip)]
+            lookup:     HashMap<u32, Vec<(u8, u8)>>,    // commas in generics don't end the field
+            #[hlsl(array)]
+            weights:    [f32; 2],
+            #[hlsl(skip)]
+            callback:   fn(u32, u32) -> u8,             // `->` doesn't close a `<`
+            #[hlsl(row_major)]
+            world:      [[f32; 2]; 2],
+            #[hlsl(column_major, rename = "view")]
+            view_cm:    [[f32; 2]; 2],
+        }
+
+        assert_eq!(Example::NAME, "ExampleCBuffer");
+        let fields = Example::FIELDS.iter().map(|f| (f.name, f.offset, f.layout.class)).collect::<Vec<_>>();
+        assert_eq!(fields, [("Scale", 0, SVC::Scalar), ("weights", 16, SVC::Scalar), ("world", 48, SVC::MatrixRows), ("view", 80, SVC::MatrixColumns)]);
+        assert_eq!(Example::SIZE, 112);
+        assert_eq!(std::mem::size_of::<ExampleHlsl>(), 112);
+
+        let hlsl = Example {
+            scale:      1.0,
+            lookup:     HashMap::new(),
+            weights:    [2.0, 3.0],
+            callback:   |_, _| 0,
+            world:      [[4.0, 5.0], [6.0, 7.0]],
+            view_cm:    [[8.0, 9.0], [10.0, 11.0]],
+        }.to_hlsl();
+        assert_eq!((hlsl.weights.len(), hlsl.world.len(), hlsl.view_cm.len()), (20, 24, 24));
+        assert_eq!(floats(&hlsl), [
+            1.0,  0.0, 0.0, 0.0,
+            2.0,  0.0, 0.0, 0.0,
+            3.0,  0.0, 0.0, 0.0,
+            4.0,  5.0, 0.0, 0.0,
+            6.0,  7.0, 0.0, 0.0,
+            8.0, 10.0, 0.0, 0.0,
+            9.0, 11.0, 0.0, 0.0,
+        ]);
+    }
+}
@@ -0,0 +1,353 @@
+use crate::d3d::*;
+use crate::d3d11::*;
+
+use std::fmt::Write;
+
+
+
+impl ReflectionData {
+    /// Reconstruct HLSL declarations for everything this shader binds:
+    /// *   `struct` definitions used by constant buffers and structured buffers
+    /// *   `cbuffer` / `tbuffer` blocks with `register(b#)` / `register(t#)`, and a `packoffset(c#.x)` for every variable
+    /// *   Texture, buffer, UAV, and sampler declarations with `register(...)`
+    ///
+    /// Useful for building shared headers for shaders whose source has been lost, or for documenting binary-only shaders.
+    /// Reconstructed declarations should reproduce the original layout and bindings, but not necessarily the original spelling:
+    /// matrices are always explicitly `row_major` or `column_major`, typedefs are lost, `$Globals` variables become `register(c#)` globals, etc.
+    /// SM4 reflection doesn't record struct names, so each distinct anonymous struct is declared as `__struct0`, `__struct1`, ...
+    ///
+    /// ### Examples
+    /// ```rust
+    /// # use thindx::{*, d3d::*};
+    /// # let d3dc = Compiler::load_system(47).unwrap();
+    /// # let vs = d3dc.compile_from_file(r"test\data\basic.hlsl", None, None, "vs_main", "vs_4_0", Compile::Debug, CompileEffect::None).unwrap();
+    /// let data = d3d11::ReflectionData::parse(&vs).unwrap();
+    /// let hlsl = data.to_hlsl();
+    /// assert!(hlsl.contains("cbuffer ExampleCBuffer : register(b0)"), "{}", hlsl);
+    /// assert!(hlsl.contains("float4 tint : packoffset(c0);"), "{}", hlsl);
+    /// ```
+    pub fn to_hlsl(&self) -> String {
+        let mut data = self.clone();
+        let mut anonymous = Vec::new();
+        for cb in data.constant_buffers.iter_mut() {
+            for v in cb.variables.iter_mut() { name_structs(&mut v.ty, &mut anonymous); }
+        }
+        data.to_hlsl_named()
+    }
+
+    /// [ReflectionData::to_hlsl], after every struct has been given a name
+    fn to_hlsl_named(&self) -> String {
+        let mut out = format!("// Reconstructed from {:?} reflection data\n", self.desc.version);
+
+        let mut structs = Vec::new();
+        for cb in self.constant_buffers.iter() {
+            for v in cb.variables.iter() { collect_structs(&v.ty, &mut structs); }
+        }
+        for s in structs.iter() {
+            out.push('\n');
+            out.push_str(&s.to_hlsl_struct().unwrap_or_default());
+        }
+
+        let mut emitted = Vec::new();
+        if !self.bindings.is_empty() { out.push('\n'); }
+        for b in self.bindings.iter() {
+            match b.ty {
+                SIT::CBuffer | SIT::TBuffer => match self.constant_buffers.iter().find(|cb| cb.name == b.name && cb.ty != CBufferType::ResourceBindInfo) {
+                    Some(cb) => {
+                        emitted.push(cb.name.as_str());
+                        out.push('\n');
+                        out.push_str(&cb.to_hlsl(Some(b.bind_point)));
+                        out.push('\n');
+                    },
+                    None => { let _ = writeln!(out, "// {}", self.binding_to_hlsl(b)); },
+                },
+                _ => { let _ = writeln!(out, "{}", self.binding_to_hlsl(b)); },
+            }
+        }
+
+        for cb in self.constant_buffers.iter().filter(|cb| matches!(cb.ty, CBufferType::CBuffer | CBufferType::TBuffer)) {
+            if emitted.contains(&cb.name.as_str()) { continue }
+            out.push('\n');
+            out.push_str(&cb.to_hlsl(None));
+        }
+
+        out
+    }
+
+    /// Reconstruct the HLSL declaration of a single resource binding, such as:
+    /// *   `Texture2D<float4> diffuse : register(t0);`
+    /// *   `StructuredBuffer<Particle> particles : register(t1);`
+    /// *   `SamplerComparisonState shadow_sampler : register(s2);`
+    ///
+    /// `cbuffer`s and `tbuffer`s are declared without their bodies (use [ReflectionConstantBuffer::to_hlsl] for those.)
+    /// Structured buffer element types are looked up in [ReflectionData::constant_buffers].
+    pub fn binding_to_hlsl(&self, binding: &ReflectionBinding) -> String {
+        let flags       = binding.flags.into_inner() as u32;
+        let components  = ((flags & SIF::TextureComponents.into_inner() as u32) >> 2) + 1;
+        let sampled     = match binding.return_type {
+            ReturnType::UNorm   => "unorm float",
+            ReturnType::SNorm   => "snorm float",
+            ReturnType::SInt    => "int",
+            ReturnType::UInt    => "uint",
+            ReturnType::Double  => "double",
+            _                   => "float",
+        };
+        let sampled     = if components == 1 { sampled.to_string() } else { format!("{}{}", sampled, components) };
+        let element     = || self.constant_buffers.iter()
+            .find(|cb| cb.ty == CBufferType::ResourceBindInfo && cb.name == binding.name)
+            .and_then(|cb| cb.variables.first())
+            .map_or_else(|| "uint".to_string(), |v| v.ty.to_hlsl_type());
+
+        let ty = match binding.ty {
+            SIT::CBuffer                    => "cbuffer".to_string(),
+            SIT::TBuffer                    => "tbuffer".to_string(),
+            SIT::Sampler if flags & SIF::ComparisonSampler.into_inner() as u32 != 0 => "SamplerComparisonState".to_string(),
+            SIT::Sampler                    => "SamplerState".to_string(),
+            SIT::Texture                    => texture_type("", binding.dimension, &sampled, binding.num_samples),
+            SIT::UavRWTyped                 => texture_type("RW", binding.dimension, &sampled, !0),
+            SIT::Structured                 => format!("StructuredBuffer<{}>", element()),
+            SIT::UavRWStructured            => format!("RWStructuredBuffer<{}>", element()),
+            SIT::UavRWStructuredWithCounter => format!("RWStructuredBuffer<{}>", element()),
+            SIT::UavAppendStructured        => format!("AppendStructuredBuffer<{}>", element()),
+            SIT::UavConsumeStructured       => format!("ConsumeStructuredBuffer<{}>", element()),
+            SIT::ByteAddress                => "ByteAddressBuffer".to_string(),
+            SIT::UavRWByteAddress           => "RWByteAddressBuffer".to_string(),
+            SIT::RTAccelerationStructure    => "RaytracingAccelerationStructure".to_string(),
+            SIT::UavFeedbackTexture         => "FeedbackTexture2D<SAMPLER_FEEDBACK_MIP_REGION_USED>".to_string(),
+            other                           => format!("/* {:?} */", other),
+        };
+        let array = if binding.bind_count > 1 { format!("[{}]", binding.bind_count) } else { String::new() };
+        format!("{} {}{} : register({});", ty, binding.name, array, binding.hlsl_register())
+    }
+}
+
+impl ReflectionConstantBuffer {
+    /// Reconstruct a `cbuffer` or `tbuffer` block, with a `packoffset(c#.x)` for every variable.
+    ///
+    /// The `$Globals` buffer can't be declared as a `cbuffer` - its variables are instead declared as globals with `register(c#)` where possible.
+    ///
+    /// ### Examples
+    /// ```text
+    /// cbuffer PerFrame : register(b1)
+    /// {
+    ///     column_major float4x4 view_proj : packoffset(c0);
+    ///     float3 eye : packoffset(c4);
+    ///     float time : packoffset(c4.w);
+    /// };
+    /// ```
+    pub fn to_hlsl(&self, register: Option<u32>) -> String {
+        let mut out = String::new();
+        if self.name == "$Globals" {
+            let _ = writeln!(out, "// $Globals ({} bytes){}", self.size, register.map_or(String::new(), |r| format!(" : register(b{})", r)));
+            for v in self.variables.iter() {
+                let decl = format!("{} {}{}", v.ty.to_hlsl_type(), v.name, array_suffix(&v.ty));
+                if v.start_offset % 16 == 0 {
+                    let _ = writeln!(out, "{} : register(c{});", decl, v.start_offset / 16);
+                } else {
+                    let _ = writeln!(out, "{}; // offset {}", decl, v.start_offset);
+                }
+            }
+            return out;
+        }
+
+        let keyword = if self.ty == CBufferType::TBuffer { "tbuffer" } else { "cbuffer" };
+        let register = register.map_or(String::new(), |r| format!(" : register({}{})", if self.ty == CBufferType::TBuffer { 't' } else { 'b' }, r));
+        let _ = writeln!(out, "{} {}{} // {} bytes", keyword, self.name, register, self.size);
+        out.push_str("{\n");
+        for v in self.variables.iter() {
+            let component = ["", ".y", ".z", ".w"][((v.start_offset % 16) / 4) as usize];
+            let _ = writeln!(out, "    {} {}{} : packoffset(c{}{});", v.ty.to_hlsl_type(), v.name, array_suffix(&v.ty), v.start_offset / 16, component);
+        }
+        out.push_str("};\n");
+        out
+    }
+}
+
+impl ReflectionType {
+    /// The HLSL name of this type, excluding any array suffix:
+    /// `float`, `int3`, `row_major float4x4`, `column_major float3x4`, `Light`, ...
+    ///
+    /// ### Examples
+    /// ```rust
+    /// # use thindx::{*, d3d11::*};
+    /// let ty = ReflectionType { class: SVC::MatrixColumns, ty: SVT::Float, rows: 4, columns: 3, elements: 2, ..Default::default() };
+    /// assert_eq!(ty.to_hlsl_type(), "column_major float4x3");
+    /// ```
+    pub fn to_hlsl_type(&self) -> String {
+        if self.class == SVC::Struct { return self.name.clone().unwrap_or_else(|| "struct".into()) }
+        let scalar = match self.ty {
+            SVT::Void       => "void",
+            SVT::Bool       => "bool",
+            SVT::Int        => "int",
+            SVT::UInt       => "uint",
+            SVT::UInt8      => "uint8_t",
+            SVT::Float      => "float",
+            SVT::Double     => "double",
+            SVT::Min8Float  => "min8float",
+            SVT::Min10Float => "min10float",
+            SVT::Min16Float => "min16float",
+            SVT::Min12Int   => "min12int",
+            SVT::Min16Int   => "min16int",
+            SVT::Min16UInt  => "min16uint",
+            SVT::Int16      => "int16_t",
+            SVT::UInt16     => "uint16_t",
+            _               => return self.name.clone().unwrap_or_else(|| format!("/* {:?} */", self.ty)),
+        };
+        match self.class {
+            SVC::Vector         => format!("{}{}", scalar, self.columns),
+            SVC::MatrixRows     => format!("row_major {}{}x{}", scalar, self.rows, self.columns),
+            SVC::MatrixColumns  => format!("column_major {}{}x{}", scalar, self.rows, self.columns),
+            _                   => scalar.into(),
+        }
+    }
+
+    /// Reconstruct the `struct` definition of a [SVC::Struct] type (but not of any structs it contains), or [None] for other types.
+    ///
+    /// HLSL doesn't allow `packoffset` on struct members, so member offsets are included as comments instead.
+    pub fn to_hlsl_struct(&self) -> Option<String> {
+        if self.class != SVC::Struct { return None }
+        let mut out = format!("struct {}\n{{\n", self.name.as_deref().unwrap_or("_"));
+        for m in self.members.iter() {
+            let _ = writeln!(out, "    {} {}{}; // offset {}", m.ty.to_hlsl_type(), m.name, array_suffix(&m.ty), m.ty.offset);
+        }
+        out.push_str("};\n");
+        Some(out)
+    }
+}
+
+impl ReflectionBinding {
+    /// The HLSL register this binding starts at: `b0`, `t3`, `s1`, `u2`, ...
+    pub fn hlsl_register(&self) -> String {
+        let register = match self.ty {
+            SIT::CBuffer                                                        => 'b',
+            SIT::Sampler                                                        => 's',
+            SIT::UavRWTyped | SIT::UavRWStructured | SIT::UavRWByteAddress |
+            SIT::UavAppendStructured | SIT::UavConsumeStructured |
+            SIT::UavRWStructuredWithCounter | SIT::UavFeedbackTexture           => 'u',
+            _                                                                   => 't',
+        };
+        format!("{}{}", register, self.bind_point)
+    }
+}
+
+
+
+fn array_suffix(ty: &ReflectionType) -> String {
+    if ty.elements == 0 { String::new() } else { format!("[{}]", ty.elements) }
+}
+
+/// Name every anonymous struct used by `ty` `__struct#`, giving structs with identical members the same name
+fn name_structs(ty: &mut ReflectionType, anonymous: &mut Vec<Vec<ReflectionMember>>) {
+    if ty.class != SVC::Struct { return }
+    for m in ty.members.iter_mut() { name_structs(&mut m.ty, anonymous); }
+    if ty.name.is_some() { return }
+    let index = match anonymous.iter().position(|members| *members == ty.members) {
+        Some(index) => index,
+        None        => { anonymous.push(ty.members.clone()); anonymous.len() - 1 },
+    };
+    ty.name = Some(format!("__struct{}", index));
+}
+
+/// Collect all named struct types used by `ty`, dependencies first
+fn collect_structs<'t>(ty: &'t ReflectionType, structs: &mut Vec<&'t ReflectionType>) {
+    if ty.class != SVC::Struct { return }
+    for m in ty.members.iter() { collect_structs(&m.ty, structs); }
+    if !structs.iter().any(|s| s.name == ty.name) { structs.push(ty); }
+}
+
+fn texture_type(rw: &str, dimension: SrvDimension, sampled: &str, samples: u32) -> String {
+    let dimension = match dimension {
+        SrvDimension::Buffer | SrvDimension::BufferEx   => "Buffer",
+        SrvDimension::Texture1D                         => "Texture1D",
+        SrvDimension::Texture1DArray                    => "Texture1DArray",
+        SrvDimension::Texture2D                         => "Texture2D",
+        SrvDimension::Texture2DArray                    => "Texture2DArray",
+        SrvDimension::Texture2DMS                       => "Texture2DMS",
+        SrvDimension::Texture2DMSArray                  => "Texture2DMSArray",
+        SrvDimension::Texture3D                         => "Texture3D",
+        SrvDimension::TextureCube                       => "TextureCube",
+        SrvDimension::TextureCubeArray                  => "TextureCubeArray",
+        _                                               => "Texture",
+    };
+    let ms = matches!(dimension, "Texture2DMS" | "Texture2DMSArray") && samples != 0 && samples != !0;
+    if ms { format!("{}{}<{}, {}>", rw, dimension, sampled, samples) } else { format!("{}{}<{}>", rw, dimension, sampled) }
+}
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    #[test] fn cbuffer() {
+        let float = |class, columns, offset| ReflectionType { class, ty: SVT::Float, rows: 1, columns, offset, ..Default::default() };
+        let light = ReflectionType {
+            class: SVC::Struct, name: Some("Light".into()), elements: 2,
+            members: vec![
+                ReflectionMember { name: "pos".into(),   ty: float(SVC::Vector, 3, 0) },
+                ReflectionMember { name: "range".into(), ty: float(SVC::Scalar, 1, 12) },
+            ],
+            ..Default::default()
+        };
+        let data = ReflectionData {
+            constant_buffers: vec![
+                ReflectionConstantBuffer { name: "PerFrame".into(), size: 64, variables: vec![
+                    ReflectionVariable { name: "eye".into(),    start_offset: 0,  ty: float(SVC::Vector, 3, 0), ..Default::default() },
+                    ReflectionVariable { name: "time".into(),   start_offset: 12, ty: float(SVC::Scalar, 1, 0), ..Default::default() },
+                    ReflectionVariable { name: "lights".into(), start_offset: 16, ty: light, ..Default::default() },
+                ], ..Default::default() },
+            ],
+            bindings: vec![
+                ReflectionBinding { name: "PerFrame".into(), ty: SIT::CBuffer, bind_point: 1, bind_count: 1, ..Default::default() },
+                ReflectionBinding { name: "diffuse".into(), ty: SIT::Texture, bind_point: 0, bind_count: 1, flags: SIF::TextureComponents, return_type: ReturnType::Float, dimension: SrvDimension::Texture2D, num_samples: !0 },
+                ReflectionBinding { name: "shadow".into(), ty: SIT::Sampler, bind_point: 2, bind_count: 1, flags: SIF::ComparisonSampler, ..Default::default() },
+            ],
+            ..Default::default()
+        };
+        let hlsl = data.to_hlsl();
+        for expected in [
+            "struct Light\n{\n    float3 pos; // offset 0\n    float range; // offset 12\n};\n",
+            "cbuffer PerFrame : register(b1) // 64 bytes\n{\n    float3 eye : packoffset(c0);\n    float time : packoffset(c0.w);\n    Light lights[2] : packoffset(c1);\n};\n",
+            "Texture2D<float4> diffuse : register(t0);\n",
+            "SamplerComparisonState shadow : register(s2);\n",
+        ] {
+            assert!(hlsl.contains(expected), "expected:\n{}\n\nin:\n{}", expected, hlsl);
+        }
+    }
+
+    #[test] fn anonymous_structs() {
+        use crate::test_fixtures::{dxbc, dwords, with_strings};
+
+        // header (28) + cbuffer (24 @ 28) + variables (3x24 @ 52) + types (5x16 @ 124) + members (3x12 @ 204) + binding (32 @ 240) = 272, then strings
+        let rdef = with_strings(dwords(&[
+            1, 28, 1, 240, 0xFFFF_0400, 0, 272,                 // header: cbuffers, resources, target, flags, creator
+            280, 3, 52, 64, 0, 0,                               // cbuffer Lighting
+            289, 0,  32, 2, 156, 0,                             // struct { float3 pos; float range; } lights[2];
+            296, 32, 16, 2, 172, 0,                             // struct { float3 pos; float range; } spot;
+            301, 48,  4, 2, 188, 0,                             // struct { float density; } fog;
+            1 | (3 << 16), 1 | (3 << 16), 0, 0,                 // float3
+            3 << 16, 1 | (1 << 16), 0, 0,                       // float
+            5, 1 | (4 << 16), 2 | (2 << 16), 204,               // struct (2 elements, 2 members)
+            5, 1 | (4 << 16), 2 << 16, 204,                     // struct (2 members)
+            5, 1 | (1 << 16), 1 << 16, 228,                     // struct (1 member)
+            305, 124, 0,                                        // pos
+            309, 140, 12,                                       // range
+            315, 140, 0,                                        // density
+            280, 0, 0, 0, 0, 0, 1, 0,                           // binding: cbuffer Lighting : register(b0)
+        ]), &["Creator", "Lighting", "lights", "spot", "fog", "pos", "range", "density"]);
+
+        let bytecode = dxbc(&[(b"RDEF", rdef)]);
+        // SAFETY: ✔️ only parsed by ReflectionData::parse, never passed to d3dcompiler
+        let data = ReflectionData::parse(unsafe { Bytecode::from_unchecked(&bytecode) }).unwrap();
+        assert_eq!(data.constant_buffers[0].variables[0].ty.name, None);
+
+        let hlsl = data.to_hlsl();
+        for expected in [
+            "struct __struct0\n{\n    float3 pos; // offset 0\n    float range; // offset 12\n};\n",
+            "struct __struct1\n{\n    float density; // offset 0\n};\n",
+            "cbuffer Lighting : register(b0) // 64 bytes\n{\n    __struct0 lights[2] : packoffset(c0);\n    __struct0 spot : packoffset(c2);\n    __struct1 fog : packoffset(c3);\n};\n",
+        ] {
+            assert!(hlsl.contains(expected), "expected:\n{}\n\nin:\n{}", expected, hlsl);
+        }
+        assert_eq!(hlsl.matches("struct __struct0\n").count(), 1, "{}", hlsl);
+    }
+}
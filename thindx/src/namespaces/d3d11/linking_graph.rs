@@ -0,0 +1,675 @@
+use crate::*;
+use crate::d3d::*;
+use crate::d3d11::*;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::{CStr, CString};
+use std::fmt::{self, Display, Formatter, Write};
+use std::sync::Mutex;
+
+
+
+/// An owned, pure-Rust model of a [FunctionLinkingGraph].
+///
+/// Unlike [FunctionLinkingGraph], this doesn't require `d3dcompiler_*.dll`, can be cloned/compared freely, and (with the `serde` feature) serialized.
+/// This allows graphs to be built, [validate](Self::validate)d, and turned into HLSL ([to_hlsl](Self::to_hlsl)) on any platform, then
+/// [replay](Self::replay)ed into a real [FunctionLinkingGraph] for linking.
+///
+/// Nodes are identified by [LinkingNodeId]s.  Parameter indicies match [FunctionLinkingGraph::pass_value]:
+/// `0..` for parameters, `-1` for a call's return value.
+///
+/// ### Examples
+/// ```rust
+/// use thindx::{*, d3d::*, d3d11::*};
+///
+/// let mut graph = LinkingGraph::default();
+/// let input = graph.set_input_signature(vec![
+///     LinkingParameter::new("inputPos",  "POSITION0", SVT::Float, SVC::Vector, 1, 3, PF::In),
+///     LinkingParameter::new("inputNorm", "NORMAL0",   SVT::Float, SVC::Vector, 1, 3, PF::In),
+/// ]);
+/// let xyz1 = graph.call_function(LinkingCall {
+///     module:         0,
+///     namespace:      None,
+///     function:       "xyz1".into(),
+///     parameters:     vec![LinkingParameter::new("v", None, SVT::Float, SVC::Vector, 1, 3, PF::In)],
+///     return_value:   Some(LinkingParameter::new(None, None, SVT::Float, SVC::Vector, 1, 4, PF::Out)),
+/// });
+/// let output = graph.set_output_signature(vec![
+///     LinkingParameter::new("outputNorm", "NORMAL0",     SVT::Float, SVC::Vector, 1, 3, PF::Out),
+///     LinkingParameter::new(None,         "SV_POSITION", SVT::Float, SVC::Vector, 1, 4, PF::Out),
+/// ]);
+/// graph.pass_value(input, 0, xyz1, 0);
+/// graph.pass_value(xyz1, -1, output, 1);
+/// graph.pass_value_with_swizzle(input, 1, "zyx", output, 0, "xyz");
+/// graph.validate().unwrap();
+/// println!("{}", graph.to_hlsl());
+///
+/// // Replay into a real FunctionLinkingGraph
+/// let d3dc = Compiler::load_system(47).unwrap();
+/// let lib = d3dc.compile(
+///     b"export float4 xyz1(float3 v) { return float4(v, 1.0); }", "example.hlsl",
+///     None, None, (), "lib_5_0", Compile::OptimizationLevel3, CompileEffect::None
+/// ).unwrap();
+/// let lib = d3dc.load_module(&lib).unwrap();
+/// let flg = d3dc.create_function_linking_graph(None).unwrap();
+/// graph.replay(&flg, &[&lib]).unwrap();
+/// let (_graph_inst, _warnings) = flg.create_module_instance().unwrap();
+/// ```
+///
+/// ### Output
+/// ```hlsl
+/// float4 xyz1(in float3 v);
+///
+/// void main(in float3 inputPos : POSITION0, in float3 inputNorm : NORMAL0, out float3 outputNorm : NORMAL0, out float4 __Output_n2_1 : SV_POSITION)
+/// {
+///     float4 xyz1_n1_0;
+///     float3 xyz1_n1_1;
+///     xyz1_n1_1 = inputPos;
+///     xyz1_n1_0 = ::xyz1(xyz1_n1_1);
+///     outputNorm.xyz = inputNorm.zyx;
+///     __Output_n2_1 = xyz1_n1_0;
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinkingGraph {
+    /// [FunctionLinkingGraph::set_input_signature]
+    pub inputs:     Vec<LinkingParameter>,
+
+    /// [FunctionLinkingGraph::call_function], in call order
+    pub calls:      Vec<LinkingCall>,
+
+    /// [FunctionLinkingGraph::set_output_signature]
+    pub outputs:    Vec<LinkingParameter>,
+
+    /// [FunctionLinkingGraph::pass_value] / [pass_value_with_swizzle](FunctionLinkingGraph::pass_value_with_swizzle)
+    pub edges:      Vec<LinkingEdge>,
+}
+
+/// A node of a [LinkingGraph].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LinkingNodeId {
+    /// The graph's input signature ([LinkingGraph::inputs])
+    #[default] Input,
+
+    /// A function call (index into [LinkingGraph::calls])
+    Call(usize),
+
+    /// The graph's output signature ([LinkingGraph::outputs])
+    Output,
+}
+
+/// An owned [ParameterDesc] (minus the register assignments, which [FunctionLinkingGraph] assigns itself.)
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinkingParameter {
+    /// The name of the parameter (optional - for debugging / readable codegen.)
+    pub name:           Option<String>,
+    /// A semantic name + index such as `"POSITION0"`, `"TEXCOORD3"`, etc.
+    pub semantic:       Option<String>,
+    pub ty:             ShaderVariableType,
+    pub class:          ShaderVariableClass,
+    pub rows:           u32,
+    pub columns:        u32,
+    pub interpolation:  InterpolationMode,
+    pub flags:          ParameterFlags,
+}
+
+/// A function call node of a [LinkingGraph].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinkingCall {
+    /// Index into the `modules` passed to [LinkingGraph::replay].
+    pub module:         usize,
+
+    /// The module instance namespace of the function, if any.
+    pub namespace:      Option<String>,
+
+    /// The name of the function.
+    pub function:       String,
+
+    /// The function's parameters (`flags` distinguish `in`, `out`, and `inout` parameters.)
+    pub parameters:     Vec<LinkingParameter>,
+
+    /// The function's return value, if it isn't `void`.
+    pub return_value:   Option<LinkingParameter>,
+}
+
+/// A [pass_value](FunctionLinkingGraph::pass_value) / [pass_value_with_swizzle](FunctionLinkingGraph::pass_value_with_swizzle) edge of a [LinkingGraph].
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinkingEdge {
+    pub src:            LinkingNodeId,
+    pub src_parameter:  i32,
+    /// e.g. `"yx"`, or [None] to pass the entire value.
+    pub src_swizzle:    Option<String>,
+    pub dst:            LinkingNodeId,
+    pub dst_parameter:  i32,
+    /// e.g. `"xy"`, or [None] to write the entire value.
+    pub dst_swizzle:    Option<String>,
+}
+
+
+
+impl LinkingParameter {
+    /// Shorthand construction for [LinkingParameter]s (with [Interpolation::Undefined].)
+    pub fn new<'a>(name: impl Into<Option<&'a str>>, semantic: impl Into<Option<&'a str>>, ty: ShaderVariableType, class: ShaderVariableClass, rows: u32, columns: u32, flags: ParameterFlags) -> Self {
+        Self { name: name.into().map(String::from), semantic: semantic.into().map(String::from), ty, class, rows, columns, interpolation: Interpolation::Undefined, flags }
+    }
+
+    /// The HLSL type of this parameter: `float3`, `int`, `row_major float4x4`, ...
+    pub fn to_hlsl_type(&self) -> String {
+        ReflectionType { class: self.class, ty: self.ty, rows: self.rows, columns: self.columns, ..Default::default() }.to_hlsl_type()
+    }
+
+    /// The number of components in this parameter (`rows * columns`, or `columns` for scalars/vectors.)
+    pub fn components(&self) -> u32 {
+        match self.class {
+            SVC::MatrixRows | SVC::MatrixColumns    => self.rows * self.columns,
+            _                                       => self.columns.max(1),
+        }
+    }
+
+    fn is_in (&self) -> bool { self.flags.into_inner() & PF::In .into_inner() != 0 || self.flags == PF::None }
+    fn is_out(&self) -> bool { self.flags.into_inner() & PF::Out.into_inner() != 0 }
+}
+
+impl From<&ParameterDesc<'_>> for LinkingParameter {
+    fn from(p: &ParameterDesc) -> Self {
+        Self {
+            name:           p.name.map(|n| n.to_string_lossy().into_owned()),
+            semantic:       p.semantic_name.map(|n| n.to_string_lossy().into_owned()),
+            ty:             p.ty,
+            class:          p.class,
+            rows:           p.rows,
+            columns:        p.columns,
+            interpolation:  p.interpolation_mode,
+            flags:          p.flags,
+        }
+    }
+}
+
+impl LinkingCall {
+    /// Read the signature of a library function via [FunctionReflection].
+    ///
+    /// ### Arguments
+    /// *   `module`    - Index of the [Module] containing `function` in the `modules` later passed to [LinkingGraph::replay].
+    /// *   `function`  - The function to call.
+    ///
+    /// ### Errors
+    /// *   [E::FAIL]   - if any `get_desc` call fails (e.g. `function` is a stub object)
+    ///
+    /// ### Examples
+    /// ```rust
+    /// # use thindx::{*, d3d::*, d3d11::*};
+    /// let d3dc = Compiler::load_system(47).unwrap();
+    /// let shader = d3dc.compile_from_file(r"test\data\library.hlsl", None, None, (), "lib_5_0", Compile::Debug, CompileEffect::None).unwrap();
+    /// let lib : LibraryReflection = d3dc.reflect_library(&shader).unwrap();
+    /// let scale4 = lib.functions().unwrap().find(|f| f.get_desc().unwrap().name.to_bytes() == b"scale4").unwrap();
+    ///
+    /// let call = LinkingCall::from_reflection(0, &scale4).unwrap();
+    /// assert_eq!(call.function, "scale4");
+    /// assert_eq!(call.parameters[0].name.as_deref(), Some("v"));
+    /// assert!(call.return_value.is_some());
+    /// ```
+    pub fn from_reflection(module: usize, function: &FunctionReflection) -> Result<Self, Error> {
+        let desc = function.get_desc()?;
+        Ok(Self {
+            module,
+            namespace:      None,
+            function:       String::from_utf8_lossy(desc.name.to_bytes()).into_owned(),
+            parameters:     (0 .. desc.function_parameter_count).map(|i| function.get_function_parameter(i).get_desc().map(|p| LinkingParameter::from(&p))).collect::<Result<_, _>>()?,
+            return_value:   if bool::from(desc.has_return) { Some(LinkingParameter::from(&function.get_function_parameter(-1).get_desc()?)) } else { None },
+        })
+    }
+
+    /// The HLSL prototype of this function, e.g. `float4 xyz1(in float3 v);`
+    pub fn to_hlsl_prototype(&self) -> String {
+        let mut out = format!("{} {}(", self.return_value.as_ref().map_or("void".into(), |r| r.to_hlsl_type()), self.function);
+        for (i, p) in self.parameters.iter().enumerate() {
+            let modifier = match (p.is_in(), p.is_out()) { (true, true) => "inout", (false, true) => "out", _ => "in" };
+            if i != 0 { out.push_str(", ") }
+            let _ = write!(out, "{} {} ", modifier, p.to_hlsl_type());
+            match p.name.as_deref() {
+                Some(name)  => out.push_str(name),
+                None        => { let _ = write!(out, "p{}", i); },
+            }
+        }
+        out.push_str(");");
+        out
+    }
+}
+
+
+
+impl LinkingGraph {
+    /// Set the graph's input signature, like [FunctionLinkingGraph::set_input_signature].
+    pub fn set_input_signature(&mut self, inputs: Vec<LinkingParameter>) -> LinkingNodeId { self.inputs = inputs; LinkingNodeId::Input }
+
+    /// Set the graph's output signature, like [FunctionLinkingGraph::set_output_signature].
+    pub fn set_output_signature(&mut self, outputs: Vec<LinkingParameter>) -> LinkingNodeId { self.outputs = outputs; LinkingNodeId::Output }
+
+    /// Append a function call, like [FunctionLinkingGraph::call_function].
+    pub fn call_function(&mut self, call: LinkingCall) -> LinkingNodeId { self.calls.push(call); LinkingNodeId::Call(self.calls.len()-1) }
+
+    /// Pass a value between nodes, like [FunctionLinkingGraph::pass_value].
+    pub fn pass_value(&mut self, src: LinkingNodeId, src_parameter: i32, dst: LinkingNodeId, dst_parameter: i32) {
+        self.edges.push(LinkingEdge { src, src_parameter, src_swizzle: None, dst, dst_parameter, dst_swizzle: None });
+    }
+
+    /// Pass a swizzled value between nodes, like [FunctionLinkingGraph::pass_value_with_swizzle].
+    pub fn pass_value_with_swizzle(&mut self, src: LinkingNodeId, src_parameter: i32, src_swizzle: &str, dst: LinkingNodeId, dst_parameter: i32, dst_swizzle: &str) {
+        self.edges.push(LinkingEdge { src, src_parameter, src_swizzle: Some(src_swizzle.into()), dst, dst_parameter, dst_swizzle: Some(dst_swizzle.into()) });
+    }
+
+    /// Look up a parameter of a node (`-1` = a call's return value.)
+    pub fn parameter(&self, node: LinkingNodeId, parameter: i32) -> Option<&LinkingParameter> {
+        let params = match node {
+            LinkingNodeId::Input    => &self.inputs,
+            LinkingNodeId::Output   => &self.outputs,
+            LinkingNodeId::Call(c)  => {
+                let call = self.calls.get(c)?;
+                if parameter == -1 { return call.return_value.as_ref() }
+                &call.parameters
+            },
+        };
+        params.get(usize::try_from(parameter).ok()?)
+    }
+
+    /// Validate the graph:
+    ///
+    /// *   Every edge must reference an existing node + parameter, read from something readable (an input, a call's return value or `out` parameter),
+    ///     and write to something writable (an output, a call's `in` parameter.)
+    /// *   Calls may only read the results of earlier calls.
+    /// *   Swizzles must only use components the parameter has, and destination swizzles may not repeat components.
+    /// *   Source and destination must have the same component type, and the source must have as many components as the destination (or be a single component.)
+    /// *   Every component of every call `in` parameter and output parameter must be written exactly once.
+    ///
+    /// ### Errors
+    /// *   [LinkingProblem::Parameter]         - if an edge references a node or parameter that doesn't exist
+    /// *   [LinkingProblem::Direction]         - if an edge reads from an output / `in` parameter, or writes to an input / return value / `out` parameter
+    /// *   [LinkingProblem::Order]             - if a call reads the result of a later call (or itself)
+    /// *   [LinkingProblem::Swizzle]           - if a swizzle is malformed
+    /// *   [LinkingProblem::Type]              - if an edge's source and destination component types differ
+    /// *   [LinkingProblem::ComponentCount]    - if an edge's source has too few components for its destination
+    /// *   [LinkingProblem::MultipleWrites]    - if a component is written more than once
+    /// *   [LinkingProblem::Unconnected]       - if a call `in` parameter or output parameter is never (fully) written
+    pub fn validate(&self) -> Result<(), LinkingGraphMismatch> {
+        let mut problems = Vec::new();
+        let mut written = BTreeMap::<(LinkingNodeId, i32), u32>::new();
+
+        for edge in self.edges.iter() {
+            let (src, dst) = match (self.parameter(edge.src, edge.src_parameter), self.parameter(edge.dst, edge.dst_parameter)) {
+                (Some(src), Some(dst))  => (src, dst),
+                (None, _)               => { problems.push(LinkingProblem::Parameter { node: edge.src, parameter: edge.src_parameter }); continue },
+                (_, None)               => { problems.push(LinkingProblem::Parameter { node: edge.dst, parameter: edge.dst_parameter }); continue },
+            };
+
+            let readable = match edge.src { LinkingNodeId::Input => true, LinkingNodeId::Output => false, LinkingNodeId::Call(_) => edge.src_parameter == -1 || src.is_out() };
+            let writable = match edge.dst { LinkingNodeId::Input => false, LinkingNodeId::Output => true, LinkingNodeId::Call(_) => edge.dst_parameter != -1 && dst.is_in() };
+            if !readable { problems.push(LinkingProblem::Direction { node: edge.src, parameter: edge.src_parameter }) }
+            if !writable { problems.push(LinkingProblem::Direction { node: edge.dst, parameter: edge.dst_parameter }) }
+            if !readable || !writable { continue }
+            if let (LinkingNodeId::Call(s), LinkingNodeId::Call(d)) = (edge.src, edge.dst) {
+                if s >= d { problems.push(LinkingProblem::Order { src: edge.src, dst: edge.dst }) }
+            }
+
+            let src_mask = swizzle_mask(edge.src_swizzle.as_deref(), src, false);
+            let dst_mask = swizzle_mask(edge.dst_swizzle.as_deref(), dst, true);
+            if src_mask.is_none() { problems.push(LinkingProblem::Swizzle { node: edge.src, parameter: edge.src_parameter, swizzle: edge.src_swizzle.clone().unwrap_or_default() }) }
+            if dst_mask.is_none() { problems.push(LinkingProblem::Swizzle { node: edge.dst, parameter: edge.dst_parameter, swizzle: edge.dst_swizzle.clone().unwrap_or_default() }) }
+            let (Some((src_count, _)), Some((dst_count, dst_mask))) = (src_mask, dst_mask) else { continue };
+
+            if src.ty != dst.ty {
+                problems.push(LinkingProblem::Type { src: edge.src, src_parameter: edge.src_parameter, src_ty: src.ty, dst: edge.dst, dst_parameter: edge.dst_parameter, dst_ty: dst.ty });
+            }
+            if src_count < dst_count && src_count != 1 {
+                problems.push(LinkingProblem::ComponentCount { src: edge.src, src_parameter: edge.src_parameter, src_count, dst: edge.dst, dst_parameter: edge.dst_parameter, dst_count });
+            }
+            let prev = written.entry((edge.dst, edge.dst_parameter)).or_default();
+            if *prev & dst_mask != 0 { problems.push(LinkingProblem::MultipleWrites { node: edge.dst, parameter: edge.dst_parameter }) }
+            *prev |= dst_mask;
+        }
+
+        let calls = self.calls.iter().enumerate().flat_map(|(c, call)| call.parameters.iter().enumerate().filter(|(_, p)| p.is_in()).map(move |(i, p)| (LinkingNodeId::Call(c), i, p)));
+        let outputs = self.outputs.iter().enumerate().map(|(i, p)| (LinkingNodeId::Output, i, p));
+        for (node, parameter, p) in calls.chain(outputs) {
+            let parameter = parameter as i32;
+            let all = full_mask(p);
+            if written.get(&(node, parameter)).copied().unwrap_or(0) & all != all { problems.push(LinkingProblem::Unconnected { node, parameter }) }
+        }
+
+        if problems.is_empty() { Ok(()) } else { Err(LinkingGraphMismatch { problems }) }
+    }
+
+    /// Generate HLSL equivalent to [FunctionLinkingGraph::generate_hlsl], without `d3dcompiler_*.dll`.
+    ///
+    /// Nodes are numbered the same way [FunctionLinkingGraph] numbers them: `n0` for the input signature, `n1..` for calls, and the output signature last.
+    /// Unnamed inputs/outputs are named `__Input_n0_#` / `__Output_n#_#`.  Call return values and parameters are passed through `function_n#_#` temporaries.
+    ///
+    /// The graph should be [validate](Self::validate)d first - invalid edges are emitted as-is, and will likely fail to compile.
+    pub fn to_hlsl(&self) -> String {
+        let mut out = String::new();
+
+        let mut prototypes = BTreeSet::new();
+        for call in self.calls.iter() {
+            let proto = call.to_hlsl_prototype();
+            let proto = match call.namespace.as_deref() {
+                Some(ns) if !ns.is_empty()  => format!("namespace {} {{ {} }}", ns, proto),
+                _                           => proto,
+            };
+            if prototypes.insert(proto.clone()) { out.push_str(&proto); out.push('\n') }
+        }
+        if !prototypes.is_empty() { out.push('\n') }
+
+        out.push_str("void main(");
+        let signature = self.inputs.iter().enumerate().map(|(i, p)| ("in", p, self.name(LinkingNodeId::Input, i as i32)))
+            .chain(self.outputs.iter().enumerate().map(|(i, p)| ("out", p, self.name(LinkingNodeId::Output, i as i32))));
+        for (i, (modifier, p, name)) in signature.enumerate() {
+            if i != 0 { out.push_str(", ") }
+            let _ = write!(out, "{} {} {}", modifier, p.to_hlsl_type(), name);
+            if let Some(semantic) = p.semantic.as_deref() { let _ = write!(out, " : {}", semantic); }
+        }
+        out.push_str(")\n{\n");
+
+        for (c, call) in self.calls.iter().enumerate() {
+            let node = LinkingNodeId::Call(c);
+            if let Some(r) = call.return_value.as_ref() { let _ = writeln!(out, "    {} {};", r.to_hlsl_type(), self.name(node, -1)); }
+            for (i, p) in call.parameters.iter().enumerate() { let _ = writeln!(out, "    {} {};", p.to_hlsl_type(), self.name(node, i as i32)); }
+        }
+
+        for c in 0 .. self.calls.len() {
+            let node = LinkingNodeId::Call(c);
+            self.hlsl_passes(node, &mut out);
+            let call = &self.calls[c];
+            out.push_str("    ");
+            if call.return_value.is_some() { let _ = write!(out, "{} = ", self.name(node, -1)); }
+            let _ = write!(out, "{}::{}(", call.namespace.as_deref().unwrap_or(""), call.function);
+            for i in 0 .. call.parameters.len() {
+                if i != 0 { out.push_str(", ") }
+                out.push_str(&self.name(node, i as i32));
+            }
+            out.push_str(");\n");
+        }
+        self.hlsl_passes(LinkingNodeId::Output, &mut out);
+
+        out.push_str("}\n");
+        out
+    }
+
+    /// Replay this graph into a (fresh) [FunctionLinkingGraph], in the order [FunctionLinkingGraph] requires:
+    /// input signature, calls, output signature, then edges.
+    ///
+    /// [FunctionLinkingGraph::set_input_signature] / [set_output_signature](FunctionLinkingGraph::set_output_signature) require `'static` names.
+    /// Names are interned for the lifetime of the process to satisfy this - each distinct name is only leaked once, no matter how many graphs are replayed.
+    ///
+    /// ### Arguments
+    /// *   `graph`     - The [FunctionLinkingGraph] to build (typically fresh from [Compiler::create_function_linking_graph].)
+    /// *   `modules`   - The library modules referenced by [LinkingCall::module].
+    ///
+    /// ### Errors
+    /// *   [E::INVALIDARG]                     - if a [LinkingCall::module] is out of bounds of `modules`
+    /// *   [E::INVALIDARG]                     - if an edge references a node that doesn't exist
+    /// *   [THINERR::STRING_CONTAINS_NULS]     - if a name or semantic contains interior `\0`s
+    /// *   [E::FAIL]                           - if `graph` was already used, a function doesn't exist, or an edge was rejected
+    pub fn replay(&self, graph: &FunctionLinkingGraph, modules: &[&Module]) -> Result<(), Error> {
+        fn_context!(d3d11::LinkingGraph::replay);
+
+        let inputs  = self.inputs .iter().map(|p| parameter_desc(p).map_err(|e| fn_error!(e))).collect::<Result<Vec<_>, _>>()?;
+        let outputs = self.outputs.iter().map(|p| parameter_desc(p).map_err(|e| fn_error!(e))).collect::<Result<Vec<_>, _>>()?;
+
+        let input = graph.set_input_signature(&inputs)?;
+        let mut calls = Vec::with_capacity(self.calls.len());
+        for call in self.calls.iter() {
+            let module = modules.get(call.module).ok_or_else(|| fn_param_error!(modules, E::INVALIDARG))?;
+            calls.push(graph.call_function(call.namespace.as_deref().unwrap_or(""), module, call.function.as_str())?);
+        }
+        let output = graph.set_output_signature(&outputs)?;
+
+        let node = |id| match id {
+            LinkingNodeId::Input    => Ok(&input),
+            LinkingNodeId::Output   => Ok(&output),
+            LinkingNodeId::Call(c)  => calls.get(c).ok_or_else(|| fn_error!(E::INVALIDARG)),
+        };
+        for edge in self.edges.iter() {
+            let (src, dst) = (node(edge.src)?, node(edge.dst)?);
+            match (edge.src_swizzle.as_deref(), edge.dst_swizzle.as_deref()) {
+                (None, None)    => graph.pass_value(src, edge.src_parameter, dst, edge.dst_parameter)?,
+                (s, d)          => {
+                    let s = s.unwrap_or_else(|| full_swizzle(self.parameter(edge.src, edge.src_parameter)));
+                    let d = d.unwrap_or_else(|| full_swizzle(self.parameter(edge.dst, edge.dst_parameter)));
+                    graph.pass_value_with_swizzle(src, edge.src_parameter, s, dst, edge.dst_parameter, d)?
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// The HLSL name of a node's parameter
+    fn name(&self, node: LinkingNodeId, parameter: i32) -> String {
+        match node {
+            LinkingNodeId::Input    => self.inputs .get(parameter as usize).and_then(|p| p.name.clone()).unwrap_or_else(|| format!("__Input_n0_{}", parameter)),
+            LinkingNodeId::Output   => self.outputs.get(parameter as usize).and_then(|p| p.name.clone()).unwrap_or_else(|| format!("__Output_n{}_{}", self.calls.len()+1, parameter)),
+            LinkingNodeId::Call(c)  => format!("{}_n{}_{}", self.calls.get(c).map_or("", |c| c.function.as_str()), c+1, parameter+1),
+        }
+    }
+
+    /// Emit all edges writing to `dst`, sorted by destination parameter
+    fn hlsl_passes(&self, dst: LinkingNodeId, out: &mut String) {
+        let mut edges = self.edges.iter().filter(|e| e.dst == dst).collect::<Vec<_>>();
+        edges.sort_by_key(|e| e.dst_parameter);
+        for e in edges {
+            let swizzle = |s: &Option<String>| s.as_ref().map_or(String::new(), |s| format!(".{}", s));
+            let _ = writeln!(out, "    {}{} = {}{};", self.name(e.dst, e.dst_parameter), swizzle(&e.dst_swizzle), self.name(e.src, e.src_parameter), swizzle(&e.src_swizzle));
+        }
+    }
+}
+
+/// `(component count, component mask)` of a (possibly swizzled) parameter, or [None] if the swizzle is invalid
+fn swizzle_mask(swizzle: Option<&str>, p: &LinkingParameter, is_dst: bool) -> Option<(u32, u32)> {
+    let Some(swizzle) = swizzle else { return Some((p.components(), full_mask(p))) };
+    if swizzle.is_empty() || swizzle.len() > 4 || !matches!(p.class, SVC::Scalar | SVC::Vector) { return None }
+    let xyzw = swizzle.bytes().all(|b| b"xyzw".contains(&b));
+    let rgba = swizzle.bytes().all(|b| b"rgba".contains(&b));
+    if !xyzw && !rgba { return None }
+    let mut mask = 0;
+    for b in swizzle.bytes() {
+        let c = if xyzw { b"xyzw".iter() } else { b"rgba".iter() }.position(|&x| x == b)? as u32;
+        if c >= p.columns.max(1) { return None }
+        if is_dst && mask & (1 << c) != 0 { return None }
+        mask |= 1 << c;
+    }
+    Some((swizzle.len() as u32, mask))
+}
+
+fn full_mask(p: &LinkingParameter) -> u32 { (1u32 << p.components().min(16)) - 1 }
+
+/// The swizzle equivalent of an unswizzled parameter (e.g. `"xy"` for a `float2`), matching [full_mask]
+fn full_swizzle(p: Option<&LinkingParameter>) -> &'static str { &"xyzw"[.. p.map_or(4, |p| p.components().clamp(1, 4)) as usize] }
+
+/// Interned, `'static` names for [ParameterDesc]s passed to [FunctionLinkingGraph]
+static NAMES : Mutex<BTreeSet<&'static CStr>> = Mutex::new(BTreeSet::new());
+
+fn intern(s: &str) -> Result<abistr::CStrNonNull<'static>, ErrorKind> {
+    let s = CString::new(s).map_err(|_| THINERR::STRING_CONTAINS_NULS)?;
+    let mut names = NAMES.lock().unwrap_or_else(|poison| poison.into_inner());
+    let s : &'static CStr = match names.get(s.as_c_str()) {
+        Some(s) => s,
+        None    => { let s = Box::leak(s.into_boxed_c_str()); names.insert(s); s },
+    };
+    // SAFETY: ✔️ `s` is a `\0`-terminated string that's never freed
+    Ok(unsafe { abistr::CStrNonNull::from_ptr_unchecked_unbounded(s.as_ptr()) })
+}
+
+fn parameter_desc(p: &LinkingParameter) -> Result<ParameterDesc<'static>, ErrorKind> {
+    Ok(ParameterDesc {
+        name:                   p.name    .as_deref().map(intern).transpose()?,
+        semantic_name:          p.semantic.as_deref().map(intern).transpose()?,
+        ty:                     p.ty,
+        class:                  p.class,
+        rows:                   p.rows,
+        columns:                p.columns,
+        interpolation_mode:     p.interpolation,
+        flags:                  p.flags,
+        first_in_register:      0,
+        first_in_component:     0,
+        first_out_register:     0,
+        first_out_component:    0,
+    })
+}
+
+
+
+/// A [LinkingGraph] failed [validation](LinkingGraph::validate).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinkingGraphMismatch {
+    /// Every problem found (always at least one.)
+    pub problems:   Vec<LinkingProblem>,
+}
+
+/// A single [LinkingGraphMismatch] problem.
+#[allow(missing_docs)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LinkingProblem {
+    /// An edge references a node or parameter that doesn't exist
+    Parameter       { node: LinkingNodeId, parameter: i32 },
+
+    /// An edge reads from something write-only, or writes to something read-only
+    Direction       { node: LinkingNodeId, parameter: i32 },
+
+    /// A call reads the result of a call that isn't made before it
+    Order           { src: LinkingNodeId, dst: LinkingNodeId },
+
+    /// A swizzle is malformed, references components the parameter doesn't have, or (as a destination) repeats components
+    Swizzle         { node: LinkingNodeId, parameter: i32, swizzle: String },
+
+    /// An edge's source and destination have different component types
+    Type            { src: LinkingNodeId, src_parameter: i32, src_ty: ShaderVariableType, dst: LinkingNodeId, dst_parameter: i32, dst_ty: ShaderVariableType },
+
+    /// An edge's source has fewer components than its destination
+    ComponentCount  { src: LinkingNodeId, src_parameter: i32, src_count: u32, dst: LinkingNodeId, dst_parameter: i32, dst_count: u32 },
+
+    /// The same component of a parameter is written by multiple edges
+    MultipleWrites  { node: LinkingNodeId, parameter: i32 },
+
+    /// A call `in` parameter or output parameter isn't (fully) written
+    Unconnected     { node: LinkingNodeId, parameter: i32 },
+}
+
+impl std::error::Error for LinkingGraphMismatch {}
+
+impl Display for LinkingGraphMismatch {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "invalid function linking graph:")?;
+        for problem in self.problems.iter() { write!(fmt, "\n    {}", problem)?; }
+        Ok(())
+    }
+}
+
+impl Display for LinkingProblem {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Parameter      { node, parameter }                                    => write!(fmt, "{:?}[{}]: no such parameter", node, parameter),
+            Self::Direction      { node, parameter }                                    => write!(fmt, "{:?}[{}]: can't pass values in this direction", node, parameter),
+            Self::Order          { src, dst }                                           => write!(fmt, "{:?} reads the result of {:?}, which isn't called before it", dst, src),
+            Self::Swizzle        { node, parameter, swizzle }                           => write!(fmt, "{:?}[{}]: invalid swizzle {:?}", node, parameter, swizzle),
+            Self::Type           { src, src_parameter, src_ty, dst, dst_parameter, dst_ty }         => write!(fmt, "{:?}[{}] is {:?}, but {:?}[{}] is {:?}", src, src_parameter, src_ty, dst, dst_parameter, dst_ty),
+            Self::ComponentCount { src, src_parameter, src_count, dst, dst_parameter, dst_count }   => write!(fmt, "{:?}[{}] has {} components, but {:?}[{}] needs {}", src, src_parameter, src_count, dst, dst_parameter, dst_count),
+            Self::MultipleWrites { node, parameter }                                    => write!(fmt, "{:?}[{}]: written multiple times", node, parameter),
+            Self::Unconnected    { node, parameter }                                    => write!(fmt, "{:?}[{}]: never written", node, parameter),
+        }
+    }
+}
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    fn float(name: &str, semantic: &str, columns: u32, flags: ParameterFlags) -> LinkingParameter {
+        LinkingParameter::new(Some(name).filter(|n| !n.is_empty()), Some(semantic).filter(|s| !s.is_empty()), SVT::Float, SVC::Vector, 1, columns, flags)
+    }
+
+    fn example() -> (LinkingGraph, [LinkingNodeId; 3]) {
+        let mut graph = LinkingGraph::default();
+        let input = graph.set_input_signature(vec![
+            float("inputPos",  "POSITION0", 3, PF::In),
+            float("inputTex",  "TEXCOORD0", 2, PF::In),
+            float("inputNorm", "NORMAL0",   3, PF::In),
+        ]);
+        let xyz1 = graph.call_function(LinkingCall {
+            module:         0,
+            namespace:      None,
+            function:       "xyz1".into(),
+            parameters:     vec![float("v", "", 3, PF::In)],
+            return_value:   Some(float("", "", 4, PF::Out)),
+        });
+        let output = graph.set_output_signature(vec![
+            float("outputTex",  "TEXCOORD0",   2, PF::Out),
+            float("outputNorm", "NORMAL0",     3, PF::Out),
+            float("",           "SV_POSITION", 4, PF::Out),
+        ]);
+        graph.pass_value(input, 0, xyz1, 0);
+        graph.pass_value(xyz1, -1, output, 2);
+        graph.pass_value(input, 1, output, 0);
+        graph.pass_value_with_swizzle(input, 2, "zyx", output, 1, "xyz");
+        (graph, [input, xyz1, output])
+    }
+
+    #[test] fn hlsl() {
+        let (graph, _) = example();
+        assert_eq!(graph.validate(), Ok(()));
+        assert_eq!(graph.to_hlsl(), concat!(
+            "float4 xyz1(in float3 v);\n",
+            "\n",
+            "void main(in float3 inputPos : POSITION0, in float2 inputTex : TEXCOORD0, in float3 inputNorm : NORMAL0, out float2 outputTex : TEXCOORD0, out float3 outputNorm : NORMAL0, out float4 __Output_n2_2 : SV_POSITION)\n",
+            "{\n",
+            "    float4 xyz1_n1_0;\n",
+            "    float3 xyz1_n1_1;\n",
+            "    xyz1_n1_1 = inputPos;\n",
+            "    xyz1_n1_0 = ::xyz1(xyz1_n1_1);\n",
+            "    outputTex = inputTex;\n",
+            "    outputNorm.xyz = inputNorm.zyx;\n",
+            "    __Output_n2_2 = xyz1_n1_0;\n",
+            "}\n",
+        ));
+    }
+
+    #[test] fn full_swizzles() {
+        let (graph, [input, xyz1, output]) = example();
+        assert_eq!(full_swizzle(graph.parameter(input, 1)), "xy");
+        assert_eq!(full_swizzle(graph.parameter(xyz1, 0)), "xyz");
+        assert_eq!(full_swizzle(graph.parameter(xyz1, -1)), "xyzw");
+        assert_eq!(full_swizzle(graph.parameter(output, 9)), "xyzw");
+        assert_eq!(full_swizzle(Some(&LinkingParameter { class: SVC::Scalar, ..float("s", "", 1, PF::In) })), "x");
+    }
+
+    #[test] fn validate() {
+        let (mut graph, [input, xyz1, output]) = example();
+        graph.edges[0] = LinkingEdge { src: input, src_parameter: 1, dst: xyz1, dst_parameter: 0, ..Default::default() };
+        graph.edges[3].dst_swizzle = Some("xx".into());
+        graph.pass_value(output, 0, input, 0);
+        graph.pass_value(input, 3, output, 0);
+        graph.pass_value(input, 1, output, 0);
+        graph.edges.push(LinkingEdge { src: xyz1, src_parameter: -1, dst: LinkingNodeId::Output, dst_parameter: 0, ..Default::default() });
+        graph.outputs[0].ty = SVT::Int;
+
+        let err = graph.validate().unwrap_err();
+        assert_eq!(err.problems, [
+            LinkingProblem::ComponentCount { src: input, src_parameter: 1, src_count: 2, dst: xyz1, dst_parameter: 0, dst_count: 3 },
+            LinkingProblem::Type { src: input, src_parameter: 1, src_ty: SVT::Float, dst: output, dst_parameter: 0, dst_ty: SVT::Int },
+            LinkingProblem::Swizzle { node: output, parameter: 1, swizzle: "xx".into() },
+            LinkingProblem::Direction { node: output, parameter: 0 },
+            LinkingProblem::Direction { node: input, parameter: 0 },
+            LinkingProblem::Parameter { node: input, parameter: 3 },
+            LinkingProblem::Type { src: input, src_parameter: 1, src_ty: SVT::Float, dst: output, dst_parameter: 0, dst_ty: SVT::Int },
+            LinkingProblem::MultipleWrites { node: output, parameter: 0 },
+            LinkingProblem::Type { src: xyz1, src_parameter: -1, src_ty: SVT::Float, dst: output, dst_parameter: 0, dst_ty: SVT::Int },
+            LinkingProblem::MultipleWrites { node: output, parameter: 0 },
+            LinkingProblem::Unconnected { node: output, parameter: 1 },
+        ], "{}", err);
+    }
+}
@@ -0,0 +1,385 @@
+use crate::*;
+use crate::d3d::*;
+use crate::d3d11::*;
+
+use std::fmt::{self, Display, Formatter};
+
+
+
+/// A single shader stage of a graphics pipeline, for [check_pipeline].
+///
+/// ### Examples
+/// ```rust
+/// # use thindx::{*, d3d::*, d3d11::*};
+/// # let d3dc = Compiler::load_system(47).unwrap();
+/// let vs = d3dc.compile_from_file(r"test\data\basic.hlsl", None, None, "vs_main", "vs_4_0", Compile::Debug, CompileEffect::None).unwrap();
+/// let ps = d3dc.compile_from_file(r"test\data\basic.hlsl", None, None, "ps_main", "ps_4_0", Compile::Debug, CompileEffect::None).unwrap();
+///
+/// // Without d3dcompiler_*.dll
+/// let stages = [PipelineStage::parse(&vs).unwrap(), PipelineStage::parse(&ps).unwrap()];
+/// check_pipeline(&stages).unwrap();
+///
+/// // With d3dcompiler_*.dll
+/// let vs = PipelineStage::from(ReflectionData::from_reflection(&d3dc.reflect11(&vs).unwrap()).unwrap());
+/// let ps = PipelineStage::from(ReflectionData::from_reflection(&d3dc.reflect11(&ps).unwrap()).unwrap());
+/// check_pipeline(&[vs, ps]).unwrap();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PipelineStage {
+    /// The stage's signatures and resource bindings.
+    pub reflection:     ReflectionData,
+
+    /// The interpolation mode of each pixel shader input register (`[(register, mode)]`), or empty if unknown.
+    ///
+    /// Interpolation modes aren't part of the input signature, nor exposed by [ShaderReflection] -
+    /// [PipelineStage::parse] reads them from the shader's `dcl_input_ps` instructions instead.
+    pub interpolation:  Vec<(u32, Interpolation)>,
+}
+
+impl PipelineStage {
+    /// Read a stage's signatures, bindings, and (for pixel shaders) input interpolation modes directly from DXBC, without `d3dcompiler_*.dll`.
+    ///
+    /// ### Errors
+    /// *   [THINERR::INVALID_BYTECODE] - if `bytecode` isn't valid DXBC (see [ReflectionData::parse])
+    pub fn parse(bytecode: &Bytecode) -> Result<Self, Error> {
+        let reflection = ReflectionData::parse(bytecode)?;
+        let interpolation = match reflection.desc.version.ty() {
+            ShVer::PixelShader  => super::reflection_parse::ps_input_interpolation(bytecode.as_bytes()).unwrap_or_default(),
+            _                   => Vec::new(),
+        };
+        Ok(Self { reflection, interpolation })
+    }
+
+    /// The [ShaderVersionType] of this stage.
+    pub fn ty(&self) -> ShaderVersionType { self.reflection.desc.version.ty() }
+}
+
+impl From<ReflectionData> for PipelineStage {
+    /// Wrap reflection data (e.g. from [ReflectionData::from_reflection]) without interpolation modes.
+    fn from(reflection: ReflectionData) -> Self { Self { reflection, interpolation: Vec::new() } }
+}
+
+
+
+/// Validate a graphics pipeline (VS, optional HS+DS, optional GS, optional PS):
+///
+/// *   Each stage's inputs must be fed by the previous stage's outputs: same semantic, same register, a superset of the components read, and the same component type.
+///     HS→DS patch constants are checked the same way.  System generated inputs (`SV_VertexID`, `SV_InstanceID`, `SV_PrimitiveID`, `SV_IsFrontFace`, ...) are ignored.
+/// *   If a pixel shader is present, the stage before it must output `SV_Position`.
+/// *   Pixel shader inputs must use an interpolation mode compatible with their type (integers must be `nointerpolation`, `SV_Position` must not be.)
+/// *   No two stages may expect different resources (by name, type, dimension, return type, or `cbuffer` size) in the same `b#`, `t#`, `s#`, or `u#` slot.
+///     Engines commonly share slot assignments between stages, so this catches accidentally reused slots.
+///
+/// `stages` may be passed in any order.  Compute shaders are only checked for slot conflicts.
+///
+/// ### Errors
+/// *   [PipelineProblem::MissingStage]     - if there's no vertex shader, or a hull shader without a domain shader (or vice versa)
+/// *   [PipelineProblem::DuplicateStage]   - if the same stage is passed twice
+/// *   [PipelineProblem::Missing] / [Register](PipelineProblem::Register) / [Mask](PipelineProblem::Mask) / [ComponentType](PipelineProblem::ComponentType) - if signatures don't link
+/// *   [PipelineProblem::NoPosition]       - if the stage before the pixel shader doesn't output `SV_Position`
+/// *   [PipelineProblem::Interpolation]    - if a pixel shader input's interpolation mode is incompatible with its type
+/// *   [PipelineProblem::SlotConflict]     - if two stages expect different resources in the same slot
+pub fn check_pipeline(stages: &[PipelineStage]) -> Result<(), PipelineMismatch> {
+    const ORDER : [ShaderVersionType; 5] = [ShVer::VertexShader, ShVer::HullShader, ShVer::DomainShader, ShVer::GeometryShader, ShVer::PixelShader];
+    let mut problems = Vec::new();
+
+    for (i, s) in stages.iter().enumerate() {
+        if stages[..i].iter().any(|prev| prev.ty() == s.ty()) && !problems.contains(&PipelineProblem::DuplicateStage { stage: s.ty() }) {
+            problems.push(PipelineProblem::DuplicateStage { stage: s.ty() });
+        }
+    }
+
+    let graphics = ORDER.iter().filter_map(|ty| stages.iter().find(|s| s.ty() == *ty)).collect::<Vec<_>>();
+    if !graphics.is_empty() {
+        let has = |ty| graphics.iter().any(|s| s.ty() == ty);
+        if !has(ShVer::VertexShader)                            { problems.push(PipelineProblem::MissingStage { stage: ShVer::VertexShader }) }
+        if  has(ShVer::HullShader) && !has(ShVer::DomainShader) { problems.push(PipelineProblem::MissingStage { stage: ShVer::DomainShader }) }
+        if !has(ShVer::HullShader) &&  has(ShVer::DomainShader) { problems.push(PipelineProblem::MissingStage { stage: ShVer::HullShader }) }
+    }
+
+    for pair in graphics.windows(2) {
+        let (producer, consumer) = (pair[0], pair[1]);
+        let outputs = producer.reflection.outputs.iter().filter(|o| o.stream == 0).collect::<Vec<_>>();
+        link(producer.ty(), consumer.ty(), &outputs, &consumer.reflection.inputs, &mut problems);
+        if consumer.ty() == ShVer::DomainShader {
+            let patch = producer.reflection.patch_constants.iter().collect::<Vec<_>>();
+            link(producer.ty(), consumer.ty(), &patch, &consumer.reflection.patch_constants, &mut problems);
+        }
+        if consumer.ty() == ShVer::PixelShader {
+            if !outputs.iter().any(|o| o.system_value_type == Name::Position) { problems.push(PipelineProblem::NoPosition { stage: producer.ty() }) }
+            interpolation(consumer, &mut problems);
+        }
+    }
+
+    slots(stages, &mut problems);
+
+    if problems.is_empty() { Ok(()) } else { Err(PipelineMismatch { problems }) }
+}
+
+fn link(producer: ShaderVersionType, consumer: ShaderVersionType, outputs: &[&ReflectionParameter], inputs: &[ReflectionParameter], problems: &mut Vec<PipelineProblem>) {
+    for input in inputs.iter().filter(|i| !is_system_generated(i.system_value_type)) {
+        let semantic = semantic(input);
+        let Some(output) = outputs.iter().find(|o| o.semantic_name.eq_ignore_ascii_case(&input.semantic_name) && o.semantic_index == input.semantic_index) else {
+            problems.push(PipelineProblem::Missing { producer, consumer, semantic });
+            continue;
+        };
+        if output.register != input.register {
+            problems.push(PipelineProblem::Register { producer, consumer, semantic: semantic.clone(), output: output.register, input: input.register });
+        }
+        let read = (if input.read_write_mask != 0 { input.read_write_mask } else { input.mask }) & 0xF;
+        if read & !output.mask != 0 {
+            problems.push(PipelineProblem::Mask { producer, consumer, semantic: semantic.clone(), output: output.mask & 0xF, input: read });
+        }
+        if output.component_type != input.component_type {
+            problems.push(PipelineProblem::ComponentType { producer, consumer, semantic, output: output.component_type, input: input.component_type });
+        }
+    }
+}
+
+fn interpolation(ps: &PipelineStage, problems: &mut Vec<PipelineProblem>) {
+    for input in ps.reflection.inputs.iter() {
+        let Some(&(_, mode)) = ps.interpolation.iter().find(|(register, _)| *register == input.register) else { continue };
+        let integer = matches!(input.component_type, RegisterComponentType::UInt32 | RegisterComponentType::SInt32);
+        let bad = match mode {
+            Interpolation::Undefined    => false,
+            Interpolation::Constant     => input.system_value_type == Name::Position,
+            _                           => integer,
+        };
+        if bad { problems.push(PipelineProblem::Interpolation { semantic: semantic(input), ty: input.component_type, mode }) }
+    }
+}
+
+fn slots(stages: &[PipelineStage], problems: &mut Vec<PipelineProblem>) {
+    let end     = |b: &ReflectionBinding| if b.bind_count == 0 { u32::MAX } else { b.bind_point.saturating_add(b.bind_count) };
+    let cb_size = |s: &PipelineStage, b: &ReflectionBinding| s.reflection.constant_buffers.iter().find(|cb| cb.name == b.name && cb.ty != CBufferType::ResourceBindInfo).map(|cb| cb.size);
+    for (i, first) in stages.iter().enumerate() {
+        for second in stages[i+1..].iter().filter(|s| s.ty() != first.ty()) {
+            for (a, b) in first.reflection.bindings.iter().flat_map(|a| second.reflection.bindings.iter().map(move |b| (a, b))) {
+                let (ra, rb) = (a.hlsl_register(), b.hlsl_register());
+                if ra[..1] != rb[..1] || a.bind_point >= end(b) || b.bind_point >= end(a) { continue }
+                let same = a.name == b.name && a.ty == b.ty && a.dimension == b.dimension && a.return_type == b.return_type && cb_size(first, a) == cb_size(second, b);
+                if same { continue }
+                problems.push(PipelineProblem::SlotConflict {
+                    register:   if a.bind_point >= b.bind_point { ra } else { rb },
+                    stages:     [first.ty(), second.ty()],
+                    names:      [a.name.clone(), b.name.clone()],
+                });
+            }
+        }
+    }
+}
+
+fn is_system_generated(name: Name) -> bool {
+    matches!(name, Name::VertexId | Name::InstanceId | Name::PrimitiveId | Name::IsFrontFace | Name::SampleIndex | Name::Coverage | Name::InnerCoverage | Name::Barycentrics)
+}
+
+fn semantic(p: &ReflectionParameter) -> String { format!("{}{}", p.semantic_name, p.semantic_index) }
+
+
+
+/// The stages passed to [check_pipeline] don't link together.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PipelineMismatch {
+    /// Every problem found (always at least one.)
+    pub problems:   Vec<PipelineProblem>,
+}
+
+/// A single [PipelineMismatch] problem.
+#[allow(missing_docs)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PipelineProblem {
+    /// A required stage wasn't provided
+    MissingStage    { stage: ShaderVersionType },
+
+    /// The same stage was provided more than once
+    DuplicateStage  { stage: ShaderVersionType },
+
+    /// `consumer` reads `semantic`, but `producer` doesn't output it
+    Missing         { producer: ShaderVersionType, consumer: ShaderVersionType, semantic: String },
+
+    /// `producer` outputs `semantic` to a different register than `consumer` reads it from
+    Register        { producer: ShaderVersionType, consumer: ShaderVersionType, semantic: String, output: u32, input: u32 },
+
+    /// `consumer` reads components of `semantic` that `producer` doesn't output
+    Mask            { producer: ShaderVersionType, consumer: ShaderVersionType, semantic: String, output: u8, input: u8 },
+
+    /// `producer` and `consumer` disagree on the type of `semantic`
+    ComponentType   { producer: ShaderVersionType, consumer: ShaderVersionType, semantic: String, output: RegisterComponentType, input: RegisterComponentType },
+
+    /// The stage before the pixel shader doesn't output `SV_Position`
+    NoPosition      { stage: ShaderVersionType },
+
+    /// A pixel shader input's interpolation mode is incompatible with its type
+    Interpolation   { semantic: String, ty: RegisterComponentType, mode: Interpolation },
+
+    /// Two stages expect different resources (`names`) in the same `register`
+    SlotConflict    { register: String, stages: [ShaderVersionType; 2], names: [String; 2] },
+}
+
+impl std::error::Error for PipelineMismatch {}
+
+impl Display for PipelineMismatch {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "shader stages don't link:")?;
+        for problem in self.problems.iter() { write!(fmt, "\n    {}", problem)?; }
+        Ok(())
+    }
+}
+
+impl Display for PipelineProblem {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::MissingStage   { stage }                                      => write!(fmt, "missing {:?}", stage),
+            Self::DuplicateStage { stage }                                      => write!(fmt, "multiple {:?}s", stage),
+            Self::Missing        { producer, consumer, semantic }               => write!(fmt, "{}: {:?} reads this, but {:?} doesn't output it", semantic, consumer, producer),
+            Self::Register       { producer, consumer, semantic, output, input }=> write!(fmt, "{}: {:?} outputs this to o{}, but {:?} reads it from v{}", semantic, producer, output, consumer, input),
+            Self::Mask           { producer, consumer, semantic, output, input }=> write!(fmt, "{}: {:?} outputs components {:#06b}, but {:?} reads {:#06b}", semantic, producer, output, consumer, input),
+            Self::ComponentType  { producer, consumer, semantic, output, input }=> write!(fmt, "{}: {:?} outputs {:?}, but {:?} reads {:?}", semantic, producer, output, consumer, input),
+            Self::NoPosition     { stage }                                      => write!(fmt, "{:?} doesn't output SV_Position for the rasterizer", stage),
+            Self::Interpolation  { semantic, ty, mode }                         => write!(fmt, "{}: {:?} isn't a valid interpolation mode for {:?}", semantic, mode, ty),
+            Self::SlotConflict   { register, stages, names }                    => write!(fmt, "{}: {:?} expects {:?}, but {:?} expects {:?}", register, stages[0], names[0], stages[1], names[1]),
+        }
+    }
+}
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    fn param(semantic_name: &str, register: u32, mask: u8, system_value_type: Name) -> ReflectionParameter {
+        ReflectionParameter { semantic_name: semantic_name.into(), register, mask, read_write_mask: mask, system_value_type, component_type: RegisterComponentType::Float32, ..Default::default() }
+    }
+
+    fn stage(ty: ShaderVersionType, inputs: Vec<ReflectionParameter>, outputs: Vec<ReflectionParameter>, bindings: Vec<ReflectionBinding>) -> PipelineStage {
+        let desc = ReflectionDesc { version: d3d11::ShaderVersion::new(ty, 4, 0), ..Default::default() };
+        PipelineStage::from(ReflectionData { desc, inputs, outputs, bindings, ..Default::default() })
+    }
+
+    fn texture(name: &str, bind_point: u32) -> ReflectionBinding {
+        ReflectionBinding { name: name.into(), ty: SIT::Texture, bind_point, bind_count: 1, dimension: SrvDimension::Texture2D, return_type: ReturnType::Float, ..Default::default() }
+    }
+
+    #[test] fn linkage() {
+        let vs = stage(ShVer::VertexShader, vec![param("POSITION", 0, 0xF, Name::Undefined)], vec![
+            param("SV_POSITION", 0, 0xF, Name::Position),
+            param("TEXCOORD",    1, 0x3, Name::Undefined),
+        ], vec![texture("heightmap", 0)]);
+        let mut ps = stage(ShVer::PixelShader, vec![
+            param("SV_POSITION",     0, 0xF, Name::Position),
+            param("TEXCOORD",        1, 0x7, Name::Undefined),
+            param("COLOR",           2, 0xF, Name::Undefined),
+            param("SV_IsFrontFace",  3, 0x1, Name::IsFrontFace),
+        ], vec![], vec![texture("heightmap", 0), texture("diffuse", 1)]);
+
+        assert_eq!(check_pipeline(&[vs.clone(), stage(ShVer::PixelShader, vec![param("SV_POSITION", 0, 0xF, Name::Position)], vec![], vec![texture("heightmap", 0)])]), Ok(()));
+
+        ps.interpolation = vec![(0, Interpolation::LinearNoPerspective), (1, Interpolation::Linear), (2, Interpolation::Linear)];
+        ps.reflection.inputs[2].component_type = RegisterComponentType::UInt32;
+        ps.reflection.bindings[0].name = "albedo".into();
+        let err = check_pipeline(&[ps, vs]).unwrap_err();
+        assert_eq!(err.problems, [
+            PipelineProblem::Mask { producer: ShVer::VertexShader, consumer: ShVer::PixelShader, semantic: "TEXCOORD0".into(), output: 0x3, input: 0x7 },
+            PipelineProblem::Missing { producer: ShVer::VertexShader, consumer: ShVer::PixelShader, semantic: "COLOR0".into() },
+            PipelineProblem::Interpolation { semantic: "COLOR0".into(), ty: RegisterComponentType::UInt32, mode: Interpolation::Linear },
+            PipelineProblem::SlotConflict { register: "t0".into(), stages: [ShVer::PixelShader, ShVer::VertexShader], names: ["albedo".into(), "heightmap".into()] },
+        ], "{}", err);
+    }
+
+    #[test] fn missing_semantics() {
+        let vs = stage(ShVer::VertexShader, vec![], vec![param("texcoord", 1, 0x3, Name::Undefined)], vec![]);
+        let gs = stage(ShVer::GeometryShader, vec![param("TEXCOORD", 1, 0x3, Name::Undefined)], vec![
+            param("SV_POSITION",    0, 0xF, Name::Position),
+            ReflectionParameter { stream: 1, ..param("COLOR", 1, 0xF, Name::Undefined) },
+        ], vec![]);
+        let ps = stage(ShVer::PixelShader, vec![
+            param("SV_POSITION",    0, 0xF, Name::Position),
+            param("COLOR",          1, 0xF, Name::Undefined),
+            ReflectionParameter { semantic_index: 1, ..param("TEXCOORD", 2, 0x3, Name::Undefined) },
+            param("SV_IsFrontFace", 3, 0x1, Name::IsFrontFace),
+        ], vec![], vec![]);
+
+        // semantics are case insensitive, and system generated inputs are ignored
+        assert_eq!(check_pipeline(&[vs.clone(), gs.clone()]), Ok(()));
+
+        let err = check_pipeline(&[vs.clone(), gs, ps.clone()]).unwrap_err();
+        assert_eq!(err.problems, [
+            PipelineProblem::Missing { producer: ShVer::GeometryShader, consumer: ShVer::PixelShader, semantic: "COLOR0".into() },       // only output to stream 1
+            PipelineProblem::Missing { producer: ShVer::GeometryShader, consumer: ShVer::PixelShader, semantic: "TEXCOORD1".into() },
+        ], "{}", err);
+
+        let err = check_pipeline(&[vs, ps]).unwrap_err();
+        assert_eq!(err.problems, [
+            PipelineProblem::Missing { producer: ShVer::VertexShader, consumer: ShVer::PixelShader, semantic: "SV_POSITION0".into() },
+            PipelineProblem::Missing { producer: ShVer::VertexShader, consumer: ShVer::PixelShader, semantic: "COLOR0".into() },
+            PipelineProblem::Missing { producer: ShVer::VertexShader, consumer: ShVer::PixelShader, semantic: "TEXCOORD1".into() },
+            PipelineProblem::NoPosition { stage: ShVer::VertexShader },
+        ], "{}", err);
+
+        let ds = stage(ShVer::DomainShader, vec![], vec![param("SV_POSITION", 0, 0xF, Name::Position)], vec![]);
+        let err = check_pipeline(&[ds.clone(), ds]).unwrap_err();
+        assert_eq!(err.problems, [
+            PipelineProblem::DuplicateStage { stage: ShVer::DomainShader },
+            PipelineProblem::MissingStage { stage: ShVer::VertexShader },
+            PipelineProblem::MissingStage { stage: ShVer::HullShader },
+        ], "{}", err);
+    }
+
+    #[test] fn register_and_mask_mismatches() {
+        let vs = stage(ShVer::VertexShader, vec![], vec![
+            param("SV_POSITION",    0, 0xF, Name::Position),
+            param("TEXCOORD",       1, 0x3, Name::Undefined),
+            param("NORMAL",         2, 0x7, Name::Undefined),
+            param("COLOR",          3, 0x3, Name::Undefined),
+        ], vec![]);
+        let ps = stage(ShVer::PixelShader, vec![
+            param("SV_POSITION",    0, 0xF, Name::Position),
+            param("TEXCOORD",       4, 0x3, Name::Undefined),
+            ReflectionParameter { component_type: RegisterComponentType::SInt32, ..param("NORMAL", 2, 0xF, Name::Undefined) },
+            ReflectionParameter { read_write_mask: 0x3, ..param("COLOR", 3, 0xF, Name::Undefined) }, // declared float4, but only .xy read
+        ], vec![], vec![]);
+
+        let err = check_pipeline(&[vs, ps]).unwrap_err();
+        assert_eq!(err.problems, [
+            PipelineProblem::Register { producer: ShVer::VertexShader, consumer: ShVer::PixelShader, semantic: "TEXCOORD0".into(), output: 1, input: 4 },
+            PipelineProblem::Mask { producer: ShVer::VertexShader, consumer: ShVer::PixelShader, semantic: "NORMAL0".into(), output: 0x7, input: 0xF },
+            PipelineProblem::ComponentType { producer: ShVer::VertexShader, consumer: ShVer::PixelShader, semantic: "NORMAL0".into(), output: RegisterComponentType::Float32, input: RegisterComponentType::SInt32 },
+        ], "{}", err);
+    }
+
+    #[test] fn slot_conflicts() {
+        let binding = |name: &str, ty, bind_point, bind_count| ReflectionBinding { name: name.into(), ty, bind_point, bind_count, ..Default::default() };
+        let cbuffer = |size| ReflectionConstantBuffer { name: "PerFrame".into(), size, ..Default::default() };
+        let position = || vec![param("SV_POSITION", 0, 0xF, Name::Position)];
+
+        let mut vs = stage(ShVer::VertexShader, vec![], position(), vec![
+            binding("PerFrame",     SIT::CBuffer,       0, 1),
+            texture("heightmap",    0),
+            binding("linear",       SIT::Sampler,       0, 1),
+            binding("textures",     SIT::Texture,       4, 0), // unbounded
+        ]);
+        vs.reflection.bindings[1].bind_count = 2;
+        vs.reflection.constant_buffers = vec![cbuffer(64)];
+
+        let mut ps = stage(ShVer::PixelShader, position(), vec![], vec![
+            binding("PerFrame",     SIT::CBuffer,       0, 1),
+            texture("diffuse",      1),
+            binding("linear",       SIT::Sampler,       0, 1),
+            texture("extra",        2),
+            binding("output",       SIT::UavRWTyped,    4, 1),
+            texture("late",         100),
+        ]);
+        ps.reflection.constant_buffers = vec![cbuffer(80)];
+
+        let err = check_pipeline(&[vs.clone(), ps.clone()]).unwrap_err();
+        assert_eq!(err.problems, [
+            PipelineProblem::SlotConflict { register: "b0".into(),   stages: [ShVer::VertexShader, ShVer::PixelShader], names: ["PerFrame".into(), "PerFrame".into()] },
+            PipelineProblem::SlotConflict { register: "t1".into(),   stages: [ShVer::VertexShader, ShVer::PixelShader], names: ["heightmap".into(), "diffuse".into()] },
+            PipelineProblem::SlotConflict { register: "t100".into(), stages: [ShVer::VertexShader, ShVer::PixelShader], names: ["textures".into(), "late".into()] },
+        ], "{}", err);
+
+        ps.reflection.constant_buffers = vec![cbuffer(64)];
+        ps.reflection.bindings.retain(|b| b.name != "diffuse" && b.name != "late");
+        assert_eq!(check_pipeline(&[vs, ps]), Ok(()));
+    }
+}
@@ -0,0 +1,404 @@
+use crate::*;
+use crate::ctypes::*;
+use crate::d3d::*;
+use crate::d3d11::*;
+
+
+
+/// An owned snapshot of everything [ShaderReflection] reports about a shader.
+///
+/// Unlike [ShaderReflection] and friends, this doesn't borrow any COM objects, can be cloned/compared freely, and (with the `serde` feature) serialized.
+/// This allows reflection data to be cached next to bytecode, and loaded on any platform.
+///
+/// Build one with:
+/// *   [ReflectionData::from_reflection] - via `d3dcompiler_*.dll`'s [ShaderReflection] interfaces
+/// *   [ReflectionData::parse] - via a pure-Rust DXBC parser (no DLLs required)
+///
+/// ### Examples
+/// ```rust
+/// # use thindx::{*, d3d::*};
+/// # let d3dc = Compiler::load_system(47).unwrap();
+/// # let vs = d3dc.compile_from_file(r"test\data\basic.hlsl", None, None, "vs_main", "vs_4_0", Compile::Debug, CompileEffect::None).unwrap();
+/// let data = d3d11::ReflectionData::from_reflection(&d3dc.reflect11(&vs).unwrap()).unwrap();
+/// let cb = data.constant_buffer("ExampleCBuffer").unwrap();
+/// assert_eq!(cb.variables[0].name, "tint");
+///
+/// let parsed = d3d11::ReflectionData::parse(&vs).unwrap();
+/// assert_eq!(parsed.inputs, data.inputs);
+/// assert_eq!(parsed.constant_buffer("ExampleCBuffer").unwrap().size, cb.size);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReflectionData {
+    /// [ShaderReflection::get_desc]
+    pub desc:                           ReflectionDesc,
+
+    /// [ShaderReflection::get_input_parameter_desc]
+    pub inputs:                         Vec<ReflectionParameter>,
+
+    /// [ShaderReflection::get_output_parameter_desc]
+    pub outputs:                        Vec<ReflectionParameter>,
+
+    /// [ShaderReflection::get_patch_constant_parameter_desc]
+    pub patch_constants:                Vec<ReflectionParameter>,
+
+    /// [ShaderReflection::get_constant_buffer_by_index]
+    pub constant_buffers:               Vec<ReflectionConstantBuffer>,
+
+    /// [ShaderReflection::get_resource_binding_desc]
+    pub bindings:                       Vec<ReflectionBinding>,
+
+    /// [ShaderReflection::get_thread_group_size]
+    pub thread_group_size:              [u32; 3],
+
+    /// [ShaderReflection::get_gs_input_primitive]
+    pub gs_input_primitive:             Primitive,
+
+    /// [ShaderReflection::get_min_feature_level]
+    pub min_feature_level:              Option<FeatureLevel>,
+
+    /// [ShaderReflection::get_requires_flags]
+    pub requires_flags:                 ShaderRequires,
+
+    /// [ShaderReflection::get_num_interface_slots]
+    pub num_interface_slots:            u32,
+
+    /// [ShaderReflection::is_sample_frequency_shader]
+    pub is_sample_frequency_shader:     bool,
+
+    /// [ShaderReflection::get_bitwise_instruction_count]
+    pub bitwise_instruction_count:      u32,
+
+    /// [ShaderReflection::get_conversion_instruction_count]
+    pub conversion_instruction_count:   u32,
+
+    /// [ShaderReflection::get_movc_instruction_count]
+    pub movc_instruction_count:         u32,
+
+    /// [ShaderReflection::get_mov_instruction_count]
+    pub mov_instruction_count:          u32,
+}
+
+/// An owned [ShaderDesc], as stored in [ReflectionData::desc].
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReflectionDesc {
+    pub version:                        d3d11::ShaderVersion,
+    /// e.g. "Microsoft (R) HLSL Shader Compiler 10.1"
+    pub creator:                        String,
+    pub flags:                          Compile,
+    pub constant_buffers:               u32,
+    pub bound_resources:                u32,
+    pub input_parameters:               u32,
+    pub output_parameters:              u32,
+    pub instruction_count:              u32,
+    pub temp_register_count:            u32,
+    pub temp_array_count:               u32,
+    pub def_count:                      u32,
+    pub dcl_count:                      u32,
+    pub texture_normal_instructions:    u32,
+    pub texture_load_instructions:      u32,
+    pub texture_comp_instructions:      u32,
+    pub texture_bias_instructions:      u32,
+    pub texture_gradient_instructions:  u32,
+    pub float_instruction_count:        u32,
+    pub int_instruction_count:          u32,
+    pub uint_instruction_count:         u32,
+    pub static_flow_control_count:      u32,
+    pub dynamic_flow_control_count:     u32,
+    pub macro_instruction_count:        u32,
+    pub array_instruction_count:        u32,
+    pub cut_instruction_count:          u32,
+    pub emit_instruction_count:         u32,
+    pub gs_output_topology:             PrimitiveTopology,
+    pub gs_max_output_vertex_count:     u32,
+    pub input_primitive:                Primitive,
+    pub patch_constant_parameters:      u32,
+    pub gs_instance_count:              u32,
+    pub control_points:                 u32,
+    pub hs_output_primitive:            TessellatorOutputPrimitive,
+    pub hs_partitioning:                TessellatorPartitioning,
+    pub tessellator_domain:             TessellatorDomain,
+    pub barrier_instructions:           u32,
+    pub interlocked_instructions:       u32,
+    pub texture_store_instructions:     u32,
+}
+
+/// An owned [SignatureParameterDesc], as stored in [ReflectionData::inputs] / [outputs](ReflectionData::outputs) / [patch_constants](ReflectionData::patch_constants).
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReflectionParameter {
+    pub semantic_name:      String,
+    pub semantic_index:     u32,
+    pub register:           u32,
+    pub system_value_type:  Name,
+    pub component_type:     RegisterComponentType,
+    pub mask:               u8,
+    pub read_write_mask:    u8,
+    pub stream:             u32,
+    pub min_precision:      MinPrecision,
+}
+
+/// An owned [ShaderBufferDesc] + variables, as stored in [ReflectionData::constant_buffers].
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReflectionConstantBuffer {
+    pub name:       String,
+    pub ty:         CBufferType,
+    pub size:       u32,
+    pub flags:      ShaderCbufferFlags,
+    /// [ShaderReflectionConstantBuffer::get_variable_by_index]
+    pub variables:  Vec<ReflectionVariable>,
+}
+
+/// An owned [ShaderVariableDesc] + type, as stored in [ReflectionConstantBuffer::variables].
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReflectionVariable {
+    pub name:           String,
+    pub start_offset:   u32,
+    pub size:           u32,
+    pub flags:          ShaderVariableFlags,
+    /// `size` bytes of default value, if the variable has one.
+    pub default_value:  Option<Vec<u8>>,
+    pub start_texture:  u32,
+    pub texture_size:   u32,
+    pub start_sampler:  u32,
+    pub sampler_size:   u32,
+    /// [ShaderReflectionVariable::get_type]
+    pub ty:             ReflectionType,
+}
+
+/// An owned [ShaderTypeDesc] + members, as stored in [ReflectionVariable::ty].
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReflectionType {
+    pub class:      ShaderVariableClass,
+    pub ty:         ShaderVariableType,
+    pub rows:       u32,
+    pub columns:    u32,
+    pub elements:   u32,
+    pub offset:     u32,
+    /// The HLSL type name (e.g. `"float4"`), if available (SM5+)
+    pub name:       Option<String>,
+    /// [ShaderReflectionType::get_member_type_by_index] / [ShaderReflectionType::get_member_type_name]
+    pub members:    Vec<ReflectionMember>,
+}
+
+/// A named struct member, as stored in [ReflectionType::members].
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReflectionMember {
+    pub name:   String,
+    pub ty:     ReflectionType,
+}
+
+/// An owned [ShaderInputBindDesc], as stored in [ReflectionData::bindings].
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReflectionBinding {
+    pub name:           String,
+    pub ty:             ShaderInputType,
+    pub bind_point:     u32,
+    pub bind_count:     u32,
+    pub flags:          ShaderInputFlags,
+    pub return_type:    ResourceReturnType,
+    pub dimension:      SrvDimension,
+    pub num_samples:    u32,
+}
+
+
+
+impl ReflectionData {
+    /// Walk every [ShaderReflection] interface and collect the results.
+    ///
+    /// ### Errors
+    /// *   [E::FAIL]   - if any `get_desc` call fails (e.g. corrupt reflection data)
+    pub fn from_reflection(reflection: &ShaderReflection) -> Result<Self, Error> {
+        let desc = reflection.get_desc()?;
+        Ok(Self {
+            inputs:                         (0 .. desc.input_parameters         ).map(|i| reflection.get_input_parameter_desc(i)         .map(|p| ReflectionParameter::from(&p))).collect::<Result<_, _>>()?,
+            outputs:                        (0 .. desc.output_parameters        ).map(|i| reflection.get_output_parameter_desc(i)        .map(|p| ReflectionParameter::from(&p))).collect::<Result<_, _>>()?,
+            patch_constants:                (0 .. desc.patch_constant_parameters).map(|i| reflection.get_patch_constant_parameter_desc(i).map(|p| ReflectionParameter::from(&p))).collect::<Result<_, _>>()?,
+            constant_buffers:               (0 .. desc.constant_buffers         ).map(|i| ReflectionConstantBuffer::from_reflection(&reflection.get_constant_buffer_by_index(i))).collect::<Result<_, _>>()?,
+            bindings:                       (0 .. desc.bound_resources          ).map(|i| reflection.get_resource_binding_desc(i)        .map(|b| ReflectionBinding::from(&b))).collect::<Result<_, _>>()?,
+            thread_group_size:              { let (x, y, z) = reflection.get_thread_group_size(); [x, y, z] },
+            gs_input_primitive:             reflection.get_gs_input_primitive(),
+            min_feature_level:              reflection.get_min_feature_level().ok(),
+            requires_flags:                 reflection.get_requires_flags(),
+            num_interface_slots:            reflection.get_num_interface_slots(),
+            is_sample_frequency_shader:     reflection.is_sample_frequency_shader(),
+            bitwise_instruction_count:      reflection.get_bitwise_instruction_count(),
+            conversion_instruction_count:   reflection.get_conversion_instruction_count(),
+            movc_instruction_count:         reflection.get_movc_instruction_count(),
+            mov_instruction_count:          reflection.get_mov_instruction_count(),
+            desc:                           ReflectionDesc::from(&desc),
+        })
+    }
+
+    /// Find a constant buffer by name.
+    pub fn constant_buffer(&self, name: &str) -> Option<&ReflectionConstantBuffer> { self.constant_buffers.iter().find(|cb| cb.name == name) }
+
+    /// Find a resource binding by name.
+    pub fn binding(&self, name: &str) -> Option<&ReflectionBinding> { self.bindings.iter().find(|b| b.name == name) }
+
+    /// Find a constant buffer variable by name, searching all constant buffers.
+    pub fn variable(&self, name: &str) -> Option<&ReflectionVariable> { self.constant_buffers.iter().flat_map(|cb| cb.variables.iter()).find(|v| v.name == name) }
+}
+
+impl ReflectionConstantBuffer {
+    /// Walk a [ShaderReflectionConstantBuffer] and all of its variables.
+    ///
+    /// ### Errors
+    /// *   [E::FAIL]   - if any `get_desc` call fails (e.g. `cb` is a stub object)
+    pub fn from_reflection(cb: &ShaderReflectionConstantBuffer) -> Result<Self, Error> {
+        let desc = cb.get_desc()?;
+        Ok(Self {
+            name:       owned(desc.name),
+            ty:         desc.ty,
+            size:       desc.size,
+            flags:      desc.flags,
+            variables:  (0 .. desc.variables).map(|i| ReflectionVariable::from_reflection(&cb.get_variable_by_index(i))).collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Find a variable by name.
+    pub fn variable(&self, name: &str) -> Option<&ReflectionVariable> { self.variables.iter().find(|v| v.name == name) }
+}
+
+impl ReflectionVariable {
+    /// Walk a [ShaderReflectionVariable] and its type.
+    ///
+    /// ### Errors
+    /// *   [E::FAIL]   - if any `get_desc` call fails (e.g. `var` is a stub object)
+    pub fn from_reflection(var: &ShaderReflectionVariable) -> Result<Self, Error> {
+        let desc = var.get_desc()?;
+        let default_value = if desc.default_value.is_null() { None } else {
+            // SAFETY: ⚠️ D3D11_SHADER_VARIABLE_DESC::DefaultValue is documented to point to `Size` bytes of default value, valid for the lifetime of the reflection interface
+            Some(unsafe { std::slice::from_raw_parts(desc.default_value as *const u8, desc.size as usize) }.to_vec())
+        };
+        Ok(Self {
+            name:           owned(desc.name),
+            start_offset:   desc.start_offset,
+            size:           desc.size,
+            flags:          desc.flags,
+            default_value,
+            start_texture:  desc.start_texture,
+            texture_size:   desc.texture_size,
+            start_sampler:  desc.start_sampler,
+            sampler_size:   desc.sampler_size,
+            ty:             ReflectionType::from_reflection(&var.get_type())?,
+        })
+    }
+}
+
+impl ReflectionType {
+    /// Walk a [ShaderReflectionType] and all of its members, recursively.
+    ///
+    /// ### Errors
+    /// *   [E::FAIL]   - if any `get_desc` call fails (e.g. `ty` is a stub object)
+    pub fn from_reflection(ty: &ShaderReflectionType) -> Result<Self, Error> {
+        let desc = ty.get_desc()?;
+        Ok(Self {
+            class:      desc.class,
+            ty:         desc.ty,
+            rows:       desc.rows,
+            columns:    desc.columns,
+            elements:   desc.elements,
+            offset:     desc.offset,
+            name:       Some(owned(desc.name)).filter(|n| !n.is_empty()),
+            members:    (0 .. desc.members).map(|i| Ok(ReflectionMember {
+                name:   ty.get_member_type_name(i).map_or(String::new(), |n| n.to_string_lossy().into_owned()),
+                ty:     ReflectionType::from_reflection(&ty.get_member_type_by_index(i))?,
+            })).collect::<Result<_, Error>>()?,
+        })
+    }
+
+    /// Find a member by name.
+    pub fn member(&self, name: &str) -> Option<&ReflectionMember> { self.members.iter().find(|m| m.name == name) }
+}
+
+impl From<&ShaderDesc<'_>> for ReflectionDesc {
+    fn from(d: &ShaderDesc) -> Self {
+        Self {
+            version:                        d.version,
+            creator:                        owned(d.creator),
+            flags:                          d.flags,
+            constant_buffers:               d.constant_buffers,
+            bound_resources:                d.bound_resources,
+            input_parameters:               d.input_parameters,
+            output_parameters:              d.output_parameters,
+            instruction_count:              d.instruction_count,
+            temp_register_count:            d.temp_register_count,
+            temp_array_count:               d.temp_array_count,
+            def_count:                      d.def_count,
+            dcl_count:                      d.dcl_count,
+            texture_normal_instructions:    d.texture_normal_instructions,
+            texture_load_instructions:      d.texture_load_instructions,
+            texture_comp_instructions:      d.texture_comp_instructions,
+            texture_bias_instructions:      d.texture_bias_instructions,
+            texture_gradient_instructions:  d.texture_gradient_instructions,
+            float_instruction_count:        d.float_instruction_count,
+            int_instruction_count:          d.int_instruction_count,
+            uint_instruction_count:         d.uint_instruction_count,
+            static_flow_control_count:      d.static_flow_control_count,
+            dynamic_flow_control_count:     d.dynamic_flow_control_count,
+            macro_instruction_count:        d.macro_instruction_count,
+            array_instruction_count:        d.array_instruction_count,
+            cut_instruction_count:          d.cut_instruction_count,
+            emit_instruction_count:         d.emit_instruction_count,
+            gs_output_topology:             d.gs_output_topology,
+            gs_max_output_vertex_count:     d.gs_max_output_vertex_count,
+            input_primitive:                d.input_primitive,
+            patch_constant_parameters:      d.patch_constant_parameters,
+            gs_instance_count:              d.gs_instance_count,
+            control_points:                 d.control_points,
+            hs_output_primitive:            d.hs_output_primitive,
+            hs_partitioning:                d.hs_partitioning,
+            tessellator_domain:             d.tessellator_domain,
+            barrier_instructions:           d.barrier_instructions,
+            interlocked_instructions:       d.interlocked_instructions,
+            texture_store_instructions:     d.texture_store_instructions,
+        }
+    }
+}
+
+impl From<&SignatureParameterDesc<'_>> for ReflectionParameter {
+    fn from(p: &SignatureParameterDesc) -> Self {
+        Self {
+            semantic_name:      owned(p.semantic_name),
+            semantic_index:     p.semantic_index,
+            register:           p.register,
+            system_value_type:  p.system_value_type,
+            component_type:     p.component_type,
+            mask:               p.mask,
+            read_write_mask:    p.read_write_mask,
+            stream:             p.stream,
+            min_precision:      p.min_precision,
+        }
+    }
+}
+
+impl From<&ShaderInputBindDesc<'_>> for ReflectionBinding {
+    fn from(b: &ShaderInputBindDesc) -> Self {
+        Self {
+            name:           owned(b.name),
+            ty:             b.ty,
+            bind_point:     b.bind_point,
+            bind_count:     b.bind_count,
+            flags:          b.flags,
+            return_type:    b.return_type,
+            dimension:      b.dimension,
+            num_samples:    b.num_samples,
+        }
+    }
+}
+
+fn owned(s: CStrPtr) -> String { String::from_utf8_lossy(s.to_bytes()).into_owned() }
@@ -0,0 +1,515 @@
+use crate::*;
+use crate::d3d::*;
+use crate::d3d11::*;
+
+
+
+impl ReflectionData {
+    /// Parse reflection data directly out of DXBC bytecode, without `d3dcompiler_*.dll`.
+    ///
+    /// The container layout (`RDEF`, `ISGN`/`OSGN`/`PCSG` and their SM5/SM5.1 variants, `STAT`, `SFI0`, `SHDR`/`SHEX`) isn't documented by Microsoft.
+    /// This follows the same reverse engineered layouts Wine's `d3dcompiler` implementation uses, and should match [ReflectionData::from_reflection] for:
+    /// *   [ReflectionData::constant_buffers], [bindings](ReflectionData::bindings)
+    /// *   [ReflectionData::inputs], [outputs](ReflectionData::outputs), [patch_constants](ReflectionData::patch_constants)
+    /// *   [ReflectionData::thread_group_size], [requires_flags](ReflectionData::requires_flags)
+    /// *   [ReflectionData::desc] (version, creator, flags, counts, and `STAT` derived instruction statistics)
+    ///
+    /// [ReflectionData::mov_instruction_count], [movc](ReflectionData::movc_instruction_count), and [conversion](ReflectionData::conversion_instruction_count)
+    /// are recounted from the `SHDR`/`SHEX` instruction stream, and may differ slightly from d3dcompiler's own bookkeeping.
+    ///
+    /// The following are derived by d3dcompiler from the instruction stream, and are left zeroed/[None]:
+    /// *   [ReflectionData::min_feature_level]
+    /// *   [ReflectionData::num_interface_slots]
+    /// *   [ReflectionData::is_sample_frequency_shader]
+    /// *   [ReflectionData::bitwise_instruction_count]
+    /// *   [ReflectionData::gs_input_primitive]
+    ///
+    /// ### Errors
+    /// *   [THINERR::INVALID_BYTECODE] - if `bytecode` isn't DXBC (SM1-3 token streams and DXIL containers aren't supported)
+    /// *   [THINERR::INVALID_BYTECODE] - if any chunk this parser understands is truncated or contains out of bounds offsets
+    ///
+    /// ### Examples
+    /// ```rust
+    /// # use thindx::{*, d3d::*};
+    /// # let d3dc = Compiler::load_system(47).unwrap();
+    /// let ps = d3dc.compile_from_file(r"test\data\basic.hlsl", None, None, "ps_main", "ps_4_0", Compile::Debug, CompileEffect::None).unwrap();
+    /// let data = d3d11::ReflectionData::parse(&ps).unwrap();
+    /// assert_eq!(format!("{:?}", data.desc.version), "ps_4_0");
+    /// assert_eq!(data.inputs.len(), 2);
+    /// assert_eq!(data.outputs[0].semantic_name, "SV_TARGET");
+    /// assert_eq!(data.outputs[0].system_value_type, d3d::Name::Target);
+    /// ```
+    pub fn parse(bytecode: &Bytecode) -> Result<Self, Error> {
+        fn_context!(d3d11::ReflectionData::parse);
+        parse(bytecode.as_bytes()).ok_or_else(|| fn_param_error!(bytecode, THINERR::INVALID_BYTECODE))
+    }
+}
+
+fn parse(b: &[u8]) -> Option<ReflectionData> {
+    if b.get(0..4)? != b"DXBC" { return None }
+    let mut data = ReflectionData::default();
+    let mut rdef_target = None;
+    let mut code_version = None;
+
+    for i in 0 .. u32_at(b, 28)? as usize {
+        let (fourcc, chunk) = chunk_at(b, i)?;
+        match fourcc {
+            b"RDEF"             => rdef_target      = Some(rdef(&mut data, chunk)?),
+            b"ISGN"             => data.inputs          = signature(chunk, Sig::Basic,  false)?,
+            b"ISG1"             => data.inputs          = signature(chunk, Sig::Sm51,   false)?,
+            b"OSGN"             => data.outputs         = signature(chunk, Sig::Basic,  true )?,
+            b"OSG5"             => data.outputs         = signature(chunk, Sig::Stream, true )?,
+            b"OSG1"             => data.outputs         = signature(chunk, Sig::Sm51,   true )?,
+            b"PCSG"             => data.patch_constants = signature(chunk, Sig::Basic,  false)?,
+            b"PSG1"             => data.patch_constants = signature(chunk, Sig::Sm51,   false)?,
+            b"STAT"             => stat(&mut data.desc, chunk)?,
+            b"SFI0"             => data.requires_flags  = ShaderRequires::from_unchecked(u64::from(u32_at(chunk, 0)?) | (u64::from(u32_at(chunk, 4)?) << 32)),
+            b"SHDR" | b"SHEX"   => code_version         = Some(code(&mut data, chunk)?),
+            _                   => {},
+        }
+    }
+
+    data.desc.version = match (code_version, rdef_target) {
+        (Some(token), _) => d3d11::ShaderVersion::new(ShaderVersionType::from_unchecked((token >> 16) as _), (token >> 4) & 0xF, token & 0xF),
+        (None, Some(target)) => {
+            let ty = match target >> 16 {
+                0xFFFF => 0,    // ps
+                0xFFFE => 1,    // vs
+                0x4753 => 2,    // "GS"
+                0x4853 => 3,    // "HS"
+                0x4453 => 4,    // "DS"
+                0x4353 => 5,    // "CS"
+                0x4C46 => 6,    // "LF" (library)
+                _      => return None,
+            };
+            d3d11::ShaderVersion::new(ShaderVersionType::from_unchecked(ty), (target >> 8) & 0xFF, target & 0xFF)
+        },
+        (None, None) => d3d11::ShaderVersion::default(),
+    };
+    data.desc.constant_buffers          = data.constant_buffers.len() as u32;
+    data.desc.bound_resources           = data.bindings.len() as u32;
+    data.desc.input_parameters          = data.inputs.len() as u32;
+    data.desc.output_parameters         = data.outputs.len() as u32;
+    data.desc.patch_constant_parameters = data.patch_constants.len() as u32;
+    Some(data)
+}
+
+/// Parse the resource definitions chunk, returning the RDEF target token (e.g. `0xFFFF0500` for `ps_5_0`)
+fn rdef(data: &mut ReflectionData, c: &[u8]) -> Option<u32> {
+    let cb_count    = u32_at(c,  0)? as usize;
+    let cb_off      = u32_at(c,  4)? as usize;
+    let res_count   = u32_at(c,  8)? as usize;
+    let res_off     = u32_at(c, 12)? as usize;
+    let target      = u32_at(c, 16)?;
+    let flags       = u32_at(c, 20)?;
+    let creator     = u32_at(c, 24)? as usize;
+
+    let major       = (target >> 8) & 0xFF;
+    let minor       = target & 0xFF;
+    let sm5         = major >= 5;
+    let sm51        = major > 5 || (major == 5 && minor >= 1);
+
+    data.desc.creator   = cstr_at(c, creator)?;
+    data.desc.flags     = Compile::from_unchecked(flags as _);
+
+    let res_stride = if sm51 { 40 } else { 32 };
+    data.bindings = (0 .. res_count).map(|i| {
+        let r = record_at(c, res_off, res_stride, i)?;
+        Some(ReflectionBinding {
+            name:           cstr_at(c, u32_at(r, 0)? as usize)?,
+            ty:             ShaderInputType::from_unchecked(u32_at(r,  4)? as _),
+            return_type:    ResourceReturnType::from_unchecked(u32_at(r,  8)? as _),
+            dimension:      SrvDimension::from_unchecked(u32_at(r, 12)? as _),
+            num_samples:    u32_at(r, 16)?,
+            bind_point:     u32_at(r, 20)?,
+            bind_count:     u32_at(r, 24)?,
+            flags:          ShaderInputFlags::from_unchecked(u32_at(r, 28)? as _),
+        })
+    }).collect::<Option<_>>()?;
+
+    let var_stride = if sm5 { 40 } else { 24 };
+    let mut type_budget = MAX_RDEF_TYPES;
+    data.constant_buffers = (0 .. cb_count).map(|i| {
+        let r = record_at(c, cb_off, 24, i)?;
+        let var_count   = u32_at(r,  4)? as usize;
+        let var_off     = u32_at(r,  8)? as usize;
+        Some(ReflectionConstantBuffer {
+            name:       cstr_at(c, u32_at(r, 0)? as usize)?,
+            size:       u32_at(r, 12)?,
+            flags:      ShaderCbufferFlags::from_unchecked(u32_at(r, 16)? as _),
+            ty:         CBufferType::from_unchecked(u32_at(r, 20)? as _),
+            variables:  (0 .. var_count).map(|j| {
+                let r = record_at(c, var_off, var_stride, j)?;
+                let size        = u32_at(r,  8)?;
+                let default_off = u32_at(r, 20)? as usize;
+                Some(ReflectionVariable {
+                    name:           cstr_at(c, u32_at(r, 0)? as usize)?,
+                    start_offset:   u32_at(r,  4)?,
+                    size,
+                    flags:          ShaderVariableFlags::from_unchecked(u32_at(r, 12)? as _),
+                    ty:             rdef_type(c, u32_at(r, 16)? as usize, 0, sm5, 0, &mut type_budget)?,
+                    default_value:  if default_off == 0 { None } else { Some(c.get(default_off .. default_off.checked_add(size as usize)?)?.to_vec()) },
+                    start_texture:  if sm5 { u32_at(r, 24)? } else { !0 },
+                    texture_size:   if sm5 { u32_at(r, 28)? } else {  0 },
+                    start_sampler:  if sm5 { u32_at(r, 32)? } else { !0 },
+                    sampler_size:   if sm5 { u32_at(r, 36)? } else {  0 },
+                })
+            }).collect::<Option<_>>()?,
+        })
+    }).collect::<Option<_>>()?;
+
+    Some(target)
+}
+
+/// Upper bound on the total number of [ReflectionType]s expanded from a single RDEF chunk.
+///
+/// Member type offsets may be shared (a DAG rather than a tree), and every use is expanded into its own owned
+/// [ReflectionType], so a small malicious chunk could otherwise describe exponentially many types.
+const MAX_RDEF_TYPES : usize = 1 << 16;
+
+fn rdef_type(c: &[u8], o: usize, offset: u32, sm5: bool, depth: usize, budget: &mut usize) -> Option<ReflectionType> {
+    if depth > 64 { return None } // guard against cyclic member offsets
+    *budget = budget.checked_sub(1)?;
+    let r           = record_at(c, o, if sm5 { 36 } else { 16 }, 0)?;
+    let class_ty    = u32_at(r, 0)?;
+    let rows_cols   = u32_at(r, 4)?;
+    let elems_mems  = u32_at(r, 8)?;
+    let member_off  = u32_at(r, 12)? as usize;
+    let name        = if sm5 { Some(cstr_at(c, u32_at(r, 32)? as usize)?) } else { None };
+    Some(ReflectionType {
+        class:      ShaderVariableClass::from_unchecked((class_ty & 0xFFFF) as _),
+        ty:         ShaderVariableType::from_unchecked((class_ty >> 16) as _),
+        rows:       rows_cols & 0xFFFF,
+        columns:    rows_cols >> 16,
+        elements:   elems_mems & 0xFFFF,
+        offset,
+        name:       name.filter(|n| !n.is_empty()),
+        members:    (0 .. (elems_mems >> 16) as usize).map(|i| {
+            let m = record_at(c, member_off, 12, i)?;
+            Some(ReflectionMember {
+                name:   cstr_at(c, u32_at(m, 0)? as usize)?,
+                ty:     rdef_type(c, u32_at(m, 4)? as usize, u32_at(m, 8)?, sm5, depth + 1, budget)?,
+            })
+        }).collect::<Option<_>>()?,
+    })
+}
+
+#[derive(Clone, Copy)] enum Sig { Basic, Stream, Sm51 }
+
+fn signature(c: &[u8], layout: Sig, output: bool) -> Option<Vec<ReflectionParameter>> {
+    let count   = u32_at(c, 0)? as usize;
+    let base    = u32_at(c, 4)? as usize;
+    let stride  = match layout { Sig::Basic => 24, Sig::Stream => 28, Sig::Sm51 => 32 };
+    (0 .. count).map(|i| {
+        let r = record_at(c, base, stride, i)?;
+        let (stream, r) = match layout { Sig::Basic => (0, r), Sig::Stream | Sig::Sm51 => (u32_at(r, 0)?, &r[4..]) };
+        let semantic_name = cstr_at(c, u32_at(r, 0)? as usize)?;
+        let masks = u32_at(r, 20)?;
+        let mut system_value_type = Name::from_unchecked(u32_at(r, 8)? as _);
+        if output && system_value_type == Name::Undefined {
+            // d3dcompiler reports these pixel shader outputs with their D3D_NAME, despite being stored as "undefined"
+            let upper = semantic_name.to_ascii_uppercase();
+            let upper = upper.trim_end_matches(|ch: char| ch.is_ascii_digit());
+            system_value_type = match upper {
+                "SV_TARGET"             => Name::Target,
+                "SV_DEPTH"              => Name::Depth,
+                "SV_COVERAGE"           => Name::Coverage,
+                "SV_DEPTHGREATEREQUAL"  => Name::DepthGreaterEqual,
+                "SV_DEPTHLESSEQUAL"     => Name::DepthLessEqual,
+                "SV_STENCILREF"         => Name::StencilRef,
+                _                       => Name::Undefined,
+            };
+        }
+        Some(ReflectionParameter {
+            semantic_name,
+            semantic_index:     u32_at(r, 4)?,
+            system_value_type,
+            component_type:     RegisterComponentType::from_unchecked(u32_at(r, 12)? as _),
+            register:           u32_at(r, 16)?,
+            mask:               masks as u8,
+            read_write_mask:    (masks >> 8) as u8,
+            stream,
+            min_precision:      match layout { Sig::Sm51 => MinPrecision::from_unchecked(u32_at(r, 24)? as _), _ => MinPrecision::Default },
+        })
+    }).collect()
+}
+
+fn stat(d: &mut ReflectionDesc, c: &[u8]) -> Option<()> {
+    let dwords = c.len() / 4;
+    if dwords < 28 { return None }
+    let dw = |i: usize| u32_at(c, 4 * i).unwrap_or(0);
+    d.instruction_count                 = dw( 0);
+    d.temp_register_count               = dw( 1);
+    d.def_count                         = dw( 2);
+    d.dcl_count                         = dw( 3);
+    d.float_instruction_count           = dw( 4);
+    d.int_instruction_count             = dw( 5);
+    d.uint_instruction_count            = dw( 6);
+    d.static_flow_control_count         = dw( 7);
+    d.dynamic_flow_control_count        = dw( 8);
+    d.macro_instruction_count           = dw( 9);
+    d.temp_array_count                  = dw(10);
+    d.array_instruction_count           = dw(11);
+    d.cut_instruction_count             = dw(12);
+    d.emit_instruction_count            = dw(13);
+    d.texture_normal_instructions       = dw(14);
+    d.texture_load_instructions         = dw(15);
+    d.texture_comp_instructions         = dw(16);
+    d.texture_bias_instructions         = dw(17);
+    d.texture_gradient_instructions     = dw(18);
+    // 19 ..= 22: mov/movc/conversion/bitwise counts - recounted from the instruction stream by `code` instead
+    d.input_primitive                   = Primitive::from_unchecked(dw(23) as _);
+    d.gs_output_topology                = PrimitiveTopology::from_unchecked(dw(24) as _);
+    d.gs_max_output_vertex_count        = dw(25);
+    // 26 ..= 28: unknown (28 = end of SM4.0 stats, 29 = end of SM4.1 stats)
+    if dwords >= 37 {
+        // 29: unknown
+        d.control_points                = dw(30);
+        d.hs_output_primitive           = TessellatorOutputPrimitive::from_unchecked(dw(31) as _);
+        d.hs_partitioning               = TessellatorPartitioning::from_unchecked(dw(32) as _);
+        d.tessellator_domain            = TessellatorDomain::from_unchecked(dw(33) as _);
+        // 34 ..= 36: unknown
+    }
+    if dwords >= 40 {
+        d.barrier_instructions          = dw(37);
+        d.interlocked_instructions      = dw(38);
+        d.texture_store_instructions    = dw(39);
+    }
+    Some(())
+}
+
+/// Parse the SHDR/SHEX chunk's declarations, returning the version token.
+fn code(data: &mut ReflectionData, c: &[u8]) -> Option<u32> {
+    let version = u32_at(c, 0)?;
+    let dwords  = (u32_at(c, 4)? as usize).min(c.len() / 4);
+    let mut i = 2;
+    while i < dwords {
+        let token   = dword_at(c, i, 0)?;
+        let opcode  = token & 0x7FF;
+        let len     = match opcode {
+            OPCODE_CUSTOMDATA   => dword_at(c, i, 1)? as usize,
+            _                   => ((token >> 24) & 0x7F) as usize,
+        };
+        if len == 0 { break } // malformed (or an extended length we don't care about)
+        match opcode {
+            OPCODE_DCL_THREAD_GROUP         => data.thread_group_size = [dword_at(c, i, 1)?, dword_at(c, i, 2)?, dword_at(c, i, 3)?],
+            OPCODE_DCL_GS_INSTANCE_COUNT    => data.desc.gs_instance_count = dword_at(c, i, 1)?,
+            OPCODE_MOV                      => data.mov_instruction_count += 1,
+            OPCODE_MOVC                     => data.movc_instruction_count += 1,
+            OPCODE_FTOI | OPCODE_FTOU | OPCODE_ITOF | OPCODE_UTOF | OPCODE_F16TOF32 | OPCODE_F32TOF16 | OPCODE_FTOD | OPCODE_DTOF
+                                            => data.conversion_instruction_count += 1,
+            _                               => {},
+        }
+        i = i.saturating_add(len);
+    }
+    Some(version)
+}
+
+/// Scan a pixel shader's `dcl_input_ps` instructions for the interpolation mode of each input register (`[(register, mode)]`.)
+///
+/// Interpolation modes aren't part of the `ISGN` signature, and aren't exposed by [ShaderReflection] either.
+pub(crate) fn ps_input_interpolation(b: &[u8]) -> Option<Vec<(u32, Interpolation)>> {
+    if b.get(0..4)? != b"DXBC" { return None }
+    for i in 0 .. u32_at(b, 28)? as usize {
+        let (fourcc, c) = chunk_at(b, i)?;
+        if !matches!(fourcc, b"SHDR" | b"SHEX") { continue }
+        let dwords  = (u32_at(c, 4)? as usize).min(c.len() / 4);
+        let mut modes = Vec::new();
+        let mut i = 2;
+        while i < dwords {
+            let token   = dword_at(c, i, 0)?;
+            let opcode  = token & 0x7FF;
+            let len     = match opcode {
+                OPCODE_CUSTOMDATA   => dword_at(c, i, 1)? as usize,
+                _                   => ((token >> 24) & 0x7F) as usize,
+            };
+            if len == 0 { break }
+            if matches!(opcode, OPCODE_DCL_INPUT_PS | OPCODE_DCL_INPUT_PS_SGV | OPCODE_DCL_INPUT_PS_SIV) {
+                let operand = dword_at(c, i, 1)?;
+                let extended = (operand >> 31) as usize;
+                let register = dword_at(c, i, 2 + extended)?;
+                modes.push((register, Interpolation::from_unchecked(((token >> 11) & 0xF) as _)));
+            }
+            i = i.saturating_add(len);
+        }
+        return Some(modes);
+    }
+    None
+}
+
+// D3D10_SB_OPCODE_TYPE / D3D11_SB_OPCODE_TYPE (d3d11TokenizedProgramFormat.hpp)
+const OPCODE_CUSTOMDATA             : u32 = 53;
+const OPCODE_FTOI                   : u32 = 27;
+const OPCODE_ITOF                   : u32 = 43;
+const OPCODE_MOV                    : u32 = 54;
+const OPCODE_MOVC                   : u32 = 55;
+const OPCODE_FTOU                   : u32 = 28;
+const OPCODE_UTOF                   : u32 = 86;
+const OPCODE_DCL_INPUT_PS           : u32 = 98;
+const OPCODE_DCL_INPUT_PS_SGV       : u32 = 99;
+const OPCODE_DCL_INPUT_PS_SIV       : u32 = 100;
+const OPCODE_DCL_THREAD_GROUP       : u32 = 155;
+const OPCODE_DCL_GS_INSTANCE_COUNT  : u32 = 206;
+const OPCODE_F32TOF16               : u32 = 130;
+const OPCODE_F16TOF32               : u32 = 131;
+const OPCODE_DTOF                   : u32 = 201;
+const OPCODE_FTOD                   : u32 = 202;
+
+/// The `i`th chunk of a DXBC container:  `(fourcc, data)`
+fn chunk_at(b: &[u8], i: usize) -> Option<(&[u8], &[u8])> {
+    let off     = u32_at(b, i.checked_mul(4)?.checked_add(32)?)? as usize;
+    let header  = b.get(off .. off.checked_add(8)?)?;
+    let size    = u32_at(header, 4)? as usize;
+    let start   = off + 8;
+    Some((&header[..4], b.get(start .. start.checked_add(size)?)?))
+}
+
+/// The `i`th `stride` byte record of an array starting at `base`
+fn record_at(b: &[u8], base: usize, stride: usize, i: usize) -> Option<&[u8]> {
+    let o = stride.checked_mul(i)?.checked_add(base)?;
+    b.get(o .. o.checked_add(stride)?)
+}
+
+/// The `i + delta`th dword of `b`
+fn dword_at(b: &[u8], i: usize, delta: usize) -> Option<u32> { u32_at(b, i.checked_add(delta)?.checked_mul(4)?) }
+
+fn u32_at(b: &[u8], o: usize) -> Option<u32> { Some(u32::from_le_bytes(b.get(o .. o.checked_add(4)?)?.try_into().ok()?)) }
+
+fn cstr_at(b: &[u8], o: usize) -> Option<String> {
+    let b = b.get(o..)?;
+    let len = b.iter().position(|&ch| ch == 0)?;
+    Some(String::from_utf8_lossy(&b[..len]).into_owned())
+}
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    #[test] fn rdef_sm4() {
+        // header (28) + cbuffer (24 @ 28) + variable (24 @ 52) + type (16 @ 76) + binding (32 @ 92) = 124, then strings
+        let rdef = with_strings(dwords(&[
+            1, 28, 1, 92, 0xFFFE_0400, 0x105, 124,              // header: cbuffers, resources, target, flags, creator
+            132, 1, 52, 16, 0, 0,                               // cbuffer: name, variables, offset, size, flags, type
+            147, 0, 16, 2, 76, 0,                               // variable: name, start_offset, size, flags, type, default
+            1 | (3 << 16), 1 | (4 << 16), 0, 0,                 // type: vector float, 1x4, 0 elements, 0 members
+            132, 0, 0, 0, 0, 1, 1, 0,                           // binding: name, cbuffer, return type, dimension, samples, point, count, flags
+        ]), &["Creator", "ExampleCBuffer", "tint"]);
+
+        let data = parse(&dxbc(&[(b"RDEF", rdef)])).unwrap();
+        assert_eq!(format!("{:?}", data.desc.version), "vs_4_0");
+        assert_eq!(data.desc.creator, "Creator");
+        assert_eq!(data.desc.constant_buffers, 1);
+        assert_eq!(data.desc.bound_resources, 1);
+
+        let cb = data.constant_buffer("ExampleCBuffer").unwrap();
+        assert_eq!(cb.size, 16);
+        assert_eq!(cb.ty, CBufferType::CBuffer);
+        let tint = cb.variable("tint").unwrap();
+        assert_eq!((tint.start_offset, tint.size, tint.flags), (0, 16, ShaderVariableFlags::Used));
+        assert_eq!((tint.ty.class, tint.ty.ty, tint.ty.rows, tint.ty.columns), (ShaderVariableClass::Vector, ShaderVariableType::Float, 1, 4));
+        assert_eq!(tint.ty.name, None);
+        assert_eq!(tint.default_value, None);
+
+        let binding = data.binding("ExampleCBuffer").unwrap();
+        assert_eq!((binding.ty, binding.bind_point, binding.bind_count), (ShaderInputType::CBuffer, 1, 1));
+
+        let mut truncated = dwords(&[1, 28, 1, 92, 0xFFFE_0400, 0, 0]);
+        truncated.push(0);
+        assert!(parse(&dxbc(&[(b"RDEF", truncated)])).is_none(), "out of bounds cbuffer");
+    }
+
+    #[test] fn signatures_and_code() {
+        let osgn = with_strings(dwords(&[
+            2, 8,
+            56, 0, 1, 3, 0, 0x0F,                               // SV_POSITION: name, index, sysval, component type, register, masks
+            68, 0, 0, 3, 1, 0x0F | (0x0F << 8),                 // SV_TARGET0 (stored as undefined)
+        ]), &["SV_POSITION", "SV_TARGET"]);
+        let stat = dwords(&[7, 2, 0, 3, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let shex = dwords(&[
+            0x0005_0050, 8,                                     // cs_5_0, 8 dwords
+            OPCODE_DCL_THREAD_GROUP | (4 << 24), 8, 4, 1,
+            OPCODE_MOV | (1 << 24),                             // (malformed, but only the opcode/length are inspected)
+            OPCODE_CUSTOMDATA, 1,                               // customdata: length in the next dword
+        ]);
+
+        let data = parse(&dxbc(&[(b"OSGN", osgn), (b"STAT", stat), (b"SFI0", dwords(&[1, 0])), (b"SHEX", shex)])).unwrap();
+        assert_eq!(format!("{:?}", data.desc.version), "cs_5_0");
+        assert_eq!(data.thread_group_size, [8, 4, 1]);
+        assert_eq!(data.mov_instruction_count, 1);
+        assert_eq!(data.requires_flags, ShaderRequires::Doubles);
+        assert_eq!((data.desc.instruction_count, data.desc.temp_register_count, data.desc.dcl_count, data.desc.float_instruction_count), (7, 2, 3, 4));
+
+        assert_eq!(data.outputs.len(), 2);
+        assert_eq!(data.outputs[0].semantic_name, "SV_POSITION");
+        assert_eq!(data.outputs[0].system_value_type, Name::Position);
+        assert_eq!(data.outputs[1].system_value_type, Name::Target);
+        assert_eq!((data.outputs[1].register, data.outputs[1].mask, data.outputs[1].read_write_mask), (1, 0x0F, 0x0F));
+        assert_eq!(data.outputs[1].component_type, RegisterComponentType::Float32);
+
+        assert!(parse(b"not bytecode").is_none());
+        assert!(parse(&dxbc(&[(b"STAT", dwords(&[0; 4]))])).is_none(), "truncated STAT");
+    }
+
+    #[test] fn interpolation() {
+        let shdr = dwords(&[
+            0x0000_0040, 13,                                                // ps_4_0, 13 dwords
+            OPCODE_DCL_INPUT_PS     | (2 << 11) | (3 << 24), 0x0010_1012, 1,    // dcl_input_ps linear v1
+            OPCODE_DCL_INPUT_PS_SIV | (4 << 11) | (4 << 24), 0x0010_1012, 0, 1, // dcl_input_ps_siv linear noperspective v0, position
+            OPCODE_DCL_INPUT_PS     | (1 << 11) | (4 << 24), 0x8010_1012, 0, 2, // dcl_input_ps constant v2 (extended operand)
+        ]);
+        assert_eq!(ps_input_interpolation(&dxbc(&[(b"STAT", dwords(&[0; 29])), (b"SHDR", shdr)])).unwrap(), [
+            (1, Interpolation::Linear),
+            (0, Interpolation::LinearNoPerspective),
+            (2, Interpolation::Constant),
+        ]);
+
+        let truncated = dwords(&[0x0000_0040, 4, OPCODE_DCL_INPUT_PS | (2 << 11) | (3 << 24), 0x0010_1012]);
+        assert!(ps_input_interpolation(&dxbc(&[(b"SHDR", truncated)])).is_none(), "truncated dcl_input_ps");
+        assert!(ps_input_interpolation(&dxbc(&[(b"STAT", dwords(&[0; 29]))])).is_none(), "no SHDR");
+
+        let mut bad_offset = dxbc(&[(b"SHDR", dwords(&[0x0000_0040, 2]))]);
+        bad_offset[32..36].copy_from_slice(&(!0u32 - 4).to_le_bytes());
+        assert!(ps_input_interpolation(&bad_offset).is_none(), "chunk offset");
+    }
+
+    #[test] fn out_of_bounds_offsets() {
+        let mut chunk = dxbc(&[(b"STAT", dwords(&[0; 29]))]);
+        chunk[32..36].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse(&chunk).is_none(), "chunk offset");
+
+        let rdef = |cbs: u32, cb_off: u32, res: u32, res_off: u32| with_strings(dwords(&[cbs, cb_off, res, res_off, 0xFFFE_0400, 0, 28]), &["Creator"]);
+        assert!(parse(&dxbc(&[(b"RDEF", rdef(0, 0, 1, !0 - 8))])).is_none(), "binding offset");
+        assert!(parse(&dxbc(&[(b"RDEF", rdef(0, 0, !0, 28))])).is_none(), "binding count");
+        assert!(parse(&dxbc(&[(b"RDEF", rdef(1, !0 - 8, 0, 0))])).is_none(), "cbuffer offset");
+
+        let vars = with_strings(dwords(&[1, 28, 0, 0, 0xFFFE_0400, 0, 52, 52, !0, !0 - 8, 16, 0, 0]), &["Creator"]);
+        assert!(parse(&dxbc(&[(b"RDEF", vars)])).is_none(), "variable offset");
+
+        let members = with_strings(dwords(&[
+            1, 28, 0, 0, 0xFFFE_0400, 0, 92,                    // header
+            92, 1, 52, 16, 0, 0,                                // cbuffer
+            92, 0, 16, 2, 76, 0,                                // variable
+            5, 0, 1 << 16, !0 - 4,                              // type: struct, 1 member
+        ]), &["Creator"]);
+        assert!(parse(&dxbc(&[(b"RDEF", members)])).is_none(), "member offset");
+
+        // `levels` struct types @ 76 + 40*i, each with two members sharing the next type: 2^levels leaves if fully expanded
+        let dag = |levels: u32| {
+            let mut d = vec![1, 28, 0, 0, 0xFFFE_0400, 0, 92 + 40 * levels, 0, 1, 52, 16, 0, 0, 0, 0, 16, 2, 76, 0];
+            for i in 0 .. levels {
+                let next = 76 + 40 * (i + 1);
+                d.extend_from_slice(&[5, 0, 2 << 16, 92 + 40 * i, 0, next, 0, 0, next, 0]);
+            }
+            d.extend_from_slice(&[1 | (3 << 16), 1 | (4 << 16), 0, 0]); // leaf: float4
+            dxbc(&[(b"RDEF", with_strings(dwords(&d), &["Creator"]))])
+        };
+        let small = parse(&dag(4)).unwrap();
+        assert_eq!(small.constant_buffers[0].variables[0].ty.members[1].ty.members.len(), 2);
+        assert!(parse(&dag(60)).is_none(), "shared member types");
+
+        assert!(parse(&dxbc(&[(b"ISGN", dwords(&[1, !0 - 8]))])).is_none(), "signature offset");
+        assert!(parse(&dxbc(&[(b"OSG5", dwords(&[!0, 8]))])).is_none(), "signature count");
+    }
+}
@@ -0,0 +1,402 @@
+use crate::*;
+use crate::d3d::*;
+use crate::d3d11::*;
+
+use std::fmt::Write;
+
+
+
+/// Generate Rust source code from [ReflectionData] - e.g. from a `build.rs` script.
+///
+/// Generates:
+/// *   A `#[repr(C)]` struct for every constant buffer, with explicit padding fields and a size assertion.
+/// *   A `pub const {REGISTER}_{NAME} : u32` for every resource binding slot (e.g. `B_EXAMPLE_CBUFFER`, `T_DIFFUSE`, `S_LINEAR`, `U_OUTPUT`.)
+/// *   A `#[repr(C)]` vertex struct matching a vertex shader's input signature.
+///
+/// Since [ReflectionData::parse] doesn't require `d3dcompiler_*.dll`, this works from non-Windows build hosts too.
+///
+/// ### Layout
+/// Members are placed at the exact byte offsets reflection reports.  Since HLSL pads every element of an array (and every register of a matrix)
+/// to 16 bytes, arrays and matrices are generated as arrays of full 16 byte registers (e.g. `float weights[3]` becomes `[[f32; 4]; 3]`, of which
+/// only the first component of each register is used.)  If a subsequent member was packed into the final register's unused components,
+/// the final register is split out into a separate `{name}_last` field instead.
+///
+/// Nested structs are generated as separate `#[repr(C)]` structs, padded to a multiple of 16 bytes.
+/// Members that can't be represented this way (e.g. a struct array with a member packed into its final element's padding) are generated as `[u8; N]`.
+///
+/// ### Examples
+/// ```rust,no_run
+/// # use thindx::*;
+/// // build.rs
+/// let bytes = std::fs::read("shaders/basic.vs.cso").unwrap();
+/// let bytecode = unsafe { d3d::Bytecode::from(&bytes) }.unwrap();
+/// let data = d3d11::ReflectionData::parse(bytecode).unwrap();
+///
+/// let rust = d3d11::RustCodegen::new().vertex_struct("BasicVertex").generate(&data);
+/// let out_dir = std::path::PathBuf::from(std::env::var_os("OUT_DIR").unwrap());
+/// std::fs::write(out_dir.join("basic_vs.rs"), rust).unwrap();
+///
+/// // src/shaders.rs
+/// // pub mod basic_vs { include!(concat!(env!("OUT_DIR"), "/basic_vs.rs")); }
+/// ```
+///
+/// ### Example Output
+/// ```rust
+/// # mod basic_vs {
+/// // Generated by thindx::d3d11::RustCodegen from vs_4_0 reflection data.  Do not edit.
+///
+/// /// `cbuffer ExampleCBuffer` (16 bytes)
+/// #[derive(Clone, Copy, Debug)]
+/// #[repr(C)] pub struct ExampleCBuffer {
+///     /// `float4 tint` (offset 0)
+///     pub tint: [f32; 4],
+/// }
+/// const _ : () = assert!(::core::mem::size_of::<ExampleCBuffer>() == 16);
+///
+/// /// `cbuffer ExampleCBuffer : register(b0);`
+/// pub const B_EXAMPLE_CBUFFER : u32 = 0;
+///
+/// /// Vertex shader input signature
+/// #[derive(Clone, Copy, Debug)]
+/// #[repr(C)] pub struct BasicVertex {
+///     /// `float4 : POSITION0` (v0)
+///     pub position: [f32; 4],
+///     /// `float4 : COLOR0` (v1)
+///     pub color: [f32; 4],
+/// }
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct RustCodegen {
+    derives:        String,
+    vertex_struct:  Option<String>,
+}
+
+impl Default for RustCodegen {
+    fn default() -> Self {
+        Self {
+            derives:        "Clone, Copy, Debug".into(),
+            vertex_struct:  Some("Vertex".into()),
+        }
+    }
+}
+
+impl RustCodegen {
+    /// Generate `#[derive(Clone, Copy, Debug)]` structs, and a vertex struct named `Vertex`.
+    pub fn new() -> Self { Self::default() }
+
+    /// Replace the comma separated list of traits to `#[derive(...)]` for every generated struct (e.g. `"Clone, Copy, Pod, Zeroable"`.)
+    pub fn derives(mut self, derives: impl Into<String>) -> Self { self.derives = derives.into(); self }
+
+    /// Name the vertex struct generated for vertex shaders (defaults to `Vertex`.)
+    pub fn vertex_struct(mut self, name: impl Into<String>) -> Self { self.vertex_struct = Some(name.into()); self }
+
+    /// Don't generate a vertex struct.
+    pub fn no_vertex_struct(mut self) -> Self { self.vertex_struct = None; self }
+
+    /// Generate Rust source code for `data`.
+    pub fn generate(&self, data: &ReflectionData) -> String {
+        let mut gen = Generator { options: self, out: String::new(), structs: Vec::new() };
+        let _ = writeln!(gen.out, "// Generated by thindx::d3d11::RustCodegen from {:?} reflection data.  Do not edit.", data.desc.version);
+
+        for cb in data.constant_buffers.iter().filter(|cb| cb.ty == CBufferType::CBuffer) {
+            let members = cb.variables.iter().map(|v| Member { hlsl: &v.name, offset: v.start_offset, ty: &v.ty }).collect::<Vec<_>>();
+            gen.emit_struct(&camel_case(&cb.name), &format!("`cbuffer {}` ({} bytes)", cb.name, cb.size), &members, cb.size);
+        }
+
+        if !data.bindings.is_empty() { gen.out.push('\n'); }
+        for b in data.bindings.iter() {
+            let register = b.hlsl_register();
+            let _ = writeln!(gen.out, "/// `{}`", data.binding_to_hlsl(b));
+            let _ = writeln!(gen.out, "pub const {}_{} : u32 = {};", register[..1].to_ascii_uppercase(), screaming_snake_case(&b.name), b.bind_point);
+        }
+
+        if let Some(vertex) = self.vertex_struct.as_deref().filter(|_| data.desc.version.ty() == ShaderVersionType::VertexShader) {
+            gen.emit_vertex(vertex, &data.inputs);
+        }
+
+        gen.out
+    }
+}
+
+
+
+struct Generator<'o> {
+    options:    &'o RustCodegen,
+    out:        String,
+    structs:    Vec<String>, // names of nested structs already generated
+}
+
+#[derive(Clone, Copy)] struct Member<'a> { hlsl: &'a str, offset: u32, ty: &'a ReflectionType }
+
+impl Generator<'_> {
+    fn emit_struct(&mut self, name: &str, doc: &str, members: &[Member], size: u32) {
+        let mut body    = String::new();
+        let mut cursor  = 0;
+        let mut pads    = 0;
+        for (i, m) in members.iter().enumerate() {
+            let next = members.get(i + 1).map_or(size, |n| n.offset);
+            if m.offset < cursor { continue } // overlapping / unrepresentable - shouldn't happen with compiler-generated reflection
+            if m.offset > cursor { let _ = writeln!(body, "    _pad{}: [u8; {}],", pads, m.offset - cursor); pads += 1; }
+            let field = snake_case(m.hlsl);
+            let _ = writeln!(body, "    /// `{} {}` (offset {})", m.ty.to_hlsl_type(), hlsl_suffixed(m.hlsl, m.ty), m.offset);
+            for (suffix, rust, bytes) in self.rust_type(name, m.hlsl, m.ty, next.saturating_sub(m.offset)) {
+                let _ = writeln!(body, "    pub {}{}: {},", field, suffix, rust);
+                cursor = m.offset + bytes;
+            }
+        }
+        if size > cursor { let _ = writeln!(body, "    _pad{}: [u8; {}],", pads, size - cursor); }
+
+        let _ = writeln!(self.out);
+        let _ = writeln!(self.out, "/// {}", doc);
+        if !self.options.derives.is_empty() { let _ = writeln!(self.out, "#[derive({})]", self.options.derives); }
+        let _ = writeln!(self.out, "#[repr(C)] pub struct {} {{", name);
+        self.out.push_str(&body);
+        let _ = writeln!(self.out, "}}");
+        let _ = writeln!(self.out, "const _ : () = assert!(::core::mem::size_of::<{}>() == {});", name, size);
+    }
+
+    /// Returns `[(field suffix, rust type, bytes)]`
+    fn rust_type(&mut self, parent: &str, hlsl: &str, ty: &ReflectionType, available: u32) -> Vec<(&'static str, String, u32)> {
+        let elements = ty.elements.max(1);
+        let size = type_size(ty);
+
+        if ty.class == SVC::Struct {
+            let padded = round16(struct_size(ty));
+            let name = ty.name.as_deref().map_or_else(|| format!("{}{}", parent, camel_case(hlsl)), camel_case);
+            if !self.structs.contains(&name) {
+                self.structs.push(name.clone());
+                let members = ty.members.iter().map(|m| Member { hlsl: &m.name, offset: m.ty.offset, ty: &m.ty }).collect::<Vec<_>>();
+                let mut nested = Generator { options: self.options, out: String::new(), structs: std::mem::take(&mut self.structs) };
+                nested.emit_struct(&name, &format!("`struct {}` ({} bytes, padded to {})", ty.name.as_deref().unwrap_or(hlsl), struct_size(ty), padded), &members, padded);
+                self.structs = nested.structs;
+                self.out.push_str(&nested.out);
+            }
+            return if elements * padded <= available {
+                vec![("", if ty.elements == 0 { name } else { format!("[{}; {}]", name, elements) }, elements * padded)]
+            } else {
+                vec![("", format!("[u8; {}]", size), size)]
+            };
+        }
+
+        let (scalar, component_size) = match scalar_of(ty) {
+            Some(s) => s,
+            None    => return vec![("", format!("[u8; {}]", size), size)],
+        };
+        let (vectors, components, stride) = vector_layout(ty, component_size);
+        let vectors         = elements * vectors;
+        let partial         = if components == 1 { scalar.to_string() } else { format!("[{}; {}]", scalar, components) };
+        let padded          = format!("[{}; {}]", scalar, stride / component_size);
+
+        if vectors == 1 {
+            vec![("", partial, components * component_size)]
+        } else if vectors * stride <= available {
+            vec![("", format!("[{}; {}]", padded, vectors), vectors * stride)]
+        } else {
+            vec![
+                ("",        format!("[{}; {}]", padded, vectors - 1), (vectors - 1) * stride),
+                ("_last",   partial, (vectors - 1) * stride + components * component_size),
+            ]
+        }
+    }
+
+    fn emit_vertex(&mut self, name: &str, inputs: &[ReflectionParameter]) {
+        let inputs = inputs.iter().filter(|p| !matches!(p.system_value_type, Name::VertexId | Name::InstanceId | Name::PrimitiveId)).collect::<Vec<_>>();
+        let _ = writeln!(self.out);
+        let _ = writeln!(self.out, "/// Vertex shader input signature");
+        if !self.options.derives.is_empty() { let _ = writeln!(self.out, "#[derive({})]", self.options.derives); }
+        let _ = writeln!(self.out, "#[repr(C)] pub struct {} {{", name);
+        for p in inputs.iter() {
+            let (scalar, hlsl) = match p.component_type {
+                RegisterComponentType::UInt32   => ("u32", "uint"),
+                RegisterComponentType::SInt32   => ("i32", "int"),
+                _                               => ("f32", "float"),
+            };
+            let n = (p.mask & 0xF).count_ones().max(1);
+            let shared = inputs.iter().filter(|o| o.semantic_name.eq_ignore_ascii_case(&p.semantic_name)).count() > 1;
+            let field = if shared || p.semantic_index != 0 { format!("{}{}", snake_case(&p.semantic_name), p.semantic_index) } else { snake_case(&p.semantic_name) };
+            let _ = writeln!(self.out, "    /// `{}{} : {}{}` (v{})", hlsl, if n == 1 { String::new() } else { n.to_string() }, p.semantic_name, p.semantic_index, p.register);
+            let _ = writeln!(self.out, "    pub {}: {},", field, if n == 1 { scalar.to_string() } else { format!("[{}; {}]", scalar, n) });
+        }
+        let _ = writeln!(self.out, "}}");
+    }
+}
+
+
+
+fn round16(n: u32) -> u32 { (n + 15) / 16 * 16 }
+
+/// The unpadded size of a single element of a struct type
+fn struct_size(ty: &ReflectionType) -> u32 { ty.members.iter().map(|m| m.ty.offset + type_size(&m.ty)).max().unwrap_or(0) }
+
+/// The HLSL size of `ty`, excluding the unused components of its final register
+fn type_size(ty: &ReflectionType) -> u32 {
+    if ty.class == SVC::Struct {
+        let s = struct_size(ty);
+        if ty.elements == 0 { s } else { (ty.elements - 1) * round16(s) + s }
+    } else {
+        let component_size = scalar_of(ty).map_or(4, |(_, size)| size);
+        let (vectors, components, stride) = vector_layout(ty, component_size);
+        (ty.elements.max(1) * vectors - 1) * stride + components * component_size
+    }
+}
+
+/// `(vectors per element, components per vector, register aligned bytes per vector)` of a non-struct type.
+///
+/// Every vector (or matrix row/column) starts on a new register, and vectors of doubles (`double3`, `double4`) span two.
+fn vector_layout(ty: &ReflectionType, component_size: u32) -> (u32, u32, u32) {
+    let layout      = HlslLayout { class: ty.class, ty: ty.ty, rows: ty.rows, columns: ty.columns, elements: 0 };
+    let components  = layout.components_per_register();
+    (layout.registers_per_element(), components, round16(components * component_size))
+}
+
+/// (rust scalar type, size in bytes)
+fn scalar_of(ty: &ReflectionType) -> Option<(&'static str, u32)> {
+    match ty.ty {
+        SVT::Float | SVT::Min16Float | SVT::Min10Float                  => Some(("f32", 4)),
+        SVT::Int   | SVT::Min16Int   | SVT::Min12Int                    => Some(("i32", 4)),
+        SVT::UInt  | SVT::Min16UInt                                     => Some(("u32", 4)),
+        SVT::Bool                                                       => Some(("u32", 4)), // 32-bit BOOL
+        SVT::Double                                                     => Some(("f64", 8)),
+        _                                                               => None,
+    }
+}
+
+fn hlsl_suffixed(name: &str, ty: &ReflectionType) -> String {
+    if ty.elements == 0 { name.into() } else { format!("{}[{}]", name, ty.elements) }
+}
+
+/// Split `name` into lowercase words on `_`, non-alphanumerics, and lower→upper case transitions (`gWorldMatrix` → `g`, `world`, `matrix`)
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut prev_lower = false;
+    for ch in name.chars() {
+        if !ch.is_ascii_alphanumeric() {
+            if !word.is_empty() { words.push(std::mem::take(&mut word)); }
+            prev_lower = false;
+            continue;
+        }
+        if ch.is_ascii_uppercase() && prev_lower { words.push(std::mem::take(&mut word)); }
+        prev_lower = ch.is_ascii_lowercase() || ch.is_ascii_digit();
+        word.push(ch.to_ascii_lowercase());
+    }
+    if !word.is_empty() { words.push(word); }
+    words
+}
+
+fn identifier(mut s: String) -> String {
+    if s.is_empty() || s.starts_with(|ch: char| ch.is_ascii_digit()) { s.insert(0, '_'); }
+    match s.as_str() {
+        "self" | "Self" | "super" | "crate" => { s.push('_'); s },
+        "as" | "break" | "const" | "continue" | "else" | "enum" | "extern" | "false" | "fn" | "for" | "if" | "impl" | "in" | "let" | "loop" | "match" |
+        "mod" | "move" | "mut" | "pub" | "ref" | "return" | "static" | "struct" | "trait" | "true" | "type" | "unsafe" | "use" | "where" | "while" |
+        "async" | "await" | "dyn" | "abstract" | "become" | "box" | "do" | "final" | "macro" | "override" | "priv" | "typeof" | "unsized" | "virtual" |
+        "yield" | "try" => format!("r#{}", s),
+        _ => s,
+    }
+}
+
+fn snake_case(name: &str) -> String { identifier(words(name).join("_")) }
+
+fn screaming_snake_case(name: &str) -> String { identifier(words(name).join("_").to_ascii_uppercase()) }
+
+fn camel_case(name: &str) -> String {
+    // preserve existing capitalization (`ExampleCBuffer` shouldn't become `ExampleCbuffer`)
+    let mut s = String::new();
+    for part in name.split(|ch: char| !ch.is_ascii_alphanumeric()).filter(|p| !p.is_empty()) {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() { s.push(first.to_ascii_uppercase()); s.extend(chars); }
+    }
+    identifier(s)
+}
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    fn ty(class: ShaderVariableClass, rows: u32, columns: u32, elements: u32) -> ReflectionType {
+        ReflectionType { class, ty: SVT::Float, rows, columns, elements, ..Default::default() }
+    }
+
+    fn var(name: &str, start_offset: u32, ty: ReflectionType) -> ReflectionVariable {
+        ReflectionVariable { name: name.into(), start_offset, size: type_size(&ty), ty, ..Default::default() }
+    }
+
+    #[test] fn names() {
+        assert_eq!(snake_case("gWorldMatrix"),              "g_world_matrix");
+        assert_eq!(snake_case("type"),                      "r#type");
+        assert_eq!(snake_case("TEXCOORD"),                  "texcoord");
+        assert_eq!(screaming_snake_case("ExampleCBuffer"),  "EXAMPLE_CBUFFER");
+        assert_eq!(camel_case("ExampleCBuffer"),            "ExampleCBuffer");
+        assert_eq!(camel_case("per_frame"),                 "PerFrame");
+        assert_eq!(camel_case("$Globals"),                  "Globals");
+    }
+
+    #[test] fn padding() {
+        let data = ReflectionData {
+            desc: ReflectionDesc { version: d3d11::ShaderVersion::new(ShaderVersionType::PixelShader, 4, 0), ..Default::default() },
+            constant_buffers: vec![ReflectionConstantBuffer {
+                name: "PerFrame".into(), size: 80,
+                variables: vec![
+                    var("scale",    0,  ty(SVC::Vector, 1, 2, 0)),
+                    var("dir",      16, ty(SVC::Vector, 1, 3, 0)),
+                    var("weights",  32, ty(SVC::Scalar, 1, 1, 3)),
+                    var("time",     68, ty(SVC::Scalar, 1, 1, 0)),
+                ],
+                ..Default::default()
+            }],
+            bindings: vec![ReflectionBinding { name: "PerFrame".into(), ty: SIT::CBuffer, bind_point: 2, bind_count: 1, ..Default::default() }],
+            ..Default::default()
+        };
+        let rust = RustCodegen::new().generate(&data);
+        for expected in [
+            "#[repr(C)] pub struct PerFrame {",
+            "    pub scale: [f32; 2],\n    _pad0: [u8; 8],\n",
+            "    pub dir: [f32; 3],\n    _pad1: [u8; 4],\n",
+            "    /// `float weights[3]` (offset 32)\n    pub weights: [[f32; 4]; 2],\n    pub weights_last: f32,\n",
+            "    pub time: f32,\n    _pad2: [u8; 8],\n}",
+            "const _ : () = assert!(::core::mem::size_of::<PerFrame>() == 80);",
+            "pub const B_PER_FRAME : u32 = 2;",
+        ] {
+            assert!(rust.contains(expected), "expected:\n{}\n\nin:\n{}", expected, rust);
+        }
+        assert!(!rust.contains("struct Vertex"), "pixel shaders shouldn't generate vertex structs");
+    }
+
+    #[test] fn doubles() {
+        let double = |class, columns, elements| ReflectionType { class, ty: SVT::Double, rows: 1, columns, elements, ..Default::default() };
+        assert_eq!(type_size(&double(SVC::Scalar, 1, 0)),  8);
+        assert_eq!(type_size(&double(SVC::Vector, 2, 0)), 16);
+        assert_eq!(type_size(&double(SVC::Vector, 3, 0)), 24);
+        assert_eq!(type_size(&double(SVC::Vector, 4, 0)), 32);
+        assert_eq!(type_size(&double(SVC::Vector, 3, 2)), 56);
+        assert_eq!(type_size(&double(SVC::Vector, 2, 2)), 32);
+
+        let data = ReflectionData {
+            desc: ReflectionDesc { version: d3d11::ShaderVersion::new(ShaderVersionType::PixelShader, 5, 0), ..Default::default() },
+            constant_buffers: vec![ReflectionConstantBuffer {
+                name: "Doubles".into(), size: 128,
+                variables: vec![
+                    var("pos",      0,  double(SVC::Vector, 3, 0)),
+                    var("dirs",     32, double(SVC::Vector, 3, 2)),
+                    var("time",     88, ty(SVC::Scalar, 1, 1, 0)),
+                    var("dirs2",    96, double(SVC::Vector, 4, 0)),
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let rust = RustCodegen::new().generate(&data);
+        for expected in [
+            "    pub pos: [f64; 3],\n    _pad0: [u8; 8],\n",
+            "    /// `double3 dirs[2]` (offset 32)\n    pub dirs: [[f64; 4]; 1],\n    pub dirs_last: [f64; 3],\n",
+            "    pub time: f32,\n    _pad1: [u8; 4],\n",
+            "    pub dirs2: [f64; 4],\n}",
+            "const _ : () = assert!(::core::mem::size_of::<Doubles>() == 128);",
+        ] {
+            assert!(rust.contains(expected), "expected:\n{}\n\nin:\n{}", expected, rust);
+        }
+    }
+}
@@ -161,8 +161,18 @@ pub use crate::d3d9caps_h::*;
 pub use crate::d3d9types_h::*;
 
 mods! {
+    inl mod block_compression;
+    inl mod dds;
+    inl mod effect;
+    inl mod image_compare;
+    inl mod image_export;
+    inl mod image_import;
     inl mod index;
+    inl mod mip_generation;
+    inl mod pixel_convert;
     inl mod shared_handle;
     inl mod texture_format;
+    inl mod texture_image;
     inl mod texture_mip_ref;
+    inl mod vertex_input;
 }
@@ -0,0 +1,536 @@
+use crate::*;
+use crate::d3d::{Format, FormatInfo};
+use crate::d3d9::TextureMipRef;
+use crate::d3d9::pixel_convert::rows_fit;
+
+
+
+/// How much effort [compress_blocks] spends searching for block endpoints.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BlockCompressionQuality {
+    /// Bounding box endpoints.  Suitable for runtime fallbacks and previews.
+    Fast,
+
+    /// Principal axis endpoints with a least squares refinement pass, and 6-value alpha modes where they fit better.
+    #[default] Normal,
+
+    /// [Normal](Self::Normal) with iterated refinement, DXT1's 3-color mode for opaque blocks, and a local alpha endpoint search.
+    /// Suitable for offline content pipelines.
+    Best,
+}
+
+/// Decompress `width` x `height` pixels of block compressed `format` data into RGBA [u8]s (row major, tightly packed.)
+///
+/// | Format                                        | RGBA
+/// | ----------------------------------------------| ----
+/// | [DXT1](Format::DXT1)                          | `(r, g, b, 255)`, or `(0, 0, 0, 0)` for 3-color mode transparent pixels
+/// | [DXT2](Format::DXT2) ..= [DXT5](Format::DXT5) | `(r, g, b, a)` - premultiplied formats are *not* unpremultiplied, matching hardware sampling
+/// | [ATI1](Format::ATI1)                          | `(r, 0, 0, 255)`
+/// | [ATI2](Format::ATI2)                          | `(r, g, 0, 255)`
+///
+/// `src.stride` is the number of bytes between rows of 4x4 *blocks*, not pixels.  `width` and `height` need not be multiples of 4.
+///
+/// ### Errors
+/// *   [D3DERR::WRONGTEXTUREFORMAT]    - `format` isn't one of the formats above
+/// *   [D3DERR::INVALIDCALL]           - `src.data` is too small for `(height+3)/4` rows of `src.stride` bytes
+/// *   [D3DERR::INVALIDCALL]           - `src.stride` is smaller than a row of `(width+3)/4` blocks
+/// *   [THINERR::ALLOC_OVERFLOW]       - a row of blocks, or the decoded image, would be larger than `usize::MAX` bytes
+///
+/// ### Example
+/// ```rust
+/// # use thindx::{d3d::*, d3d9::*};
+/// // A DXT1 block:  color0 = red, color1 = blue, indices alternate 0, 1, 2, 3 along each row
+/// let dxt1 = [0x00, 0xF8, 0x1F, 0x00, 0xE4, 0xE4, 0xE4, 0xE4];
+/// let rgba = decompress_blocks(Format::DXT1, TextureMipRef { data: &dxt1, stride: 8 }, 4, 4).unwrap();
+/// assert_eq!(rgba[..4], [[255, 0, 0, 255], [0, 0, 255, 255], [170, 0, 85, 255], [85, 0, 170, 255]]);
+/// ```
+pub fn decompress_blocks(format: impl Into<Format>, src: TextureMipRef, width: u32, height: u32) -> Result<Vec<[u8; 4]>, Error> {
+    fn_context!(d3d9::decompress_blocks);
+    let format = format.into();
+    let bc = Bc::new(format).ok_or_else(|| fn_param_error!(format, D3DERR::WRONGTEXTUREFORMAT))?;
+    let row_bytes = bc.info().checked_row_bytes(width).ok_or_else(|| fn_param_error!(width, THINERR::ALLOC_OVERFLOW))?;
+    let block_rows = (height as usize + 3) / 4;
+    if !rows_fit(src, row_bytes, block_rows) { return Err(fn_param_error!(src, D3DERR::INVALIDCALL)) }
+
+    let (width, height) = (width as usize, height as usize);
+    let len = width.checked_mul(height).ok_or_else(|| fn_param_error!(height, THINERR::ALLOC_OVERFLOW))?;
+    let mut pixels = vec![[0u8; 4]; len];
+    for by in 0 .. block_rows {
+        let row = &src.data[by * src.stride ..][..row_bytes];
+        for (bx, block) in row.chunks_exact(bc.block_bytes()).enumerate() {
+            let decoded = bc.decode(block);
+            for y in (by * 4 .. height).take(4) {
+                for x in (bx * 4 .. width).take(4) {
+                    pixels[y * width + x] = decoded[(y % 4) * 4 + x % 4];
+                }
+            }
+        }
+    }
+    Ok(pixels)
+}
+
+/// Compress `width` x `height` RGBA [u8] `pixels` (row major, tightly packed) as block compressed `format`.
+///
+/// The result is tightly packed:  each row of 4x4 blocks is <code>[FormatInfo::row_bytes]\(width\)</code> bytes, ready for use as a
+/// [TextureMipRef] with that `stride`.  Partial edge blocks are padded by repeating the last row/column.
+///
+/// *   [DXT1](Format::DXT1) encodes pixels with `a < 128` as transparent (3-color mode), and ignores alpha otherwise.
+/// *   [DXT2](Format::DXT2) / [DXT4](Format::DXT4) expect already premultiplied `pixels`.
+/// *   [ATI1](Format::ATI1) encodes `r`, [ATI2](Format::ATI2) encodes `r` and `g`.
+///
+/// ### Errors
+/// *   [D3DERR::WRONGTEXTUREFORMAT]    - `format` isn't a DXT or ATI format
+/// *   [D3DERR::INVALIDCALL]           - `pixels.len() != width * height`
+///
+/// ### Example
+/// ```rust
+/// # use thindx::{d3d::*, d3d9::*};
+/// let pixels = [[255, 128, 0, 255]; 8 * 8];
+/// let dxt5 = compress_blocks(Format::DXT5, 8, 8, &pixels, BlockCompressionQuality::Best).unwrap();
+/// assert_eq!(dxt5.len(), 4 * 16);
+///
+/// let src = TextureMipRef { data: &dxt5, stride: FormatInfo::of(Format::DXT5).unwrap().row_bytes(8) };
+/// for px in decompress_blocks(Format::DXT5, src, 8, 8).unwrap() {
+///     assert!(px[0] == 255 && px[1].abs_diff(128) <= 2 && px[2] == 0 && px[3] == 255);
+/// }
+/// ```
+pub fn compress_blocks(format: impl Into<Format>, width: u32, height: u32, pixels: &[[u8; 4]], quality: BlockCompressionQuality) -> Result<Vec<u8>, Error> {
+    fn_context!(d3d9::compress_blocks);
+    let format = format.into();
+    let bc = Bc::new(format).ok_or_else(|| fn_param_error!(format, D3DERR::WRONGTEXTUREFORMAT))?;
+    if Some(pixels.len()) != (width as usize).checked_mul(height as usize) { return Err(fn_param_error!(pixels, D3DERR::INVALIDCALL)) }
+
+    let mut bytes = Vec::with_capacity(bc.info().surface_bytes(width, height));
+    let (width, height) = (width as usize, height as usize);
+    for by in 0 .. (height + 3) / 4 {
+        for bx in 0 .. (width + 3) / 4 {
+            let mut block = [[0u8; 4]; 16];
+            for (i, px) in block.iter_mut().enumerate() {
+                let x = (bx * 4 + i % 4).min(width - 1);
+                let y = (by * 4 + i / 4).min(height - 1);
+                *px = pixels[y * width + x];
+            }
+            bc.encode(&block, quality, &mut bytes);
+        }
+    }
+    Ok(bytes)
+}
+
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)] enum Bc { Bc1, Bc2, Bc3, Bc4, Bc5 }
+
+impl Bc {
+    fn new(format: Format) -> Option<Self> {
+        match format {
+            Format::DXT1                => Some(Bc::Bc1),
+            Format::DXT2 | Format::DXT3 => Some(Bc::Bc2),
+            Format::DXT4 | Format::DXT5 => Some(Bc::Bc3),
+            Format::ATI1                => Some(Bc::Bc4),
+            Format::ATI2                => Some(Bc::Bc5),
+            _                           => None,
+        }
+    }
+
+    fn info(self) -> &'static FormatInfo {
+        let format = match self { Bc::Bc1 => Format::DXT1, Bc::Bc2 => Format::DXT3, Bc::Bc3 => Format::DXT5, Bc::Bc4 => Format::ATI1, Bc::Bc5 => Format::ATI2 };
+        FormatInfo::of(format).expect("block compressed formats should have FormatInfo")
+    }
+
+    fn block_bytes(self) -> usize { if matches!(self, Bc::Bc1 | Bc::Bc4) { 8 } else { 16 } }
+
+    fn decode(self, block: &[u8]) -> [[u8; 4]; 16] {
+        let mut px = [[0, 0, 0, 255]; 16];
+        match self {
+            Bc::Bc1 => px = decode_color(&block[..8], true),
+            Bc::Bc2 => {
+                px = decode_color(&block[8..], false);
+                let bits = u64::from_le_bytes(block[..8].try_into().unwrap());
+                for (i, px) in px.iter_mut().enumerate() { px[3] = ((bits >> (4 * i)) & 0xF) as u8 * 17 }
+            },
+            Bc::Bc3 => {
+                px = decode_color(&block[8..], false);
+                for (px, a) in px.iter_mut().zip(decode_alpha(&block[..8])) { px[3] = a }
+            },
+            Bc::Bc4 => for (px, r) in px.iter_mut().zip(decode_alpha(&block[..8])) { px[0] = r },
+            Bc::Bc5 => {
+                for (px, r) in px.iter_mut().zip(decode_alpha(&block[..8])) { px[0] = r }
+                for (px, g) in px.iter_mut().zip(decode_alpha(&block[8..])) { px[1] = g }
+            },
+        }
+        px
+    }
+
+    fn encode(self, px: &[[u8; 4]; 16], quality: BlockCompressionQuality, out: &mut Vec<u8>) {
+        let channel = |c: usize| px.map(|p| p[c]);
+        match self {
+            Bc::Bc1 => out.extend_from_slice(&encode_color(px, true, quality)),
+            Bc::Bc2 => {
+                let bits = px.iter().enumerate().fold(0u64, |bits, (i, p)| bits | u64::from((u32::from(p[3]) * 15 + 127) / 255) << (4 * i));
+                out.extend_from_slice(&bits.to_le_bytes());
+                out.extend_from_slice(&encode_color(px, false, quality));
+            },
+            Bc::Bc3 => {
+                out.extend_from_slice(&encode_alpha(&channel(3), quality));
+                out.extend_from_slice(&encode_color(px, false, quality));
+            },
+            Bc::Bc4 => out.extend_from_slice(&encode_alpha(&channel(0), quality)),
+            Bc::Bc5 => {
+                out.extend_from_slice(&encode_alpha(&channel(0), quality));
+                out.extend_from_slice(&encode_alpha(&channel(1), quality));
+            },
+        }
+    }
+}
+
+
+
+// Color blocks (BC1-3):  two RGB565 endpoints, then 2-bit indices
+
+fn rgb565(c: u16) -> [u8; 3] {
+    let (r, g, b) = ((c >> 11) & 31, (c >> 5) & 63, c & 31);
+    [(r << 3 | r >> 2) as u8, (g << 2 | g >> 4) as u8, (b << 3 | b >> 2) as u8]
+}
+
+fn to_rgb565(c: [f32; 3]) -> u16 {
+    let q = |v: f32, max: f32| (v.clamp(0.0, 255.0) * max / 255.0).round() as u16;
+    q(c[0], 31.0) << 11 | q(c[1], 63.0) << 5 | q(c[2], 31.0)
+}
+
+fn color_palette(c0: u16, c1: u16, four: bool) -> [[u8; 4]; 4] {
+    let (a, b) = (rgb565(c0), rgb565(c1));
+    let mix = |wa: u16, wb: u16| {
+        let d = wa + wb;
+        let m = |i: usize| ((wa * u16::from(a[i]) + wb * u16::from(b[i]) + d / 2) / d) as u8;
+        [m(0), m(1), m(2), 255]
+    };
+    let [a, b] = [a, b].map(|c| [c[0], c[1], c[2], 255]);
+    if four { [a, b, mix(2, 1), mix(1, 2)] } else { [a, b, mix(1, 1), [0, 0, 0, 0]] }
+}
+
+fn decode_color(block: &[u8], allow_3_color: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let palette = color_palette(c0, c1, !allow_3_color || c0 > c1);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    std::array::from_fn(|i| palette[((indices >> (2 * i)) & 3) as usize])
+}
+
+#[derive(Clone, Copy)] struct ColorFit {
+    c0:         u16,
+    c1:         u16,
+    four:       bool,
+    indices:    [u8; 16],
+    error:      u32,
+}
+
+impl ColorFit {
+    fn new(px: &[[u8; 4]; 16], transparent: &[bool; 16], c0: u16, c1: u16, four: bool) -> Self {
+        let palette = color_palette(c0, c1, four);
+        let candidates = if four { 4 } else { 3 };
+        let mut fit = ColorFit { c0, c1, four, indices: [0; 16], error: 0 };
+        for i in 0 .. 16 {
+            if transparent[i] { fit.indices[i] = 3; continue }
+            let (index, error) = (0 .. candidates).map(|c| (c, rgb_error(&px[i], &palette[c]))).min_by_key(|&(_, e)| e).unwrap();
+            fit.indices[i] = index as u8;
+            fit.error += error;
+        }
+        fit
+    }
+
+    /// Solve for the endpoints minimizing the squared error of the current index assignments
+    fn refine(&self, px: &[[u8; 4]; 16], transparent: &[bool; 16]) -> Option<(u16, u16)> {
+        let (mut aa, mut ab, mut bb) = (0.0, 0.0, 0.0);
+        let (mut ax, mut bx) = ([0.0f32; 3], [0.0f32; 3]);
+        for i in (0 .. 16).filter(|&i| !transparent[i]) {
+            let (wa, wb) = match (self.four, self.indices[i]) {
+                (_, 0)      => (1.0, 0.0),
+                (_, 1)      => (0.0, 1.0),
+                (true, 2)   => (2.0/3.0, 1.0/3.0),
+                (true, _)   => (1.0/3.0, 2.0/3.0),
+                (false, _)  => (0.5, 0.5),
+            };
+            aa += wa * wa; ab += wa * wb; bb += wb * wb;
+            for c in 0 .. 3 { ax[c] += wa * f32::from(px[i][c]); bx[c] += wb * f32::from(px[i][c]); }
+        }
+        let det = aa * bb - ab * ab;
+        if det.abs() < 1e-6 { return None }
+        let e0 = std::array::from_fn(|c| (bb * ax[c] - ab * bx[c]) / det);
+        let e1 = std::array::from_fn(|c| (aa * bx[c] - ab * ax[c]) / det);
+        Some((to_rgb565(e0), to_rgb565(e1)))
+    }
+
+    fn emit(mut self) -> [u8; 8] {
+        if self.four {
+            if self.c0 < self.c1 {
+                std::mem::swap(&mut self.c0, &mut self.c1);
+                for i in self.indices.iter_mut() { *i ^= 1 }
+            } else if self.c0 == self.c1 {
+                self.indices = [0; 16]; // c0 == c1 decodes as 3-color mode for DXT1:  avoid index 3 (transparent)
+            }
+        } else if self.c0 > self.c1 {
+            std::mem::swap(&mut self.c0, &mut self.c1);
+            for i in self.indices.iter_mut() { if *i < 2 { *i ^= 1 } }
+        }
+        let indices = self.indices.iter().enumerate().fold(0u32, |bits, (i, &index)| bits | u32::from(index) << (2 * i));
+        let [c0, c1] = [self.c0.to_le_bytes(), self.c1.to_le_bytes()];
+        let i = indices.to_le_bytes();
+        [c0[0], c0[1], c1[0], c1[1], i[0], i[1], i[2], i[3]]
+    }
+}
+
+fn rgb_error(a: &[u8; 4], b: &[u8; 4]) -> u32 {
+    (0 .. 3).map(|c| { let d = i32::from(a[c]) - i32::from(b[c]); (d * d) as u32 }).sum()
+}
+
+fn encode_color(px: &[[u8; 4]; 16], dxt1: bool, quality: BlockCompressionQuality) -> [u8; 8] {
+    let transparent = px.map(|p| dxt1 && p[3] < 128);
+    let opaque = (0 .. 16).filter(|&i| !transparent[i]).map(|i| px[i].map(f32::from)).collect::<Vec<_>>();
+    if opaque.is_empty() { return [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF] } // c0 == c1: 3-color mode, every index transparent
+
+    let any_transparent = opaque.len() < 16;
+    let modes : &[bool] = if any_transparent { &[false] } else if dxt1 && quality == BlockCompressionQuality::Best { &[true, false] } else { &[true] };
+    let mut endpoints = vec![bounding_box(&opaque)];
+    if quality != BlockCompressionQuality::Fast { endpoints.push(principal_axis(&opaque)) }
+    let iterations = match quality { BlockCompressionQuality::Fast => 0, BlockCompressionQuality::Normal => 1, BlockCompressionQuality::Best => 8 };
+
+    let mut best : Option<ColorFit> = None;
+    for &four in modes {
+        for &(e0, e1) in endpoints.iter() {
+            let mut fit = ColorFit::new(px, &transparent, to_rgb565(e0), to_rgb565(e1), four);
+            for _ in 0 .. iterations {
+                let Some((c0, c1)) = fit.refine(px, &transparent) else { break };
+                let refined = ColorFit::new(px, &transparent, c0, c1, four);
+                if refined.error >= fit.error { break }
+                fit = refined;
+            }
+            match best { Some(best) if best.error <= fit.error => {}, _ => best = Some(fit) }
+        }
+    }
+    best.unwrap().emit()
+}
+
+fn bounding_box(px: &[[f32; 4]]) -> ([f32; 3], [f32; 3]) {
+    let mut lo = [255.0f32; 3];
+    let mut hi = [0.0f32; 3];
+    for p in px { for c in 0 .. 3 { lo[c] = lo[c].min(p[c]); hi[c] = hi[c].max(p[c]); } }
+
+    // pick the box diagonal that follows the colors:  flip channels anti-correlated with the widest channel
+    let w = (0 .. 3).max_by(|&a, &b| (hi[a] - lo[a]).total_cmp(&(hi[b] - lo[b]))).unwrap();
+    let mid : [f32; 3] = std::array::from_fn(|c| (lo[c] + hi[c]) / 2.0);
+    for c in 0 .. 3 {
+        if px.iter().map(|p| (p[w] - mid[w]) * (p[c] - mid[c])).sum::<f32>() < 0.0 { std::mem::swap(&mut lo[c], &mut hi[c]) }
+    }
+    (hi, lo)
+}
+
+fn principal_axis(px: &[[f32; 4]]) -> ([f32; 3], [f32; 3]) {
+    let n = px.len() as f32;
+    let mean : [f32; 3] = std::array::from_fn(|c| px.iter().map(|p| p[c]).sum::<f32>() / n);
+    let mut cov = [[0.0f32; 3]; 3];
+    for p in px {
+        let d : [f32; 3] = std::array::from_fn(|c| p[c] - mean[c]);
+        for i in 0 .. 3 { for j in 0 .. 3 { cov[i][j] += d[i] * d[j]; } }
+    }
+
+    // start from the covariance row of the widest channel, which can't be orthogonal to the principal axis
+    let widest = (0 .. 3).max_by(|&a, &b| cov[a][a].total_cmp(&cov[b][b])).unwrap();
+    let mut axis = cov[widest];
+    for _ in 0 .. 8 {
+        let next : [f32; 3] = std::array::from_fn(|i| (0 .. 3).map(|j| cov[i][j] * axis[j]).sum());
+        let len = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if len < 1e-6 { break }
+        axis = next.map(|v| v / len);
+    }
+
+    let t = |p: &[f32; 4]| (0 .. 3).map(|c| (p[c] - mean[c]) * axis[c]).sum::<f32>();
+    let (lo, hi) = px.iter().map(t).fold((f32::MAX, f32::MIN), |(lo, hi), t| (lo.min(t), hi.max(t)));
+    (std::array::from_fn(|c| mean[c] + axis[c] * hi), std::array::from_fn(|c| mean[c] + axis[c] * lo))
+}
+
+
+
+// Interpolated alpha blocks (BC3 alpha, BC4, BC5):  two 8-bit endpoints, then 3-bit indices
+
+fn alpha_palette(a0: u8, a1: u8) -> [u8; 8] {
+    let (a, b) = (u16::from(a0), u16::from(a1));
+    let mut p = [a0, a1, 0, 0, 0, 0, 0, 255];
+    if a0 > a1 {
+        for i in 1 .. 7 { p[i + 1] = (((7 - i as u16) * a + i as u16 * b + 3) / 7) as u8 }
+    } else {
+        for i in 1 .. 5 { p[i + 1] = (((5 - i as u16) * a + i as u16 * b + 2) / 5) as u8 }
+    }
+    p
+}
+
+fn decode_alpha(block: &[u8]) -> [u8; 16] {
+    let palette = alpha_palette(block[0], block[1]);
+    let mut bits = [0u8; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bits);
+    std::array::from_fn(|i| palette[((indices >> (3 * i)) & 7) as usize])
+}
+
+fn fit_alpha(values: &[u8; 16], a0: u8, a1: u8) -> (u32, [u8; 8]) {
+    let palette = alpha_palette(a0, a1);
+    let mut indices = 0u64;
+    let mut error = 0;
+    for (i, &v) in values.iter().enumerate() {
+        let (index, e) = palette.iter().enumerate().map(|(p, &a)| (p, (i32::from(v) - i32::from(a)).pow(2) as u32)).min_by_key(|&(_, e)| e).unwrap();
+        indices |= (index as u64) << (3 * i);
+        error += e;
+    }
+    let i = indices.to_le_bytes();
+    (error, [a0, a1, i[0], i[1], i[2], i[3], i[4], i[5]])
+}
+
+fn encode_alpha(values: &[u8; 16], quality: BlockCompressionQuality) -> [u8; 8] {
+    let lo = *values.iter().min().unwrap();
+    let hi = *values.iter().max().unwrap();
+    if lo == hi { return [hi, hi, 0, 0, 0, 0, 0, 0] }
+
+    let mut best = fit_alpha(values, hi, lo);
+    let mut consider = |a0: u8, a1: u8| { let fit = fit_alpha(values, a0, a1); if fit.0 < best.0 { best = fit } };
+
+    if quality >= BlockCompressionQuality::Normal {
+        // 6-value mode, with the extremes handled by the implicit 0 / 255 entries
+        let inner = values.iter().copied().filter(|&v| v != 0 && v != 255);
+        let inner_lo = inner.clone().min().unwrap_or(lo);
+        let inner_hi = inner.max().unwrap_or(hi);
+        if inner_lo <= inner_hi { consider(inner_lo, inner_hi) }
+    }
+
+    if quality >= BlockCompressionQuality::Best {
+        for d0 in -2 ..= 2i16 {
+            for d1 in -2 ..= 2i16 {
+                let a0 = (i16::from(hi) + d0).clamp(0, 255) as u8;
+                let a1 = (i16::from(lo) + d1).clamp(0, 255) as u8;
+                if a0 > a1 { consider(a0, a1) }
+            }
+        }
+    }
+
+    best.1
+}
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use BlockCompressionQuality::*;
+
+    fn test_image(width: usize, height: usize) -> Vec<[u8; 4]> {
+        (0 .. width * height).map(|i| {
+            let (x, y) = (i % width, i / width);
+            [(x * 255 / (width - 1).max(1)) as u8, (y * 255 / (height - 1).max(1)) as u8, ((x ^ y) * 16) as u8, (255 - (x * 8 + y * 4) % 128) as u8]
+        }).collect()
+    }
+
+    fn rmse(a: &[[u8; 4]], b: &[[u8; 4]], channels: std::ops::Range<usize>) -> f64 {
+        let n = (a.len() * channels.len()) as f64;
+        let sum : f64 = a.iter().zip(b.iter()).map(|(a, b)| channels.clone().map(|c| (f64::from(a[c]) - f64::from(b[c])).powi(2)).sum::<f64>()).sum();
+        (sum / n).sqrt()
+    }
+
+    fn round_trip(format: Format, width: u32, height: u32, pixels: &[[u8; 4]], quality: BlockCompressionQuality) -> Vec<[u8; 4]> {
+        let bytes = compress_blocks(format, width, height, pixels, quality).unwrap();
+        let info = FormatInfo::of(format).unwrap();
+        assert_eq!(bytes.len(), info.surface_bytes(width, height));
+        decompress_blocks(format, TextureMipRef { data: &bytes, stride: info.row_bytes(width) }, width, height).unwrap()
+    }
+
+    #[test] fn decode_known_blocks() {
+        // DXT1 3-color mode:  color0 <= color1
+        let dxt1 = [0x1F, 0x00, 0x00, 0xF8, 0xE4, 0xE4, 0xE4, 0xE4];
+        let px = decompress_blocks(Format::DXT1, TextureMipRef { data: &dxt1, stride: 8 }, 4, 1).unwrap();
+        assert_eq!(px, [[0, 0, 255, 255], [255, 0, 0, 255], [128, 0, 128, 255], [0, 0, 0, 0]]);
+
+        // DXT3 explicit alpha + DXT5 interpolated alpha share the same (4-color mode) color block, even when color0 <= color1
+        let color = [0x1F, 0x00, 0x00, 0xF8, 0x00, 0x00, 0x00, 0x00];
+        let dxt3 = [&[0x10, 0x32, 0x54, 0x76, 0x98, 0xBA, 0xDC, 0xFE][..], &color].concat();
+        let px = decompress_blocks(Format::DXT3, TextureMipRef { data: &dxt3, stride: 16 }, 4, 4).unwrap();
+        assert!(px.iter().enumerate().all(|(i, p)| p[..3] == [0, 0, 255] && p[3] == i as u8 * 17));
+
+        let alpha = [0xFF, 0x00, 0x88, 0xC6, 0xFA, 0, 0, 0]; // indices 0, 1, 2, 3, 4, 5, 6, 7
+        let dxt5 = [&alpha[..], &color].concat();
+        let px = decompress_blocks(Format::DXT5, TextureMipRef { data: &dxt5, stride: 16 }, 4, 2).unwrap();
+        assert_eq!(px.iter().map(|p| p[3]).collect::<Vec<_>>(), [255, 0, 219, 182, 146, 109, 73, 36]);
+
+        let ati1 = [0x00, 0xFF, 0x88, 0xC6, 0xFA, 0, 0, 0]; // 6-value mode
+        let px = decompress_blocks(Format::ATI1, TextureMipRef { data: &ati1, stride: 8 }, 4, 2).unwrap();
+        assert_eq!(px.iter().map(|p| p[0]).collect::<Vec<_>>(), [0, 255, 51, 102, 153, 204, 0, 255]);
+        assert!(px.iter().all(|p| p[1] == 0 && p[2] == 0 && p[3] == 255));
+
+        let ati2 = [ati1, [0x80; 8]].concat();
+        let px = decompress_blocks(Format::ATI2, TextureMipRef { data: &ati2, stride: 16 }, 1, 1).unwrap();
+        assert_eq!(px, [[0, 0x80, 0, 255]]);
+    }
+
+    #[test] fn quality() {
+        let (w, h) = (32, 32);
+        let image = test_image(w, h);
+        for format in [Format::DXT1, Format::DXT3, Format::DXT5] {
+            let errors = [Fast, Normal, Best].map(|q| rmse(&image, &round_trip(format, w as _, h as _, &image, q), 0 .. 3));
+            assert!(errors[0] < 16.0, "{format:?} {errors:?}");
+            assert!(errors[1] <= errors[0] + 0.01, "{format:?} {errors:?}");
+            assert!(errors[2] <= errors[1] + 0.01, "{format:?} {errors:?}");
+        }
+
+        let errors = [Fast, Normal, Best].map(|q| rmse(&image, &round_trip(Format::DXT5, w as _, h as _, &image, q), 3 .. 4));
+        assert!(errors[2] <= errors[0] && errors[2] < 4.0, "{errors:?}");
+        let errors = [Fast, Normal, Best].map(|q| rmse(&image, &round_trip(Format::ATI2, w as _, h as _, &image, q), 0 .. 2));
+        assert!(errors[2] <= errors[0] && errors[2] < 2.0, "{errors:?}");
+        assert!(rmse(&image, &round_trip(Format::DXT3, w as _, h as _, &image, Fast), 3 .. 4) < 10.0);
+    }
+
+    #[test] fn dxt1_alpha() {
+        let mut image = test_image(8, 8);
+        for (i, px) in image.iter_mut().enumerate() { px[3] = if i % 3 == 0 { 0 } else { 255 } }
+        for q in [Fast, Normal, Best] {
+            let decoded = round_trip(Format::DXT1, 8, 8, &image, q);
+            for (src, dst) in image.iter().zip(decoded.iter()) {
+                assert_eq!(src[3], dst[3]);
+                if dst[3] == 0 { assert_eq!(dst, &[0, 0, 0, 0]) }
+            }
+        }
+
+        let clear = round_trip(Format::DXT1, 4, 4, &[[255, 255, 255, 0]; 16], Best);
+        assert_eq!(clear, [[0; 4]; 16]);
+
+        // opaque solid colors must never accidentally hit 3-color mode's transparent index
+        for c in [[0, 0, 0, 255], [255, 255, 255, 255], [8, 4, 8, 255], [255, 0, 255, 200]] {
+            for q in [Fast, Normal, Best] { assert!(round_trip(Format::DXT1, 4, 4, &[c; 16], q).iter().all(|p| p[3] == 255)) }
+        }
+    }
+
+    #[test] fn exact() {
+        // endpoints representable in the target format should round trip losslessly
+        let two = |a: [u8; 4], b: [u8; 4]| (0 .. 16).map(|i| if i % 2 == 0 { a } else { b }).collect::<Vec<_>>();
+        let pixels = two([255, 0, 0, 255], [0, 0, 255, 0]);
+        assert_eq!(round_trip(Format::DXT5, 4, 4, &pixels, Normal), pixels);
+        let pixels = two([0, 255, 0, 17], [255, 255, 255, 255]);
+        assert_eq!(round_trip(Format::DXT3, 4, 4, &pixels, Fast), pixels);
+        let pixels = two([255, 0, 0, 255], [0, 255, 255, 255]);
+        assert_eq!(round_trip(Format::DXT1, 4, 4, &pixels, Fast), pixels);
+        let pixels = two([12, 200, 0, 255], [250, 3, 0, 255]);
+        assert_eq!(round_trip(Format::ATI2, 4, 4, &pixels, Fast), pixels);
+    }
+
+    #[test] fn sizes_and_errors() {
+        for (w, h) in [(1, 1), (5, 3), (3, 9), (0, 0), (0, 4)] {
+            let image = test_image(w, h);
+            for format in [Format::DXT1, Format::DXT2, Format::DXT3, Format::DXT4, Format::DXT5, Format::ATI1, Format::ATI2] {
+                assert_eq!(round_trip(format, w as _, h as _, &image, Normal).len(), w * h);
+            }
+        }
+
+        let bytes = [0u8; 16 * 3];
+        assert!(decompress_blocks(Format::DXT5, TextureMipRef { data: &bytes, stride: 16 }, 4, 12).is_ok());
+        assert!(decompress_blocks(Format::DXT1, TextureMipRef { data: &bytes, stride: 24 }, 12, 5).is_ok());
+        assert_eq!(decompress_blocks(Format::DXT5, TextureMipRef { data: &bytes, stride: 16 }, 4, 13).unwrap_err().kind(), D3DERR::INVALIDCALL);
+        assert_eq!(decompress_blocks(Format::DXT5, TextureMipRef { data: &bytes, stride: 16 }, 5, 8).unwrap_err().kind(), D3DERR::INVALIDCALL);
+        assert_eq!(decompress_blocks(Format::A8R8G8B8, TextureMipRef { data: &bytes, stride: 16 }, 4, 4).unwrap_err().kind(), D3DERR::WRONGTEXTUREFORMAT);
+        assert_eq!(compress_blocks(Format::L8, 4, 4, &[[0; 4]; 16], Fast).unwrap_err().kind(), D3DERR::WRONGTEXTUREFORMAT);
+        assert_eq!(compress_blocks(Format::DXT1, 4, 4, &[[0; 4]; 15], Fast).unwrap_err().kind(), D3DERR::INVALIDCALL);
+    }
+}
@@ -0,0 +1,659 @@
+use crate::*;
+use crate::d3d::{Format, FormatInfo, FormatNumeric};
+use crate::d3d9::{CubeTextureMipRef, FixedTextureFormat, TextureImage, TextureKind, TextureMipRef, VolumeTextureMipRef};
+use crate::d3d9::pixel_convert::rows_fit;
+
+
+
+/// A [DirectDraw Surface](https://learn.microsoft.com/en-us/windows/win32/direct3ddds/dx-graphics-dds-pguide) (`.dds`) file:
+/// a 2D texture, cube texture, or volume texture, with a full or partial mip chain.
+///
+/// [Dds::from_bytes] reads both the legacy `DDS_PIXELFORMAT` header (FOURCC codes, including Direct3D 9's numeric
+/// `D3DFORMAT` codes, and RGB / luminance / alpha / bump bitmasks) and the `DX10` extended header, mapping either to a [d3d::Format].
+/// [Dds::to_bytes] always writes the legacy header, which every Direct3D 9 era tool understands.
+///
+/// The mip accessors borrow the file's pixel data in exactly the shape
+/// [create_texture_from](d3d9::IDirect3DDevice9Ext::create_texture_from) /
+/// [create_cube_texture_from](d3d9::IDirect3DDevice9Ext::create_cube_texture_from) /
+/// [create_volume_texture_from](d3d9::IDirect3DDevice9Ext::create_volume_texture_from) take.
+///
+/// ### Example
+/// ```rust
+/// # use dev::d3d9::*; let device = device_pure();
+/// let pixels = [0xFF112233_u32; 4*4 + 2*2 + 1];
+/// let pixels = bytemuck::cast_slice::<u32, u8>(&pixels);
+/// let mips = [
+///     TextureMipRef { data: &pixels[..4*4*4], stride: 4*4 },
+///     TextureMipRef { data: &pixels[4*4*4..][..2*2*4], stride: 2*4 },
+///     TextureMipRef { data: &pixels[(4*4+2*2)*4..], stride: 4 },
+/// ];
+/// let file = Dds::from_texture(Format::A8R8G8B8, 4, 4, &mips).unwrap().to_bytes();
+///
+/// let dds = Dds::from_bytes(&file).unwrap();
+/// assert_eq!((dds.kind(), dds.format(), dds.width(), dds.height(), dds.mip_levels()), (TextureKind::Texture, Format::A8R8G8B8, 4, 4, 3));
+/// let texture = device.create_texture_from(
+///     dds.width(), dds.height(), &dds.texture_mips().unwrap(),
+///     Usage::None, dds.fixed_format().unwrap(), Pool::Managed, (),
+/// ).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct Dds {
+    kind:       TextureKind,
+    format:     Format,
+    width:      u32,
+    height:     u32,
+    depth:      u32,
+    mip_levels: u32,
+    data:       Vec<u8>,
+}
+
+/// The kind of texture a [Dds] file contains (an alias of [TextureKind], which [TextureImage] shares.)
+pub type DdsKind = TextureKind;
+
+impl Dds {
+    /// Parse a `.dds` file.
+    ///
+    /// Trailing bytes after the last surface are ignored.
+    /// 1D `DX10` textures are loaded as [TextureKind::Texture]s with a height of 1.
+    ///
+    /// ### Errors
+    /// *   [D3DERR::INVALIDDATA]           - not a `.dds` file, a malformed header, or truncated pixel data
+    /// *   [D3DERR::WRONGTEXTUREFORMAT]    - the pixel format doesn't map to a supported [d3d::Format] (palettized, YUV bitmasks, signed BC4/BC5, ...)
+    /// *   [D3DERR::NOTAVAILABLE]          - the layout has no Direct3D 9 equivalent (texture arrays, partial cube maps, dimensions over 65536)
+    //#allow_missing_argument_docs
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        fn_context!(d3d9::Dds::from_bytes);
+        let invalid = || fn_param_error!(bytes, D3DERR::INVALIDDATA);
+        let u32_at = |offset: usize| bytes.get(offset .. offset + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap())).ok_or_else(invalid);
+
+        if bytes.get(..4) != Some(b"DDS ") || u32_at(4)? != HEADER_SIZE || u32_at(4 + 72)? != PIXEL_FORMAT_SIZE { return Err(invalid()) }
+        let flags   = u32_at(4 + 4)?;
+        let height  = u32_at(4 + 8)?;
+        let width   = u32_at(4 + 12)?;
+        let depth   = u32_at(4 + 20)?;
+        let mips    = u32_at(4 + 24)?;
+        let pf = PixelFormat {
+            flags:  u32_at(4 + 76)?,
+            fourcc: u32_at(4 + 80)?,
+            bits:   u32_at(4 + 84)?,
+            masks:  [u32_at(4 + 88)?, u32_at(4 + 92)?, u32_at(4 + 96)?, u32_at(4 + 100)?],
+        };
+        let caps    = u32_at(4 + 104)?;
+        let caps2   = u32_at(4 + 108)?;
+        let mut data_offset = 4 + HEADER_SIZE as usize;
+
+        let mut height = height;
+        let (format, kind) = if pf.flags & DDPF_FOURCC != 0 && pf.fourcc == FOURCC_DX10 {
+            let dxgi        = u32_at(data_offset)?;
+            let dimension   = u32_at(data_offset + 4)?;
+            let misc        = u32_at(data_offset + 8)?;
+            let array_size  = u32_at(data_offset + 12)?;
+            data_offset += 20;
+
+            let format = DXGI_FORMATS.iter().find(|(d, _)| *d == dxgi).map(|(_, f)| *f).ok_or_else(|| fn_param_error!(bytes, D3DERR::WRONGTEXTUREFORMAT))?;
+            if array_size != 1 { return Err(fn_param_error!(bytes, D3DERR::NOTAVAILABLE)) }
+            let kind = match dimension {
+                DIMENSION_TEXTURE1D                                 => { height = 1; TextureKind::Texture },
+                DIMENSION_TEXTURE2D if misc & MISC_TEXTURECUBE != 0 => TextureKind::CubeTexture,
+                DIMENSION_TEXTURE2D                                 => TextureKind::Texture,
+                DIMENSION_TEXTURE3D                                 => TextureKind::VolumeTexture,
+                _                                                   => return Err(invalid()),
+            };
+            (format, kind)
+        } else {
+            let format = pf.to_format().ok_or_else(|| fn_param_error!(bytes, D3DERR::WRONGTEXTUREFORMAT))?;
+            let kind = if caps2 & DDSCAPS2_CUBEMAP != 0 {
+                if caps2 & DDSCAPS2_CUBEMAP_ALLFACES != DDSCAPS2_CUBEMAP_ALLFACES { return Err(fn_param_error!(bytes, D3DERR::NOTAVAILABLE)) }
+                TextureKind::CubeTexture
+            } else if caps2 & DDSCAPS2_VOLUME != 0 || flags & DDSD_DEPTH != 0 {
+                TextureKind::VolumeTexture
+            } else {
+                TextureKind::Texture
+            };
+            (format, kind)
+        };
+
+        let depth = if kind == TextureKind::VolumeTexture { depth } else { 1 };
+        let mip_levels = if flags & DDSD_MIPMAPCOUNT != 0 || caps & DDSCAPS_MIPMAP != 0 { mips.max(1) } else { 1 };
+        if width == 0 || height == 0 || depth == 0 { return Err(invalid()) }
+        if kind == TextureKind::CubeTexture && width != height { return Err(invalid()) }
+        if width.max(height).max(depth) > MAX_DIMENSION { return Err(fn_param_error!(bytes, D3DERR::NOTAVAILABLE)) }
+        if mip_levels > max_mip_levels(width, height, depth) { return Err(invalid()) }
+
+        let dds = Self { kind, format, width, height, depth, mip_levels, data: Vec::new() };
+        let size = dds.expected_data_len().ok_or_else(invalid)?;
+        let data = bytes.get(data_offset ..).and_then(|d| d.get(..size)).ok_or_else(invalid)?;
+        Ok(Self { data: data.to_vec(), ..dds })
+    }
+
+    /// Serialize as a `.dds` file with a legacy (non-`DX10`) header.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let info = self.info();
+        let mut flags = DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT;
+        let mut caps  = DDSCAPS_TEXTURE;
+        let mut caps2 = 0;
+        if self.mip_levels > 1 { flags |= DDSD_MIPMAPCOUNT; caps |= DDSCAPS_COMPLEX | DDSCAPS_MIPMAP; }
+        let pitch = if info.compressed { flags |= DDSD_LINEARSIZE; info.checked_surface_bytes(self.width, self.height) } else { flags |= DDSD_PITCH; info.checked_row_bytes(self.width) };
+        match self.kind {
+            TextureKind::Texture        => {},
+            TextureKind::CubeTexture    => { caps |= DDSCAPS_COMPLEX; caps2 |= DDSCAPS2_CUBEMAP | DDSCAPS2_CUBEMAP_ALLFACES; },
+            TextureKind::VolumeTexture  => { caps |= DDSCAPS_COMPLEX; caps2 |= DDSCAPS2_VOLUME; flags |= DDSD_DEPTH; },
+        }
+        let pf = PixelFormat::from_format(self.format);
+
+        let mut header = [0u32; 1 + HEADER_SIZE as usize / 4];
+        header[0]   = u32::from_le_bytes(*b"DDS ");
+        header[1]   = HEADER_SIZE;
+        header[2]   = flags;
+        header[3]   = self.height;
+        header[4]   = self.width;
+        header[5]   = pitch.and_then(|pitch| u32::try_from(pitch).ok()).unwrap_or(0);
+        header[6]   = if self.kind == TextureKind::VolumeTexture { self.depth } else { 0 };
+        header[7]   = self.mip_levels;
+        header[19]  = PIXEL_FORMAT_SIZE;
+        header[20]  = pf.flags;
+        header[21]  = pf.fourcc;
+        header[22]  = pf.bits;
+        header[23 .. 27].copy_from_slice(&pf.masks);
+        header[27]  = caps;
+        header[28]  = caps2;
+
+        let mut bytes = Vec::with_capacity(header.len() * 4 + self.data.len());
+        for v in header { bytes.extend_from_slice(&v.to_le_bytes()) }
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// Create a 2D texture [Dds] from a mip chain, such as the one passed to [create_texture_from](d3d9::IDirect3DDevice9Ext::create_texture_from).
+    ///
+    /// ### Errors
+    /// *   [D3DERR::WRONGTEXTUREFORMAT]    - `format` has no fixed size per pixel/block, or is palettized
+    /// *   [D3DERR::INVALIDCALL]           - `width` or `height` is 0, `mips` is empty, or there are more `mips` than the dimensions allow
+    /// *   [D3DERR::INVALIDCALL]           - a mip's `data` is too small for its dimensions and `stride`
+    /// *   [THINERR::ALLOC_OVERFLOW]       - the mip chain would be larger than `usize::MAX` bytes
+    //#allow_missing_argument_docs
+    pub fn from_texture(format: impl Into<Format>, width: u32, height: u32, mips: &[TextureMipRef]) -> Result<Self, Error> {
+        fn_context!(d3d9::Dds::from_texture);
+        let mut dds = Self::new(TextureKind::Texture, format.into(), width, height, 1, mips.len())?;
+        for (level, mip) in mips.iter().enumerate() {
+            let (w, h, _) = dds.mip_size(level as u32);
+            if !dds.append_surface(*mip, w, h) { return Err(fn_param_error!(mips, D3DERR::INVALIDCALL)) }
+        }
+        Ok(dds)
+    }
+
+    /// Create a cube texture [Dds] from a mip chain, such as the one passed to [create_cube_texture_from](d3d9::IDirect3DDevice9Ext::create_cube_texture_from).
+    ///
+    /// ### Errors
+    /// *   See [Dds::from_texture]
+    //#allow_missing_argument_docs
+    pub fn from_cube_texture(format: impl Into<Format>, size: u32, mips: &[CubeTextureMipRef]) -> Result<Self, Error> {
+        fn_context!(d3d9::Dds::from_cube_texture);
+        let mut dds = Self::new(TextureKind::CubeTexture, format.into(), size, size, 1, mips.len())?;
+        for face in 0 .. 6 {
+            for (level, mip) in mips.iter().enumerate() {
+                let (w, h, _) = dds.mip_size(level as u32);
+                let mip = [&mip.pos_x, &mip.neg_x, &mip.pos_y, &mip.neg_y, &mip.pos_z, &mip.neg_z][face];
+                if !dds.append_surface(*mip, w, h) { return Err(fn_param_error!(mips, D3DERR::INVALIDCALL)) }
+            }
+        }
+        Ok(dds)
+    }
+
+    /// Create a volume texture [Dds] from a mip chain, such as the one passed to [create_volume_texture_from](d3d9::IDirect3DDevice9Ext::create_volume_texture_from).
+    ///
+    /// ### Errors
+    /// *   See [Dds::from_texture]
+    //#allow_missing_argument_docs
+    pub fn from_volume_texture(format: impl Into<Format>, width: u32, height: u32, depth: u32, mips: &[VolumeTextureMipRef]) -> Result<Self, Error> {
+        fn_context!(d3d9::Dds::from_volume_texture);
+        let mut dds = Self::new(TextureKind::VolumeTexture, format.into(), width, height, depth, mips.len())?;
+        for (level, mip) in mips.iter().enumerate() {
+            let (w, h, d) = dds.mip_size(level as u32);
+            for slice in 0 .. d as usize {
+                let data = slice.checked_mul(mip.stride_slice).and_then(|start| mip.data.get(start ..)).unwrap_or(&[]);
+                if !dds.append_surface(TextureMipRef { data, stride: mip.stride_row }, w, h) { return Err(fn_param_error!(mips, D3DERR::INVALIDCALL)) }
+            }
+        }
+        Ok(dds)
+    }
+
+    /// Create a [Dds] from a [TextureImage].
+    ///
+    /// ### Errors
+    /// *   [D3DERR::WRONGTEXTUREFORMAT]    - the image's format is palettized
+    //#allow_missing_argument_docs
+    pub fn from_image(image: &TextureImage) -> Result<Self, Error> {
+        fn_context!(d3d9::Dds::from_image);
+        let mut dds = Self::new(image.kind(), image.format().format, image.width(), image.height(), image.depth(), image.levels() as usize)?;
+        debug_assert_eq!(dds.expected_data_len(), Some(image.data().len()));
+        dds.data.extend_from_slice(image.data());
+        Ok(dds)
+    }
+
+    /// Convert into a [TextureImage].
+    ///
+    /// ### Errors
+    /// *   [D3DERR::WRONGTEXTUREFORMAT]    - [Dds::format] has no [FixedTextureFormat] (such as [d3d::Format::YUY2])
+    pub fn into_image(self) -> Result<TextureImage, Error> {
+        fn_context!(d3d9::Dds::into_image);
+        let format = self.fixed_format().ok_or_else(|| fn_param_error!(self, D3DERR::WRONGTEXTUREFORMAT))?;
+        TextureImage::from_data(self.kind, format, self.width, self.height, self.depth, self.mip_levels, self.data)
+    }
+
+    /// The kind of texture this file contains
+    pub fn kind(&self) -> TextureKind { self.kind }
+
+    /// The pixel format of every surface
+    pub fn format(&self) -> Format { self.format }
+
+    /// The [FixedTextureFormat] for [Dds::format], if there is one, for use with `create_*texture_from`.
+    pub fn fixed_format(&self) -> Option<&'static FixedTextureFormat> { FixedTextureFormat::try_from_standard(self.format) }
+
+    /// The width of the top mip, in pixels
+    pub fn width(&self) -> u32 { self.width }
+
+    /// The height of the top mip, in pixels
+    pub fn height(&self) -> u32 { self.height }
+
+    /// The depth of the top mip, in pixels (`1` unless this is a [TextureKind::VolumeTexture])
+    pub fn depth(&self) -> u32 { self.depth }
+
+    /// The number of mip levels (at least `1`)
+    pub fn mip_levels(&self) -> u32 { self.mip_levels }
+
+    /// The raw, tightly packed pixel data of every surface, in `.dds` order (face major for cube textures.)
+    pub fn data(&self) -> &[u8] { &self.data }
+
+    /// The mip chain of a [TextureKind::Texture], or [None] for other kinds.
+    pub fn texture_mips(&self) -> Option<Vec<TextureMipRef<'_>>> {
+        if self.kind != TextureKind::Texture { return None }
+        Some(self.chain(0).collect())
+    }
+
+    /// The mip chain of a [TextureKind::CubeTexture], or [None] for other kinds.
+    pub fn cube_texture_mips(&self) -> Option<Vec<CubeTextureMipRef<'_>>> {
+        if self.kind != TextureKind::CubeTexture { return None }
+        let chain = self.chain_bytes();
+        let faces : Vec<Vec<TextureMipRef>> = (0 .. 6).map(|face| self.chain(face * chain).collect()).collect();
+        Some((0 .. self.mip_levels as usize).map(|level| CubeTextureMipRef {
+            pos_x: faces[0][level], neg_x: faces[1][level],
+            pos_y: faces[2][level], neg_y: faces[3][level],
+            pos_z: faces[4][level], neg_z: faces[5][level],
+        }).collect())
+    }
+
+    /// The mip chain of a [TextureKind::VolumeTexture], or [None] for other kinds.
+    pub fn volume_texture_mips(&self) -> Option<Vec<VolumeTextureMipRef<'_>>> {
+        if self.kind != TextureKind::VolumeTexture { return None }
+        let info = self.info();
+        let mut offset = 0;
+        Some((0 .. self.mip_levels).map(|level| {
+            let (w, h, d) = self.mip_size(level);
+            let slice = info.surface_bytes(w, h);
+            let data = &self.data[offset ..][.. slice * d as usize];
+            offset += data.len();
+            VolumeTextureMipRef { data, stride_row: info.row_bytes(w), stride_slice: slice }
+        }).collect())
+    }
+}
+
+impl std::fmt::Debug for Dds {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.debug_struct("Dds")
+            .field("kind",          &self.kind)
+            .field("format",        &self.format)
+            .field("width",         &self.width)
+            .field("height",        &self.height)
+            .field("depth",         &self.depth)
+            .field("mip_levels",    &self.mip_levels)
+            .field("data",          &format_args!("[u8; {}]", self.data.len()))
+            .finish()
+    }
+}
+
+impl Dds {
+    fn new(kind: TextureKind, format: Format, width: u32, height: u32, depth: u32, mips: usize) -> Result<Self, Error> {
+        fn_context!(d3d9::Dds::new);
+        if !PixelFormat::supported(format) { return Err(fn_param_error!(format, D3DERR::WRONGTEXTUREFORMAT)) }
+        if width == 0 || height == 0 || depth == 0 || width.max(height).max(depth) > MAX_DIMENSION { return Err(fn_param_error!(width, D3DERR::INVALIDCALL)) }
+        if mips == 0 || mips > max_mip_levels(width, height, depth) as usize { return Err(fn_param_error!(mips, D3DERR::INVALIDCALL)) }
+        let dds = Self { kind, format, width, height, depth, mip_levels: mips as u32, data: Vec::new() };
+        // don't reserve `expected_data_len` bytes:  the dimensions aren't backed by any mip data yet, and could be enormous
+        dds.expected_data_len().ok_or_else(|| fn_param_error!(width, THINERR::ALLOC_OVERFLOW))?;
+        Ok(dds)
+    }
+
+    fn info(&self) -> &'static FormatInfo { FormatInfo::of(self.format).expect("Dds::format should be validated on construction") }
+
+    fn mip_size(&self, level: u32) -> (u32, u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1), (self.depth >> level).max(1))
+    }
+
+    fn chain_bytes(&self) -> usize {
+        let info = self.info();
+        (0 .. self.mip_levels).map(|level| { let (w, h, d) = self.mip_size(level); info.surface_bytes(w, h) * d as usize }).sum()
+    }
+
+    fn expected_data_len(&self) -> Option<usize> {
+        let info = self.info();
+        let chain = (0 .. self.mip_levels).try_fold(0usize, |chain, level| {
+            let (w, h, d) = self.mip_size(level);
+            chain.checked_add(info.checked_surface_bytes(w, h)?.checked_mul(d as usize)?)
+        })?;
+        let faces = if self.kind == TextureKind::CubeTexture { 6 } else { 1 };
+        chain.checked_mul(faces)
+    }
+
+    fn chain(&self, offset: usize) -> impl Iterator<Item = TextureMipRef<'_>> {
+        let info = self.info();
+        let mut offset = offset;
+        (0 .. self.mip_levels).map(move |level| {
+            let (w, h, _) = self.mip_size(level);
+            let data = &self.data[offset ..][.. info.surface_bytes(w, h)];
+            offset += data.len();
+            TextureMipRef { data, stride: info.row_bytes(w) }
+        })
+    }
+
+    fn append_surface(&mut self, src: TextureMipRef, width: u32, height: u32) -> bool {
+        let info = self.info();
+        let Some(row_bytes) = info.checked_row_bytes(width) else { return false };
+        let rows = (height as usize + info.block_size.1 as usize - 1) / info.block_size.1 as usize;
+        if !rows_fit(src, row_bytes, rows) { return false }
+        for row in 0 .. rows { self.data.extend_from_slice(&src.data[row * src.stride ..][..row_bytes]) }
+        true
+    }
+}
+
+fn max_mip_levels(width: u32, height: u32, depth: u32) -> u32 { 32 - width.max(height).max(depth).leading_zeros() }
+
+
+
+struct PixelFormat {
+    flags:  u32,
+    fourcc: u32,
+    bits:   u32,
+    masks:  [u32; 4],
+}
+
+impl PixelFormat {
+    fn supported(format: Format) -> bool {
+        FormatInfo::of(format).is_some_and(|info| info.bits_per_block != 0 && !info.index)
+    }
+
+    /// Formats that are written as (and matched against) `DDS_PIXELFORMAT` bitmasks rather than FOURCCs
+    fn masked(info: &FormatInfo) -> bool {
+        let numeric = matches!(info.numeric, FormatNumeric::Unorm | FormatNumeric::Snorm | FormatNumeric::Mixed);
+        numeric && !info.fourcc && !info.compressed && !info.depth && !info.stencil && !info.index
+            && matches!(info.bits_per_block, 8 | 16 | 24 | 32) && info.format != Format::CxV8U8
+    }
+
+    fn from_format(format: Format) -> Self {
+        let info = FormatInfo::of(format).filter(|info| Self::masked(info));
+        let Some(info) = info else { return Self { flags: DDPF_FOURCC, fourcc: format.into(), bits: 0, masks: [0; 4] } };
+
+        let c = &info.channels;
+        let mask = |ch: d3d::FormatChannel| if ch.bits == 0 { 0 } else { (((1u64 << ch.bits) - 1) << ch.offset) as u32 };
+        let alpha = if c.a.is_present() { DDPF_ALPHAPIXELS } else { 0 };
+        let (flags, masks) = if c.r.is_present() {
+            (DDPF_RGB | alpha,              [mask(c.r), mask(c.g), mask(c.b), mask(c.a)])
+        } else if c.l.is_present() && c.u.is_present() {
+            (DDPF_BUMPLUMINANCE,            [mask(c.u), mask(c.v), mask(c.l), 0])
+        } else if c.u.is_present() {
+            (DDPF_BUMPDUDV | alpha,         [mask(c.u), mask(c.v), mask(c.w), mask(c.q) | mask(c.a)])
+        } else if c.l.is_present() {
+            (DDPF_LUMINANCE | alpha,        [mask(c.l), 0, 0, mask(c.a)])
+        } else {
+            (DDPF_ALPHA,                    [0, 0, 0, mask(c.a)])
+        };
+        Self { flags, fourcc: 0, bits: info.bits_per_block.into(), masks }
+    }
+
+    fn to_format(&self) -> Option<Format> {
+        if self.flags & DDPF_FOURCC != 0 {
+            let format = match &self.fourcc.to_le_bytes() {
+                b"BC4U" => Format::ATI1,
+                b"BC5U" => Format::ATI2,
+                _       => Format::from_unchecked(self.fourcc),
+            };
+            return Some(format).filter(|&f| Self::supported(f))
+        }
+
+        let category = self.flags & DDPF_CATEGORIES;
+        let mut masks = self.masks;
+        if self.flags & DDPF_ALPHAPIXELS == 0 && category & (DDPF_RGB | DDPF_LUMINANCE) != 0 { masks[3] = 0 }
+        FormatInfo::all().iter().filter(|info| Self::masked(info)).map(|info| info.format).find(|&format| {
+            let pf = Self::from_format(format);
+            pf.flags & DDPF_CATEGORIES == category && pf.bits == self.bits && pf.masks == masks
+        })
+    }
+}
+
+
+
+const HEADER_SIZE                   : u32 = 124;
+const PIXEL_FORMAT_SIZE             : u32 = 32;
+const MAX_DIMENSION                 : u32 = 1 << 16;
+const FOURCC_DX10                   : u32 = u32::from_le_bytes(*b"DX10");
+
+const DDSD_CAPS                     : u32 = 0x00000001;
+const DDSD_HEIGHT                   : u32 = 0x00000002;
+const DDSD_WIDTH                    : u32 = 0x00000004;
+const DDSD_PITCH                    : u32 = 0x00000008;
+const DDSD_PIXELFORMAT              : u32 = 0x00001000;
+const DDSD_MIPMAPCOUNT              : u32 = 0x00020000;
+const DDSD_LINEARSIZE               : u32 = 0x00080000;
+const DDSD_DEPTH                    : u32 = 0x00800000;
+
+const DDPF_ALPHAPIXELS              : u32 = 0x00000001;
+const DDPF_ALPHA                    : u32 = 0x00000002;
+const DDPF_FOURCC                   : u32 = 0x00000004;
+const DDPF_RGB                      : u32 = 0x00000040;
+const DDPF_LUMINANCE                : u32 = 0x00020000;
+const DDPF_BUMPLUMINANCE            : u32 = 0x00040000;
+const DDPF_BUMPDUDV                 : u32 = 0x00080000;
+const DDPF_CATEGORIES               : u32 = DDPF_ALPHA | DDPF_RGB | DDPF_LUMINANCE | DDPF_BUMPLUMINANCE | DDPF_BUMPDUDV;
+
+const DDSCAPS_COMPLEX               : u32 = 0x00000008;
+const DDSCAPS_TEXTURE               : u32 = 0x00001000;
+const DDSCAPS_MIPMAP                : u32 = 0x00400000;
+const DDSCAPS2_CUBEMAP              : u32 = 0x00000200;
+const DDSCAPS2_CUBEMAP_ALLFACES     : u32 = 0x0000FC00;
+const DDSCAPS2_VOLUME               : u32 = 0x00200000;
+
+const DIMENSION_TEXTURE1D           : u32 = 2;
+const DIMENSION_TEXTURE2D           : u32 = 3;
+const DIMENSION_TEXTURE3D           : u32 = 4;
+const MISC_TEXTURECUBE              : u32 = 0x4;
+
+/// `DXGI_FORMAT` values of `DX10` headers with a Direct3D 9 equivalent.  `_SRGB` and `_TYPELESS` variants map to the same format.
+const DXGI_FORMATS : &[(u32, Format)] = &[
+    (  2, Format::A32B32G32R32F),       // R32G32B32A32_FLOAT
+    ( 10, Format::A16B16G16R16F),       // R16G16B16A16_FLOAT
+    ( 11, Format::A16B16G16R16),        // R16G16B16A16_UNORM
+    ( 13, Format::Q16W16V16U16),        // R16G16B16A16_SNORM
+    ( 16, Format::G32R32F),             // R32G32_FLOAT
+    ( 24, Format::A2B10G10R10),         // R10G10B10A2_UNORM
+    ( 28, Format::A8B8G8R8),            // R8G8B8A8_UNORM
+    ( 29, Format::A8B8G8R8),            // R8G8B8A8_UNORM_SRGB
+    ( 31, Format::Q8W8V8U8),            // R8G8B8A8_SNORM
+    ( 34, Format::G16R16F),             // R16G16_FLOAT
+    ( 35, Format::G16R16),              // R16G16_UNORM
+    ( 37, Format::V16U16),              // R16G16_SNORM
+    ( 40, Format::D32F_LOCKABLE),       // D32_FLOAT
+    ( 41, Format::R32F),                // R32_FLOAT
+    ( 45, Format::D24S8),               // D24_UNORM_S8_UINT
+    ( 51, Format::V8U8),                // R8G8_SNORM
+    ( 54, Format::R16F),                // R16_FLOAT
+    ( 55, Format::D16),                 // D16_UNORM
+    ( 56, Format::L16),                 // R16_UNORM
+    ( 61, Format::L8),                  // R8_UNORM
+    ( 65, Format::A8),                  // A8_UNORM
+    ( 68, Format::G8R8_G8B8),           // R8G8_B8G8_UNORM
+    ( 69, Format::R8G8_B8G8),           // G8R8_G8B8_UNORM
+    ( 70, Format::DXT1),                // BC1_TYPELESS
+    ( 71, Format::DXT1),                // BC1_UNORM
+    ( 72, Format::DXT1),                // BC1_UNORM_SRGB
+    ( 73, Format::DXT3),                // BC2_TYPELESS
+    ( 74, Format::DXT3),                // BC2_UNORM
+    ( 75, Format::DXT3),                // BC2_UNORM_SRGB
+    ( 76, Format::DXT5),                // BC3_TYPELESS
+    ( 77, Format::DXT5),                // BC3_UNORM
+    ( 78, Format::DXT5),                // BC3_UNORM_SRGB
+    ( 79, Format::ATI1),                // BC4_TYPELESS
+    ( 80, Format::ATI1),                // BC4_UNORM
+    ( 82, Format::ATI2),                // BC5_TYPELESS
+    ( 83, Format::ATI2),                // BC5_UNORM
+    ( 85, Format::R5G6B5),              // B5G6R5_UNORM
+    ( 86, Format::A1R5G5B5),            // B5G5R5A1_UNORM
+    ( 87, Format::A8R8G8B8),            // B8G8R8A8_UNORM
+    ( 88, Format::X8R8G8B8),            // B8G8R8X8_UNORM
+    ( 89, Format::A2B10G10R10_XR_BIAS), // R10G10B10_XR_BIAS_A2_UNORM
+    ( 91, Format::A8R8G8B8),            // B8G8R8A8_UNORM_SRGB
+    ( 93, Format::X8R8G8B8),            // B8G8R8X8_UNORM_SRGB
+    (107, Format::YUY2),                // YUY2
+    (115, Format::A4R4G4B4),            // B4G4R4A4_UNORM
+];
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    fn pattern(len: usize) -> Vec<u8> { (0 .. len).map(|i| (i * 7 + i / 251) as u8).collect() }
+
+    #[test] fn texture_round_trip() {
+        for format in [Format::A8R8G8B8, Format::X8R8G8B8, Format::R5G6B5, Format::A2B10G10R10, Format::A2R10G10B10, Format::G16R16, Format::L8,
+            Format::A8L8, Format::A4L4, Format::L16, Format::A8, Format::V8U8, Format::L6V5U5, Format::X8L8V8U8, Format::Q8W8V8U8, Format::V16U16,
+            Format::A2W10V10U10, Format::R8G8B8, Format::A16B16G16R16, Format::R16F, Format::A32B32G32R32F, Format::CxV8U8, Format::D24S8,
+            Format::DXT1, Format::DXT5, Format::ATI1, Format::ATI2, Format::YUY2, Format::R8G8_B8G8,
+        ] {
+            let info = FormatInfo::of(format).unwrap();
+            let (w, h) = (13, 6);
+            let data = pattern(info.surface_bytes(w, h) * 2);
+            let padded = info.row_bytes(w) + 3;
+            let mips = (0 .. 4).map(|level| TextureMipRef { data: &data[level * 8 ..], stride: if level == 0 { padded } else { info.row_bytes(w >> level) } }).collect::<Vec<_>>();
+            let dds = Dds::from_texture(format, w, h, &mips).unwrap();
+            let file = dds.to_bytes();
+            let read = Dds::from_bytes(&file).unwrap_or_else(|err| panic!("{format:?}: {err:?}"));
+            assert_eq!((read.kind(), read.format(), read.width(), read.height(), read.depth(), read.mip_levels()), (TextureKind::Texture, format, w, h, 1, 4));
+            assert_eq!(read.data(), dds.data());
+
+            let mips = read.texture_mips().unwrap();
+            assert_eq!(mips[0].data[..info.row_bytes(w)], data[..info.row_bytes(w)]);
+            assert_eq!(mips[0].data[info.row_bytes(w)..][..info.row_bytes(w)], data[padded..][..info.row_bytes(w)]);
+            assert_eq!(mips[3].data.len(), info.surface_bytes(1, 1));
+            assert!(read.cube_texture_mips().is_none() && read.volume_texture_mips().is_none());
+        }
+    }
+
+    #[test] fn cube_and_volume() {
+        let data = pattern(1024);
+        let face = |i: usize, size: usize| TextureMipRef { data: &data[i * 64 ..][.. size * size * 4], stride: size * 4 };
+        let mips = [4, 2, 1].map(|s| CubeTextureMipRef { pos_x: face(0, s), neg_x: face(1, s), pos_y: face(2, s), neg_y: face(3, s), pos_z: face(4, s), neg_z: face(5, s) });
+        let dds = Dds::from_bytes(&Dds::from_cube_texture(Format::A8R8G8B8, 4, &mips).unwrap().to_bytes()).unwrap();
+        assert_eq!((dds.kind(), dds.width(), dds.height(), dds.mip_levels(), dds.data().len()), (TextureKind::CubeTexture, 4, 4, 3, 6 * 4 * (16 + 4 + 1)));
+        let read = dds.cube_texture_mips().unwrap();
+        assert_eq!(read.len(), 3);
+        assert_eq!(read[0].neg_z.data, face(5, 4).data);
+        assert_eq!(read[2].pos_y.data, face(2, 1).data);
+
+        let mips = [
+            VolumeTextureMipRef { data: &data[..4 * 2 * 3], stride_row: 4, stride_slice: 8 },
+            VolumeTextureMipRef { data: &data[100 ..], stride_row: 3, stride_slice: 5 }, // padded
+        ];
+        let dds = Dds::from_bytes(&Dds::from_volume_texture(Format::L8, 4, 2, 3, &mips).unwrap().to_bytes()).unwrap();
+        assert_eq!((dds.kind(), dds.width(), dds.height(), dds.depth(), dds.mip_levels()), (DdsKind::VolumeTexture, 4, 2, 3, 2));
+        let read = dds.volume_texture_mips().unwrap();
+        assert_eq!(read[0].data, &data[..24]);
+        assert_eq!((read[1].data, read[1].stride_row, read[1].stride_slice), (&data[100 .. 102], 2, 2));
+    }
+
+    #[test] fn image() {
+        let mut image = TextureImage::cube_texture(FixedTextureFormat::DXT5, 8, 0).unwrap();
+        image.surface_mut(3, 1).unwrap().fill(0x5A);
+        let dds = Dds::from_bytes(&Dds::from_image(&image).unwrap().to_bytes()).unwrap();
+        assert_eq!((dds.kind(), dds.format(), dds.width(), dds.mip_levels()), (TextureKind::CubeTexture, Format::DXT5, 8, 4));
+        let read = dds.into_image().unwrap();
+        assert_eq!((read.kind(), read.levels(), read.data()), (TextureKind::CubeTexture, 4, image.data()));
+
+        let yuy2 = Dds::from_texture(Format::YUY2, 2, 1, &[TextureMipRef { data: &[0; 4], stride: 4 }]).unwrap();
+        assert_eq!(yuy2.into_image().unwrap_err().kind(), D3DERR::WRONGTEXTUREFORMAT);
+        assert_eq!(Dds::from_image(&TextureImage::texture(FixedTextureFormat::P8, 1, 1, 1).unwrap()).unwrap_err().kind(), D3DERR::WRONGTEXTUREFORMAT);
+    }
+
+    fn legacy(pf_flags: u32, fourcc: &[u8; 4], bits: u32, masks: [u32; 4]) -> Vec<u8> {
+        let mut file = Dds::from_texture(Format::L8, 1, 1, &[TextureMipRef { data: &[0x42], stride: 1 }]).unwrap().to_bytes();
+        file[80 .. 84].copy_from_slice(&pf_flags.to_le_bytes());
+        file[84 .. 88].copy_from_slice(fourcc);
+        file[88 .. 92].copy_from_slice(&bits.to_le_bytes());
+        for (i, m) in masks.iter().enumerate() { file[92 + 4 * i ..][..4].copy_from_slice(&m.to_le_bytes()) }
+        file.extend_from_slice(&[0; 16]); // enough for any 1x1 format
+        file
+    }
+
+    #[test] fn legacy_pixel_formats() {
+        let format = |file: Vec<u8>| Dds::from_bytes(&file).unwrap().format();
+        let error  = |file: Vec<u8>| Dds::from_bytes(&file).unwrap_err().kind();
+        assert_eq!(format(legacy(DDPF_RGB | DDPF_ALPHAPIXELS, b"\0\0\0\0", 32, [0xFF0000, 0xFF00, 0xFF, 0xFF000000])), Format::A8R8G8B8);
+        assert_eq!(format(legacy(DDPF_RGB, b"\0\0\0\0", 32, [0xFF0000, 0xFF00, 0xFF, 0xFF000000])), Format::X8R8G8B8); // no DDPF_ALPHAPIXELS
+        assert_eq!(format(legacy(DDPF_RGB | DDPF_ALPHAPIXELS, b"\0\0\0\0", 32, [0xFF, 0xFF00, 0xFF0000, 0xFF000000])), Format::A8B8G8R8);
+        assert_eq!(format(legacy(DDPF_RGB, b"\0\0\0\0", 16, [0xF800, 0x7E0, 0x1F, 0])), Format::R5G6B5);
+        assert_eq!(format(legacy(DDPF_LUMINANCE | DDPF_ALPHAPIXELS, b"\0\0\0\0", 16, [0xFF, 0, 0, 0xFF00])), Format::A8L8);
+        assert_eq!(format(legacy(DDPF_ALPHA, b"\0\0\0\0", 8, [0, 0, 0, 0xFF])), Format::A8);
+        assert_eq!(format(legacy(DDPF_BUMPDUDV, b"\0\0\0\0", 32, [0xFF, 0xFF00, 0xFF0000, 0xFF000000])), Format::Q8W8V8U8);
+        assert_eq!(format(legacy(DDPF_BUMPLUMINANCE, b"\0\0\0\0", 16, [0x1F, 0x3E0, 0xFC00, 0])), Format::L6V5U5);
+        assert_eq!(format(legacy(DDPF_FOURCC, b"DXT1", 0, [0; 4])), Format::DXT1);
+        assert_eq!(format(legacy(DDPF_FOURCC, b"BC5U", 0, [0; 4])), Format::ATI2);
+        assert_eq!(format(legacy(DDPF_FOURCC, &113u32.to_le_bytes(), 0, [0; 4])), Format::A16B16G16R16F);
+        assert_eq!(error(legacy(DDPF_FOURCC, b"BC5S", 0, [0; 4])), D3DERR::WRONGTEXTUREFORMAT);
+        assert_eq!(error(legacy(DDPF_FOURCC, &41u32.to_le_bytes(), 0, [0; 4])), D3DERR::WRONGTEXTUREFORMAT); // P8
+        assert_eq!(error(legacy(DDPF_RGB, b"\0\0\0\0", 32, [0xFF, 0xFF, 0xFF, 0])), D3DERR::WRONGTEXTUREFORMAT);
+    }
+
+    #[test] fn dx10() {
+        let mut file = legacy(DDPF_FOURCC, b"DX10", 0, [0; 4]);
+        let dx10 = [98u32, DIMENSION_TEXTURE2D, 0, 1, 0]; // BC7:  no Direct3D 9 equivalent
+        let ext = |file: &mut Vec<u8>, dx10: [u32; 5]| { file.splice(128 .. 128, dx10.iter().flat_map(|v| v.to_le_bytes())); };
+        ext(&mut file, dx10);
+        assert_eq!(Dds::from_bytes(&file).unwrap_err().kind(), D3DERR::WRONGTEXTUREFORMAT);
+
+        file[128 .. 148].copy_from_slice(&[87u32, DIMENSION_TEXTURE2D, MISC_TEXTURECUBE, 1, 0].iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<_>>());
+        file.extend_from_slice(&[0; 6 * 4]);
+        let dds = Dds::from_bytes(&file).unwrap();
+        assert_eq!((dds.kind(), dds.format(), dds.data().len()), (TextureKind::CubeTexture, Format::A8R8G8B8, 6 * 4));
+
+        file[128 + 12 .. 128 + 16].copy_from_slice(&2u32.to_le_bytes()); // arraySize
+        assert_eq!(Dds::from_bytes(&file).unwrap_err().kind(), D3DERR::NOTAVAILABLE);
+    }
+
+    #[test] fn errors() {
+        let file = Dds::from_texture(Format::DXT1, 8, 8, &[TextureMipRef { data: &[0; 32], stride: 16 }]).unwrap().to_bytes();
+        assert_eq!(file.len(), 128 + 32);
+        assert!(Dds::from_bytes(&file).is_ok());
+        assert_eq!(Dds::from_bytes(&file[..file.len() - 1]).unwrap_err().kind(), D3DERR::INVALIDDATA); // truncated data
+        assert_eq!(Dds::from_bytes(&file[..100]).unwrap_err().kind(), D3DERR::INVALIDDATA); // truncated header
+        assert_eq!(Dds::from_bytes(b"PNG\0").unwrap_err().kind(), D3DERR::INVALIDDATA);
+
+        let mut bad = file.clone();
+        bad[4 + 4 .. 4 + 8].copy_from_slice(&(DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT | DDSD_MIPMAPCOUNT).to_le_bytes());
+        bad[4 + 24 .. 4 + 28].copy_from_slice(&5u32.to_le_bytes()); // 5 mips claimed for 8x8
+        assert_eq!(Dds::from_bytes(&bad).unwrap_err().kind(), D3DERR::INVALIDDATA);
+
+        let mut partial_cube = file.clone();
+        partial_cube[4 + 108 .. 4 + 112].copy_from_slice(&(DDSCAPS2_CUBEMAP | 0x400).to_le_bytes());
+        assert_eq!(Dds::from_bytes(&partial_cube).unwrap_err().kind(), D3DERR::NOTAVAILABLE);
+
+        let mip = [TextureMipRef { data: &[0; 16], stride: 4 }];
+        assert_eq!(Dds::from_texture(Format::A8R8G8B8, 2, 2, &mip).unwrap_err().kind(), D3DERR::INVALIDCALL); // stride < row
+        assert_eq!(Dds::from_texture(Format::A8R8G8B8, 2, 3, &[TextureMipRef { data: &[0; 16], stride: 8 }]).unwrap_err().kind(), D3DERR::INVALIDCALL);
+        assert_eq!(Dds::from_texture(Format::A8R8G8B8, 1, 1, &[mip[0], mip[0]]).unwrap_err().kind(), D3DERR::INVALIDCALL); // too many mips
+        assert_eq!(Dds::from_texture(Format::A8R8G8B8, 0, 1, &mip).unwrap_err().kind(), D3DERR::INVALIDCALL);
+        assert_eq!(Dds::from_texture(Format::P8, 1, 1, &mip).unwrap_err().kind(), D3DERR::WRONGTEXTUREFORMAT);
+        assert_eq!(Dds::from_texture(Format::MULTI2_ARGB8, 1, 1, &mip).unwrap_err().kind(), D3DERR::WRONGTEXTUREFORMAT);
+
+        let huge = CubeTextureMipRef { pos_x: mip[0], neg_x: mip[0], pos_y: mip[0], neg_y: mip[0], pos_z: mip[0], neg_z: mip[0] }; // 384 GiB of faces claimed
+        assert_eq!(Dds::from_cube_texture(Format::A32B32G32R32F, MAX_DIMENSION, &[huge]).unwrap_err().kind(), D3DERR::INVALIDCALL);
+    }
+}
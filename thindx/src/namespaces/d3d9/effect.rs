@@ -0,0 +1,909 @@
+use crate::*;
+use crate::d3d9::*;
+use crate::d3d::{Diagnostic, HlslDeclarations, HlslFunction, HlslLocation};
+use crate::d3d::hlsl_declarations::{Parser, Tok, tokenize};
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+
+
+/// A legacy D3DX-style effect (`.fx` / `fx_2_0`): HLSL plus `technique` / `pass` blocks of render states, sampler states, and shader bindings.
+///
+/// This is a pure-Rust replacement for the parsing half of `ID3DXEffect`.  State assignments are resolved to typed
+/// [RenderStateType] / [SamplerStateValue]s up front, so typos such as `SrcBlend = SrcAplha;` are reported as
+/// [Diagnostic]s at load time instead of being silently ignored at draw time.  Use [EffectRuntime] to compile the
+/// effect's shaders and apply passes to a [Device].
+///
+/// Supported:
+/// *   `technique Name < annotations > { pass Name < annotations > { states... } }`
+/// *   Render states: `AlphaBlendEnable = TRUE;`, `SrcBlend = SrcAlpha;`, `ColorWriteEnable = Red | Green;`, `DepthBias = -0.0001;`, ...
+/// *   Sampler states: `MinFilter[0] = Linear;`, `AddressU[1] = Clamp;`, ...
+/// *   Bindings: `Sampler[0] = (samp);`, `Texture[0] = <tex>;`, `VertexShader = compile vs_2_0 vs_main(true);`, `PixelShader = NULL;`
+/// *   Globals: `< annotations >` and `sampler s = sampler_state { Texture = <tex>; MipFilter = Linear; };`
+///
+/// Unsupported (reported as errors): fixed function texture stage / transform / light / material states, `asm { ... }`
+/// shaders, and expressions referencing parameters (`CullMode = <cull>;`).
+///
+/// No preprocessing is performed: run the source through [d3d::Compiler::preprocess] first if it uses `#include`s or macros.
+///
+/// ### Examples
+/// ```rust
+/// # use thindx::d3d9::*;
+/// let effect = Effect::parse(r#"
+///     texture diffuse < string ResourceName = "brick.dds"; >;
+///     sampler diffuse_sampler = sampler_state { Texture = <diffuse>; MinFilter = Linear; AddressU = Clamp; };
+///
+///     float4 ps_main(float2 uv : TEXCOORD0) : COLOR0 { return tex2D(diffuse_sampler, uv); }
+///
+///     technique Translucent < string Category = "World"; > {
+///         pass P0 {
+///             AlphaBlendEnable    = TRUE;
+///             SrcBlend            = SrcAlpha;
+///             DestBlend           = InvSrcAlpha;
+///             ZWriteEnable        = false;
+///             MaxAnisotropy[0]    = 8;
+///             VertexShader        = NULL;
+///             PixelShader         = compile ps_2_0 ps_main();
+///         }
+///     }
+/// "#).unwrap();
+///
+/// let pass = &effect.technique("Translucent").unwrap().passes[0];
+/// assert_eq!(pass.states[1], EffectState::RenderState(RS::SrcBlend, Blend::SrcAlpha.into()));
+/// assert_eq!(pass.states[4], EffectState::SamplerState(0, SampV::MaxAnisotropy(8)));
+///
+/// let sampler = effect.parameter("diffuse_sampler").unwrap().sampler.as_ref().unwrap();
+/// assert_eq!(sampler.texture.as_deref(), Some("diffuse"));
+/// assert_eq!(sampler.states, [SampV::MinFilter(TexF::Linear), SampV::AddressU(TAddress::Clamp)]);
+///
+/// let err = Effect::parse("technique T { pass { SrcBlend = SrcAplha; } }").unwrap_err();
+/// assert_eq!(err.to_string(), "error: invalid value for SrcBlend");
+/// ```
+///
+/// ### See Also
+/// *   [d3d::HlslDeclarations] - for the declarations of non-effect HLSL
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Effect {
+    /// The effect's HLSL declarations (functions, structs, globals, ...)
+    pub declarations:   HlslDeclarations,
+
+    /// Non-`static` global variables, with their annotations and `sampler_state`s
+    pub parameters:     Vec<EffectParameter>,
+
+    /// `technique` blocks, in declaration order
+    pub techniques:     Vec<EffectTechnique>,
+}
+
+/// A global variable of an [Effect], such as `float4 tint < string UIName = "Tint"; > = 1;` or `sampler s = sampler_state { ... };`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EffectParameter {
+    /// The variable's name.
+    pub name:           String,
+
+    /// The variable's type (e.g. `"float4"`, `"texture"`, `"sampler2D"`.)
+    pub ty:             String,
+
+    /// `< type name = value; ... >` annotations.
+    pub annotations:    Vec<EffectAnnotation>,
+
+    /// The `sampler_state { ... }` initializer, if any.
+    pub sampler:        Option<EffectSampler>,
+
+    /// Where the variable was declared.
+    pub location:       HlslLocation,
+}
+
+/// A `sampler_state { Texture = <tex>; MinFilter = Linear; ... }` block.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EffectSampler {
+    /// The texture parameter bound by `Texture = <name>;`, if any.
+    pub texture:        Option<String>,
+
+    /// Sampler states, in declaration order.
+    pub states:         Vec<SamplerStateValue>,
+}
+
+/// An annotation such as `string UIName = "Tint";` or `float UIMax = 1.0;`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EffectAnnotation {
+    /// The annotation's type (e.g. `"string"`, `"float"`.)
+    pub ty:             String,
+
+    /// The annotation's name.
+    pub name:           String,
+
+    /// The annotation's value, as written (string literals are unquoted.)
+    pub value:          String,
+}
+
+/// A `technique Name { pass ... }` block.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EffectTechnique {
+    /// The technique's name (empty if unnamed.)
+    pub name:           String,
+
+    /// `< type name = value; ... >` annotations.
+    pub annotations:    Vec<EffectAnnotation>,
+
+    /// The technique's passes, in order.
+    pub passes:         Vec<EffectPass>,
+
+    /// Where the technique was declared.
+    pub location:       HlslLocation,
+}
+
+/// A `pass Name { states... }` block.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EffectPass {
+    /// The pass's name (empty if unnamed.)
+    pub name:           String,
+
+    /// `< type name = value; ... >` annotations.
+    pub annotations:    Vec<EffectAnnotation>,
+
+    /// State assignments, in declaration order.
+    pub states:         Vec<EffectState>,
+
+    /// Where the pass was declared.
+    pub location:       HlslLocation,
+}
+
+/// A single state assignment of an [EffectPass].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EffectState {
+    /// `SrcBlend = SrcAlpha;`
+    RenderState(RenderStateType, u32),
+
+    /// `MinFilter[sampler] = Linear;`
+    SamplerState(u32, SamplerStateValue),
+
+    /// `Texture[sampler] = <texture_parameter>;`
+    Texture(u32, String),
+
+    /// `Sampler[sampler] = (sampler_parameter);` - binds the parameter's texture and `sampler_state`.
+    Sampler(u32, String),
+
+    /// `VertexShader = compile vs_2_0 entry(args...);` or `VertexShader = NULL;`
+    VertexShader(Option<EffectShader>),
+
+    /// `PixelShader = compile ps_2_0 entry(args...);` or `PixelShader = NULL;`
+    PixelShader(Option<EffectShader>),
+}
+
+/// A `compile profile entry(arguments...)` shader binding.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EffectShader {
+    /// The shader profile (e.g. `"vs_2_0"`, `"ps_3_0"`.)
+    pub profile:        String,
+
+    /// The entry point's name.
+    pub entry:          String,
+
+    /// Arguments for the entry point's `uniform` parameters, as written.
+    pub arguments:      Vec<String>,
+}
+
+impl Effect {
+    /// Parse (preprocessed) `.fx` source.
+    ///
+    /// ### Errors
+    /// *   [Diagnostic] - if a declaration is malformed, or a state / state value is unknown or unsupported
+    //#allow_missing_argument_docs
+    pub fn parse(source: &str) -> Result<Self, Diagnostic> {
+        let declarations = HlslDeclarations::parse(source)?;
+        let mut p = Parser { tokens: tokenize(source)?, pos: 0 };
+        let (mut parameters, mut techniques) = (Vec::new(), Vec::new());
+
+        while let Some(tok) = p.peek() {
+            match tok {
+                Tok::Punct(';')                             => { p.pos += 1; },
+                Tok::Punct('[')                             => { p.attribute()?; },
+                Tok::Ident(kw) if kw == "technique"         => techniques.push(technique(&mut p)?),
+                Tok::Ident(kw) if SKIPPED_BLOCKS.contains(&kw.as_str()) => p.skip_block()?,
+                Tok::Ident(kw) if kw == "typedef" || (kw == "struct" && matches!(p.peek_at(2), Some(Tok::Punct('{')))) => p.skip_statement()?,
+                _                                           => parameters.extend(globals(&mut p)?),
+            }
+        }
+        Ok(Self { declarations, parameters, techniques })
+    }
+
+    /// Find a `technique` by name.
+    pub fn technique(&self, name: &str) -> Option<&EffectTechnique> { self.techniques.iter().find(|t| t.name == name) }
+
+    /// Find a global parameter by name.
+    pub fn parameter(&self, name: &str) -> Option<&EffectParameter> { self.parameters.iter().find(|p| p.name == name) }
+}
+
+impl EffectTechnique {
+    /// Find a `pass` by name.
+    pub fn pass(&self, name: &str) -> Option<&EffectPass> { self.passes.iter().find(|p| p.name == name) }
+}
+
+
+
+/// An [Effect] with its shaders compiled and created on a [Device], ready to apply passes - an `ID3DXEffect` replacement.
+///
+/// Applying a pass sets, in order:
+/// *   The pass's render states, sampler states, texture / sampler bindings, and shaders
+/// *   The `sampler_state` and texture of every sampler the pass's shaders read, at the registers in their `CTAB` constant tables
+/// *   Every `float4` register constant set via [set_vectors](Self::set_vectors) that the pass's shaders read
+///
+/// Nothing is restored afterwards: capture a [StateBlock] around passes if you need that.
+///
+/// ### Examples
+/// ```rust
+/// # use dev::d3d9::*; let device = device_test();
+/// # let d3dc = d3d::Compiler::load_system(47).unwrap();
+/// let source = r#"
+///     float4 tint;
+///     texture diffuse;
+///     sampler diffuse_sampler = sampler_state { Texture = <diffuse>; MinFilter = Linear; };
+///     float4 vs_main(float4 pos : POSITION) : POSITION { return pos; }
+///     float4 ps_main(uniform float scale) : COLOR0 { return tex2D(diffuse_sampler, 0.5) * tint * scale; }
+///     technique Main { pass { CullMode = None; VertexShader = compile vs_2_0 vs_main(); PixelShader = compile ps_2_0 ps_main(2.0); } }
+/// "#;
+/// let effect = Effect::parse(source).unwrap();
+/// let mut runtime = EffectRuntime::new(&d3dc, &device, &effect, source, "example.fx", d3d::Compile::None).unwrap();
+///
+/// let texture = device.create_texture(4, 4, 1, Usage::None, Format::A8R8G8B8, Pool::Managed, ()).unwrap();
+/// runtime.set_texture("diffuse", &*texture);
+/// runtime.set_vectors("tint", &[[1.0, 0.5, 0.5, 1.0]]);
+/// runtime.apply_pass(&device, "Main", 0).unwrap();
+/// assert_eq!(device.get_render_state_untyped(RS::CullMode).unwrap(), Cull::None.into());
+/// ```
+pub struct EffectRuntime {
+    effect:     Effect,
+    shaders:    BTreeMap<EffectShader, CompiledShader>,
+    textures:   BTreeMap<String, BaseTexture>,
+    vectors:    BTreeMap<String, Vec<[f32; 4]>>,
+}
+
+impl EffectRuntime {
+    /// Compile and create every shader referenced by `effect`'s passes.
+    ///
+    /// `effect` must have been parsed from `source`.  Shaders with `compile` arguments are compiled via a generated
+    /// entry point, which passes the arguments to the entry's `uniform` parameters in order.
+    ///
+    /// ### Errors
+    /// *   [E::INVALIDARG]     - if a shader targets a non-D3D9 profile, or `compile` arguments don't match the entry's `uniform` parameters
+    /// *   [E::FAIL]           - if a shader failed to compile ([CompileError::errors](d3d::CompileError::errors) has the details)
+    /// *   [D3DERR::INVALIDCALL] / [E::OUTOFMEMORY] - if creating a shader failed
+    ///
+    /// ### Examples
+    /// See [EffectRuntime]
+    //#allow_missing_argument_docs
+    pub fn new(compiler: &d3d::Compiler, device: &Device, effect: &Effect, source: &str, source_name: &str, flags: impl Into<d3d::Compile>) -> Result<Self, d3d::CompileError> {
+        fn_context!(d3d9::EffectRuntime::new);
+        let flags = flags.into();
+        let mut shaders = BTreeMap::new();
+
+        for state in effect.techniques.iter().flat_map(|t| t.passes.iter()).flat_map(|p| p.states.iter()) {
+            let (vertex, shader) = match state {
+                EffectState::VertexShader(Some(shader)) => (true, shader),
+                EffectState::PixelShader(Some(shader))  => (false, shader),
+                _                                       => continue,
+            };
+            if shaders.contains_key(shader) { continue }
+
+            let version = shader.profile.strip_prefix(if vertex { "vs_" } else { "ps_" }).and_then(|v| v.chars().next());
+            if !matches!(version, Some('1' ..= '3')) { return Err(fn_error!(E::INVALIDARG).into()) }
+
+            let (source, entry) = if shader.arguments.is_empty() {
+                (Cow::Borrowed(source), shader.entry.as_str())
+            } else {
+                let f = effect.declarations.function(&shader.entry).ok_or_else(|| fn_error!(E::INVALIDARG))?;
+                let wrapper = wrapper(f, &shader.arguments).ok_or_else(|| fn_error!(E::INVALIDARG))?;
+                (Cow::Owned(format!("{}\n{}", source, wrapper)), WRAPPER)
+            };
+
+            let compiled = compiler.compile(source.as_bytes(), source_name, None, None, entry, shader.profile.as_str(), flags, d3d::CompileEffect::None)?;
+            let bytes = compiled.shader.as_bytes();
+            let tokens = bytes.chunks_exact(4).map(|t| u32::from_le_bytes([t[0], t[1], t[2], t[3]])).collect::<Vec<_>>();
+            // SAFETY: ✔️ `tokens` is a complete SM1-3 shader, just generated by the compiler for a `vs_[1-3]_*` / `ps_[1-3]_*` profile
+            let object = if vertex {
+                ShaderObject::Vertex(unsafe { device.create_vertex_shader(&tokens) }?)
+            } else {
+                ShaderObject::Pixel(unsafe { device.create_pixel_shader(&tokens) }?)
+            };
+            shaders.insert(shader.clone(), CompiledShader { object, constants: constant_table(bytes).unwrap_or_default() });
+        }
+
+        Ok(Self { effect: effect.clone(), shaders, textures: Default::default(), vectors: Default::default() })
+    }
+
+    /// The [Effect] this runtime was created from.
+    pub fn effect(&self) -> &Effect { &self.effect }
+
+    /// Set (or clear) the texture bound to the texture parameter `name` by `Texture = <name>;` states.
+    //#allow_missing_argument_docs
+    pub fn set_texture<'t>(&mut self, name: &str, texture: impl Into<Option<&'t BaseTexture>>) {
+        match texture.into() {
+            Some(texture)   => { self.textures.insert(name.into(), texture.clone()); },
+            None            => { self.textures.remove(name); },
+        }
+    }
+
+    /// Set the `float4` registers of the parameter `name` (a `float4x4` is four registers: rows if `row_major`, otherwise columns.)
+    //#allow_missing_argument_docs
+    pub fn set_vectors(&mut self, name: &str, values: &[[f32; 4]]) {
+        self.vectors.insert(name.into(), values.into());
+    }
+
+    /// Apply pass # `pass` of `technique` to `device`.
+    ///
+    /// ### Errors
+    /// *   [E::INVALIDARG]     - if `technique` or `pass` doesn't exist, or a sampler index is out of range
+    /// *   [D3DERR::INVALIDCALL] - if a constant doesn't fit in the device's registers
+    //#allow_missing_argument_docs
+    pub fn apply_pass(&self, device: &Device, technique: &str, pass: usize) -> Result<(), Error> {
+        fn_context!(d3d9::EffectRuntime::apply_pass);
+        let t = self.effect.technique(technique).ok_or_else(|| fn_param_error!(technique, E::INVALIDARG))?;
+        let p = t.passes.get(pass).ok_or_else(|| fn_param_error!(pass, E::INVALIDARG))?;
+
+        for state in p.states.iter() {
+            match state {
+                EffectState::RenderState(rs, value)         => device.set_render_state_untyped(*rs, *value)?,
+                EffectState::SamplerState(sampler, value)   => device.set_sampler_state(*sampler, *value)?,
+                EffectState::Texture(sampler, name)         => self.bind_texture(device, *sampler, Some(name))?,
+                EffectState::Sampler(sampler, name)         => self.bind_sampler(device, *sampler, name)?,
+                EffectState::VertexShader(shader)           => self.bind_shader(device, true, shader.as_ref())?,
+                EffectState::PixelShader(shader)            => self.bind_shader(device, false, shader.as_ref())?,
+            }
+        }
+        Ok(())
+    }
+
+    fn bind_shader(&self, device: &Device, vertex: bool, shader: Option<&EffectShader>) -> Result<(), Error> {
+        let compiled = match shader.and_then(|s| self.shaders.get(s)) {
+            Some(compiled)      => compiled,
+            None if vertex      => return device.set_vertex_shader(None::<&VertexShader>),
+            None                => return device.set_pixel_shader(None::<&PixelShader>),
+        };
+        match &compiled.object {
+            ShaderObject::Vertex(vs)    => device.set_vertex_shader(vs)?,
+            ShaderObject::Pixel(ps)     => device.set_pixel_shader(ps)?,
+        }
+
+        for c in compiled.constants.iter() {
+            match c.register_set {
+                REGISTER_SET_SAMPLER    => {
+                    let sampler = u32::from(c.index) + if vertex { VERTEX_TEXTURE_SAMPLER0 } else { 0 };
+                    self.bind_sampler(device, sampler, &c.name)?;
+                },
+                REGISTER_SET_FLOAT4     => if let Some(values) = self.vectors.get(&c.name) {
+                    let values = &values[.. values.len().min(c.count.into())];
+                    if vertex {
+                        device.set_vertex_shader_constant_f(c.index.into(), values)?;
+                    } else {
+                        device.set_pixel_shader_constant_f(c.index.into(), values)?;
+                    }
+                },
+                _                       => {},
+            }
+        }
+        Ok(())
+    }
+
+    fn bind_sampler(&self, device: &Device, sampler: u32, name: &str) -> Result<(), Error> {
+        let state = match self.effect.parameter(name).and_then(|p| p.sampler.as_ref()) {
+            Some(state) => state,
+            None        => return self.bind_texture(device, sampler, None),
+        };
+        self.bind_texture(device, sampler, state.texture.as_deref())?;
+        for value in state.states.iter() { device.set_sampler_state(sampler, *value)?; }
+        Ok(())
+    }
+
+    fn bind_texture(&self, device: &Device, sampler: u32, name: Option<&str>) -> Result<(), Error> {
+        fn_context!(d3d9::EffectRuntime::bind_texture);
+        if !(sampler < 16 || (VERTEX_TEXTURE_SAMPLER0 .. VERTEX_TEXTURE_SAMPLER0 + 4).contains(&sampler)) { return Err(fn_param_error!(sampler, E::INVALIDARG)) }
+        let texture = name.and_then(|name| self.textures.get(name));
+        // SAFETY: ✔️ `sampler` was bounds checked above, and `texture` is kept alive by `self`
+        unsafe { device.set_texture(sampler, texture) }
+    }
+}
+
+
+
+/// `D3DVERTEXTEXTURESAMPLER0`
+const VERTEX_TEXTURE_SAMPLER0 : u32 = 257;
+
+/// `D3DXRS_FLOAT4`
+const REGISTER_SET_FLOAT4   : u16 = 2;
+
+/// `D3DXRS_SAMPLER`
+const REGISTER_SET_SAMPLER  : u16 = 3;
+
+/// The entry point generated for shaders with `compile` arguments
+const WRAPPER : &str = "thindx_effect_entry";
+
+/// Top-level blocks that are skipped entirely
+const SKIPPED_BLOCKS : &[&str] = &["cbuffer", "tbuffer", "namespace", "technique10", "technique11", "interface", "class"];
+
+enum ShaderObject { Vertex(VertexShader), Pixel(PixelShader) }
+
+struct CompiledShader { object: ShaderObject, constants: Vec<ShaderConstant> }
+
+/// A `D3DXSHADER_CONSTANTINFO` from the `CTAB` comment of SM1-3 bytecode
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ShaderConstant { name: String, register_set: u16, index: u16, count: u16 }
+
+/// How to interpret a state's value
+#[derive(Clone, Copy)]
+enum Values {
+    Int,
+    Float,
+    Names(&'static [(&'static str, u32)]),
+}
+
+macro_rules! names { ( $ty:ident; $($name:ident),* $(,)? ) => { Values::Names(&[$((stringify!($name), $ty::$name.into_inner())),*]) } }
+
+const BOOL      : Values = Values::Names(&[]); // `TRUE` / `FALSE` are always accepted
+const ZBUFFER   : Values = names!(ZBufferType; False, True, UseW);
+const FILL      : Values = names!(FillMode; Point, Wireframe, Solid);
+const SHADE     : Values = names!(ShadeMode; Flat, Gouraud, Phong);
+const BLEND     : Values = names!(Blend; Zero, One, SrcColor, InvSrcColor, SrcAlpha, InvSrcAlpha, DestAlpha, InvDestAlpha, DestColor, InvDestColor, SrcAlphaSat, BothSrcAlpha, BothInvSrcAlpha, BlendFactor, InvBlendFactor, SrcColor2, InvSrcColor2);
+const BLEND_OP  : Values = names!(BlendOp; Add, Subtract, RevSubtract, Min, Max);
+const CMP       : Values = names!(CmpFunc; Never, Less, Equal, LessEqual, Greater, NotEqual, GreaterEqual, Always);
+const CULL      : Values = names!(Cull; None, CW, CCW);
+const FOG       : Values = names!(FogMode; None, Exp, Exp2, Linear);
+const STENCIL   : Values = names!(StencilOp; Keep, Zero, Replace, IncrSat, DecrSat, Invert, Incr, Decr);
+const MATERIAL  : Values = names!(MaterialColorSource; Material, Color1, Color2);
+const PATCH     : Values = names!(PatchEdgeStyle; Discrete, Continuous);
+const ADDRESS   : Values = names!(TextureAddress; Wrap, Mirror, Clamp, Border, MirrorOnce);
+const FILTER    : Values = names!(TextureFilterType; None, Point, Linear, Anisotropic, PyramidalQuad, GaussianQuad, ConvolutionMono);
+const COLOR_WRITE : Values = Values::Names(&[("Red", 1), ("Green", 2), ("Blue", 4), ("Alpha", 8)]);
+const WRAP      : Values = Values::Names(&[("U", 1), ("V", 2), ("W", 4)]);
+const VERTEX_BLEND : Values = Values::Names(&[("Disable", 0), ("Tweening", 255)]);
+
+macro_rules! rs { ( $($name:ident : $values:expr),* $(,)? ) => { &[$((stringify!($name), RenderStateType::$name, $values)),*] } }
+
+const RENDER_STATES : &[(&str, RenderStateType, Values)] = rs! {
+    ZEnable: ZBUFFER, FillMode: FILL, ShadeMode: SHADE, ZWriteEnable: BOOL, AlphaTestEnable: BOOL, LastPixel: BOOL,
+    SrcBlend: BLEND, DestBlend: BLEND, CullMode: CULL, ZFunc: CMP, AlphaRef: Values::Int, AlphaFunc: CMP,
+    DitherEnable: BOOL, AlphaBlendEnable: BOOL, FogEnable: BOOL, SpecularEnable: BOOL, FogColor: Values::Int,
+    FogTableMode: FOG, FogStart: Values::Float, FogEnd: Values::Float, FogDensity: Values::Float, RangeFogEnable: BOOL,
+    StencilEnable: BOOL, StencilFail: STENCIL, StencilZFail: STENCIL, StencilPass: STENCIL, StencilFunc: CMP,
+    StencilRef: Values::Int, StencilMask: Values::Int, StencilWriteMask: Values::Int, TextureFactor: Values::Int,
+    Wrap0: WRAP, Wrap1: WRAP, Wrap2: WRAP, Wrap3: WRAP, Wrap4: WRAP, Wrap5: WRAP, Wrap6: WRAP, Wrap7: WRAP,
+    Clipping: BOOL, Lighting: BOOL, Ambient: Values::Int, FogVertexMode: FOG, ColorVertex: BOOL, LocalViewer: BOOL,
+    NormalizeNormals: BOOL, DiffuseMaterialSource: MATERIAL, SpecularMaterialSource: MATERIAL,
+    AmbientMaterialSource: MATERIAL, EmissiveMaterialSource: MATERIAL, VertexBlend: VERTEX_BLEND,
+    ClipPlaneEnable: Values::Int, PointSize: Values::Float, PointSizeMin: Values::Float, PointSpriteEnable: BOOL,
+    PointScaleEnable: BOOL, PointScaleA: Values::Float, PointScaleB: Values::Float, PointScaleC: Values::Float,
+    MultiSampleAntiAlias: BOOL, MultiSampleMask: Values::Int, PatchEdgeStyle: PATCH, DebugMonitorToken: Values::Int,
+    PointSizeMax: Values::Float, IndexedVertexBlendEnable: BOOL, ColorWriteEnable: COLOR_WRITE,
+    TweenFactor: Values::Float, BlendOp: BLEND_OP, PositionDegree: Values::Int, NormalDegree: Values::Int,
+    ScissorTestEnable: BOOL, SlopeScaleDepthBias: Values::Float, AntiAliasedLineEnable: BOOL,
+    MinTessellationLevel: Values::Float, MaxTessellationLevel: Values::Float, AdaptiveTessX: Values::Float,
+    AdaptiveTessY: Values::Float, AdaptiveTessZ: Values::Float, AdaptiveTessW: Values::Float,
+    EnableAdaptiveTessellation: BOOL, TwoSidedStencilMode: BOOL, CcwStencilFail: STENCIL, CcwStencilZFail: STENCIL,
+    CcwStencilPass: STENCIL, CcwStencilFunc: CMP, ColorWriteEnable1: COLOR_WRITE, ColorWriteEnable2: COLOR_WRITE,
+    ColorWriteEnable3: COLOR_WRITE, BlendFactor: Values::Int, SRGBWriteEnable: BOOL, DepthBias: Values::Float,
+    Wrap8: WRAP, Wrap9: WRAP, Wrap10: WRAP, Wrap11: WRAP, Wrap12: WRAP, Wrap13: WRAP, Wrap14: WRAP, Wrap15: WRAP,
+    SeparateAlphaBlendEnable: BOOL, SrcBlendAlpha: BLEND, DestBlendAlpha: BLEND, BlendOpAlpha: BLEND_OP,
+};
+
+/// `modifiers type name [arrays] [: annotations] [< annotations >] [= initializer], ...;`, or a function (skipped)
+fn globals(p: &mut Parser) -> Result<Vec<EffectParameter>, Diagnostic> {
+    let mut location = p.location();
+    let (modifiers, ty) = p.ty()?;
+    let mut name = p.ident()?;
+    if p.is_punct('(') {
+        p.skip_group()?;
+        p.skip_until(&['{', ';'])?;
+        if p.is_punct('{') { p.skip_group()? } else { p.pos += 1 }
+        return Ok(Vec::new())
+    }
+
+    let mut parameters = Vec::new();
+    loop {
+        while p.is_punct('[') { p.skip_group()? }
+        let (mut semantic, mut register, mut packoffset) = (None, None, None);
+        p.annotations(&mut semantic, &mut register, &mut packoffset)?;
+        let annotations = if p.is_punct('<') { annotations(p)? } else { Vec::new() };
+        let mut sampler = None;
+        if p.is_punct('=') {
+            p.pos += 1;
+            if p.peek() == Some(&Tok::Ident("sampler_state".into())) {
+                p.pos += 1;
+                sampler = Some(sampler_state(p)?);
+            } else {
+                p.skip_until(&[',', ';'])?;
+            }
+        } else if p.is_punct('{') {
+            p.skip_group()?; // D3D10 style state blocks: `SamplerState s { Filter = ...; };`
+        }
+        if !modifiers.iter().any(|m| m == "static") {
+            parameters.push(EffectParameter { name, ty: ty.clone(), annotations, sampler, location });
+        }
+        match p.next()? {
+            Tok::Punct(',') => { location = p.location(); name = p.ident()?; },
+            Tok::Punct(';') => return Ok(parameters),
+            _               => { p.pos -= 1; return Err(p.error("expected ';'")) },
+        }
+    }
+}
+
+/// `< type name = value; ... >`
+fn annotations(p: &mut Parser) -> Result<Vec<EffectAnnotation>, Diagnostic> {
+    p.punct('<')?;
+    let mut annotations = Vec::new();
+    while !p.is_punct('>') {
+        let (_, ty) = p.ty()?;
+        let name = p.ident()?;
+        p.punct('=')?;
+        let start = p.pos;
+        p.skip_until(&[';'])?;
+        let value = p.text(start);
+        p.punct(';')?;
+        annotations.push(EffectAnnotation { ty, name, value });
+    }
+    p.pos += 1;
+    Ok(annotations)
+}
+
+/// `{ Texture = <name>; State = Value; ... }` following `sampler_state`
+fn sampler_state(p: &mut Parser) -> Result<EffectSampler, Diagnostic> {
+    p.punct('{')?;
+    let mut sampler = EffectSampler::default();
+    loop {
+        match p.peek() {
+            Some(Tok::Punct('}'))   => { p.pos += 1; return Ok(sampler) },
+            Some(Tok::Punct(';'))   => { p.pos += 1; continue },
+            _                       => {},
+        }
+        let start = p.pos;
+        let state = p.ident()?;
+        p.punct('=')?;
+        if state.eq_ignore_ascii_case("texture") {
+            sampler.texture = Some(reference(p)?);
+        } else if let Some(value) = sampler_state_value(p, &state)? {
+            sampler.states.push(value);
+        } else {
+            p.pos = start;
+            return Err(p.error(format!("unknown sampler state {:?}", state)));
+        }
+        p.punct(';')?;
+    }
+}
+
+/// `technique [Name] [< annotations >] { passes... }`
+fn technique(p: &mut Parser) -> Result<EffectTechnique, Diagnostic> {
+    let location = p.location();
+    p.pos += 1; // "technique"
+    let name = if let Some(Tok::Ident(_)) = p.peek() { p.ident()? } else { String::new() };
+    let annotations = if p.is_punct('<') { annotations(p)? } else { Vec::new() };
+    p.punct('{')?;
+    let mut passes = Vec::new();
+    loop {
+        match p.peek() {
+            Some(Tok::Punct('}'))               => { p.pos += 1; break },
+            Some(Tok::Punct(';'))               => p.pos += 1,
+            Some(Tok::Ident(kw)) if kw == "pass"=> passes.push(pass(p)?),
+            _                                   => return Err(p.error("expected 'pass' or '}'")),
+        }
+    }
+    Ok(EffectTechnique { name, annotations, passes, location })
+}
+
+/// `pass [Name] [< annotations >] { states... }`
+fn pass(p: &mut Parser) -> Result<EffectPass, Diagnostic> {
+    let location = p.location();
+    p.pos += 1; // "pass"
+    let name = if let Some(Tok::Ident(_)) = p.peek() { p.ident()? } else { String::new() };
+    let annotations = if p.is_punct('<') { annotations(p)? } else { Vec::new() };
+    p.punct('{')?;
+    let mut states = Vec::new();
+    loop {
+        match p.peek() {
+            Some(Tok::Punct('}'))   => { p.pos += 1; break },
+            Some(Tok::Punct(';'))   => p.pos += 1,
+            _                       => states.push(state(p)?),
+        }
+    }
+    Ok(EffectPass { name, annotations, states, location })
+}
+
+/// `State[index] = value;`
+fn state(p: &mut Parser) -> Result<EffectState, Diagnostic> {
+    let start = p.pos;
+    let name = p.ident()?;
+    let index = if p.is_punct('[') {
+        p.pos += 1;
+        let index = match p.peek() { Some(Tok::Number(n)) => number(n, false), _ => None }.ok_or_else(|| p.error("expected an index"))?;
+        p.pos += 1;
+        p.punct(']')?;
+        Some(index)
+    } else {
+        None
+    };
+    p.punct('=')?;
+
+    let state = match name.to_ascii_lowercase().as_str() {
+        "vertexshader"  => EffectState::VertexShader(shader(p)?),
+        "pixelshader"   => EffectState::PixelShader(shader(p)?),
+        "texture"       => EffectState::Texture(index.unwrap_or(0), reference(p)?),
+        "sampler"       => EffectState::Sampler(index.unwrap_or(0), reference(p)?),
+        _               => if let Some(value) = sampler_state_value(p, &name)? {
+            EffectState::SamplerState(index.unwrap_or(0), value)
+        } else if let Some(&(_, rs, values)) = RENDER_STATES.iter().find(|(n, ..)| n.eq_ignore_ascii_case(&name)).filter(|_| index.is_none()) {
+            EffectState::RenderState(rs, value(p, &name, values)?)
+        } else {
+            p.pos = start;
+            return Err(p.error(format!("unknown or unsupported state {:?}", name)));
+        },
+    };
+    p.punct(';')?;
+    Ok(state)
+}
+
+/// `compile profile entry(args...)` or `NULL`
+fn shader(p: &mut Parser) -> Result<Option<EffectShader>, Diagnostic> {
+    match p.peek() {
+        Some(Tok::Ident(kw)) if kw.eq_ignore_ascii_case("null") => { p.pos += 1; return Ok(None) },
+        Some(Tok::Ident(kw)) if kw == "compile"                 => p.pos += 1,
+        _                                                       => return Err(p.error("expected 'compile' or NULL")),
+    }
+    let profile = p.ident()?;
+    let entry = p.ident()?;
+    p.punct('(')?;
+    let arguments = p.arguments(')')?;
+    Ok(Some(EffectShader { profile, entry, arguments }))
+}
+
+/// `<name>`, `(name)`, or `name`
+fn reference(p: &mut Parser) -> Result<String, Diagnostic> {
+    let close = match p.peek() { Some(Tok::Punct('<')) => Some('>'), Some(Tok::Punct('(')) => Some(')'), _ => None };
+    if close.is_some() { p.pos += 1 }
+    let name = p.ident()?;
+    if let Some(close) = close { p.punct(close)? }
+    Ok(name)
+}
+
+/// A typed sampler state value, or [None] (without consuming anything) if `state` isn't a sampler state
+fn sampler_state_value(p: &mut Parser, state: &str) -> Result<Option<SamplerStateValue>, Diagnostic> {
+    use SamplerStateValue as SV;
+    Ok(Some(match state.to_ascii_lowercase().as_str() {
+        "addressu"      => SV::AddressU(TextureAddress::from_unchecked(value(p, state, ADDRESS)?)),
+        "addressv"      => SV::AddressV(TextureAddress::from_unchecked(value(p, state, ADDRESS)?)),
+        "addressw"      => SV::AddressW(TextureAddress::from_unchecked(value(p, state, ADDRESS)?)),
+        "bordercolor"   => SV::BorderColor(d3d::Color::from(value(p, state, Values::Int)?)),
+        "magfilter"     => SV::MagFilter(TextureFilterType::from_unchecked(value(p, state, FILTER)?)),
+        "minfilter"     => SV::MinFilter(TextureFilterType::from_unchecked(value(p, state, FILTER)?)),
+        "mipfilter"     => SV::MipFilter(TextureFilterType::from_unchecked(value(p, state, FILTER)?)),
+        "mipmaplodbias" => SV::MipMapLODBias(value(p, state, Values::Float)?),
+        "maxmiplevel"   => SV::MaxMipLevel(value(p, state, Values::Int)?),
+        "maxanisotropy" => SV::MaxAnisotropy(value(p, state, Values::Int)?),
+        "srgbtexture"   => SV::SRGBTexture(value(p, state, BOOL)? != 0),
+        "elementindex"  => SV::ElementIndex(value(p, state, Values::Int)?),
+        "dmapoffset"    => SV::DMapOffset(value(p, state, Values::Int)?),
+        _               => return Ok(None),
+    }))
+}
+
+/// `TRUE`, `FALSE`, a named value, or a number - or several, `|`ed together - up to (but excluding) the `;`
+fn value(p: &mut Parser, state: &str, values: Values) -> Result<u32, Diagnostic> {
+    let mut value = 0;
+    loop {
+        let start = p.pos;
+        let negative = p.is_punct('-');
+        if negative { p.pos += 1 }
+        let term = match (p.next()?, values) {
+            (Tok::Ident(i), _) if i.eq_ignore_ascii_case("true")    => Some(1),
+            (Tok::Ident(i), _) if i.eq_ignore_ascii_case("false")   => Some(0),
+            (Tok::Ident(i), Values::Names(names))                   => names.iter().find(|(n, _)| n.eq_ignore_ascii_case(&i)).map(|(_, v)| *v),
+            (Tok::Number(n), _)                                     => number(&format!("{}{}", if negative { "-" } else { "" }, n), matches!(values, Values::Float)),
+            _                                                       => None,
+        };
+        match term {
+            Some(term)  => value |= term,
+            None        => { p.pos = start; return Err(p.error(format!("invalid value for {}", state))) },
+        }
+        if !p.is_punct('|') { return Ok(value) }
+        p.pos += 1;
+    }
+}
+
+/// Parse an integer (`12`, `0xFF00FF00`, `-1`) or float (`0.5`, `1.0f`, `-2`) state value into its `DWORD` representation
+fn number(text: &str, float: bool) -> Option<u32> {
+    let (negative, digits) = match text.strip_prefix('-') { Some(digits) => (true, digits), None => (false, text) };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        u32::from_str_radix(hex.trim_end_matches(['u', 'U', 'l', 'L']), 16).ok()?
+    } else if float {
+        let value = digits.trim_end_matches(['f', 'F', 'h', 'H']).parse::<f32>().ok()?;
+        return Some(if negative { -value } else { value }.to_bits())
+    } else {
+        digits.trim_end_matches(['u', 'U', 'l', 'L']).parse::<u32>().ok()?
+    };
+    Some(if negative { value.wrapping_neg() } else { value })
+}
+
+/// Generate an entry point passing `arguments` to the `uniform` parameters of `f`
+fn wrapper(f: &HlslFunction, arguments: &[String]) -> Option<String> {
+    let (mut parameters, mut call) = (Vec::new(), Vec::new());
+    let mut arguments = arguments.iter();
+    for param in f.parameters.iter() {
+        if param.modifiers.iter().any(|m| m == "uniform") {
+            call.push(arguments.next()?.clone());
+            continue;
+        }
+        let mut decl = String::new();
+        for m in param.modifiers.iter() { let _ = write!(decl, "{} ", m); }
+        let _ = write!(decl, "{} {}", param.ty, param.name);
+        for a in param.array.iter() { let _ = write!(decl, "[{}]", a); }
+        if let Some(semantic) = param.semantic.as_ref() { let _ = write!(decl, " : {}", semantic); }
+        parameters.push(decl);
+        call.push(param.name.clone());
+    }
+    if arguments.next().is_some() { return None }
+
+    let semantic = f.semantic.as_ref().map_or(String::new(), |s| format!(" : {}", s));
+    let ret = if f.ty == "void" { "" } else { "return " };
+    Some(format!("{} {}({}){} {{ {}{}({}); }}\n", f.ty, WRAPPER, parameters.join(", "), semantic, ret, f.name, call.join(", ")))
+}
+
+/// Read the `CTAB` constant table comment of SM1-3 bytecode
+fn constant_table(b: &[u8]) -> Option<Vec<ShaderConstant>> {
+    let u16_at = |b: &[u8], o: usize| -> Option<u16> { Some(u16::from_le_bytes(b.get(o .. o.checked_add(2)?)?.try_into().ok()?)) };
+    let u32_at = |b: &[u8], o: usize| -> Option<u32> { Some(u32::from_le_bytes(b.get(o .. o.checked_add(4)?)?.try_into().ok()?)) };
+
+    let mut i = 4; // skip the version token
+    loop {
+        let instruction = u32_at(b, i)?;
+        if instruction & 0xFFFF != 0xFFFE { return None } // the constant table is one of the leading D3DSIO_COMMENTs
+        let len = 4 * ((instruction >> 16) & 0x7FFF) as usize;
+        let start = i.checked_add(4)?;
+        let end = start.checked_add(len)?;
+        let comment = b.get(start .. end)?;
+        i = end;
+        if comment.get(..4) != Some(b"CTAB") { continue }
+
+        let ctab = &comment[4..];
+        let (count, info) = (u32_at(ctab, 12)?, u32_at(ctab, 16)? as usize);
+        return (0 .. count as usize).map(|c| {
+            let o = c.checked_mul(20)?.checked_add(info)?;
+            let constant = ctab.get(o .. o.checked_add(20)?)?; // D3DXSHADER_CONSTANTINFO
+            let name = ctab.get(u32_at(constant, 0)? as usize ..)?;
+            let name = &name[.. name.iter().position(|b| *b == 0)?];
+            Some(ShaderConstant {
+                name:           String::from_utf8_lossy(name).into(),
+                register_set:   u16_at(constant, 4)?,
+                index:          u16_at(constant, 6)?,
+                count:          u16_at(constant, 8)?,
+            })
+        }).collect();
+    }
+}
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    #[test] fn parse() {
+        let effect = Effect::parse(concat!(
+            "float4x4 world_view_proj : WORLDVIEWPROJECTION;\n",
+            "float4 tint < string UIName = \"Tint\"; float UIMax = 1.0; > = { 1, 1, 1, 1 };\n",
+            "static const float PI = 3.14159;\n",
+            "texture diffuse;\n",
+            "sampler2D diffuse_sampler : register(s1) = sampler_state {\n",
+            "    Texture = (diffuse); AddressU = Border; BorderColor = 0xFF00FF00; MipMapLODBias = -0.5; SRGBTexture = true;\n",
+            "};\n",
+            "struct VsOut { float4 pos : POSITION; };\n",
+            "VsOut vs_main(float4 pos : POSITION, uniform bool skinned) { VsOut o; o.pos = pos; return o; }\n",
+            "technique Shadow < bool hidden = true; > {\n",
+            "    pass Depth {\n",
+            "        ZEnable = true; ZFunc = LESSEQUAL; ColorWriteEnable = Red | Alpha; DepthBias = -0.001f; StencilRef = 0x80;\n",
+            "        Sampler[2] = <diffuse_sampler>; Texture[3] = <diffuse>; MagFilter[3] = Anisotropic;\n",
+            "        VertexShader = compile vs_2_0 vs_main(true); PixelShader = null;\n",
+            "    }\n",
+            "    pass { CullMode = CCW; }\n",
+            "}\n",
+            "technique { pass { } }\n",
+        )).unwrap();
+
+        assert_eq!(effect.parameters.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), ["world_view_proj", "tint", "diffuse", "diffuse_sampler"]);
+        assert_eq!(effect.parameter("tint").unwrap().annotations, [
+            EffectAnnotation { ty: "string".into(), name: "UIName".into(), value: "Tint".into() },
+            EffectAnnotation { ty: "float".into(), name: "UIMax".into(), value: "1.0".into() },
+        ]);
+        assert_eq!(effect.parameter("diffuse_sampler").unwrap().sampler, Some(EffectSampler {
+            texture: Some("diffuse".into()),
+            states:  vec![SampV::AddressU(TAddress::Border), SampV::BorderColor(d3d::Color::from(0xFF00FF00)), SampV::MipMapLODBias((-0.5f32).to_bits()), SampV::SRGBTexture(true)],
+        }));
+        assert_eq!(effect.declarations.function("vs_main").unwrap().parameters.len(), 2);
+
+        assert_eq!(effect.techniques.len(), 2);
+        let shadow = effect.technique("Shadow").unwrap();
+        assert_eq!(shadow.annotations[0].value, "true");
+        assert_eq!(shadow.location.line, 10);
+        assert_eq!(shadow.pass("Depth").unwrap().states, [
+            EffectState::RenderState(RS::ZEnable, 1),
+            EffectState::RenderState(RS::ZFunc, CmpFunc::LessEqual.into()),
+            EffectState::RenderState(RS::ColorWriteEnable, 0b1001),
+            EffectState::RenderState(RS::DepthBias, (-0.001f32).to_bits()),
+            EffectState::RenderState(RS::StencilRef, 0x80),
+            EffectState::Sampler(2, "diffuse_sampler".into()),
+            EffectState::Texture(3, "diffuse".into()),
+            EffectState::SamplerState(3, SampV::MagFilter(TexF::Anisotropic)),
+            EffectState::VertexShader(Some(EffectShader { profile: "vs_2_0".into(), entry: "vs_main".into(), arguments: vec!["true".into()] })),
+            EffectState::PixelShader(None),
+        ]);
+        assert_eq!(shadow.passes[1].states, [EffectState::RenderState(RS::CullMode, Cull::CCW.into())]);
+        assert_eq!(effect.techniques[1].name, "");
+        assert!(effect.techniques[1].passes[0].states.is_empty());
+    }
+
+    #[test] fn errors() {
+        let err = Effect::parse("technique T {\n    pass { CullMode = Sideways; }\n}").unwrap_err();
+        assert_eq!((err.line, err.message.as_str()), (Some(2), "invalid value for CullMode"));
+
+        let err = Effect::parse("technique T { pass { ColorOp[0] = Modulate; } }").unwrap_err();
+        assert_eq!(err.message, "unknown or unsupported state \"ColorOp\"");
+
+        let err = Effect::parse("technique T { pass { CullMode[0] = None; } }").unwrap_err();
+        assert_eq!(err.message, "unknown or unsupported state \"CullMode\"");
+
+        let err = Effect::parse("technique T { pass { PixelShader = asm { ps_1_1 }; } }").unwrap_err();
+        assert_eq!(err.message, "expected 'compile' or NULL");
+
+        let err = Effect::parse("sampler s = sampler_state { Filter = MIN_MAG_MIP_LINEAR; };").unwrap_err();
+        assert_eq!(err.message, "unknown sampler state \"Filter\"");
+
+        let err = Effect::parse("technique T { SrcBlend = One; }").unwrap_err();
+        assert_eq!(err.message, "expected 'pass' or '}'");
+    }
+
+    #[test] fn numbers() {
+        assert_eq!(number("12", false), Some(12));
+        assert_eq!(number("-1", false), Some(!0));
+        assert_eq!(number("0xFF00FF00", false), Some(0xFF00FF00));
+        assert_eq!(number("0x3F800000", true), Some(0x3F800000));
+        assert_eq!(number("1", true), Some(1.0f32.to_bits()));
+        assert_eq!(number("-0.25f", true), Some((-0.25f32).to_bits()));
+        assert_eq!(number("1.5", false), None);
+    }
+
+    #[test] fn wrappers() {
+        let decls = HlslDeclarations::parse("float4 ps(in float2 uv : TEXCOORD0, uniform float scale, uniform bool fog) : COLOR0 { return scale; }").unwrap();
+        let f = decls.function("ps").unwrap();
+        assert_eq!(wrapper(f, &["2.0".into(), "true".into()]).unwrap(), "float4 thindx_effect_entry(in float2 uv : TEXCOORD0) : COLOR0 { return ps(uv, 2.0, true); }\n");
+        assert_eq!(wrapper(f, &["2.0".into()]), None);
+        assert_eq!(wrapper(f, &["2.0".into(), "true".into(), "1".into()]), None);
+    }
+
+    #[test] fn ctab() {
+        let mut ctab = Vec::new();
+        ctab.extend(b"CTAB");
+        for v in [28u32, 0, 0xFFFF0200, 2, 28, 0, 0] { ctab.extend(v.to_le_bytes()) } // D3DXSHADER_CONSTANTTABLE
+        for (name, set, index, count) in [(68u32, 2u16, 4u16, 4u16), (73, 3, 1, 1)] {   // D3DXSHADER_CONSTANTINFO[2]
+            ctab.extend(name.to_le_bytes());
+            for v in [set, index, count, 0] { ctab.extend(v.to_le_bytes()) }
+            ctab.extend([0; 8]);
+        }
+        ctab.extend(b"mvp\0\0samp\0\0\0\0\0\0\0");
+
+        let mut bytecode = Vec::new();
+        bytecode.extend(0xFFFE0200u32.to_le_bytes());                                       // vs_2_0
+        bytecode.extend((0xFFFE | (1 << 16) as u32).to_le_bytes());                         // unrelated comment
+        bytecode.extend(b"DBUG");
+        bytecode.extend((0xFFFE | ((ctab.len() / 4) << 16) as u32).to_le_bytes());
+        bytecode.extend(&ctab);
+        bytecode.extend(0x0000FFFFu32.to_le_bytes());                                       // end
+
+        assert_eq!(constant_table(&bytecode).unwrap(), [
+            ShaderConstant { name: "mvp".into(),  register_set: REGISTER_SET_FLOAT4,  index: 4, count: 4 },
+            ShaderConstant { name: "samp".into(), register_set: REGISTER_SET_SAMPLER, index: 1, count: 1 },
+        ]);
+        assert_eq!(constant_table(&0xFFFE0200u32.to_le_bytes()), None);
+
+        let patched = |o: usize, v: u32| { let mut b = bytecode.clone(); b[o .. o + 4].copy_from_slice(&v.to_le_bytes()); b };
+        assert_eq!(constant_table(&patched(32, !0)), None, "constant count");
+        assert_eq!(constant_table(&patched(36, !0 - 8)), None, "constant info offset");
+        assert_eq!(constant_table(&patched(48, !0 - 2)), None, "constant name offset");
+        assert_eq!(constant_table(&patched(12, 0xFFFE | (0x7FFF << 16))), None, "comment length");
+    }
+}
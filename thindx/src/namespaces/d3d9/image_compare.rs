@@ -0,0 +1,366 @@
+use crate::*;
+use crate::d3d9::{RgbaImage, TextureMipRef};
+use crate::d3d9::pixel_convert::decode_pixels;
+
+use std::fmt::{self, Display, Formatter};
+
+
+
+/// Options for [compare_pixels] and [compare_texture_mips].
+///
+/// A channel differs "beyond tolerance" only if its difference exceeds **both** [abs_tolerance](Self::abs_tolerance)
+/// and [rel_tolerance](Self::rel_tolerance).  A pixel fails if any compared channel differs beyond tolerance, or if
+/// its [perceptual difference](ImageComparison::max_perceptual_diff) exceeds [perceptual_tolerance](Self::perceptual_tolerance).
+/// The defaults require an exact match of every pixel.
+#[derive(Clone, Debug, Default)]
+pub struct ImageCompareOptions {
+    /// The largest allowed absolute difference per channel (`|actual - expected|`), e.g. `1.0 / 255.0` for 8-bit off-by-one rounding.
+    pub abs_tolerance:          f32,
+
+    /// The largest allowed relative difference per channel (`|actual - expected| / max(|actual|, |expected|)`), useful for floating point targets.
+    pub rel_tolerance:          f32,
+
+    /// The largest allowed perceptual difference, or [None] to skip the perceptual check.  `0.1` is a reasonable starting point.
+    pub perceptual_tolerance:   Option<f32>,
+
+    /// Ignore differences in alpha (e.g. for [X8R8G8B8](d3d::Format::X8R8G8B8) back buffers.)
+    pub ignore_alpha:           bool,
+
+    /// The number of failing pixels to tolerate before [ImageComparison::passed] returns `false`.
+    pub max_failing_pixels:     usize,
+
+    /// Regions to ignore entirely (`x1 .. x2`, `y1 .. y2`), such as text, timers, or other nondeterministic output.
+    pub ignore:                 Vec<d3d::Rect>,
+
+    /// A `width * height` mask of pixels to compare (`false` pixels are ignored), or [None] to compare everything [ignore](Self::ignore) doesn't cover.
+    pub mask:                   Option<Vec<bool>>,
+}
+
+/// The result of [compare_pixels] or [compare_texture_mips].
+///
+/// [Display]s as a short human readable report.
+/// Channel statistics are in `[r, g, b, a]` order, and only cover compared (non-ignored) pixels.
+#[derive(Clone, Debug)]
+pub struct ImageComparison {
+    /// The number of pixels compared (excluding ignored pixels.)
+    pub compared_pixels:        usize,
+
+    /// The number of pixels ignored via [ImageCompareOptions::ignore] or [ImageCompareOptions::mask].
+    pub ignored_pixels:         usize,
+
+    /// The number of compared pixels that differ beyond tolerance.
+    pub failing_pixels:         usize,
+
+    /// [ImageCompareOptions::max_failing_pixels]
+    pub max_failing_pixels:     usize,
+
+    /// The `(x, y)` coordinates of the first failing pixel in row major order, if any.
+    pub first_failure:          Option<(u32, u32)>,
+
+    /// The largest absolute difference per channel.  NaN vs. non-NaN differences are [infinite](f32::INFINITY).
+    pub max_abs_diff:           [f32; 4],
+
+    /// The mean absolute difference per channel.
+    pub mean_abs_diff:          [f32; 4],
+
+    /// The largest relative difference per channel.
+    pub max_rel_diff:           [f32; 4],
+
+    /// The peak signal-to-noise ratio in decibels, over every compared channel, treating `1.0` as the peak value.
+    /// [Infinite](f32::INFINITY) if every compared channel is identical, [negative infinity](f32::NEG_INFINITY) if any differ infinitely.
+    pub psnr:                   f32,
+
+    /// The largest perceptual difference:  a [YIQ](https://en.wikipedia.org/wiki/YIQ)-weighted color distance of the
+    /// (clamped, alpha-over-white) colors, scaled to match the thresholds used by the popular `pixelmatch` library.
+    /// Black vs. white is `~0.97`.
+    pub max_perceptual_diff:    f32,
+
+    /// A visualization of the differences:  failing pixels are red, differing pixels within tolerance are yellow, ignored
+    /// pixels are light blue, and identical pixels are a faded grayscale copy of `expected`.
+    pub diff:                   RgbaImage<u8>,
+
+    /// The absolute difference per channel (`0` for ignored pixels and channels.)
+    pub abs_diff:               RgbaImage<f32>,
+}
+
+impl ImageComparison {
+    /// `true` if no more than [max_failing_pixels](Self::max_failing_pixels) failed.
+    pub fn passed(&self) -> bool { self.failing_pixels <= self.max_failing_pixels }
+}
+
+/// Formats as a multi-line summary, e.g.:
+///
+/// ```text
+/// FAILED: 3 of 4092 pixels differ beyond tolerance (0 allowed, 4 ignored), first at (12, 7)
+/// max abs diff:           r 0.5020  g 0.0000  b 0.0000  a 0.0000
+/// mean abs diff:          r 0.0004  g 0.0000  b 0.0000  a 0.0000
+/// max rel diff:           r 1.0000  g 0.0000  b 0.0000  a 0.0000
+/// PSNR:                   41.13 dB
+/// max perceptual diff:    0.3102
+/// ```
+impl Display for ImageComparison {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let channels = |fmt: &mut Formatter, label: &str, v: [f32; 4]| writeln!(fmt, "{label:<24}r {:.4}  g {:.4}  b {:.4}  a {:.4}", v[0], v[1], v[2], v[3]);
+        write!(fmt, "{}: {} of {} pixels differ beyond tolerance ({} allowed, {} ignored)",
+            if self.passed() { "PASSED" } else { "FAILED" }, self.failing_pixels, self.compared_pixels, self.max_failing_pixels, self.ignored_pixels)?;
+        if let Some((x, y)) = self.first_failure { write!(fmt, ", first at ({x}, {y})")?; }
+        writeln!(fmt)?;
+        channels(fmt, "max abs diff:",  self.max_abs_diff)?;
+        channels(fmt, "mean abs diff:", self.mean_abs_diff)?;
+        channels(fmt, "max rel diff:",  self.max_rel_diff)?;
+        writeln!(fmt, "{:<24}{:.2} dB", "PSNR:", self.psnr)?;
+        write!(fmt, "{:<24}{:.4}", "max perceptual diff:", self.max_perceptual_diff)
+    }
+}
+
+/// Compare `width` x `height` RGBA `actual` pixels against `expected` ones (row major, tightly packed.)
+///
+/// ### Errors
+/// *   [D3DERR::INVALIDCALL]           - `expected.len()` or `actual.len()` != `width * height`
+/// *   [D3DERR::INVALIDCALL]           - `options.mask` is [Some], but its length != `width * height`
+///
+/// ### Example
+/// ```rust
+/// # use thindx::d3d9::*;
+/// let expected = [[0.0, 0.5, 1.0, 1.0], [1.0, 1.0, 1.0, 1.0]];
+/// let actual   = [[0.0, 0.5, 1.0, 0.0], [1.0, 0.9, 1.0, 1.0]];
+/// let options  = ImageCompareOptions { abs_tolerance: 0.2, ..Default::default() };
+/// let result   = compare_pixels(2, 1, &expected, &actual, &options).unwrap();
+/// assert!(!result.passed(), "{result}"); // alpha differs by 1.0
+///
+/// let options  = ImageCompareOptions { ignore_alpha: true, ..options };
+/// let result   = compare_pixels(2, 1, &expected, &actual, &options).unwrap();
+/// assert!(result.passed(), "{result}");
+/// assert_eq!(result.diff.pixels[1], [0xFF, 0xFF, 0x00, 0xFF]); // yellow:  differs, but within tolerance
+/// ```
+pub fn compare_pixels(width: u32, height: u32, expected: &[[f32; 4]], actual: &[[f32; 4]], options: &ImageCompareOptions) -> Result<ImageComparison, Error> {
+    fn_context!(d3d9::compare_pixels);
+    let n = (width as usize).checked_mul(height as usize);
+    if Some(expected.len()) != n { return Err(fn_param_error!(expected, D3DERR::INVALIDCALL)) }
+    if Some(actual  .len()) != n { return Err(fn_param_error!(actual,   D3DERR::INVALIDCALL)) }
+    if options.mask.as_ref().is_some_and(|mask| Some(mask.len()) != n) { return Err(fn_param_error!(options, D3DERR::INVALIDCALL)) }
+    let channels = if options.ignore_alpha { 3 } else { 4 };
+
+    let mut result = ImageComparison {
+        compared_pixels:        0,
+        ignored_pixels:         0,
+        failing_pixels:         0,
+        max_failing_pixels:     options.max_failing_pixels,
+        first_failure:          None,
+        max_abs_diff:           [0.0; 4],
+        mean_abs_diff:          [0.0; 4],
+        max_rel_diff:           [0.0; 4],
+        psnr:                   f32::INFINITY,
+        max_perceptual_diff:    0.0,
+        diff:                   RgbaImage { width, height, pixels: Vec::with_capacity(expected.len()) },
+        abs_diff:               RgbaImage { width, height, pixels: Vec::with_capacity(expected.len()) },
+    };
+    let mut sum_abs = [0.0f64; 4];
+    let mut sum_sq  = 0.0f64;
+
+    for (i, (e, a)) in expected.iter().zip(actual.iter()).enumerate() {
+        let (x, y) = ((i % width as usize) as u32, (i / width as usize) as u32);
+        if is_ignored(options, i, x, y) {
+            result.ignored_pixels += 1;
+            result.diff.pixels.push(IGNORED);
+            result.abs_diff.pixels.push([0.0; 4]);
+            continue;
+        }
+        result.compared_pixels += 1;
+
+        let mut abs = [0.0; 4];
+        let mut beyond = false;
+        for c in 0 .. channels {
+            let (e, a) = (e[c], a[c]);
+            let d = if e.is_nan() && a.is_nan() { 0.0 } else if e.is_nan() || a.is_nan() { f32::INFINITY } else { (a - e).abs() };
+            let rel = if d == 0.0 { 0.0 } else if d.is_infinite() { f32::INFINITY } else { d / a.abs().max(e.abs()) };
+            beyond |= d > options.abs_tolerance && rel > options.rel_tolerance;
+            abs[c] = d;
+            sum_abs[c] += f64::from(d);
+            sum_sq += f64::from(d) * f64::from(d);
+            result.max_abs_diff[c] = result.max_abs_diff[c].max(d);
+            result.max_rel_diff[c] = result.max_rel_diff[c].max(rel);
+        }
+
+        let perceptual = perceptual_diff(*e, *a, options.ignore_alpha);
+        result.max_perceptual_diff = result.max_perceptual_diff.max(perceptual);
+        beyond |= options.perceptual_tolerance.is_some_and(|tolerance| perceptual > tolerance);
+
+        if beyond {
+            result.failing_pixels += 1;
+            result.first_failure.get_or_insert((x, y));
+            result.diff.pixels.push(FAILING);
+        } else if abs != [0.0; 4] {
+            result.diff.pixels.push(DIFFERING);
+        } else {
+            let v = (255.0 * (0.75 + 0.25 * luma(over_white(*e, options.ignore_alpha)))).round() as u8;
+            result.diff.pixels.push([v, v, v, 0xFF]);
+        }
+        result.abs_diff.pixels.push(abs);
+    }
+
+    if result.compared_pixels != 0 {
+        let n = result.compared_pixels as f64;
+        result.mean_abs_diff = sum_abs.map(|sum| (sum / n) as f32);
+        let mse = sum_sq / (n * channels as f64);
+        if mse != 0.0 { result.psnr = (10.0 * (1.0 / mse).log10()) as f32 }
+    }
+    Ok(result)
+}
+
+/// Compare `width` x `height` pixels of `format`, such as [RgbaImage::texture_mip_ref]s or [locked](d3d::LockedRect::as_texture_mip_ref) readback surfaces.
+///
+/// Both `expected` and `actual` are decoded via [decode_pixels], then compared via [compare_pixels].
+///
+/// ### Errors
+/// *   See [decode_pixels] and [compare_pixels]
+///
+/// ### Example
+/// ```rust
+/// # use dev::d3d9::*; let device = device_test();
+/// device.clear(None, Some(d3d::Color::argb(0xFF112233)), None, None).unwrap();
+///
+/// let rt = device.get_render_target(0).unwrap().unwrap();
+/// let desc = rt.get_desc().unwrap();
+/// let sysmem = device.create_offscreen_plain_surface(desc.width, desc.height, desc.format, Pool::SystemMem, ()).unwrap();
+/// device.get_render_target_data(&rt, &sysmem).unwrap();
+///
+/// let expected = RgbaImage::<u8> { width: desc.width, height: desc.height, pixels: vec![[0x11, 0x22, 0x33, 0xFF]; (desc.width * desc.height) as usize] };
+/// let options = ImageCompareOptions { ignore_alpha: true, ..Default::default() };
+/// let result = unsafe {
+///     let locked = sysmem.lock_rect_unchecked(.., Lock::ReadOnly).unwrap();
+///     let actual = RgbaImage::<u8>::from_surface(&desc, locked.as_texture_mip_ref(&desc).unwrap()).unwrap();
+///     sysmem.unlock_rect().unwrap();
+///     compare_texture_mips(Format::A8B8G8R8, desc.width, desc.height, expected.texture_mip_ref(), actual.texture_mip_ref(), &options).unwrap()
+/// };
+/// assert!(result.passed(), "{result}");
+/// ```
+pub fn compare_texture_mips(format: impl Into<d3d::Format>, width: u32, height: u32, expected: TextureMipRef, actual: TextureMipRef, options: &ImageCompareOptions) -> Result<ImageComparison, Error> {
+    fn_context!(d3d9::compare_texture_mips);
+    let format = format.into();
+    let expected    = decode_pixels(format, expected, width, height).map_err(|e| fn_param_error!(expected, e.kind()))?;
+    let actual      = decode_pixels(format, actual,   width, height).map_err(|e| fn_param_error!(actual,   e.kind()))?;
+    compare_pixels(width, height, &expected, &actual, options)
+}
+
+
+
+const FAILING   : [u8; 4] = [0xFF, 0x00, 0x00, 0xFF];
+const DIFFERING : [u8; 4] = [0xFF, 0xFF, 0x00, 0xFF];
+const IGNORED   : [u8; 4] = [0xA0, 0xC0, 0xFF, 0xFF];
+
+/// pixelmatch's maximum YIQ delta (35215) for 0 ..= 255 channels, rescaled for 0 ..= 1 channels
+const PERCEPTUAL_SCALE : f32 = 35215.0 / (255.0 * 255.0);
+
+fn is_ignored(options: &ImageCompareOptions, i: usize, x: u32, y: u32) -> bool {
+    let (x, y) = (i64::from(x), i64::from(y));
+    options.mask.as_ref().is_some_and(|mask| !mask[i])
+    || options.ignore.iter().any(|r| (i64::from(r.x1) .. i64::from(r.x2)).contains(&x) && (i64::from(r.y1) .. i64::from(r.y2)).contains(&y))
+}
+
+fn over_white(px: [f32; 4], ignore_alpha: bool) -> [f32; 3] {
+    let a = if ignore_alpha || px[3].is_nan() { 1.0 } else { px[3].clamp(0.0, 1.0) };
+    [0, 1, 2].map(|c| { let v = px[c].clamp(0.0, 1.0); if v.is_nan() { 0.0 } else { 1.0 + (v - 1.0) * a } })
+}
+
+fn luma([r, g, b]: [f32; 3]) -> f32 { 0.2988953 * r + 0.5866225 * g + 0.1144822 * b }
+
+fn perceptual_diff(expected: [f32; 4], actual: [f32; 4], ignore_alpha: bool) -> f32 {
+    let [r0, g0, b0] = over_white(expected, ignore_alpha);
+    let [r1, g1, b1] = over_white(actual,   ignore_alpha);
+    let (dr, dg, db) = (r1 - r0, g1 - g0, b1 - b0);
+    let y = luma([dr, dg, db]);
+    let i = 0.595978 * dr - 0.2741761 * dg - 0.3218019 * db;
+    let q = 0.2114702 * dr - 0.5226171 * dg + 0.3111469 * db;
+    ((0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q) / PERCEPTUAL_SCALE).sqrt()
+}
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    #[test] fn identical() {
+        let pixels = [[0.0, 0.25, 0.5, 1.0], [1.0, 1.0, 1.0, 0.0], [f32::NAN, 0.0, 0.0, 1.0], [0.0; 4]];
+        let result = compare_pixels(2, 2, &pixels, &pixels, &Default::default()).unwrap();
+        assert!(result.passed());
+        assert_eq!((result.compared_pixels, result.ignored_pixels, result.failing_pixels, result.first_failure), (4, 0, 0, None));
+        assert_eq!((result.max_abs_diff, result.psnr, result.max_perceptual_diff), ([0.0; 4], f32::INFINITY, 0.0));
+        assert_eq!(result.diff.pixels[1], [0xFF; 4]); // white => faded white
+        assert_eq!(result.diff.pixels[3], [0xFF; 4]); // transparent black over white => faded white
+        assert!(result.to_string().starts_with("PASSED: 0 of 4 pixels"));
+    }
+
+    #[test] fn tolerances() {
+        let expected = [[0.5, 0.5, 0.5, 1.0], [100.0, 0.0, 0.0, 1.0], [0.0, 0.0, 0.0, 1.0]];
+        let actual   = [[0.5, 0.6, 0.5, 1.0], [101.0, 0.0, 0.0, 1.0], [1.0, 1.0, 1.0, 1.0]];
+        let compare = |options: ImageCompareOptions| compare_pixels(3, 1, &expected, &actual, &options).unwrap();
+
+        let exact = compare(Default::default());
+        assert_eq!((exact.failing_pixels, exact.first_failure), (3, Some((0, 0))));
+        assert_eq!(exact.max_abs_diff, [1.0, 1.0, 1.0, 0.0]);
+        assert!((exact.max_rel_diff[0] - 1.0).abs() < 1e-6 && (exact.max_rel_diff[1] - 1.0).abs() < 1e-6);
+        assert!((exact.mean_abs_diff[1] - 1.1 / 3.0).abs() < 1e-6);
+        let mse = (1.0 + 0.01 + 3.0) / 12.0;
+        assert!((exact.psnr - 10.0 * (1.0 / mse as f32).log10()).abs() < 1e-3);
+        assert!((exact.max_perceptual_diff - (0.5053 / PERCEPTUAL_SCALE).sqrt()).abs() < 1e-3);
+        assert_eq!(exact.diff.pixels, [FAILING; 3]);
+        assert!(exact.to_string().starts_with("FAILED: 3 of 3 pixels differ beyond tolerance (0 allowed, 0 ignored), first at (0, 0)\n"), "{exact}");
+
+        let abs = compare(ImageCompareOptions { abs_tolerance: 0.2, ..Default::default() });
+        assert_eq!((abs.failing_pixels, abs.first_failure), (2, Some((1, 0))));
+        assert_eq!(abs.diff.pixels[0], DIFFERING);
+
+        let rel = compare(ImageCompareOptions { abs_tolerance: 0.2, rel_tolerance: 0.05, ..Default::default() });
+        assert_eq!((rel.failing_pixels, rel.first_failure), (1, Some((2, 0))));
+
+        let allowed = compare(ImageCompareOptions { abs_tolerance: 0.2, rel_tolerance: 0.05, max_failing_pixels: 1, ..Default::default() });
+        assert!(allowed.passed());
+
+        let perceptual = compare(ImageCompareOptions { abs_tolerance: 2.0, perceptual_tolerance: Some(0.1), ..Default::default() });
+        assert_eq!((perceptual.failing_pixels, perceptual.first_failure), (1, Some((2, 0)))); // 100 vs 101 clamps to identical
+    }
+
+    #[test] fn ignored() {
+        let expected = [[0.0; 4]; 6];
+        let mut actual = [[0.0; 4]; 6];
+        actual[1] = [1.0, 0.0, 0.0, 0.0];
+        actual[5] = [0.0, 0.0, 0.0, 1.0];
+        actual[3] = [f32::NAN, 0.0, 0.0, 0.0];
+
+        let rect = ImageCompareOptions { ignore: vec![d3d::Rect { x1: 1, y1: 0, x2: 2, y2: 1 }], ..Default::default() };
+        let result = compare_pixels(3, 2, &expected, &actual, &rect).unwrap();
+        assert_eq!((result.compared_pixels, result.ignored_pixels, result.failing_pixels, result.first_failure), (5, 1, 2, Some((0, 1))));
+        assert_eq!(result.diff.pixels[1], IGNORED);
+        assert_eq!(result.abs_diff.pixels[3][0], f32::INFINITY);
+        assert_eq!(result.psnr, f32::NEG_INFINITY);
+
+        let mask = ImageCompareOptions { mask: Some(vec![true, true, true, false, true, true]), ignore_alpha: true, ..Default::default() };
+        let result = compare_pixels(3, 2, &expected, &actual, &mask).unwrap();
+        assert_eq!((result.compared_pixels, result.ignored_pixels, result.failing_pixels, result.first_failure), (5, 1, 1, Some((1, 0))));
+        assert_eq!(result.abs_diff.pixels[5], [0.0; 4]);
+        assert_eq!(result.diff.pixels[5], [0xBF, 0xBF, 0xBF, 0xFF]); // alpha ignored:  identical
+
+        let short_mask = ImageCompareOptions { mask: Some(vec![true; 5]), ..Default::default() };
+        assert_eq!(compare_pixels(3, 2, &expected, &actual, &short_mask).unwrap_err().kind(), D3DERR::INVALIDCALL);
+        assert_eq!(compare_pixels(3, 2, &expected[1..], &actual, &Default::default()).unwrap_err().kind(), D3DERR::INVALIDCALL);
+        assert_eq!(compare_pixels(3, 2, &expected, &actual[1..], &Default::default()).unwrap_err().kind(), D3DERR::INVALIDCALL);
+    }
+
+    #[test] fn texture_mips() {
+        let expected = [0xFF102030_u32, 0xFF405060];
+        let actual   = [0xFF102031_u32, 0x00405060];
+        fn mip(argb: &[u32; 2]) -> TextureMipRef<'_> { TextureMipRef { data: bytemuck::cast_slice(argb), stride: 8 } }
+        let options = ImageCompareOptions { abs_tolerance: 1.5 / 255.0, ignore_alpha: true, ..Default::default() };
+        let result = compare_texture_mips(d3d::Format::A8R8G8B8, 2, 1, mip(&expected), mip(&actual), &options).unwrap();
+        assert!(result.passed(), "{result}");
+        assert_eq!(result.diff.pixels[0], DIFFERING);
+
+        let options = ImageCompareOptions { ignore_alpha: false, ..options };
+        let result = compare_texture_mips(d3d::Format::X8R8G8B8, 2, 1, mip(&expected), mip(&actual), &options).unwrap();
+        assert!(result.passed(), "{result}"); // X8 decodes alpha as 1
+        let result = compare_texture_mips(d3d::Format::A8R8G8B8, 2, 1, mip(&expected), mip(&actual), &options).unwrap();
+        assert_eq!(result.first_failure, Some((1, 0)));
+        assert_eq!(compare_texture_mips(d3d::Format::A8R8G8B8, 3, 1, mip(&expected), mip(&actual), &options).unwrap_err().kind(), D3DERR::INVALIDCALL);
+    }
+}
@@ -0,0 +1,250 @@
+use crate::*;
+use crate::d3d::{FormatInfo, LockedRect, SurfaceDesc};
+use crate::d3d9::{Dds, TextureMipRef};
+use crate::d3d9::pixel_convert::decode_pixels;
+
+
+
+/// Tightly packed, row major, top-down RGBA pixels, such as those read back from a [Surface](d3d9::Surface).
+///
+/// [RgbaImage]&lt;[u8]&gt; is the usual choice for screenshots, [RgbaImage]&lt;[f32]&gt; preserves floating point render targets.
+/// Either can be written out via [to_png](Self::to_png) or [to_dds](Self::to_dds).
+///
+/// ### Example
+/// ```rust
+/// # use dev::d3d9::*; let device = device_test();
+/// let rt = device.get_render_target(0).unwrap().unwrap();
+/// let desc = rt.get_desc().unwrap();
+/// let sysmem = device.create_offscreen_plain_surface(desc.width, desc.height, desc.format, Pool::SystemMem, ()).unwrap();
+/// device.get_render_target_data(&rt, &sysmem).unwrap();
+///
+/// let screenshot = unsafe {
+///     let locked = sysmem.lock_rect_unchecked(.., Lock::ReadOnly).unwrap();
+///     let screenshot = RgbaImage::<u8>::from_surface(&desc, locked.as_texture_mip_ref(&desc).unwrap());
+///     sysmem.unlock_rect().unwrap();
+///     screenshot.unwrap()
+/// };
+///
+/// let dds = screenshot.to_dds().unwrap();
+/// assert_eq!(&dds[..4], b"DDS ");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RgbaImage<T> {
+    /// The number of pixels per row.
+    pub width:  u32,
+
+    /// The number of rows.
+    pub height: u32,
+
+    /// `width * height` RGBA pixels.
+    pub pixels: Vec<[T; 4]>,
+}
+
+impl RgbaImage<u8> {
+    /// Decode a `desc.width` x `desc.height` surface of `desc.format` into 8-bit RGBA.
+    ///
+    /// Channels are mapped as per [decode_pixels], then clamped to `0 ..= 1` and rounded to `0 ..= 255`.
+    ///
+    /// ### Errors
+    /// *   See [decode_pixels]
+    pub fn from_surface(desc: &SurfaceDesc, src: TextureMipRef) -> Result<Self, Error> {
+        fn_context!(d3d9::RgbaImage::from_surface);
+        let pixels = decode_pixels(desc.format, src, desc.width, desc.height)?;
+        let pixels = pixels.into_iter().map(|px| px.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)).collect();
+        Ok(Self { width: desc.width, height: desc.height, pixels })
+    }
+
+    /// Reference the pixels as [A8B8G8R8](d3d::Format::A8B8G8R8) data (R in the lowest byte), e.g. for [Dds::from_texture].
+    pub fn texture_mip_ref(&self) -> TextureMipRef<'_> { TextureMipRef { data: bytemuck::cast_slice(&self.pixels), stride: 4 * self.width as usize } }
+
+    /// Encode as an 8-bit RGBA PNG.
+    ///
+    /// ### Errors
+    /// *   [D3DERR::INVALIDCALL]           - `width` or `height` is `0`, or `pixels.len() != width * height`
+    /// *   [D3DERR::NOTAVAILABLE]          - the `png` feature is disabled
+    pub fn to_png(&self) -> Result<Vec<u8>, Error> {
+        fn_context!(d3d9::RgbaImage::to_png);
+        if !self.valid() { return Err(fn_param_error!(self, D3DERR::INVALIDCALL)) }
+        write_png(self.width, self.height, 8, bytemuck::cast_slice(&self.pixels))
+    }
+
+    /// Encode as an [A8B8G8R8](d3d::Format::A8B8G8R8) DDS file.
+    ///
+    /// ### Errors
+    /// *   [D3DERR::INVALIDCALL]           - `width` or `height` is `0`, or `pixels.len() != width * height`
+    pub fn to_dds(&self) -> Result<Vec<u8>, Error> {
+        fn_context!(d3d9::RgbaImage::to_dds);
+        if !self.valid() { return Err(fn_param_error!(self, D3DERR::INVALIDCALL)) }
+        Ok(Dds::from_texture(d3d::Format::A8B8G8R8, self.width, self.height, &[self.texture_mip_ref()])?.to_bytes())
+    }
+}
+
+impl RgbaImage<f32> {
+    /// Decode a `desc.width` x `desc.height` surface of `desc.format` into floating point RGBA.
+    ///
+    /// Channels are mapped as per [decode_pixels], without clamping.
+    ///
+    /// ### Errors
+    /// *   See [decode_pixels]
+    pub fn from_surface(desc: &SurfaceDesc, src: TextureMipRef) -> Result<Self, Error> {
+        fn_context!(d3d9::RgbaImage::from_surface);
+        let pixels = decode_pixels(desc.format, src, desc.width, desc.height)?;
+        Ok(Self { width: desc.width, height: desc.height, pixels })
+    }
+
+    /// Reference the pixels as [A32B32G32R32F](d3d::Format::A32B32G32R32F) data, e.g. for [Dds::from_texture].
+    pub fn texture_mip_ref(&self) -> TextureMipRef<'_> { TextureMipRef { data: bytemuck::cast_slice(&self.pixels), stride: 16 * self.width as usize } }
+
+    /// Encode as a 16-bit RGBA PNG.  Channels are clamped to `0 ..= 1` without any tone mapping or color space conversion.
+    ///
+    /// ### Errors
+    /// *   [D3DERR::INVALIDCALL]           - `width` or `height` is `0`, or `pixels.len() != width * height`
+    /// *   [D3DERR::NOTAVAILABLE]          - the `png` feature is disabled
+    pub fn to_png(&self) -> Result<Vec<u8>, Error> {
+        fn_context!(d3d9::RgbaImage::to_png);
+        if !self.valid() { return Err(fn_param_error!(self, D3DERR::INVALIDCALL)) }
+        let data = self.pixels.iter().flatten().flat_map(|c| ((c.clamp(0.0, 1.0) * 65535.0).round() as u16).to_be_bytes()).collect::<Vec<u8>>();
+        write_png(self.width, self.height, 16, &data)
+    }
+
+    /// Encode as an [A32B32G32R32F](d3d::Format::A32B32G32R32F) DDS file.
+    ///
+    /// ### Errors
+    /// *   [D3DERR::INVALIDCALL]           - `width` or `height` is `0`, or `pixels.len() != width * height`
+    pub fn to_dds(&self) -> Result<Vec<u8>, Error> {
+        fn_context!(d3d9::RgbaImage::to_dds);
+        if !self.valid() { return Err(fn_param_error!(self, D3DERR::INVALIDCALL)) }
+        Ok(Dds::from_texture(d3d::Format::A32B32G32R32F, self.width, self.height, &[self.texture_mip_ref()])?.to_bytes())
+    }
+}
+
+impl<T> RgbaImage<T> {
+    fn valid(&self) -> bool { self.width != 0 && self.height != 0 && Some(self.pixels.len()) == (self.width as usize).checked_mul(self.height as usize) }
+}
+
+impl LockedRect {
+    /// Reference the locked bits of a `desc.width` x `desc.height` surface of `desc.format`.
+    ///
+    /// ### Safety
+    /// *   `self` must be the result of locking the entire surface `desc` describes (or a `desc.width` x `desc.height` [d3d::Rect] of it)
+    /// *   The surface must remain locked, and unmodified by the GPU, for as long as the resulting [TextureMipRef] is used
+    ///
+    /// ### Errors
+    /// *   [D3DERR::WRONGTEXTUREFORMAT]    - `desc.format` has no fixed size, e.g. [Unknown](d3d::Format::Unknown)
+    /// *   [D3DERR::INVALIDCALL]           - `bits` is null, or `pitch` is negative or smaller than a row of `desc.width` pixels
+    /// *   [THINERR::ALLOC_OVERFLOW]       - a row of `desc.width` pixels, or the locked bits, would be larger than `usize::MAX` bytes
+    pub unsafe fn as_texture_mip_ref<'a>(&self, desc: &SurfaceDesc) -> Result<TextureMipRef<'a>, Error> {
+        fn_context!(d3d::LockedRect::as_texture_mip_ref);
+        let info = FormatInfo::of(desc.format).filter(|info| info.bits_per_block != 0).ok_or_else(|| fn_param_error!(desc, D3DERR::WRONGTEXTUREFORMAT))?;
+        let row_bytes = info.checked_row_bytes(desc.width).ok_or_else(|| fn_param_error!(desc, THINERR::ALLOC_OVERFLOW))?;
+        let rows = (desc.height as usize + info.block_size.1 as usize - 1) / info.block_size.1 as usize;
+        let stride = usize::try_from(self.pitch).map_err(|_| fn_param_error!(self, D3DERR::INVALIDCALL))?;
+        if stride < row_bytes { return Err(fn_param_error!(self, D3DERR::INVALIDCALL)) }
+        if rows == 0 || row_bytes == 0 { return Ok(TextureMipRef { data: &[], stride }) }
+        if self.bits.is_null() { return Err(fn_param_error!(self, D3DERR::INVALIDCALL)) }
+        let len = stride.checked_mul(rows - 1).and_then(|n| n.checked_add(row_bytes)).ok_or_else(|| fn_param_error!(self, THINERR::ALLOC_OVERFLOW))?;
+        // SAFETY: ⚠️ per this fn's safety contract, `bits` points at `rows` locked rows `pitch` bytes apart, each at least `row_bytes` long,
+        // and the lock outlives `'a` - so `bits` is valid for `pitch * (rows-1) + row_bytes` bytes.  `bits` was null checked above.
+        Ok(TextureMipRef { data: unsafe { std::slice::from_raw_parts(self.bits, len) }, stride })
+    }
+}
+
+
+
+#[cfg(feature = "png")] fn write_png(width: u32, height: u32, bits: u8, data: &[u8]) -> Result<Vec<u8>, Error> {
+    fn_context!(d3d9::RgbaImage::to_png);
+    let invalid = |_| fn_param_error!(data, D3DERR::INVALIDCALL);
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(if bits == 16 { png::BitDepth::Sixteen } else { png::BitDepth::Eight });
+    let mut writer = encoder.write_header().map_err(invalid)?;
+    writer.write_image_data(data).map_err(invalid)?;
+    writer.finish().map_err(invalid)?;
+    Ok(png)
+}
+
+#[cfg(not(feature = "png"))] fn write_png(width: u32, height: u32, bits: u8, data: &[u8]) -> Result<Vec<u8>, Error> {
+    fn_context!(d3d9::RgbaImage::to_png);
+    let _ = (width, height, bits, data);
+    Err(fn_param_error!(data, D3DERR::NOTAVAILABLE))
+}
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    fn desc(format: d3d::Format, width: u32, height: u32) -> SurfaceDesc {
+        SurfaceDesc { format, width, height, ..bytemuck::Zeroable::zeroed() }
+    }
+
+    #[test] fn from_surface() {
+        let argb = [0xFF00FF00_u32, 0x80FF0000, 0xDEADBEEF, 0x00000000, 0x404040FF, 0xDEADBEEF]; // 2x2 + padding
+        let src = TextureMipRef { data: bytemuck::cast_slice(&argb), stride: 12 };
+        let image = RgbaImage::<u8>::from_surface(&desc(d3d::Format::A8R8G8B8, 2, 2), src).unwrap();
+        assert_eq!(image.pixels, [[0, 0xFF, 0, 0xFF], [0xFF, 0, 0, 0x80], [0, 0, 0, 0], [0x40, 0x40, 0xFF, 0x40]]);
+        assert_eq!(image.texture_mip_ref().data, bytemuck::cast_slice::<u32, u8>(&[0xFF00FF00, 0x800000FF, 0x00000000, 0x40FF4040]));
+
+        let r16f = [d3d::Half::from_f32(2.5), d3d::Half::from_f32(-1.0)].map(|h| h.to_bits());
+        let image = RgbaImage::<f32>::from_surface(&desc(d3d::Format::R16F, 2, 1), TextureMipRef { data: bytemuck::cast_slice(&r16f), stride: 4 }).unwrap();
+        assert_eq!(image.pixels, [[2.5, 1.0, 1.0, 1.0], [-1.0, 1.0, 1.0, 1.0]]);
+
+        let short = TextureMipRef { data: &[0; 12], stride: 8 };
+        assert_eq!(RgbaImage::<u8>::from_surface(&desc(d3d::Format::A8R8G8B8, 2, 2), short).unwrap_err().kind(), D3DERR::INVALIDCALL);
+        assert_eq!(RgbaImage::<u8>::from_surface(&desc(d3d::Format::P8, 1, 1), TextureMipRef { data: &[0], stride: 1 }).unwrap_err().kind(), D3DERR::WRONGTEXTUREFORMAT);
+    }
+
+    #[test] fn locked_rect() {
+        let mut bits = [0u8; 3 * 16 + 8]; // DXT1 8x10 => 2x3 blocks, 16 byte pitch
+        let locked = LockedRect { pitch: 16, bits: bits.as_mut_ptr() };
+        let mip = unsafe { locked.as_texture_mip_ref(&desc(d3d::Format::DXT1, 8, 10)) }.unwrap();
+        assert_eq!((mip.data.len(), mip.stride), (2 * 16 + 16, 16));
+
+        let mip = unsafe { locked.as_texture_mip_ref(&desc(d3d::Format::A8R8G8B8, 3, 3)) }.unwrap();
+        assert_eq!(mip.data.len(), 2 * 16 + 12);
+
+        let error = |locked: LockedRect, desc: SurfaceDesc| unsafe { locked.as_texture_mip_ref(&desc) }.map(|_| ()).unwrap_err().kind();
+        assert_eq!(error(locked, desc(d3d::Format::A8R8G8B8, 5, 1)), D3DERR::INVALIDCALL); // too narrow
+        assert_eq!(error(LockedRect { pitch: -16, ..locked }, desc(d3d::Format::A8R8G8B8, 1, 1)), D3DERR::INVALIDCALL);
+        assert_eq!(error(LockedRect { pitch: -16, ..locked }, desc(d3d::Format::A8R8G8B8, 1, 0)), D3DERR::INVALIDCALL); // empty, but still invalid
+        assert_eq!(error(LockedRect { pitch: 4, ..locked }, desc(d3d::Format::A8R8G8B8, 2, 0)), D3DERR::INVALIDCALL);
+        assert_eq!(unsafe { LockedRect { pitch: 4, bits: std::ptr::null_mut() }.as_texture_mip_ref(&desc(d3d::Format::A8R8G8B8, 1, 0)) }.unwrap().data.len(), 0);
+        assert_eq!(error(locked, desc(d3d::Format::Unknown, 1, 1)), D3DERR::WRONGTEXTUREFORMAT);
+        assert_eq!(unsafe { locked.as_texture_mip_ref(&desc(d3d::Format::UYVY, 2, 1)) }.unwrap().data.len(), 4);
+    }
+
+    #[test] fn dds() {
+        let image = RgbaImage::<u8> { width: 2, height: 1, pixels: vec![[1, 2, 3, 4], [5, 6, 7, 8]] };
+        let dds = Dds::from_bytes(&image.to_dds().unwrap()).unwrap();
+        assert_eq!((dds.format(), dds.width(), dds.height(), dds.mip_levels()), (d3d::Format::A8B8G8R8, 2, 1, 1));
+        assert_eq!(dds.data(), [1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let image = RgbaImage::<f32> { width: 1, height: 1, pixels: vec![[1.5, -2.0, 0.25, 1.0]] };
+        let dds = Dds::from_bytes(&image.to_dds().unwrap()).unwrap();
+        assert_eq!(dds.format(), d3d::Format::A32B32G32R32F);
+        assert_eq!(dds.data(), bytemuck::cast_slice::<f32, u8>(&[1.5, -2.0, 0.25, 1.0]));
+
+        let empty = RgbaImage::<u8> { width: 0, height: 1, pixels: vec![] };
+        assert_eq!(empty.to_dds().unwrap_err().kind(), D3DERR::INVALIDCALL);
+        let mismatched = RgbaImage::<f32> { width: 2, height: 1, pixels: vec![[0.0; 4]] };
+        assert_eq!(mismatched.to_dds().unwrap_err().kind(), D3DERR::INVALIDCALL);
+        assert_eq!(mismatched.to_png().unwrap_err().kind(), D3DERR::INVALIDCALL);
+    }
+
+    #[cfg(feature = "png")] #[test] fn png() {
+        use crate::d3d9::{import_image, ImageImportOptions};
+        let image = RgbaImage::<u8> { width: 2, height: 1, pixels: vec![[0xFF, 0, 0, 0xFF], [0x10, 0x20, 0x30, 0x40]] };
+        let imported = import_image(&image.to_png().unwrap(), &ImageImportOptions::default()).unwrap();
+        assert_eq!(imported.image.data(), [0, 0, 0xFF, 0xFF,  0x30, 0x20, 0x10, 0x40]); // BGRA
+
+        let image = RgbaImage::<f32> { width: 1, height: 1, pixels: vec![[2.0, 0.5, -1.0, 1.0]] };
+        let imported = import_image(&image.to_png().unwrap(), &ImageImportOptions::default()).unwrap();
+        assert_eq!(imported.image.data(), [0, 0x80, 0xFF, 0xFF]);
+    }
+
+    #[cfg(not(feature = "png"))] #[test] fn png() {
+        let image = RgbaImage::<u8> { width: 1, height: 1, pixels: vec![[0; 4]] };
+        assert_eq!(image.to_png().unwrap_err().kind(), D3DERR::NOTAVAILABLE);
+    }
+}
@@ -0,0 +1,654 @@
+use crate::*;
+use crate::d3d9::{FixedTextureFormat, TextureImage, TextureKind};
+use crate::d3d9::mip_generation::{linear_to_srgb, srgb_to_linear};
+use crate::d3d9::pixel_convert::encode_pixels;
+
+
+
+/// Options for [import_image].
+#[derive(Clone, Copy, Debug)]
+pub struct ImageImportOptions {
+    /// The format of the resulting [TextureImage]:  one of
+    /// [A8R8G8B8](FixedTextureFormat::A8R8G8B8) (the default),
+    /// [X8R8G8B8](FixedTextureFormat::X8R8G8B8),
+    /// [A16B16G16R16F](FixedTextureFormat::A16B16G16R16F), or
+    /// [A32B32G32R32F](FixedTextureFormat::A32B32G32R32F).
+    pub format:             &'static FixedTextureFormat,
+
+    /// Premultiply color by alpha (in linear space) unless the source image is already premultiplied.
+    /// Ignored for [X8R8G8B8](FixedTextureFormat::X8R8G8B8).
+    pub premultiply_alpha:  bool,
+}
+
+impl Default for ImageImportOptions {
+    fn default() -> Self { Self { format: FixedTextureFormat::A8R8G8B8, premultiply_alpha: false } }
+}
+
+/// The result of [import_image].
+#[derive(Clone, Debug)]
+pub struct ImportedImage {
+    /// A single level [TextureKind::Texture] in [ImageImportOptions::format].
+    /// Use [TextureImage::generate_mips] on a copy with more levels if you want mipmaps.
+    pub image:          TextureImage,
+
+    /// `true` if RGB is sRGB encoded (always the case for 8-bit formats, never for floating point formats.)
+    /// Sample with [SamplerStateType::SRGBTexture](d3d::SamplerStateType::SRGBTexture) for correct filtering and blending.
+    pub srgb:           bool,
+
+    /// `true` if RGB is premultiplied by alpha, either by [ImageImportOptions::premultiply_alpha] or because the source said so.
+    pub premultiplied:  bool,
+}
+
+/// Import a PNG, TGA, BMP, or Radiance HDR image into a [TextureImage].
+///
+/// | Format    | Supported
+/// | ----------| ---------
+/// | PNG       | Every color type and bit depth, including palettes and `tRNS` transparency.  Requires the `png` feature.
+/// | TGA       | Uncompressed and RLE truecolor (15/16/24/32-bit), grayscale (8/16-bit), and color mapped images.
+/// | BMP       | Uncompressed 1/4/8-bit palettized, 16/24/32-bit, and `BI_BITFIELDS` / `BI_ALPHABITFIELDS` images.
+/// | HDR       | `32-bit_rle_rgbe` images, flat or RLE encoded, stored top-down (`-Y h +X w`) or bottom-up (`+Y h +X w`).
+///
+/// 8-bit sources are treated as sRGB unless a PNG says otherwise via `gAMA` (without `sRGB`), and HDR sources are linear.
+/// Pixels are converted to linear before [premultiplying](ImageImportOptions::premultiply_alpha), then to sRGB for 8-bit formats.
+/// A TGA 2.0 extension area's alpha attribute is honored (alpha may be ignored, or reported as already premultiplied.)
+///
+/// ### Errors
+/// *   [D3DERR::INVALIDDATA]           - the image is truncated, corrupt, or not one of the formats above
+/// *   [D3DERR::WRONGTEXTUREFORMAT]    - the image uses an unsupported encoding (RLE BMPs, XYZE HDRs, ...)
+/// *   [D3DERR::WRONGTEXTUREFORMAT]    - `options.format` isn't one of the formats [ImageImportOptions::format] lists
+/// *   [D3DERR::NOTAVAILABLE]          - the image is a PNG, but the `png` feature is disabled
+/// *   [D3DERR::NOTAVAILABLE]          - the image is larger than 65536 pixels in either dimension
+///
+/// ### Example
+/// ```rust
+/// # use dev::d3d9::*; let device = device_pure();
+/// let tga = [
+///     0, 0, 2,   0, 0, 0, 0, 0,   0, 0, 0, 0,   2, 0, 1, 0,   32, 8 | 0x20, // 2x1 32-bit BGRA, top-left origin
+///     0x00, 0x00, 0xFF, 0xFF,   0xFF, 0x00, 0x00, 0x80,
+/// ];
+/// let imported = import_image(&tga, &ImageImportOptions::default()).unwrap();
+/// assert!(imported.srgb && !imported.premultiplied);
+/// assert_eq!(imported.image.data(), &tga[18..]);
+///
+/// let texture = device.create_texture_from(
+///     imported.image.width(), imported.image.height(), &imported.image.texture_mips().unwrap(),
+///     Usage::None, imported.image.format(), Pool::Managed, (),
+/// ).unwrap();
+/// ```
+pub fn import_image(bytes: &[u8], options: &ImageImportOptions) -> Result<ImportedImage, Error> {
+    fn_context!(d3d9::import_image);
+    let format = options.format;
+    let (eight_bit, alpha) = match format.format {
+        d3d::Format::A8R8G8B8                                       => (true,  true ),
+        d3d::Format::X8R8G8B8                                       => (true,  false),
+        d3d::Format::A16B16G16R16F | d3d::Format::A32B32G32R32F     => (false, true ),
+        _ => return Err(fn_param_error!(options, D3DERR::WRONGTEXTUREFORMAT)),
+    };
+
+    let src = if bytes.starts_with(PNG_SIGNATURE) {
+        decode_png(bytes)?
+    } else if bytes.starts_with(b"BM") {
+        decode_bmp(bytes)?
+    } else if bytes.starts_with(b"#?") {
+        decode_hdr(bytes)?
+    } else {
+        decode_tga(bytes)?
+    };
+
+    let premultiply = options.premultiply_alpha && alpha && !src.premultiplied;
+    let pixels = if eight_bit && src.transfer == Transfer::Srgb && !premultiply {
+        src.pixels // already sRGB encoded:  skip the lossy round trip
+    } else {
+        src.pixels.into_iter().map(|px| {
+            let [r, g, b, a] = px;
+            let [r, g, b] = [r, g, b].map(|c| src.transfer.to_linear(c));
+            let [r, g, b] = if premultiply { [r*a, g*a, b*a] } else { [r, g, b] };
+            let [r, g, b] = if eight_bit { [r, g, b].map(linear_to_srgb) } else { [r, g, b] };
+            [r, g, b, a]
+        }).collect()
+    };
+
+    let data = encode_pixels(format.format, src.width, src.height, &pixels)?;
+    let image = TextureImage::from_data(TextureKind::Texture, format, src.width, src.height, 1, 1, data)?;
+    Ok(ImportedImage { image, srgb: eight_bit, premultiplied: alpha && (premultiply || src.premultiplied) })
+}
+
+
+
+const MAX_DIMENSION : u32 = 1 << 16;
+const PNG_SIGNATURE : &[u8] = b"\x89PNG\r\n\x1A\n";
+
+/// Decoded, but not yet converted, source pixels
+struct Source {
+    width:          u32,
+    height:         u32,
+    pixels:         Vec<[f32; 4]>, // row major, top-down
+    transfer:       Transfer,
+    premultiplied:  bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Transfer {
+    Srgb,
+    Linear,
+    /// `linear = encoded.powf(gamma)`
+    Gamma(f32),
+}
+
+impl Transfer {
+    fn to_linear(self, c: f32) -> f32 {
+        match self {
+            Transfer::Srgb      => srgb_to_linear(c),
+            Transfer::Linear    => c,
+            Transfer::Gamma(g)  => c.max(0.0).powf(g),
+        }
+    }
+}
+
+fn pixel_count(width: u32, height: u32) -> Result<usize, Error> {
+    fn_context!(d3d9::import_image);
+    if width == 0 || height == 0                        { return Err(fn_param_error!(bytes, D3DERR::INVALIDDATA)) }
+    if width > MAX_DIMENSION || height > MAX_DIMENSION  { return Err(fn_param_error!(bytes, D3DERR::NOTAVAILABLE)) }
+    Ok(width as usize * height as usize)
+}
+
+/// Little endian reads that fail with [D3DERR::INVALIDDATA] instead of panicking
+struct Reader<'b> { bytes: &'b [u8] }
+
+impl<'b> Reader<'b> {
+    fn slice(&self, offset: usize, len: usize) -> Result<&'b [u8], Error> {
+        fn_context!(d3d9::import_image);
+        offset.checked_add(len).and_then(|end| self.bytes.get(offset .. end)).ok_or_else(|| fn_param_error!(bytes, D3DERR::INVALIDDATA))
+    }
+    fn u8 (&self, offset: usize) -> Result<u8,  Error> { Ok(self.slice(offset, 1)?[0]) }
+    fn u16(&self, offset: usize) -> Result<u16, Error> { Ok(u16::from_le_bytes(self.slice(offset, 2)?.try_into().unwrap())) }
+    fn u32(&self, offset: usize) -> Result<u32, Error> { Ok(u32::from_le_bytes(self.slice(offset, 4)?.try_into().unwrap())) }
+}
+
+fn unorm(value: u32, bits: u32) -> f32 { value as f32 / ((1u64 << bits) - 1) as f32 }
+
+
+
+#[cfg(feature = "png")] fn decode_png(bytes: &[u8]) -> Result<Source, Error> {
+    fn_context!(d3d9::import_image);
+    let invalid = |_| fn_param_error!(bytes, D3DERR::INVALIDDATA);
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::EXPAND); // palettes => RGB(A), tRNS => alpha, 1/2/4-bit gray => 8-bit
+    let mut reader = decoder.read_info().map_err(invalid)?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buf).map_err(invalid)?;
+
+    let info = reader.info();
+    let transfer = match (info.srgb, info.source_gamma.map(|g| g.into_value())) {
+        (Some(_), _) | (None, None)                 => Transfer::Srgb,
+        (None, Some(g)) if (g - 1.0/2.2).abs() < 0.01 => Transfer::Srgb,
+        (None, Some(g)) if (g - 1.0).abs() < 0.01   => Transfer::Linear,
+        (None, Some(g)) if g > 0.0                  => Transfer::Gamma(1.0 / g),
+        (None, Some(_))                             => Transfer::Srgb,
+    };
+
+    let channels = frame.color_type.samples();
+    let sixteen = frame.bit_depth == png::BitDepth::Sixteen;
+    let sample_bytes = if sixteen { 2 } else { 1 };
+    let n = pixel_count(frame.width, frame.height)?;
+    let mut pixels = Vec::with_capacity(n);
+    for row in buf.chunks_exact(frame.line_size).take(frame.height as usize) {
+        for px in row.chunks_exact(channels * sample_bytes).take(frame.width as usize) {
+            let s = |i: usize| if sixteen { unorm(u16::from_be_bytes([px[2*i], px[2*i+1]]).into(), 16) } else { unorm(px[i].into(), 8) };
+            pixels.push(match channels {
+                1 => [s(0), s(0), s(0), 1.0 ],
+                2 => [s(0), s(0), s(0), s(1)],
+                3 => [s(0), s(1), s(2), 1.0 ],
+                _ => [s(0), s(1), s(2), s(3)],
+            });
+        }
+    }
+    if pixels.len() != n { return Err(fn_param_error!(bytes, D3DERR::INVALIDDATA)) }
+    Ok(Source { width: frame.width, height: frame.height, pixels, transfer, premultiplied: false })
+}
+
+#[cfg(not(feature = "png"))] fn decode_png(bytes: &[u8]) -> Result<Source, Error> {
+    fn_context!(d3d9::import_image);
+    let _ = bytes;
+    Err(fn_param_error!(bytes, D3DERR::NOTAVAILABLE))
+}
+
+
+
+fn decode_tga(bytes: &[u8]) -> Result<Source, Error> {
+    fn_context!(d3d9::import_image);
+    let invalid     = || fn_param_error!(bytes, D3DERR::INVALIDDATA);
+    let unsupported = || fn_param_error!(bytes, D3DERR::WRONGTEXTUREFORMAT);
+    let r = Reader { bytes };
+
+    let id_len      = r.u8(0)? as usize;
+    let cmap_type   = r.u8(1)?;
+    let image_type  = r.u8(2)?;
+    let cmap_first  = r.u16(3)? as usize;
+    let cmap_len    = r.u16(5)? as usize;
+    let cmap_bits   = r.u8(7)?;
+    let width       = u32::from(r.u16(12)?);
+    let height      = u32::from(r.u16(14)?);
+    let bits        = r.u8(16)?;
+    let descriptor  = r.u8(17)?;
+    if cmap_type > 1 || !matches!(image_type, 1 | 2 | 3 | 9 | 10 | 11) || !matches!(bits, 8 | 15 | 16 | 24 | 32) { return Err(invalid()) }
+    let n = pixel_count(width, height)?;
+
+    // TGA 2.0 extension area:  the attributes type says what (if anything) alpha means
+    let mut alpha = descriptor & 0x0F != 0;
+    let mut premultiplied = false;
+    if bytes.len() >= 18 + 26 && bytes.ends_with(b"TRUEVISION-XFILE.\0") {
+        let ext = r.u32(bytes.len() - 26)? as usize;
+        if ext != 0 && r.u16(ext)? >= 495 {
+            match r.u8(ext.checked_add(494).ok_or_else(invalid)?)? {
+                3 => alpha = true,
+                4 => { alpha = true; premultiplied = true },
+                _ => alpha = false,
+            }
+        }
+    }
+
+    let truecolor = |bits: u8, b: &[u8]| -> [f32; 4] {
+        match bits {
+            15 | 16 => {
+                let v = u32::from(u16::from_le_bytes([b[0], b[1]]));
+                [unorm((v >> 10) & 31, 5), unorm((v >> 5) & 31, 5), unorm(v & 31, 5), if bits == 16 && alpha { (v >> 15) as f32 } else { 1.0 }]
+            },
+            24 => [unorm(b[2].into(), 8), unorm(b[1].into(), 8), unorm(b[0].into(), 8), 1.0],
+            _  => [unorm(b[2].into(), 8), unorm(b[1].into(), 8), unorm(b[0].into(), 8), if alpha { unorm(b[3].into(), 8) } else { 1.0 }],
+        }
+    };
+
+    let mut offset = 18 + id_len;
+    let palette = if cmap_type == 1 {
+        if !matches!(cmap_bits, 15 | 16 | 24 | 32) { return Err(unsupported()) }
+        let entry = (usize::from(cmap_bits) + 7) / 8;
+        let data = r.slice(offset, cmap_len * entry)?;
+        offset += data.len();
+        data.chunks_exact(entry).map(|e| truecolor(cmap_bits, e)).collect()
+    } else {
+        Vec::new()
+    };
+
+    let bytes_per_pixel = (usize::from(bits) + 7) / 8;
+    let decode = |b: &[u8]| -> Result<[f32; 4], Error> {
+        Ok(match image_type & !8 {
+            1 => {
+                let index = if bits == 8 { usize::from(b[0]) } else if bits == 16 { usize::from(u16::from_le_bytes([b[0], b[1]])) } else { return Err(unsupported()) };
+                *index.checked_sub(cmap_first).and_then(|i| palette.get(i)).ok_or_else(invalid)?
+            },
+            2 if bits != 8  => truecolor(bits, b),
+            3 if bits == 8  => { let l = unorm(b[0].into(), 8); [l, l, l, 1.0] },
+            3 if bits == 16 => { let l = unorm(b[0].into(), 8); [l, l, l, if alpha { unorm(b[1].into(), 8) } else { 1.0 }] },
+            _ => return Err(unsupported()),
+        })
+    };
+
+    let mut pixels = Vec::new();
+    if image_type & 8 == 0 {
+        let data = r.slice(offset, n.checked_mul(bytes_per_pixel).ok_or_else(invalid)?)?; // before trusting the header's dimensions with an allocation
+        pixels.reserve_exact(n);
+        for px in data.chunks_exact(bytes_per_pixel) { pixels.push(decode(px)?) }
+    } else {
+        // runs can expand a few bytes into many pixels:  reserve only what the remaining data holds uncompressed, and grow from there
+        pixels.reserve(n.min(bytes.len().saturating_sub(offset) / bytes_per_pixel));
+        while pixels.len() < n {
+            let header = r.u8(offset)?;
+            let count = usize::from(header & 0x7F) + 1;
+            offset += 1;
+            if header & 0x80 != 0 {
+                let px = decode(r.slice(offset, bytes_per_pixel)?)?;
+                offset += bytes_per_pixel;
+                pixels.extend(std::iter::repeat(px).take(count));
+            } else {
+                for px in r.slice(offset, count * bytes_per_pixel)?.chunks_exact(bytes_per_pixel) { pixels.push(decode(px)?) }
+                offset += count * bytes_per_pixel;
+            }
+        }
+        pixels.truncate(n); // packets may (incorrectly) span past the last pixel
+    }
+
+    let (w, h) = (width as usize, height as usize);
+    if descriptor & 0x10 != 0 { pixels.chunks_exact_mut(w).for_each(|row| row.reverse()) } // right-to-left
+    if descriptor & 0x20 == 0 { flip_rows(&mut pixels, w, h) } // bottom-up
+    Ok(Source { width, height, pixels, transfer: Transfer::Srgb, premultiplied })
+}
+
+fn flip_rows(pixels: &mut [[f32; 4]], width: usize, height: usize) {
+    for y in 0 .. height / 2 {
+        let (top, bottom) = pixels.split_at_mut((height - 1 - y) * width);
+        top[y * width ..][.. width].swap_with_slice(&mut bottom[.. width]);
+    }
+}
+
+
+
+fn decode_bmp(bytes: &[u8]) -> Result<Source, Error> {
+    fn_context!(d3d9::import_image);
+    let invalid     = || fn_param_error!(bytes, D3DERR::INVALIDDATA);
+    let unsupported = || fn_param_error!(bytes, D3DERR::WRONGTEXTUREFORMAT);
+    let r = Reader { bytes };
+
+    let pixels_offset   = r.u32(10)? as usize;
+    let header_size     = r.u32(14)? as usize;
+    let (width, height, bits, compression, colors_used) = if header_size == 12 {
+        (i32::from(r.u16(18)?), i32::from(r.u16(20)?), r.u16(24)?, BI_RGB, 0)
+    } else if header_size >= 40 {
+        (r.u32(18)? as i32, r.u32(22)? as i32, r.u16(28)?, r.u32(30)?, r.u32(46)? as usize)
+    } else {
+        return Err(invalid())
+    };
+    let top_down = height < 0;
+    let (width, height) = (u32::try_from(width).map_err(|_| invalid())?, height.unsigned_abs());
+    let n = pixel_count(width, height)?;
+
+    let masks = match (compression, bits) {
+        (BI_RGB, 16)                                    => [0x7C00, 0x03E0, 0x001F, 0],
+        (BI_RGB, 24 | 32)                               => [0xFF0000, 0xFF00, 0xFF, 0],
+        (BI_RGB, 1 | 4 | 8)                             => [0; 4],
+        (BI_BITFIELDS | BI_ALPHABITFIELDS, 16 | 32) if header_size == 40 => {
+            let alpha = if compression == BI_ALPHABITFIELDS { r.u32(14 + 52)? } else { 0 };
+            [r.u32(14 + 40)?, r.u32(14 + 44)?, r.u32(14 + 48)?, alpha]
+        },
+        (BI_BITFIELDS | BI_ALPHABITFIELDS, 16 | 32) if header_size >= 52 => {
+            let alpha = if header_size >= 56 { r.u32(14 + 52)? } else { 0 };
+            [r.u32(14 + 40)?, r.u32(14 + 44)?, r.u32(14 + 48)?, alpha]
+        },
+        _ => return Err(unsupported()), // RLE4/RLE8, embedded JPEG/PNG, odd bit counts
+    };
+
+    let palette = if bits <= 8 {
+        let entry = if header_size == 12 { 3 } else { 4 };
+        let count = if colors_used == 0 { 1 << bits } else { colors_used.min(1 << bits) };
+        r.slice(14 + header_size, count * entry)?.chunks_exact(entry).map(|e| [unorm(e[2].into(), 8), unorm(e[1].into(), 8), unorm(e[0].into(), 8), 1.0]).collect()
+    } else {
+        Vec::new()
+    };
+
+    let channel = |v: u32, mask: u32| -> Option<f32> {
+        if mask == 0 { return None }
+        let shift = mask.trailing_zeros();
+        let bits = (mask >> shift).trailing_ones();
+        Some(unorm((v & mask) >> shift, bits.min(32)))
+    };
+
+    let stride = (width as usize).checked_mul(bits.into()).map(|b| (b + 31) / 32 * 4).ok_or_else(invalid)?;
+    let rows = r.slice(pixels_offset, stride.checked_mul(height as usize).ok_or_else(invalid)?)?;
+    let mut pixels = Vec::with_capacity(n);
+    for y in 0 .. height as usize {
+        let row = &rows[if top_down { y } else { height as usize - 1 - y } * stride ..][.. stride];
+        for x in 0 .. width as usize {
+            pixels.push(match bits {
+                1 | 4 | 8 => {
+                    let bit = x * usize::from(bits);
+                    let index = (row[bit / 8] >> (8 - usize::from(bits) - bit % 8)) & ((1u16 << bits) - 1) as u8;
+                    *palette.get(usize::from(index)).ok_or_else(invalid)?
+                },
+                _ => {
+                    let b = &row[x * usize::from(bits / 8) ..];
+                    let v = match bits { 16 => u32::from(u16::from_le_bytes([b[0], b[1]])), 24 => u32::from_le_bytes([b[0], b[1], b[2], 0]), _ => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) };
+                    let [r, g, b, a] = masks.map(|m| channel(v, m));
+                    [r.unwrap_or(0.0), g.unwrap_or(0.0), b.unwrap_or(0.0), a.unwrap_or(1.0)]
+                },
+            });
+        }
+    }
+    Ok(Source { width, height, pixels, transfer: Transfer::Srgb, premultiplied: false })
+}
+
+const BI_RGB            : u32 = 0;
+const BI_BITFIELDS      : u32 = 3;
+const BI_ALPHABITFIELDS : u32 = 6;
+
+
+
+fn decode_hdr(bytes: &[u8]) -> Result<Source, Error> {
+    fn_context!(d3d9::import_image);
+    let invalid     = || fn_param_error!(bytes, D3DERR::INVALIDDATA);
+    let unsupported = || fn_param_error!(bytes, D3DERR::WRONGTEXTUREFORMAT);
+
+    let mut rest = bytes;
+    let mut line = || -> Result<&[u8], Error> {
+        let end = rest.iter().position(|&b| b == b'\n').ok_or_else(invalid)?;
+        let line = &rest[..end];
+        rest = &rest[end+1 ..];
+        Ok(line)
+    };
+    loop {
+        let header = line()?;
+        if header.is_empty() { break }
+        if let Some(format) = header.strip_prefix(b"FORMAT=") {
+            if format != b"32-bit_rle_rgbe" { return Err(unsupported()) }
+        }
+    }
+    let resolution = std::str::from_utf8(line()?).map_err(|_| invalid())?;
+    let (bottom_up, height, width) = match resolution.split_ascii_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", h, "+X", w] => (false, h, w),
+        ["+Y", h, "+X", w] => (true,  h, w),
+        [_, _, _, _]       => return Err(unsupported()),
+        _                  => return Err(invalid()),
+    };
+    let width  : u32 = width .parse().map_err(|_| invalid())?;
+    let height : u32 = height.parse().map_err(|_| invalid())?;
+    let n = pixel_count(width, height)?;
+    let w = width as usize;
+
+    // runs can expand a few bytes into many pixels:  reserve only what the remaining data holds uncompressed, and grow from there
+    let mut rgbe = Vec::with_capacity(n.min(rest.len() / 4));
+    let mut scanline = vec![[0u8; 4]; w];
+    for _ in 0 .. height {
+        if (8 .. 0x8000).contains(&w) && starts_new_rle(rest, w) {
+            rest = &rest[4..];
+            for c in 0 .. 4 {
+                let mut x = 0;
+                while x < w {
+                    let count = usize::from(*take(&mut rest, 1).ok_or_else(invalid)?.first().unwrap());
+                    let (count, run) = if count > 128 { (count - 128, true) } else { (count, false) };
+                    let dst = scanline.get_mut(x .. x + count).filter(|dst| !dst.is_empty()).ok_or_else(invalid)?;
+                    let src = take(&mut rest, if run { 1 } else { count }).ok_or_else(invalid)?;
+                    if run { dst.iter_mut().for_each(|px| px[c] = src[0]) } else { dst.iter_mut().zip(src).for_each(|(px, v)| px[c] = *v) }
+                    x += count;
+                }
+            }
+        } else {
+            // flat pixels, possibly with old-style (1, 1, 1, count) run length encoding
+            let (mut x, mut shift) = (0, 0);
+            while x < w {
+                let px : [u8; 4] = take(&mut rest, 4).ok_or_else(invalid)?.try_into().unwrap();
+                if px[..3] == [1, 1, 1] && x > 0 {
+                    let count = usize::from(px[3]).checked_shl(shift).ok_or_else(invalid)?;
+                    let prev = scanline[x - 1];
+                    scanline.get_mut(x .. x + count).ok_or_else(invalid)?.fill(prev);
+                    x += count;
+                    shift += 8;
+                } else {
+                    scanline[x] = px;
+                    x += 1;
+                    shift = 0;
+                }
+            }
+        }
+        rgbe.extend_from_slice(&scanline);
+    }
+
+    let mut pixels = rgbe.into_iter().map(|[r, g, b, e]| {
+        if e == 0 { return [0.0, 0.0, 0.0, 1.0] }
+        let f = 2f32.powi(i32::from(e) - (128 + 8));
+        [f32::from(r) * f, f32::from(g) * f, f32::from(b) * f, 1.0]
+    }).collect::<Vec<_>>();
+    if bottom_up { flip_rows(&mut pixels, w, height as usize) }
+    Ok(Source { width, height, pixels, transfer: Transfer::Linear, premultiplied: false })
+}
+
+fn take<'b>(rest: &mut &'b [u8], count: usize) -> Option<&'b [u8]> {
+    if rest.len() < count { return None }
+    let (head, tail) = rest.split_at(count);
+    *rest = tail;
+    Some(head)
+}
+
+fn starts_new_rle(rest: &[u8], width: usize) -> bool {
+    matches!(rest, [2, 2, hi, lo, ..] if hi & 0x80 == 0 && usize::from(u16::from_be_bytes([*hi, *lo])) == width)
+}
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    fn import(bytes: &[u8], format: &'static FixedTextureFormat) -> ImportedImage { import_image(bytes, &ImageImportOptions { format, premultiply_alpha: false }).unwrap() }
+    fn floats(image: &TextureImage) -> Vec<f32> { image.data().chunks_exact(4).map(|f| f32::from_le_bytes(f.try_into().unwrap())).collect() }
+
+    fn tga(image_type: u8, bits: u8, descriptor: u8, data: &[u8]) -> Vec<u8> {
+        let mut tga = vec![0, 0, image_type, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 2, 0, bits, descriptor];
+        tga.extend_from_slice(data);
+        tga
+    }
+
+    #[test] fn tga_variants() {
+        let argb = [0x00, 0x00, 0xFF, 0xFF,  0x00, 0xFF, 0x00, 0x80,  0xFF, 0x00, 0x00, 0x40,  0x10, 0x20, 0x30, 0x00]; // BGRA, top-down
+        assert_eq!(import(&tga(2, 32, 0x28, &argb), FixedTextureFormat::A8R8G8B8).image.data(), argb);
+
+        let bottom_up = [&argb[8..], &argb[..8]].concat();
+        assert_eq!(import(&tga(2, 32, 0x08, &bottom_up), FixedTextureFormat::A8R8G8B8).image.data(), argb);
+
+        let rle = [0x81, 0x00, 0x00, 0xFF, 0xFF,  0x01, 0xFF, 0x00, 0x00, 0x40, 0x10, 0x20, 0x30, 0x00];
+        assert_eq!(import(&tga(10, 32, 0x28, &rle), FixedTextureFormat::A8R8G8B8).image.data(), [0x00, 0x00, 0xFF, 0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x40, 0x10, 0x20, 0x30, 0x00]);
+
+        let no_alpha = import(&tga(2, 32, 0x20, &argb), FixedTextureFormat::A8R8G8B8); // 0 alpha bits
+        assert!(no_alpha.image.data().chunks(4).all(|px| px[3] == 0xFF));
+
+        let gray = import(&tga(3, 8, 0x20, &[0, 0x80, 0xFF, 0x40]), FixedTextureFormat::X8R8G8B8); // X is left zeroed
+        assert_eq!(gray.image.data(), [0, 0, 0, 0,  0x80, 0x80, 0x80, 0,  0xFF, 0xFF, 0xFF, 0,  0x40, 0x40, 0x40, 0]);
+
+        let mut mapped = vec![0, 1, 1,  1, 0, 2, 0, 24,  0, 0, 0, 0,  2, 0, 2, 0,  8, 0x20,  0x00, 0x00, 0xFF,  0xFF, 0x00, 0x00];
+        mapped.extend_from_slice(&[2, 1, 1, 2]);
+        assert_eq!(import(&mapped, FixedTextureFormat::X8R8G8B8).image.data(), [0xFF, 0, 0, 0,  0, 0, 0xFF, 0,  0, 0, 0xFF, 0,  0xFF, 0, 0, 0]);
+        mapped[18 + 6] = 0; // index 0 < first entry
+        assert_eq!(import_image(&mapped, &ImageImportOptions::default()).unwrap_err().kind(), D3DERR::INVALIDDATA);
+
+        // TGA 2.0 footer marking alpha as premultiplied
+        let mut premul = tga(2, 32, 0x28, &argb);
+        let ext = premul.len() as u32;
+        premul.extend_from_slice(&495u16.to_le_bytes());
+        premul.extend(std::iter::repeat(0).take(492));
+        premul.push(4);
+        premul.extend_from_slice(&ext.to_le_bytes());
+        premul.extend_from_slice(&[0; 4]);
+        premul.extend_from_slice(b"TRUEVISION-XFILE.\0");
+        let imported = import_image(&premul, &ImageImportOptions { premultiply_alpha: true, ..Default::default() }).unwrap();
+        assert!(imported.premultiplied);
+        assert_eq!(imported.image.data(), argb); // not premultiplied twice
+
+        // truncated pixel data claiming the largest dimensions
+        for image_type in [2, 10] {
+            let mut huge = tga(image_type, 32, 0x28, &argb);
+            huge[12..16].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF]);
+            assert_eq!(import_image(&huge, &ImageImportOptions::default()).unwrap_err().kind(), D3DERR::INVALIDDATA);
+        }
+    }
+
+    #[test] fn premultiply_and_float() {
+        let tga = tga(2, 32, 0x28, &[0x00, 0x00, 0xFF, 0xFF,  0x00, 0xFF, 0x00, 0x80,  0xFF, 0xFF, 0xFF, 0x00,  0xBC, 0xBC, 0xBC, 0xFF]);
+        let imported = import_image(&tga, &ImageImportOptions { premultiply_alpha: true, ..Default::default() }).unwrap();
+        assert!(imported.srgb && imported.premultiplied);
+        assert_eq!(imported.image.data(), [0x00, 0x00, 0xFF, 0xFF,  0x00, 0xBC, 0x00, 0x80,  0x00, 0x00, 0x00, 0x00,  0xBC, 0xBC, 0xBC, 0xFF]);
+
+        let imported = import(&tga, FixedTextureFormat::A32B32G32R32F);
+        assert!(!imported.srgb && !imported.premultiplied);
+        let f = floats(&imported.image);
+        assert_eq!(f[..8], [1.0, 0.0, 0.0, 1.0,  0.0, 1.0, 0.0, 128.0 / 255.0]);
+        assert!((f[12] - 0.5029).abs() < 1e-3); // 0xBC sRGB => ~0.5 linear
+
+        let imported = import(&tga, FixedTextureFormat::A16B16G16R16F);
+        assert_eq!(imported.image.data().len(), 4 * 8);
+        assert_eq!(import_image(&tga, &ImageImportOptions { format: FixedTextureFormat::R5G6B5, ..Default::default() }).unwrap_err().kind(), D3DERR::WRONGTEXTUREFORMAT);
+    }
+
+    fn bmp(header_size: u32, width: i32, height: i32, bits: u16, compression: u32, extra: &[u8], pixels: &[u8]) -> Vec<u8> {
+        let offset = 14 + header_size as usize + extra.len();
+        let mut bmp = b"BM".to_vec();
+        bmp.extend_from_slice(&((offset + pixels.len()) as u32).to_le_bytes());
+        bmp.extend_from_slice(&[0; 4]);
+        bmp.extend_from_slice(&(offset as u32).to_le_bytes());
+        bmp.extend_from_slice(&header_size.to_le_bytes());
+        bmp.extend_from_slice(&width.to_le_bytes());
+        bmp.extend_from_slice(&height.to_le_bytes());
+        bmp.extend_from_slice(&1u16.to_le_bytes());
+        bmp.extend_from_slice(&bits.to_le_bytes());
+        bmp.extend_from_slice(&compression.to_le_bytes());
+        bmp.resize(14 + header_size as usize, 0);
+        bmp.extend_from_slice(extra);
+        bmp.extend_from_slice(pixels);
+        bmp
+    }
+
+    #[test] fn bmp_variants() {
+        // 24-bit, bottom-up, 2x2 with row padding
+        let rgb = bmp(40, 2, 2, 24, BI_RGB, &[], &[0xFF, 0, 0,  0, 0xFF, 0,  0, 0,   0, 0, 0xFF,  0x10, 0x20, 0x30,  0, 0]);
+        assert_eq!(import(&rgb, FixedTextureFormat::A8R8G8B8).image.data(), [0, 0, 0xFF, 0xFF,  0x10, 0x20, 0x30, 0xFF,  0xFF, 0, 0, 0xFF,  0, 0xFF, 0, 0xFF]);
+
+        // 1-bit palettized, top-down
+        let mono = bmp(40, 3, -1, 1, BI_RGB, &[0, 0, 0, 0,  0xFF, 0xFF, 0xFF, 0], &[0b1010_0000, 0, 0, 0]);
+        assert_eq!(import(&mono, FixedTextureFormat::X8R8G8B8).image.data(), [0xFF, 0xFF, 0xFF, 0,  0, 0, 0, 0,  0xFF, 0xFF, 0xFF, 0]);
+
+        // 16-bit 565 bitfields
+        let masks = [0xF800u32, 0x07E0, 0x001F].iter().flat_map(|m| m.to_le_bytes()).collect::<Vec<_>>();
+        let r5g6b5 = bmp(40, 1, 1, 16, BI_BITFIELDS, &masks, &0xF81Fu16.to_le_bytes().iter().chain(&[0, 0]).copied().collect::<Vec<_>>());
+        assert_eq!(import(&r5g6b5, FixedTextureFormat::A8R8G8B8).image.data(), [0xFF, 0, 0xFF, 0xFF]);
+
+        // 32-bit V5 header with alpha mask
+        let mut v5 = bmp(124, 1, 1, 32, BI_BITFIELDS, &[], &[0x11, 0x22, 0x33, 0x80]);
+        for (i, m) in [0x00FF0000u32, 0x0000FF00, 0x000000FF, 0xFF000000].iter().enumerate() { v5[14 + 40 + 4*i ..][..4].copy_from_slice(&m.to_le_bytes()) }
+        assert_eq!(import(&v5, FixedTextureFormat::A8R8G8B8).image.data(), [0x11, 0x22, 0x33, 0x80]);
+
+        let rle = bmp(40, 1, 1, 8, 1, &[0; 4], &[1, 0, 0, 1]);
+        assert_eq!(import_image(&rle, &ImageImportOptions::default()).unwrap_err().kind(), D3DERR::WRONGTEXTUREFORMAT);
+        assert_eq!(import_image(&rgb[..rgb.len() - 1], &ImageImportOptions::default()).unwrap_err().kind(), D3DERR::INVALIDDATA);
+    }
+
+    #[test] fn hdr_variants() {
+        let mut flat = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\nEXPOSURE=1.0\n\n-Y 1 +X 3\n".to_vec();
+        flat.extend_from_slice(&[128, 64, 0, 129,  128, 128, 128, 128,  1, 1, 1, 1]); // 1.0 0.5 0.0 | 0.5 x3 (old style run)
+        let imported = import(&flat, FixedTextureFormat::A32B32G32R32F);
+        assert_eq!(floats(&imported.image), [1.0, 0.5, 0.0, 1.0,  0.5, 0.5, 0.5, 1.0,  0.5, 0.5, 0.5, 1.0]);
+        assert!(!imported.srgb);
+
+        let mut rle = b"#?RGBE\n\n+Y 2 +X 8\n".to_vec();
+        for e in [131, 127] { // 4.0, 0.25
+            rle.extend_from_slice(&[2, 2, 0, 8]);
+            for value in [128, 128, 128, e] { rle.extend_from_slice(&[128 + 8, value]) }
+        }
+        let f = floats(&import(&rle, FixedTextureFormat::A32B32G32R32F).image);
+        assert_eq!(f[..4], [0.25, 0.25, 0.25, 1.0]); // bottom-up:  the second row is the top
+        assert_eq!(f[32..36], [4.0, 4.0, 4.0, 1.0]);
+
+        let srgb = import(&flat, FixedTextureFormat::A8R8G8B8);
+        assert_eq!(srgb.image.data()[..4], [0x00, 0xBC, 0xFF, 0xFF]); // BGRA, 0.5 linear => 0xBC sRGB
+
+        let xyze = b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n\0\0\0\0";
+        assert_eq!(import_image(xyze, &ImageImportOptions::default()).unwrap_err().kind(), D3DERR::WRONGTEXTUREFORMAT);
+        assert_eq!(import_image(&flat[..flat.len() - 1], &ImageImportOptions::default()).unwrap_err().kind(), D3DERR::INVALIDDATA);
+
+        let mut huge = b"#?RADIANCE\n\n-Y 65535 +X 65535\n".to_vec();
+        huge.extend_from_slice(&[128, 64, 0, 129]);
+        assert_eq!(import_image(&huge, &ImageImportOptions::default()).unwrap_err().kind(), D3DERR::INVALIDDATA);
+    }
+
+    #[cfg(feature = "png")] #[test] fn png() {
+        let mut file = Vec::new();
+        let mut encoder = png::Encoder::new(&mut file, 2, 1);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Sixteen);
+        encoder.write_header().unwrap().write_image_data(&[0xFF, 0xFF, 0, 0, 0, 0, 0xFF, 0xFF,  0, 0, 0x80, 0x80, 0, 0, 0, 0]).unwrap();
+        assert_eq!(import(&file, FixedTextureFormat::A8R8G8B8).image.data(), [0, 0, 0xFF, 0xFF,  0, 0x80, 0, 0]);
+
+        let mut file = Vec::new();
+        let mut encoder = png::Encoder::new(&mut file, 1, 1);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_source_gamma(png::ScaledFloat::new(1.0));
+        encoder.write_header().unwrap().write_image_data(&[0x80]).unwrap();
+        let f = floats(&import(&file, FixedTextureFormat::A32B32G32R32F).image);
+        assert_eq!(f, [128.0 / 255.0, 128.0 / 255.0, 128.0 / 255.0, 1.0]); // linear:  no sRGB decode
+    }
+}
@@ -0,0 +1,321 @@
+use crate::*;
+use crate::d3d9::{TextureImage, TextureMipRef};
+use crate::d3d9::pixel_convert::{decode_pixels, encode_pixels};
+
+
+
+/// The reconstruction filter [generate_mip_chain] / [TextureImage::generate_mips] downsample with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MipFilter {
+    /// Area average of the source pixels each destination pixel covers.  Matches `D3DX_FILTER_BOX` for power of two sizes.
+    #[default] Box,
+
+    /// Tent filter spanning two destination pixels.  Slightly softer than [Box](Self::Box), with less aliasing.
+    Triangle,
+
+    /// Kaiser windowed sinc (radius 3, α = 4).  Sharpest, at the cost of mild ringing near hard edges.
+    Kaiser,
+}
+
+/// How [generate_mip_chain] / [TextureImage::generate_mips] sample past the edges of a mip.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MipEdgeMode {
+    /// Repeat the edge pixels, for textures sampled with [TAddress::Clamp](d3d::TAddress::Clamp).
+    #[default] Clamp,
+
+    /// Wrap around to the opposite edge, for tiling textures sampled with [TAddress::Wrap](d3d::TAddress::Wrap).
+    Wrap,
+}
+
+/// Options for [generate_mip_chain] / [TextureImage::generate_mips].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MipOptions {
+    /// The downsampling filter
+    pub filter:         MipFilter,
+
+    /// How to sample past the edges of each mip
+    pub edge:           MipEdgeMode,
+
+    /// Treat RGB as sRGB encoded:  convert to linear before filtering, and back to sRGB afterwards.  Alpha is always linear.
+    pub srgb:           bool,
+
+    /// Weight RGB by alpha while filtering, so fully transparent pixels don't bleed their (often garbage) color into their neighbors.
+    pub alpha_weighted: bool,
+}
+
+/// Generate a mip chain from `width` x `height` x `depth` RGBA [f32] `level0` pixels (row major, then slice major, tightly packed.)
+///
+/// Returns `levels` levels (or the full chain down to 1x1x1 if `levels` is `0`), each tightly packed, with `chain[0]` being a copy of `level0`.
+/// Each level is filtered from the previous one, halving (rounding down) each dimension larger than 1, so non-power-of-two sizes are
+/// resampled rather than truncated.  Filtering is separable and deterministic.
+///
+/// ### Errors
+/// *   [D3DERR::INVALIDCALL]       - `width`, `height`, or `depth` is 0, or `levels` exceeds the full mip chain
+/// *   [D3DERR::INVALIDCALL]       - `level0.len() != width * height * depth`
+///
+/// ### Example
+/// ```rust
+/// # use thindx::d3d9::*;
+/// let level0 = [[1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0], [0.0, 0.0, 1.0, 1.0], [1.0, 0.0, 0.0, 1.0]];
+/// let chain = generate_mip_chain(2, 2, 1, &level0, 0, &MipOptions::default()).unwrap();
+/// assert_eq!(chain.len(), 2);
+/// assert_eq!(chain[1], [[0.5, 0.0, 0.5, 1.0]]);
+/// ```
+pub fn generate_mip_chain(width: u32, height: u32, depth: u32, level0: &[[f32; 4]], levels: u32, options: &MipOptions) -> Result<Vec<Vec<[f32; 4]>>, Error> {
+    fn_context!(d3d9::generate_mip_chain);
+    if width == 0 || height == 0 || depth == 0 { return Err(fn_param_error!(width, D3DERR::INVALIDCALL)) }
+    let max_levels = 32 - width.max(height).max(depth).leading_zeros();
+    let levels = if levels == 0 { max_levels } else { levels };
+    if levels > max_levels { return Err(fn_param_error!(levels, D3DERR::INVALIDCALL)) }
+    let pixels = (width as usize).checked_mul(height as usize).and_then(|n| n.checked_mul(depth as usize));
+    if pixels != Some(level0.len()) { return Err(fn_param_error!(level0, D3DERR::INVALIDCALL)) }
+
+    let mut chain = vec![level0.to_vec()];
+    let mut linear = level0.iter().map(|px| to_linear(*px, options)).collect::<Vec<_>>();
+    let mut size = [width, height, depth];
+    for _ in 1 .. levels {
+        let next = size.map(|n| (n / 2).max(1));
+        linear = if options.alpha_weighted {
+            // filter premultiplied and straight color side by side, so fully transparent regions keep a sane color
+            let wide = linear.iter().map(|&[r, g, b, a]| [r*a, g*a, b*a, a, r, g, b, a]).collect::<Vec<_>>();
+            resample(wide, size, next, options).into_iter().map(|[pr, pg, pb, a, r, g, b, _]| {
+                if a > 1.0 / 65536.0 { [pr/a, pg/a, pb/a, a] } else { [r, g, b, a] }
+            }).collect()
+        } else {
+            resample(linear, size, next, options)
+        };
+        size = next;
+        chain.push(linear.iter().map(|px| from_linear(*px, options)).collect());
+    }
+    Ok(chain)
+}
+
+impl TextureImage {
+    /// Regenerate every mip level below level 0 of every face (or every slice, for a volume texture) with [generate_mip_chain].
+    ///
+    /// Pixels are decoded with [decode_pixels] and re-encoded with [encode_pixels], so any format those support works, including
+    /// block compressed formats (which are filtered from their decompressed level 0, then recompressed.)
+    /// Cube faces are filtered independently, so [MipEdgeMode::Clamp] is usually what you want for them.
+    ///
+    /// Create the image with the number of levels you want (e.g. `0` for a full chain) first.
+    ///
+    /// ### Errors
+    /// *   [D3DERR::WRONGTEXTUREFORMAT]    - [decode_pixels] / [encode_pixels] doesn't support [TextureImage::format]
+    ///
+    /// ### Example
+    /// ```rust
+    /// # use dev::d3d9::*; let device = device_pure();
+    /// let mut image = TextureImage::texture(FixedTextureFormat::A8R8G8B8, 64, 64, 0).unwrap();
+    /// for (i, px) in image.surface_mut(0, 0).unwrap().chunks_exact_mut(4).enumerate() {
+    ///     px.copy_from_slice(&if (i % 64 + i / 64) % 2 == 0 { [0xFF; 4] } else { [0x00, 0x00, 0x00, 0xFF] });
+    /// }
+    /// image.generate_mips(&MipOptions { filter: MipFilter::Kaiser, edge: MipEdgeMode::Wrap, srgb: true, ..Default::default() }).unwrap();
+    /// assert_eq!(image.surface(0, 6).unwrap(), [0xBC, 0xBC, 0xBC, 0xFF]); // 50% linear gray, sRGB encoded
+    ///
+    /// let texture = device.create_texture_from(
+    ///     image.width(), image.height(), &image.texture_mips().unwrap(),
+    ///     Usage::None, image.format(), Pool::Managed, (),
+    /// ).unwrap();
+    /// ```
+    pub fn generate_mips(&mut self, options: &MipOptions) -> Result<(), Error> {
+        fn_context!(d3d9::TextureImage::generate_mips);
+        let format = self.format().format;
+        let top = *self.level(0).expect("TextureImage always has at least 1 level");
+        for face in 0 .. self.faces() {
+            let mut level0 = Vec::new();
+            let surface = self.surface(face, 0).expect("face < faces()");
+            for slice in surface.chunks_exact(top.slice_pitch.max(1)).take(top.depth as usize) {
+                level0.extend(decode_pixels(format, TextureMipRef { data: slice, stride: top.row_pitch }, top.width, top.height)?);
+            }
+
+            let chain = generate_mip_chain(top.width, top.height, top.depth, &level0, self.levels(), options)?;
+            for (level, pixels) in chain.iter().enumerate().skip(1) {
+                let layout = *self.level(level as u32).expect("chain.len() == levels()");
+                let slice_pixels = layout.width as usize * layout.height as usize;
+                let surface = self.surface_mut(face, level as u32).expect("chain.len() == levels()");
+                for (dst, src) in surface.chunks_exact_mut(layout.slice_pitch.max(1)).zip(pixels.chunks_exact(slice_pixels)) {
+                    dst.copy_from_slice(&encode_pixels(format, layout.width, layout.height, src)?);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+
+
+pub(crate) fn srgb_to_linear(c: f32) -> f32 { if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) } }
+pub(crate) fn linear_to_srgb(c: f32) -> f32 { if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 } }
+
+fn to_linear(px: [f32; 4], options: &MipOptions) -> [f32; 4] {
+    if !options.srgb { return px }
+    [srgb_to_linear(px[0]), srgb_to_linear(px[1]), srgb_to_linear(px[2]), px[3]]
+}
+
+fn from_linear(px: [f32; 4], options: &MipOptions) -> [f32; 4] {
+    if !options.srgb { return px }
+    [linear_to_srgb(px[0]), linear_to_srgb(px[1]), linear_to_srgb(px[2]), px[3]]
+}
+
+/// Separably resample `src` (`size[0]` x `size[1]` x `size[2]`) down to `dst`, one axis at a time.
+fn resample<const N: usize>(mut pixels: Vec<[f32; N]>, size: [u32; 3], dst: [u32; 3], options: &MipOptions) -> Vec<[f32; N]> {
+    let mut size = size.map(|n| n as usize);
+    for axis in 0 .. 3 {
+        let (src_n, dst_n) = (size[axis], dst[axis] as usize);
+        if src_n == dst_n { continue }
+
+        let taps = taps(src_n, dst_n, options);
+        let stride = size[..axis].iter().product::<usize>();
+        let outer = size[axis+1 ..].iter().product::<usize>();
+        let mut out = vec![[0.0; N]; stride * dst_n * outer];
+        for o in 0 .. outer {
+            for (d, taps) in taps.iter().enumerate() {
+                for i in 0 .. stride {
+                    let px = &mut out[(o * dst_n + d) * stride + i];
+                    for &(s, w) in taps {
+                        let src = pixels[(o * src_n + s) * stride + i];
+                        for c in 0 .. N { px[c] += w * src[c] }
+                    }
+                }
+            }
+        }
+        pixels = out;
+        size[axis] = dst_n;
+    }
+    pixels
+}
+
+/// The normalized `(source index, weight)` taps of each destination pixel when resampling `src` pixels down to `dst`.
+fn taps(src: usize, dst: usize, options: &MipOptions) -> Vec<Vec<(usize, f32)>> {
+    let scale = src as f64 / dst as f64;
+    let edge = |i: isize| match options.edge {
+        MipEdgeMode::Clamp  => i.clamp(0, src as isize - 1) as usize,
+        MipEdgeMode::Wrap   => i.rem_euclid(src as isize) as usize,
+    };
+    (0 .. dst).map(|d| {
+        let center = (d as f64 + 0.5) * scale;
+        let mut taps = Vec::new();
+        if options.filter == MipFilter::Box {
+            let (lo, hi) = (center - scale / 2.0, center + scale / 2.0);
+            for s in lo.floor() as isize .. hi.ceil() as isize {
+                let w = hi.min(s as f64 + 1.0) - lo.max(s as f64);
+                if w > 0.0 { taps.push((s, w)) }
+            }
+        } else {
+            let radius = if options.filter == MipFilter::Triangle { 1.0 } else { KAISER_RADIUS } * scale;
+            for s in (center - radius).floor() as isize ..= (center + radius).ceil() as isize {
+                let x = (s as f64 + 0.5 - center) / scale;
+                let w = if options.filter == MipFilter::Triangle { (1.0 - x.abs()).max(0.0) } else { kaiser(x) };
+                if w != 0.0 { taps.push((s, w)) }
+            }
+        }
+        let sum = taps.iter().map(|&(_, w)| w).sum::<f64>();
+        taps.into_iter().map(|(s, w)| (edge(s), (w / sum) as f32)).collect()
+    }).collect()
+}
+
+const KAISER_RADIUS : f64 = 3.0;
+const KAISER_ALPHA  : f64 = 4.0;
+
+fn kaiser(x: f64) -> f64 {
+    if x.abs() >= KAISER_RADIUS { return 0.0 }
+    let sinc = if x == 0.0 { 1.0 } else { let px = std::f64::consts::PI * x; px.sin() / px };
+    let t = x / KAISER_RADIUS;
+    sinc * bessel_i0(KAISER_ALPHA * (1.0 - t * t).sqrt()) / bessel_i0(KAISER_ALPHA)
+}
+
+/// Zeroth order modified Bessel function of the first kind
+fn bessel_i0(x: f64) -> f64 {
+    let (mut sum, mut term, q) = (1.0, 1.0, x * x / 4.0);
+    for k in 1 .. 32 {
+        term *= q / f64::from(k * k);
+        sum += term;
+        if term < sum * 1e-12 { break }
+    }
+    sum
+}
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::d3d::Format;
+    use crate::d3d9::FixedTextureFormat;
+
+    fn gray(v: &[f32]) -> Vec<[f32; 4]> { v.iter().map(|&v| [v, v, v, 1.0]).collect() }
+    fn close(a: &[[f32; 4]], b: &[[f32; 4]]) -> bool { a.len() == b.len() && a.iter().flatten().zip(b.iter().flatten()).all(|(a, b)| (a - b).abs() < 1e-5) }
+
+    #[test] fn box_filter() {
+        let level0 = gray(&[0.0, 0.25, 0.5, 0.75,  1.0, 0.0, 0.0, 0.0,  0.0, 0.0, 1.0, 1.0,  0.0, 0.0, 1.0, 1.0]);
+        let chain = generate_mip_chain(4, 4, 1, &level0, 0, &MipOptions::default()).unwrap();
+        assert_eq!(chain.len(), 3);
+        assert_eq!(chain[0], level0);
+        assert!(close(&chain[1], &gray(&[0.3125, 0.3125, 0.0, 1.0])));
+        assert!(close(&chain[2], &gray(&[0.40625])));
+
+        // non-power-of-two:  5 => 2 => 1, each destination pixel covering 2.5 source pixels
+        let chain = generate_mip_chain(5, 1, 1, &gray(&[1.0, 1.0, 0.0, 0.0, 0.5]), 0, &MipOptions::default()).unwrap();
+        assert_eq!(chain.iter().map(|l| l.len()).collect::<Vec<_>>(), [5, 2, 1]);
+        assert!(close(&chain[1], &gray(&[0.8, 0.2])));
+        assert!(close(&chain[2], &gray(&[0.5])));
+
+        // volume
+        let chain = generate_mip_chain(1, 2, 2, &gray(&[0.0, 0.25, 0.5, 1.0]), 0, &MipOptions::default()).unwrap();
+        assert!(close(&chain[1], &gray(&[0.4375])));
+    }
+
+    #[test] fn filters_and_edges() {
+        let level0 = gray(&[1.0, 0.0, 0.0, 0.0]);
+        let tri = |edge| MipOptions { filter: MipFilter::Triangle, edge, ..Default::default() };
+        assert!(close(&generate_mip_chain(4, 1, 1, &level0, 2, &tri(MipEdgeMode::Clamp)).unwrap()[1], &gray(&[0.5,   0.0  ])));
+        assert!(close(&generate_mip_chain(4, 1, 1, &level0, 2, &tri(MipEdgeMode::Wrap )).unwrap()[1], &gray(&[0.375, 0.125])));
+
+        let kaiser = MipOptions { filter: MipFilter::Kaiser, ..Default::default() };
+        let flat = generate_mip_chain(7, 3, 1, &gray(&[0.25; 21]), 0, &kaiser).unwrap();
+        assert!(flat.iter().skip(1).all(|level| close(level, &gray(&vec![0.25; level.len()]))));
+        let checker = (0 .. 64).map(|i| ((i % 8 + i / 8) % 2) as f32).collect::<Vec<_>>();
+        let chain = generate_mip_chain(8, 8, 1, &gray(&checker), 0, &MipOptions { edge: MipEdgeMode::Wrap, ..kaiser }).unwrap();
+        assert!(chain[1].iter().all(|px| (px[0] - 0.5).abs() < 1e-4));
+    }
+
+    #[test] fn srgb_and_alpha() {
+        let srgb = MipOptions { srgb: true, ..Default::default() };
+        let chain = generate_mip_chain(2, 1, 1, &[[0.0, 0.0, 0.0, 0.0], [1.0, 1.0, 1.0, 1.0]], 0, &srgb).unwrap();
+        assert!(close(&chain[1], &[[0.735357, 0.735357, 0.735357, 0.5]]));
+
+        let weighted = MipOptions { alpha_weighted: true, ..Default::default() };
+        let chain = generate_mip_chain(2, 1, 1, &[[1.0, 0.0, 0.0, 1.0], [0.0, 1.0, 0.0, 0.0]], 0, &weighted).unwrap();
+        assert!(close(&chain[1], &[[1.0, 0.0, 0.0, 0.5]]));
+        let chain = generate_mip_chain(2, 1, 1, &[[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0]], 0, &weighted).unwrap();
+        assert!(close(&chain[1], &[[0.5, 0.5, 0.0, 0.0]])); // fully transparent:  straight average
+    }
+
+    #[test] fn images() {
+        let mut image = TextureImage::cube_texture(FixedTextureFormat::A8R8G8B8, 4, 0).unwrap();
+        image.surface_mut(2, 0).unwrap().fill(0x80);
+        image.generate_mips(&MipOptions::default()).unwrap();
+        assert_eq!(image.surface(2, 2).unwrap(), [0x80; 4]);
+        assert_eq!(image.surface(3, 1).unwrap(), [0; 16]);
+
+        let mut image = TextureImage::volume_texture(FixedTextureFormat::L8, 2, 2, 2, 0).unwrap();
+        image.surface_mut(0, 0).unwrap().copy_from_slice(&[0, 0, 0, 0, 255, 255, 255, 255]);
+        image.generate_mips(&MipOptions::default()).unwrap();
+        assert_eq!(image.surface(0, 1).unwrap(), [128]);
+
+        let mut image = TextureImage::texture(FixedTextureFormat::DXT1, 8, 8, 0).unwrap();
+        image.generate_mips(&MipOptions::default()).unwrap();
+        let level3 = decode_pixels(Format::DXT1, image.texture_mips().unwrap()[3], 1, 1).unwrap();
+        assert_eq!(level3, [[0.0, 0.0, 0.0, 1.0]]);
+
+        let mut image = TextureImage::texture(FixedTextureFormat::A1, 8, 8, 0).unwrap();
+        assert_eq!(image.generate_mips(&MipOptions::default()).unwrap_err().kind(), D3DERR::WRONGTEXTUREFORMAT);
+    }
+
+    #[test] fn errors() {
+        let options = MipOptions::default();
+        assert_eq!(generate_mip_chain(0, 1, 1, &[], 0, &options).unwrap_err().kind(), D3DERR::INVALIDCALL);
+        assert_eq!(generate_mip_chain(2, 1, 1, &[[0.0; 4]], 0, &options).unwrap_err().kind(), D3DERR::INVALIDCALL);
+        assert_eq!(generate_mip_chain(2, 2, 1, &[[0.0; 4]; 4], 3, &options).unwrap_err().kind(), D3DERR::INVALIDCALL);
+        assert_eq!(generate_mip_chain(2, 2, 1, &[[0.0; 4]; 4], 1, &options).unwrap().len(), 1);
+    }
+}
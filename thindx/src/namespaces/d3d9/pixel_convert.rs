@@ -0,0 +1,486 @@
+use crate::*;
+use crate::d3d::{Half, Format, FormatChannel, FormatInfo, FormatNumeric};
+use crate::d3d9::{BlockCompressionQuality, TextureMipRef, compress_blocks, decompress_blocks};
+
+
+
+/// Decode `width` x `height` pixels of `format` into RGBA [f32]s (row major, tightly packed.)
+///
+/// Channels are mapped the way Direct3D 9 samplers expose them:
+///
+/// | Format family                                         | RGBA
+/// | ------------------------------------------------------| ----
+/// | `A8R8G8B8`, `R5G6B5`, `A2B10G10R10`, ...              | `(r, g, b, a)`, missing color channels read as `1`
+/// | `R16F`, `G16R16`, `G32R32F`, ...                      | `(r, g, 1, 1)` etc.
+/// | [A8](Format::A8)                                      | `(0, 0, 0, a)`
+/// | [L8](Format::L8), [A8L8](Format::A8L8), [L16](Format::L16), ... | `(l, l, l, a)`
+/// | [V8U8](Format::V8U8), [Q8W8V8U8](Format::Q8W8V8U8), ... | `(u, v, w, q)` (signed `-1 ..= 1`), missing channels read as `1`
+/// | [CxV8U8](Format::CxV8U8)                              | `(u, v, sqrt(1 - u² - v²), 1)`
+/// | [L6V5U5](Format::L6V5U5), [X8L8V8U8](Format::X8L8V8U8) | `(u, v, l, 1)`
+/// | [YUY2](Format::YUY2), [UYVY](Format::UYVY)            | BT.601 studio range YUV → RGB
+/// | `D16`, `D24S8`, `D32F_LOCKABLE`, ...                  | `(depth, stencil, 0, 1)`, stencil as an integer
+///
+/// [A2B10G10R10_XR_BIAS](Format::A2B10G10R10_XR_BIAS) decodes to its extended `-0.75 ..= 1.25` range.
+/// Block compressed formats are decoded via [decompress_blocks], with `src.stride` being the bytes per row of 4x4 blocks.
+///
+/// ### Errors
+/// *   [D3DERR::WRONGTEXTUREFORMAT]    - `format` isn't a color/depth format (palettized, index, [A1](Format::A1), [MULTI2_ARGB8](Format::MULTI2_ARGB8), ...)
+/// *   [D3DERR::INVALIDCALL]           - `src.data` is too small for `height` rows of `src.stride` bytes
+/// *   [D3DERR::INVALIDCALL]           - `src.stride` is smaller than a row of `width` pixels
+/// *   [THINERR::ALLOC_OVERFLOW]       - a row of pixels, or the decoded image, would be larger than `usize::MAX` bytes
+///
+/// ### Example
+/// ```rust
+/// # use thindx::{d3d::*, d3d9::*};
+/// let argb = [0xFF00FF00_u32, 0x80FF0000, 0x00FF0000, 0x00000000];
+/// let src  = TextureMipRef { data: bytemuck::cast_slice(&argb), stride: 8 };
+/// let rgba = decode_pixels(Format::A8R8G8B8, src, 1, 2).unwrap(); // ignore the second column
+/// assert_eq!(rgba, [[0.0, 1.0, 0.0, 1.0], [1.0, 0.0, 0.0, 0.0]]);
+/// ```
+pub fn decode_pixels(format: impl Into<Format>, src: TextureMipRef, width: u32, height: u32) -> Result<Vec<[f32; 4]>, Error> {
+    fn_context!(d3d9::decode_pixels);
+    let format = format.into();
+    if is_block_compressed(format) {
+        let pixels = decompress_blocks(format, src, width, height)?;
+        return Ok(pixels.into_iter().map(|px| px.map(|c| f32::from(c) / 255.0)).collect());
+    }
+    let codec = Codec::new(format).ok_or_else(|| fn_param_error!(format, D3DERR::WRONGTEXTUREFORMAT))?;
+    let row_bytes = codec.info.checked_row_bytes(width).ok_or_else(|| fn_param_error!(width, THINERR::ALLOC_OVERFLOW))?;
+    if !rows_fit(src, row_bytes, height as usize) { return Err(fn_param_error!(src, D3DERR::INVALIDCALL)) }
+
+    let len = (width as usize).checked_mul(height as usize).ok_or_else(|| fn_param_error!(height, THINERR::ALLOC_OVERFLOW))?;
+    let mut pixels = Vec::with_capacity(len);
+    for y in 0 .. height as usize {
+        codec.decode_row(&src.data[y * src.stride ..][..row_bytes], width as usize, &mut pixels);
+    }
+    Ok(pixels)
+}
+
+/// Decode `width` x `height` pixels of `format` into RGBA unorm [u16]s (row major, tightly packed.)
+///
+/// Equivalent to [decode_pixels], with each channel clamped to `0 ..= 1` and rounded to `0 ..= 65535`.
+/// Signed and stencil values lose their sign/range accordingly.
+///
+/// ### Errors
+/// *   See [decode_pixels]
+///
+/// ### Example
+/// ```rust
+/// # use thindx::{d3d::*, d3d9::*};
+/// let l8 = [0x00, 0x80, 0xFF];
+/// let rgba = decode_pixels_u16(Format::L8, TextureMipRef { data: &l8, stride: 3 }, 3, 1).unwrap();
+/// assert_eq!(rgba, [[0, 0, 0, 0xFFFF], [0x8080, 0x8080, 0x8080, 0xFFFF], [0xFFFF; 4]]);
+/// ```
+pub fn decode_pixels_u16(format: impl Into<Format>, src: TextureMipRef, width: u32, height: u32) -> Result<Vec<[u16; 4]>, Error> {
+    let pixels = decode_pixels(format, src, width, height)?;
+    Ok(pixels.into_iter().map(|px| px.map(|c| (c.clamp(0.0, 1.0) * 65535.0).round() as u16)).collect())
+}
+
+/// Encode `width` x `height` RGBA [f32] `pixels` (row major, tightly packed) as `format`.
+///
+/// The result is tightly packed:  each row is <code>[FormatInfo::row_bytes]\(width\)</code> bytes, ready for use as a
+/// [TextureMipRef] with that `stride`.  Channels are mapped as the inverse of [decode_pixels]:  luminance formats store
+/// Rec. 709 luma, [YUY2](Format::YUY2) / [UYVY](Format::UYVY) / [R8G8_B8G8](Format::R8G8_B8G8) / [G8R8_G8B8](Format::G8R8_G8B8)
+/// average the shared channels of each pixel pair, and out of range values are clamped.
+/// Block compressed formats are encoded via [compress_blocks] with [BlockCompressionQuality::Normal].
+///
+/// ### Errors
+/// *   [D3DERR::WRONGTEXTUREFORMAT]    - `format` isn't a color/depth format
+/// *   [D3DERR::INVALIDCALL]           - `pixels.len() != width * height`
+///
+/// ### Example
+/// ```rust
+/// # use thindx::{d3d::*, d3d9::*};
+/// let bytes = encode_pixels(Format::R5G6B5, 2, 1, &[[1.0, 0.0, 0.0, 1.0], [0.0, 1.0, 1.0, 0.5]]).unwrap();
+/// assert_eq!(bytes, [0x00, 0xF8, 0xFF, 0x07]);
+///
+/// let bytes = encode_pixels(Format::R16F, 1, 1, &[[0.5, 0.0, 0.0, 0.0]]).unwrap();
+/// assert_eq!(bytes, Half::from_f32(0.5).to_bits().to_le_bytes());
+/// ```
+pub fn encode_pixels(format: impl Into<Format>, width: u32, height: u32, pixels: &[[f32; 4]]) -> Result<Vec<u8>, Error> {
+    fn_context!(d3d9::encode_pixels);
+    let format = format.into();
+    if is_block_compressed(format) {
+        let pixels = pixels.iter().map(|px| px.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)).collect::<Vec<_>>();
+        return compress_blocks(format, width, height, &pixels, BlockCompressionQuality::default());
+    }
+    let codec = Codec::new(format).ok_or_else(|| fn_param_error!(format, D3DERR::WRONGTEXTUREFORMAT))?;
+    if Some(pixels.len()) != (width as usize).checked_mul(height as usize) { return Err(fn_param_error!(pixels, D3DERR::INVALIDCALL)) }
+
+    let mut bytes = Vec::with_capacity(codec.info.surface_bytes(width, height));
+    if width != 0 {
+        for row in pixels.chunks_exact(width as usize) { codec.encode_row(row, &mut bytes) }
+    }
+    Ok(bytes)
+}
+
+/// Convert `width` x `height` pixels of `src_format` into tightly packed `dst_format` pixels.
+///
+/// Useful for offline asset conversion, or for creating fallback textures at load time when
+/// [IDirect3D9Ext::check_device_format](crate::d3d9::IDirect3D9Ext::check_device_format) rejects the original format.
+/// This is [decode_pixels] followed by [encode_pixels], and maps channels accordingly.
+///
+/// ### Errors
+/// *   See [decode_pixels] and [encode_pixels]
+///
+/// ### Example
+/// ```rust
+/// # use thindx::{d3d::*, d3d9::*};
+/// let l8      = [0x00, 0x40, 0x80, 0xFF, /* padding: */ 0x12, 0x34];
+/// let src     = TextureMipRef { data: &l8, stride: 3 };
+/// let argb    = convert_pixels(Format::L8, src, 2, 2, Format::A8R8G8B8).unwrap();
+/// let argb    = argb.chunks(4).map(|c| u32::from_le_bytes(c.try_into().unwrap())).collect::<Vec<_>>();
+/// assert_eq!(argb, [0xFF000000, 0xFF404040, 0xFFFFFFFF, 0xFF121212]);
+/// ```
+pub fn convert_pixels(src_format: impl Into<Format>, src: TextureMipRef, width: u32, height: u32, dst_format: impl Into<Format>) -> Result<Vec<u8>, Error> {
+    let pixels = decode_pixels(src_format, src, width, height)?;
+    encode_pixels(dst_format, width, height, &pixels)
+}
+
+
+
+fn is_block_compressed(format: Format) -> bool { FormatInfo::of(format).is_some_and(|info| info.compressed) }
+
+pub(crate) fn rows_fit(src: TextureMipRef, row_bytes: usize, rows: usize) -> bool {
+    if rows == 0 { return true }
+    if rows > 1 && src.stride < row_bytes { return false }
+    let last = (rows - 1).checked_mul(src.stride).and_then(|o| o.checked_add(row_bytes));
+    last.is_some_and(|last| last <= src.data.len())
+}
+
+#[derive(Clone, Copy)] struct Codec {
+    info:   &'static FormatInfo,
+    layout: Layout,
+    kinds:  [Kind; SLOTS], // indexed by R, G, B, A, L, U, V, W, Q, D, S
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)] enum Layout { Channels, Yuv, Rgbg }
+
+#[derive(Clone, Copy, PartialEq, Eq)] enum Kind { Unorm, Snorm, Uint, Float, XrBias, Float20e4 }
+
+const SLOTS : usize = 11;
+const R : usize = 0; const G : usize = 1; const B : usize = 2; const A : usize = 3; const L : usize = 4;
+const U : usize = 5; const V : usize = 6; const W : usize = 7; const Q : usize = 8; const D : usize = 9; const S : usize = 10;
+
+impl Codec {
+    fn new(format: Format) -> Option<Self> {
+        let info = FormatInfo::of(format)?;
+        if info.bits_per_block == 0 || info.bits_per_block % 8 != 0 || info.compressed || info.index { return None }
+
+        let layout = match format {
+            Format::YUY2 | Format::UYVY             => Layout::Yuv,
+            Format::R8G8_B8G8 | Format::G8R8_G8B8   => Layout::Rgbg,
+            _                                       => Layout::Channels,
+        };
+
+        let mut kinds = [Kind::Unorm; SLOTS];
+        if layout == Layout::Channels {
+            for slot in [U, V, W, Q] { kinds[slot] = Kind::Snorm }
+            kinds[S] = Kind::Uint;
+            if format == Format::D24FS8 { kinds[D] = Kind::Float20e4 }
+        }
+        match info.numeric {
+            FormatNumeric::Float    => kinds = [Kind::Float; SLOTS],
+            FormatNumeric::XrBias   => for slot in [R, G, B] { kinds[slot] = Kind::XrBias },
+            _                       => {},
+        }
+
+        Some(Self { info, layout, kinds })
+    }
+
+    fn channels(&self) -> [FormatChannel; SLOTS] {
+        let c = &self.info.channels;
+        [c.r, c.g, c.b, c.a, c.l, c.u, c.v, c.w, c.q, c.d, c.s]
+    }
+
+    fn block_bytes(&self) -> usize { usize::from(self.info.bits_per_block / 8) }
+
+    fn decode_row(&self, row: &[u8], width: usize, pixels: &mut Vec<[f32; 4]>) {
+        let bb = self.block_bytes();
+        match self.layout {
+            Layout::Channels => pixels.extend(row.chunks_exact(bb).take(width).map(|block| self.decode_pixel(read_block(block)))),
+            Layout::Yuv | Layout::Rgbg => {
+                for x in 0 .. width {
+                    let block = read_block(&row[x / 2 * bb ..][..bb]);
+                    pixels.push(if self.layout == Layout::Yuv { self.decode_yuv(block, x % 2) } else { self.decode_rgbg(block, x % 2) });
+                }
+            },
+        }
+    }
+
+    fn encode_row(&self, pixels: &[[f32; 4]], bytes: &mut Vec<u8>) {
+        let bb = self.block_bytes();
+        match self.layout {
+            Layout::Channels => for px in pixels.iter() { bytes.extend_from_slice(&self.encode_pixel(px).to_le_bytes()[..bb]) },
+            Layout::Yuv | Layout::Rgbg => {
+                for pair in pixels.chunks(2) {
+                    let p0 = &pair[0];
+                    let p1 = pair.get(1).unwrap_or(p0);
+                    let block = if self.layout == Layout::Yuv { self.encode_yuv(p0, p1) } else { self.encode_rgbg(p0, p1) };
+                    bytes.extend_from_slice(&block.to_le_bytes()[..bb]);
+                }
+            },
+        }
+    }
+
+    fn decode_pixel(&self, block: u128) -> [f32; 4] {
+        let ch = self.channels();
+        let mut v = [0.0; SLOTS];
+        for slot in 0 .. SLOTS {
+            if ch[slot].is_present() { v[slot] = self.kinds[slot].decode(ch[slot].bits, extract(block, ch[slot])) }
+        }
+        let has = |slot: usize| ch[slot].is_present();
+
+        let alpha_only  = has(A) && ![R, G, B, L, U, V, W, Q].into_iter().any(has);
+        let depth       = has(D) || has(S);
+        let mut px      = if alpha_only || depth { [0.0, 0.0, 0.0, 1.0] } else { [1.0; 4] };
+        for (slot, i) in [(R, 0), (G, 1), (B, 2), (A, 3), (U, 0), (V, 1), (W, 2), (Q, 3), (D, 0), (S, 1)] {
+            if has(slot) { px[i] = v[slot] }
+        }
+        if has(L) {
+            if has(U) { px[2] = v[L] } else { px[0] = v[L]; px[1] = v[L]; px[2] = v[L] }
+        }
+        if self.info.format == Format::CxV8U8 { px[2] = (1.0 - v[U]*v[U] - v[V]*v[V]).max(0.0).sqrt() }
+        px
+    }
+
+    fn encode_pixel(&self, px: &[f32; 4]) -> u128 {
+        let ch = self.channels();
+        let luma = if ch[U].is_present() { px[2] } else { 0.2126 * px[0] + 0.7152 * px[1] + 0.0722 * px[2] };
+        let v = [px[0], px[1], px[2], px[3], luma, px[0], px[1], px[2], px[3], px[0], px[1]];
+        let mut block = 0;
+        for slot in 0 .. SLOTS {
+            if ch[slot].is_present() { block |= u128::from(self.kinds[slot].encode(ch[slot].bits, v[slot])) << ch[slot].offset }
+        }
+        block
+    }
+
+    // The second `Y` / `G` of each 2x1 block is 16 bits after the first
+    fn second(ch: FormatChannel) -> FormatChannel { FormatChannel { offset: ch.offset + 16, ..ch } }
+
+    fn decode_yuv(&self, block: u128, x: usize) -> [f32; 4] {
+        let c = &self.info.channels;
+        let y = extract(block, if x == 0 { c.l } else { Self::second(c.l) }) as f32 - 16.0;
+        let u = extract(block, c.u) as f32 - 128.0;
+        let v = extract(block, c.v) as f32 - 128.0;
+        let rgb = [
+            1.164_384 * y + 1.596_027 * v,
+            1.164_384 * y - 0.391_762 * u - 0.812_968 * v,
+            1.164_384 * y + 2.017_232 * u,
+        ];
+        let [r, g, b] = rgb.map(|c| (c / 255.0).clamp(0.0, 1.0));
+        [r, g, b, 1.0]
+    }
+
+    fn encode_yuv(&self, p0: &[f32; 4], p1: &[f32; 4]) -> u128 {
+        let c = &self.info.channels;
+        let y = |p: &[f32; 4]| 16.0 + 65.481 * p[0] + 128.553 * p[1] + 24.966 * p[2];
+        let u = |p: &[f32; 4]| 128.0 - 37.797 * p[0] - 74.203 * p[1] + 112.0 * p[2];
+        let v = |p: &[f32; 4]| 128.0 + 112.0 * p[0] - 93.786 * p[1] - 18.214 * p[2];
+        let byte = |f: f32| u128::from(f.round().clamp(0.0, 255.0) as u8);
+        let p0 = p0.map(|c| c.clamp(0.0, 1.0));
+        let p1 = p1.map(|c| c.clamp(0.0, 1.0));
+        byte(y(&p0)) << c.l.offset | byte(y(&p1)) << Self::second(c.l).offset
+            | byte((u(&p0) + u(&p1)) / 2.0) << c.u.offset
+            | byte((v(&p0) + v(&p1)) / 2.0) << c.v.offset
+    }
+
+    fn decode_rgbg(&self, block: u128, x: usize) -> [f32; 4] {
+        let c = &self.info.channels;
+        let unorm = |ch| Kind::Unorm.decode(8, extract(block, ch));
+        [unorm(c.r), unorm(if x == 0 { c.g } else { Self::second(c.g) }), unorm(c.b), 1.0]
+    }
+
+    fn encode_rgbg(&self, p0: &[f32; 4], p1: &[f32; 4]) -> u128 {
+        let c = &self.info.channels;
+        let unorm = |f: f32, ch: FormatChannel| u128::from(Kind::Unorm.encode(8, f)) << ch.offset;
+        unorm((p0[0] + p1[0]) / 2.0, c.r) | unorm(p0[1], c.g) | unorm(p1[1], Self::second(c.g)) | unorm((p0[2] + p1[2]) / 2.0, c.b)
+    }
+}
+
+impl Kind {
+    fn decode(self, bits: u8, raw: u32) -> f32 {
+        match self {
+            Kind::Unorm     => (f64::from(raw) / f64::from(mask(bits))) as f32,
+            Kind::Snorm     => {
+                let shift = 32 - u32::from(bits);
+                let signed = ((raw << shift) as i32) >> shift;
+                (signed as f32 / (mask(bits - 1)) as f32).max(-1.0)
+            },
+            Kind::Uint      => raw as f32,
+            Kind::Float     => if bits == 16 { Half::from_bits(raw as u16).to_f32() } else { f32::from_bits(raw) },
+            Kind::XrBias    => (raw as f32 - 384.0) / 510.0,
+            Kind::Float20e4 => {
+                let (exp, man) = (((raw >> 20) & 0xF) as i32, raw & 0xF_FFFF);
+                let man = if exp == 0 { man } else { man | 0x10_0000 };
+                (f64::from(man) * 2f64.powi(exp.max(1) - 15 - 20)) as f32
+            },
+        }
+    }
+
+    fn encode(self, bits: u8, value: f32) -> u32 {
+        let value = if value.is_nan() { 0.0 } else { f64::from(value) };
+        match self {
+            Kind::Unorm     => (value.clamp(0.0, 1.0) * f64::from(mask(bits))).round() as u32,
+            Kind::Snorm     => ((value.clamp(-1.0, 1.0) * f64::from(mask(bits - 1))).round() as i32 as u32) & mask(bits),
+            Kind::Uint      => value.round().clamp(0.0, f64::from(mask(bits))) as u32,
+            Kind::Float     => if bits == 16 { Half::from_f32(value as f32).to_bits().into() } else { (value as f32).to_bits() },
+            Kind::XrBias    => (value * 510.0 + 384.0).round().clamp(0.0, 1023.0) as u32,
+            Kind::Float20e4 => {
+                if value <= 0.0 { return 0 }
+                let exp = (value.log2().floor() as i32).clamp(-14, 0);
+                let mut man = (value * 2f64.powi(20 - exp)).round() as u64;
+                let mut biased = exp + 15;
+                if man >= 0x20_0000 { man >>= 1; biased += 1; }
+                if biased > 15 { return 0xFF_FFFF }
+                if man < 0x10_0000 { biased = 0 } // subnormal
+                ((biased as u32) << 20) | (man as u32 & 0xF_FFFF)
+            },
+        }
+    }
+}
+
+fn mask(bits: u8) -> u32 { ((1u64 << bits) - 1) as u32 }
+
+fn extract(block: u128, ch: FormatChannel) -> u32 { ((block >> ch.offset) as u32) & mask(ch.bits) }
+
+fn read_block(bytes: &[u8]) -> u128 {
+    let mut b = [0u8; 16];
+    b[..bytes.len()].copy_from_slice(bytes);
+    u128::from_le_bytes(b)
+}
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    fn supported() -> impl Iterator<Item = Format> {
+        FormatInfo::all().iter().map(|i| i.format).filter(|&f| Codec::new(f).is_some())
+    }
+
+    fn assert_close(a: [f32; 4], b: [f32; 4], eps: f32, what: impl std::fmt::Debug) {
+        assert!(a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() <= eps), "{what:?}: {a:?} != {b:?}");
+    }
+
+    #[test] fn supported_formats() {
+        for f in [Format::A8R8G8B8, Format::R5G6B5, Format::A2B10G10R10, Format::A2R10G10B10, Format::A16B16G16R16F, Format::A32B32G32R32F,
+            Format::V8U8, Format::Q8W8V8U8, Format::L8, Format::A8L8, Format::L16, Format::D16, Format::D24S8, Format::D32F_LOCKABLE,
+            Format::D24FS8, Format::YUY2, Format::UYVY, Format::R8G8_B8G8, Format::CxV8U8, Format::A2B10G10R10_XR_BIAS, Format::X8_LOCKABLE,
+        ] { assert!(Codec::new(f).is_some(), "{f:?}") }
+
+        for f in [Format::UNKNOWN, Format::DXT1, Format::DXT5, Format::P8, Format::A8P8, Format::A1, Format::INDEX16, Format::INDEX32,
+            Format::VERTEXDATA, Format::MULTI2_ARGB8, Format::BINARYBUFFER, Format::from_unchecked(0x12345678),
+        ] { assert!(Codec::new(f).is_none(), "{f:?}") }
+    }
+
+    #[test] fn round_trip_raw() {
+        // every representable value of every supported format should survive decode => encode unchanged (modulo unused bits)
+        for format in supported() {
+            let codec = Codec::new(format).unwrap();
+            let used = codec.channels().iter().filter(|c| c.is_present()).fold(0u128, |m, c| m | u128::from(mask(c.bits)) << c.offset);
+            if codec.layout != Layout::Channels || format == Format::D24FS8 { continue }
+            if codec.kinds[D] == Kind::Unorm && codec.info.channels.d.bits > 24 { continue } // D32:  more bits than f32 has mantissa
+            let bb = codec.block_bytes();
+            let mut seed = 0x1234_5678_9ABC_DEF0_u64;
+            for _ in 0 .. 256 {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                let block = (u128::from(seed) << 64 | u128::from(seed.rotate_left(17))) & used;
+                if codec.decode_pixel(block).iter().any(|c| c.is_nan()) { continue }
+                if codec.kinds.contains(&Kind::Snorm) {
+                    // the most negative snorm value aliases -1.0
+                    let px = codec.decode_pixel(block);
+                    if px.iter().any(|&c| c == -1.0) { continue }
+                }
+                let bytes = &block.to_le_bytes()[..bb];
+                let decoded = decode_pixels(format, TextureMipRef { data: bytes, stride: bb }, 1, 1).unwrap();
+                let encoded = encode_pixels(format, 1, 1, &decoded).unwrap();
+                assert_eq!(encoded, bytes, "{format:?} {decoded:?}");
+            }
+        }
+    }
+
+    #[test] fn values() {
+        let one = |format, bytes: &[u8]| decode_pixels(format, TextureMipRef { data: bytes, stride: bytes.len() }, 1, 1).unwrap()[0];
+        assert_eq!(one(Format::A2B10G10R10, &0xC00F_FC00_u32.to_le_bytes()), [0.0, 1.0, 0.0, 1.0]);
+        assert_eq!(one(Format::A2R10G10B10, &0x3FF0_0000_u32.to_le_bytes()), [1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(one(Format::R5G6B5,      &0x001F_u16.to_le_bytes()), [0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(one(Format::A8,          &[0x33]), [0.0, 0.0, 0.0, 0.2]);
+        assert_eq!(one(Format::A8L8,        &[0xFF, 0x00]), [1.0, 1.0, 1.0, 0.0]);
+        assert_eq!(one(Format::V8U8,        &[0x81, 0x7F]), [-1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(one(Format::V8U8,        &[0x80, 0x00]), [-1.0, 0.0, 1.0, 1.0]);
+        assert_eq!(one(Format::Q8W8V8U8,    &[0x7F, 0x00, 0x81, 0x7F]), [1.0, 0.0, -1.0, 1.0]);
+        assert_eq!(one(Format::CxV8U8,      &[0x00, 0x00]), [0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(one(Format::L6V5U5,      &0xFC0F_u16.to_le_bytes()), [1.0, 0.0, 1.0, 1.0]);
+        assert_eq!(one(Format::R16F,        &Half::from_f32(-2.5).to_bits().to_le_bytes()), [-2.5, 1.0, 1.0, 1.0]);
+        assert_eq!(one(Format::G32R32F,     bytemuck::cast_slice(&[0.25f32, 8.0])), [0.25, 8.0, 1.0, 1.0]);
+        assert_eq!(one(Format::D16,         &[0xFF, 0xFF]), [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(one(Format::D24S8,       &0xFFFF_FF2A_u32.to_le_bytes()), [1.0, 42.0, 0.0, 1.0]);
+        assert_eq!(one(Format::D32F_LOCKABLE, &0.75f32.to_le_bytes()), [0.75, 0.0, 0.0, 1.0]);
+        assert_eq!(one(Format::A2B10G10R10_XR_BIAS, &0xC000_0180_u32.to_le_bytes()), [0.0, -384.0/510.0, -384.0/510.0, 1.0]);
+
+        for depth in [0.0, 1.0, 0.5, 0.999_999, 1.0e-5, 3.0e-9] {
+            let bytes = encode_pixels(Format::D24FS8, 1, 1, &[[depth, 7.0, 0.0, 1.0]]).unwrap();
+            assert_eq!(bytes[0], 7);
+            assert_close(one(Format::D24FS8, &bytes), [depth, 7.0, 0.0, 1.0], depth * 1.0e-6 + 1.0e-10, depth);
+        }
+        assert_eq!(encode_pixels(Format::D24FS8, 1, 1, &[[2.0, 0.0, 0.0, 1.0]]).unwrap(), [0x00, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test] fn encode_mapping() {
+        let one = |format, px| encode_pixels(format, 1, 1, &[px]).unwrap();
+        assert_eq!(one(Format::L8,          [1.0, 1.0, 1.0, 0.0]), [0xFF]);
+        assert_eq!(one(Format::L8,          [0.0, 1.0, 0.0, 0.0]), [0xB6]); // Rec. 709 luma
+        assert_eq!(one(Format::A4L4,        [0.0, 0.0, 0.0, 1.0]), [0xF0]);
+        assert_eq!(one(Format::V16U16,      [-1.0, 0.5, 0.0, 0.0]), [0x01, 0x80, 0x00, 0x40]);
+        assert_eq!(one(Format::X8L8V8U8,    [0.0, 0.0, 1.0, 0.0]), [0x00, 0x00, 0xFF, 0x00]);
+        assert_eq!(one(Format::X8_LOCKABLE, [0.0, 300.0, 0.0, 0.0]), [0xFF]);
+        assert_eq!(one(Format::A8R8G8B8,    [2.0, -1.0, f32::NAN, 0.5]), [0x00, 0x00, 0xFF, 0x80]);
+    }
+
+    #[test] fn packed_2x1() {
+        let pixels = [[1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0], [0.5, 0.5, 0.5, 1.0]];
+        for format in [Format::YUY2, Format::UYVY] {
+            let bytes = encode_pixels(format, 3, 1, &pixels).unwrap();
+            assert_eq!(bytes.len(), 8);
+            let decoded = decode_pixels(format, TextureMipRef { data: &bytes, stride: 8 }, 3, 1).unwrap();
+            assert_close(decoded[2], pixels[2], 0.01, format);
+            let gray = encode_pixels(format, 2, 1, &[[0.25, 0.25, 0.25, 1.0]; 2]).unwrap();
+            for px in decode_pixels(format, TextureMipRef { data: &gray, stride: 4 }, 2, 1).unwrap() { assert_close(px, [0.25, 0.25, 0.25, 1.0], 0.01, format) }
+        }
+
+        let rgbg = encode_pixels(Format::R8G8_B8G8, 2, 1, &[[1.0, 0.0, 0.0, 1.0], [0.0, 1.0, 1.0, 1.0]]).unwrap();
+        assert_eq!(rgbg, [0x00, 0x80, 0xFF, 0x80]);
+        let grbg = encode_pixels(Format::G8R8_G8B8, 2, 1, &[[1.0, 0.0, 0.0, 1.0], [0.0, 1.0, 1.0, 1.0]]).unwrap();
+        assert_eq!(grbg, [0x80, 0x00, 0x80, 0xFF]);
+        let decoded = decode_pixels(Format::G8R8_G8B8, TextureMipRef { data: &grbg, stride: 4 }, 2, 1).unwrap();
+        assert_close(decoded[1], [0.5, 1.0, 0.5, 1.0], 0.01, "G8R8_G8B8");
+    }
+
+    #[test] fn block_compressed() {
+        let pixels = [[1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0]].repeat(8);
+        let dxt1 = encode_pixels(Format::DXT1, 4, 4, &pixels).unwrap();
+        assert_eq!(dxt1.len(), 8);
+        assert_eq!(decode_pixels(Format::DXT1, TextureMipRef { data: &dxt1, stride: 8 }, 4, 4).unwrap(), pixels);
+        let argb = convert_pixels(Format::DXT1, TextureMipRef { data: &dxt1, stride: 8 }, 2, 1, Format::A8R8G8B8).unwrap();
+        assert_eq!(argb, [0x00, 0x00, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0xFF]);
+    }
+
+    #[test] fn strides_and_errors() {
+        let data = [1, 2, 3, 0xAA, 4, 5, 6, 0xAA, 7, 8, 9];
+        let src = TextureMipRef { data: &data, stride: 4 };
+        let x = |b: u8| [b, b, b, 255];
+        assert_eq!(decode_pixels_u16(Format::L8, src, 3, 3).unwrap().len(), 9);
+        assert_eq!(convert_pixels(Format::L8, src, 3, 3, Format::A8L8).unwrap(), [1, 255, 2, 255, 3, 255, 4, 255, 5, 255, 6, 255, 7, 255, 8, 255, 9, 255]);
+        assert_eq!(convert_pixels(Format::L8, src, 1, 3, Format::A8B8G8R8).unwrap(), [x(1), x(4), x(7)].concat());
+
+        assert_eq!(decode_pixels(Format::L8, src, 4, 3).unwrap_err().kind(), D3DERR::INVALIDCALL); // last row too short
+        assert_eq!(decode_pixels(Format::L8, src, 3, 4).unwrap_err().kind(), D3DERR::INVALIDCALL); // too many rows
+        assert_eq!(decode_pixels(Format::L8, TextureMipRef { data: &data, stride: 2 }, 3, 2).unwrap_err().kind(), D3DERR::INVALIDCALL); // overlapping rows
+        assert_eq!(decode_pixels(Format::MULTI2_ARGB8, src, 1, 1).unwrap_err().kind(), D3DERR::WRONGTEXTUREFORMAT);
+        assert_eq!(encode_pixels(Format::P8, 1, 1, &[[0.0; 4]]).unwrap_err().kind(), D3DERR::WRONGTEXTUREFORMAT);
+        assert_eq!(encode_pixels(Format::L8, 2, 1, &[[0.0; 4]]).unwrap_err().kind(), D3DERR::INVALIDCALL);
+        assert_eq!(decode_pixels(Format::L8, TextureMipRef { data: &[], stride: 0 }, 0, 0).unwrap(), [[0.0; 4]; 0]);
+        assert_eq!(encode_pixels(Format::L8, 0, 5, &[]).unwrap(), [0u8; 0]);
+    }
+}
@@ -3,6 +3,9 @@ use crate::*;
 
 
 /// Similar to [d3d::Format], but limited to texture-friendly formats, and adding metadata to allow safe + sound bounds.
+///
+/// ### See Also
+/// *   [d3d::FormatInfo] - channel layouts, numeric types, and flags for every [d3d::Format]
 #[derive(Clone, Copy, Debug)]
 pub struct FixedTextureFormat(UncheckedTextureFormat);
 
@@ -145,11 +148,11 @@ formats! {
         //(R8G8_B8G8,           ??, (?,?)), // TODO: should these be (2,1) block size? or (1,1)?
         //(YUY2,                ??, (?,?)), // TODO: block size?
         //(G8R8_G8B8,           ??, (?,?)), // TODO: should these be (2,1) block size? or (1,1)?
-        (DXT1,                  32, (4,4)), // aka BC1 w/  premultiplied alpha
-        (DXT2,                  64, (4,4)), // aka BC2 w/  premultiplied alpha
-        (DXT3,                  64, (4,4)), // aka BC2 w/o premultiplied alpha
-        (DXT4,                  64, (4,4)), // aka BC3 w/  premultiplied alpha
-        (DXT5,                  64, (4,4)), // aka BC3 w/o premultiplied alpha
+        (DXT1,                  64, (4,4)), // aka BC1 w/  1-bit alpha
+        (DXT2,                 128, (4,4)), // aka BC2 w/  premultiplied alpha
+        (DXT3,                 128, (4,4)), // aka BC2 w/o premultiplied alpha
+        (DXT4,                 128, (4,4)), // aka BC3 w/  premultiplied alpha
+        (DXT5,                 128, (4,4)), // aka BC3 w/o premultiplied alpha
 
         (D16_LOCKABLE,          16, (1,1)),
         (D32,                   32, (1,1)),
@@ -192,5 +195,8 @@ formats! {
         (A1,                     1, (1,1)),
         (A2B10G10R10_XR_BIAS,   32, (1,1)),
         //(BinaryBuffer,        ??, (?,?)), // not pixel data
+
+        (ATI1,                  64, (4,4)), // aka BC4
+        (ATI2,                 128, (4,4)), // aka BC5
     }
 }
@@ -0,0 +1,413 @@
+use crate::*;
+use crate::d3d9::{CubeTextureMipRef, FixedTextureFormat, TextureMipRef, VolumeTextureMipRef};
+use crate::d3d9::pixel_convert::rows_fit;
+
+
+
+/// The kind of texture a [TextureImage] or [Dds] holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextureKind {
+    /// A 2D texture
+    Texture,
+
+    /// A cube texture with all 6 faces, in [d3d::CubeMapFace] order (+X, -X, +Y, -Y, +Z, -Z)
+    CubeTexture,
+
+    /// A volume (3D) texture
+    VolumeTexture,
+}
+
+/// The layout of a single mip level of a [TextureImage].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureImageLevel {
+    /// Width in pixels (at least `1`)
+    pub width:          u32,
+
+    /// Height in pixels (at least `1`)
+    pub height:         u32,
+
+    /// Depth in pixels (at least `1`, and always `1` unless this is a [TextureKind::VolumeTexture])
+    pub depth:          u32,
+
+    /// The number of **bytes** between rows of pixel blocks (rows are tightly packed)
+    pub row_pitch:      usize,
+
+    /// The number of **bytes** between slices of a volume (slices are tightly packed)
+    pub slice_pitch:    usize,
+}
+
+impl TextureImageLevel {
+    /// The number of bytes in this level, per face.
+    pub fn size(&self) -> usize { self.slice_pitch * self.depth as usize }
+}
+
+/// An owned 2D texture, cube texture, or volume texture:  a [FixedTextureFormat], a mip chain, and tightly packed pixel data.
+///
+/// All layout arithmetic (mip dimensions, pitches, and the total size) is overflow checked on construction,
+/// and the pixel data is always exactly as large as the layout requires, so the mip accessors never fail or panic
+/// for the matching kind.  Data is stored face major (each cube face holds a full mip chain), then by mip level, then by slice.
+///
+/// ### Example
+/// ```rust
+/// # use dev::d3d9::*; let device = device_pure();
+/// let mut image = TextureImage::texture(FixedTextureFormat::A8R8G8B8, 13, 6, 0).unwrap();
+/// assert_eq!(image.levels(), 4); // 13x6, 6x3, 3x1, 1x1
+/// assert_eq!(image.level(2).unwrap().row_pitch, 3 * 4);
+/// image.surface_mut(0, 0).unwrap().fill(0xFF);
+///
+/// let texture = device.create_texture_from(
+///     image.width(), image.height(), &image.texture_mips().unwrap(),
+///     Usage::None, image.format(), Pool::Managed, (),
+/// ).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct TextureImage {
+    kind:       TextureKind,
+    format:     &'static FixedTextureFormat,
+    levels:     Vec<TextureImageLevel>,
+    offsets:    Vec<usize>,
+    face_size:  usize,
+    data:       Vec<u8>,
+}
+
+impl TextureImage {
+    /// Create a zeroed 2D texture image.  `levels` may be `0` to allocate a full mip chain down to 1x1.
+    ///
+    /// ### Errors
+    /// *   [D3DERR::INVALIDCALL]       - `width` or `height` is 0, or `levels` exceeds the full mip chain
+    /// *   [THINERR::ALLOC_OVERFLOW]   - the image would be larger than `isize::MAX` bytes
+    //#allow_missing_argument_docs
+    pub fn texture(format: &'static FixedTextureFormat, width: u32, height: u32, levels: u32) -> Result<Self, Error> {
+        fn_context!(d3d9::TextureImage::texture);
+        Self::zeroed(TextureKind::Texture, format, width, height, 1, levels)
+    }
+
+    /// Create a zeroed cube texture image.  `levels` may be `0` to allocate a full mip chain down to 1x1.
+    ///
+    /// ### Errors
+    /// *   See [TextureImage::texture]
+    //#allow_missing_argument_docs
+    pub fn cube_texture(format: &'static FixedTextureFormat, size: u32, levels: u32) -> Result<Self, Error> {
+        fn_context!(d3d9::TextureImage::cube_texture);
+        Self::zeroed(TextureKind::CubeTexture, format, size, size, 1, levels)
+    }
+
+    /// Create a zeroed volume texture image.  `levels` may be `0` to allocate a full mip chain down to 1x1x1.
+    ///
+    /// ### Errors
+    /// *   See [TextureImage::texture]
+    //#allow_missing_argument_docs
+    pub fn volume_texture(format: &'static FixedTextureFormat, width: u32, height: u32, depth: u32, levels: u32) -> Result<Self, Error> {
+        fn_context!(d3d9::TextureImage::volume_texture);
+        Self::zeroed(TextureKind::VolumeTexture, format, width, height, depth, levels)
+    }
+
+    /// Take ownership of tightly packed pixel data, such as [Dds::data], after validating its size against the layout.
+    ///
+    /// ### Errors
+    /// *   [D3DERR::INVALIDCALL]       - a dimension is 0, a cube texture isn't square, a 2D or cube texture has a `depth` other than 1, or `levels` exceeds the full mip chain
+    /// *   [D3DERR::INVALIDCALL]       - `data.len()` doesn't exactly match the layout
+    /// *   [THINERR::ALLOC_OVERFLOW]   - the layout would be larger than `isize::MAX` bytes
+    //#allow_missing_argument_docs
+    pub fn from_data(kind: TextureKind, format: &'static FixedTextureFormat, width: u32, height: u32, depth: u32, levels: u32, data: Vec<u8>) -> Result<Self, Error> {
+        fn_context!(d3d9::TextureImage::from_data);
+        let mut image = Self::layout(kind, format, width, height, depth, levels)?;
+        if data.len() != image.face_size * image.faces() { return Err(fn_param_error!(data, D3DERR::INVALIDCALL)) }
+        image.data = data;
+        Ok(image)
+    }
+
+    /// Copy a 2D texture's mip chain, such as the one passed to [create_texture_from](d3d9::IDirect3DDevice9Ext::create_texture_from).
+    ///
+    /// ### Errors
+    /// *   [D3DERR::INVALIDCALL]       - `width` or `height` is 0, `mips` is empty, or there are more `mips` than the dimensions allow
+    /// *   [D3DERR::INVALIDCALL]       - a mip's `data` is too small for its dimensions and `stride`
+    /// *   [THINERR::ALLOC_OVERFLOW]   - the image would be larger than `isize::MAX` bytes
+    //#allow_missing_argument_docs
+    pub fn from_texture_mips(format: &'static FixedTextureFormat, width: u32, height: u32, mips: &[TextureMipRef]) -> Result<Self, Error> {
+        fn_context!(d3d9::TextureImage::from_texture_mips);
+        if mips.is_empty() { return Err(fn_param_error!(mips, D3DERR::INVALIDCALL)) }
+        let levels = u32::try_from(mips.len()).map_err(|_| fn_param_error!(mips, D3DERR::INVALIDCALL))?;
+        let mut image = Self::layout(TextureKind::Texture, format, width, height, 1, levels)?;
+        if !mips.iter().enumerate().all(|(level, mip)| image.surface_fits(level, *mip)) { return Err(fn_param_error!(mips, D3DERR::INVALIDCALL)) }
+        image.allocate();
+        for (level, mip) in mips.iter().enumerate() { image.copy_surface(0, level, 0, *mip) }
+        Ok(image)
+    }
+
+    /// Copy a cube texture's mip chain, such as the one passed to [create_cube_texture_from](d3d9::IDirect3DDevice9Ext::create_cube_texture_from).
+    ///
+    /// ### Errors
+    /// *   See [TextureImage::from_texture_mips]
+    //#allow_missing_argument_docs
+    pub fn from_cube_texture_mips(format: &'static FixedTextureFormat, size: u32, mips: &[CubeTextureMipRef]) -> Result<Self, Error> {
+        fn_context!(d3d9::TextureImage::from_cube_texture_mips);
+        if mips.is_empty() { return Err(fn_param_error!(mips, D3DERR::INVALIDCALL)) }
+        let levels = u32::try_from(mips.len()).map_err(|_| fn_param_error!(mips, D3DERR::INVALIDCALL))?;
+        let mut image = Self::layout(TextureKind::CubeTexture, format, size, size, 1, levels)?;
+        fn faces<'m>(mip: &CubeTextureMipRef<'m>) -> [TextureMipRef<'m>; 6] { [mip.pos_x, mip.neg_x, mip.pos_y, mip.neg_y, mip.pos_z, mip.neg_z] }
+        if !mips.iter().enumerate().all(|(level, mip)| faces(mip).into_iter().all(|mip| image.surface_fits(level, mip))) { return Err(fn_param_error!(mips, D3DERR::INVALIDCALL)) }
+        image.allocate();
+        for (level, mip) in mips.iter().enumerate() {
+            for (face, mip) in faces(mip).into_iter().enumerate() { image.copy_surface(face, level, 0, mip) }
+        }
+        Ok(image)
+    }
+
+    /// Copy a volume texture's mip chain, such as the one passed to [create_volume_texture_from](d3d9::IDirect3DDevice9Ext::create_volume_texture_from).
+    ///
+    /// ### Errors
+    /// *   See [TextureImage::from_texture_mips]
+    //#allow_missing_argument_docs
+    pub fn from_volume_texture_mips(format: &'static FixedTextureFormat, width: u32, height: u32, depth: u32, mips: &[VolumeTextureMipRef]) -> Result<Self, Error> {
+        fn_context!(d3d9::TextureImage::from_volume_texture_mips);
+        if mips.is_empty() { return Err(fn_param_error!(mips, D3DERR::INVALIDCALL)) }
+        let levels = u32::try_from(mips.len()).map_err(|_| fn_param_error!(mips, D3DERR::INVALIDCALL))?;
+        let mut image = Self::layout(TextureKind::VolumeTexture, format, width, height, depth, levels)?;
+        fn slice<'m>(mip: &VolumeTextureMipRef<'m>, slice: usize) -> TextureMipRef<'m> {
+            let data = slice.checked_mul(mip.stride_slice).and_then(|start| mip.data.get(start ..)).unwrap_or(&[]);
+            TextureMipRef { data, stride: mip.stride_row }
+        }
+        if !mips.iter().enumerate().all(|(level, mip)| (0 .. image.levels[level].depth as usize).all(|s| image.surface_fits(level, slice(mip, s)))) { return Err(fn_param_error!(mips, D3DERR::INVALIDCALL)) }
+        image.allocate();
+        for (level, mip) in mips.iter().enumerate() {
+            for s in 0 .. image.levels[level].depth as usize { image.copy_surface(0, level, s, slice(mip, s)) }
+        }
+        Ok(image)
+    }
+
+    /// The kind of texture this image holds
+    pub fn kind(&self) -> TextureKind { self.kind }
+
+    /// The pixel format of every surface
+    pub fn format(&self) -> &'static FixedTextureFormat { self.format }
+
+    /// The width of the top mip, in pixels
+    pub fn width(&self) -> u32 { self.levels[0].width }
+
+    /// The height of the top mip, in pixels
+    pub fn height(&self) -> u32 { self.levels[0].height }
+
+    /// The depth of the top mip, in pixels (`1` unless this is a [TextureKind::VolumeTexture])
+    pub fn depth(&self) -> u32 { self.levels[0].depth }
+
+    /// The number of mip levels (at least `1`)
+    pub fn levels(&self) -> u32 { self.levels.len() as u32 } // safe: validated on construction
+
+    /// The number of faces (`6` for a [TextureKind::CubeTexture], otherwise `1`)
+    pub fn faces(&self) -> usize { if self.kind == TextureKind::CubeTexture { 6 } else { 1 } }
+
+    /// The layout of mip `level`, or [None] if `level >= self.levels()`
+    pub fn level(&self, level: u32) -> Option<&TextureImageLevel> { self.levels.get(level as usize) }
+
+    /// All tightly packed pixel data
+    pub fn data(&self) -> &[u8] { &self.data }
+
+    /// All tightly packed pixel data
+    pub fn data_mut(&mut self) -> &mut [u8] { &mut self.data }
+
+    /// Consume the image, returning its tightly packed pixel data
+    pub fn into_data(self) -> Vec<u8> { self.data }
+
+    /// The pixel data of mip `level` of `face` (every slice, for a volume), or [None] if either is out of bounds.
+    pub fn surface(&self, face: usize, level: u32) -> Option<&[u8]> {
+        let range = self.surface_range(face, level)?;
+        Some(&self.data[range])
+    }
+
+    /// The pixel data of mip `level` of `face` (every slice, for a volume), or [None] if either is out of bounds.
+    pub fn surface_mut(&mut self, face: usize, level: u32) -> Option<&mut [u8]> {
+        let range = self.surface_range(face, level)?;
+        Some(&mut self.data[range])
+    }
+
+    /// The mip chain of a [TextureKind::Texture], or [None] for other kinds.
+    pub fn texture_mips(&self) -> Option<Vec<TextureMipRef<'_>>> {
+        if self.kind != TextureKind::Texture { return None }
+        Some(self.face_mips(0).collect())
+    }
+
+    /// The mip chain of a [TextureKind::CubeTexture], or [None] for other kinds.
+    pub fn cube_texture_mips(&self) -> Option<Vec<CubeTextureMipRef<'_>>> {
+        if self.kind != TextureKind::CubeTexture { return None }
+        let faces : Vec<Vec<TextureMipRef>> = (0 .. 6).map(|face| self.face_mips(face).collect()).collect();
+        Some((0 .. self.levels.len()).map(|level| CubeTextureMipRef {
+            pos_x: faces[0][level], neg_x: faces[1][level],
+            pos_y: faces[2][level], neg_y: faces[3][level],
+            pos_z: faces[4][level], neg_z: faces[5][level],
+        }).collect())
+    }
+
+    /// The mip chain of a [TextureKind::VolumeTexture], or [None] for other kinds.
+    pub fn volume_texture_mips(&self) -> Option<Vec<VolumeTextureMipRef<'_>>> {
+        if self.kind != TextureKind::VolumeTexture { return None }
+        Some(self.levels.iter().zip(self.offsets.iter()).map(|(level, &offset)| VolumeTextureMipRef {
+            data:           &self.data[offset ..][.. level.size()],
+            stride_row:     level.row_pitch,
+            stride_slice:   level.slice_pitch,
+        }).collect())
+    }
+}
+
+impl std::fmt::Debug for TextureImage {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.debug_struct("TextureImage")
+            .field("kind",      &self.kind)
+            .field("format",    &self.format.format)
+            .field("levels",    &self.levels)
+            .field("data",      &format_args!("[u8; {}]", self.data.len()))
+            .finish()
+    }
+}
+
+impl TextureImage {
+    fn zeroed(kind: TextureKind, format: &'static FixedTextureFormat, width: u32, height: u32, depth: u32, levels: u32) -> Result<Self, Error> {
+        let mut image = Self::layout(kind, format, width, height, depth, levels)?;
+        image.allocate();
+        Ok(image)
+    }
+
+    /// Allocate zeroed `data` for the layout.  [Self::layout] bounds the size, but not whether it's sane to allocate:
+    /// validate any caller supplied mips against the layout before calling this.
+    fn allocate(&mut self) { self.data = vec![0; self.face_size * self.faces()]; }
+
+    fn layout(kind: TextureKind, format: &'static FixedTextureFormat, width: u32, height: u32, depth: u32, levels: u32) -> Result<Self, Error> {
+        fn_context!(d3d9::TextureImage::layout);
+        if width == 0 || height == 0 || depth == 0                  { return Err(fn_param_error!(width, D3DERR::INVALIDCALL)) }
+        if kind != TextureKind::VolumeTexture && depth != 1         { return Err(fn_param_error!(depth, D3DERR::INVALIDCALL)) }
+        if kind == TextureKind::CubeTexture && width != height      { return Err(fn_param_error!(height, D3DERR::INVALIDCALL)) }
+        let max_levels = 32 - width.max(height).max(depth).leading_zeros();
+        let levels = if levels == 0 { max_levels } else { levels };
+        if levels > max_levels                                      { return Err(fn_param_error!(levels, D3DERR::INVALIDCALL)) }
+
+        let overflow = || fn_param_error!(width, THINERR::ALLOC_OVERFLOW);
+        let blocks = |pixels: u32, block: u8| u64::from(pixels / u32::from(block) + u32::from(pixels % u32::from(block) != 0));
+        let faces = if kind == TextureKind::CubeTexture { 6 } else { 1 };
+        let mut image = Self { kind, format, levels: Vec::new(), offsets: Vec::new(), face_size: 0, data: Vec::new() };
+        for level in 0 .. levels {
+            let (w, h, d) = ((width >> level).max(1), (height >> level).max(1), (depth >> level).max(1));
+            let row_bits    = blocks(w, format.block_size.0).checked_mul(u64::from(format.bits_per_block)).ok_or_else(overflow)?;
+            let row_pitch   = (row_bits / 8) + u64::from(row_bits % 8 != 0);
+            let slice_pitch = row_pitch.checked_mul(blocks(h, format.block_size.1)).ok_or_else(overflow)?;
+            let size        = slice_pitch.checked_mul(u64::from(d)).ok_or_else(overflow)?;
+            let row_pitch   = usize::try_from(row_pitch).map_err(|_| overflow())?;
+            let slice_pitch = usize::try_from(slice_pitch).map_err(|_| overflow())?;
+            let size        = usize::try_from(size).map_err(|_| overflow())?;
+            image.offsets.push(image.face_size);
+            image.levels.push(TextureImageLevel { width: w, height: h, depth: d, row_pitch, slice_pitch });
+            image.face_size = image.face_size.checked_add(size).ok_or_else(overflow)?;
+        }
+        let total = image.face_size.checked_mul(faces).ok_or_else(overflow)?;
+        if total > isize::MAX as usize { return Err(overflow()) }
+        Ok(image)
+    }
+
+    fn surface_range(&self, face: usize, level: u32) -> Option<std::ops::Range<usize>> {
+        if face >= self.faces() { return None }
+        let size = self.levels.get(level as usize)?.size();
+        let start = face * self.face_size + self.offsets[level as usize];
+        Some(start .. start + size)
+    }
+
+    fn face_mips(&self, face: usize) -> impl Iterator<Item = TextureMipRef<'_>> {
+        self.levels.iter().zip(self.offsets.iter()).map(move |(level, &offset)| TextureMipRef {
+            data:   &self.data[face * self.face_size + offset ..][.. level.size()],
+            stride: level.row_pitch,
+        })
+    }
+
+    /// `true` if `src` holds every row of a (single slice of) mip `level`
+    fn surface_fits(&self, level: usize, src: TextureMipRef) -> bool {
+        let layout = self.levels[level];
+        rows_fit(src, layout.row_pitch, layout.slice_pitch / layout.row_pitch.max(1))
+    }
+
+    /// Copy a (single slice of) mip `level`, which must already have been checked with [Self::surface_fits]
+    fn copy_surface(&mut self, face: usize, level: usize, slice: usize, src: TextureMipRef) {
+        debug_assert!(self.surface_fits(level, src));
+        let layout = self.levels[level];
+        let dst = &mut self.data[face * self.face_size + self.offsets[level] + slice * layout.slice_pitch ..][.. layout.slice_pitch];
+        for (row, dst) in dst.chunks_exact_mut(layout.row_pitch.max(1)).enumerate() {
+            dst.copy_from_slice(&src.data[row * src.stride ..][.. dst.len()]);
+        }
+    }
+}
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    #[test] fn layout() {
+        let image = TextureImage::texture(FixedTextureFormat::DXT1, 13, 6, 0).unwrap();
+        let levels = (0 .. image.levels()).map(|l| *image.level(l).unwrap()).collect::<Vec<_>>();
+        assert_eq!(levels, [
+            TextureImageLevel { width: 13, height: 6, depth: 1, row_pitch: 4 * 8, slice_pitch: 4 * 8 * 2 },
+            TextureImageLevel { width:  6, height: 3, depth: 1, row_pitch: 2 * 8, slice_pitch: 2 * 8     },
+            TextureImageLevel { width:  3, height: 1, depth: 1, row_pitch:     8, slice_pitch:     8     },
+            TextureImageLevel { width:  1, height: 1, depth: 1, row_pitch:     8, slice_pitch:     8     },
+        ]);
+        assert_eq!(image.data().len(), 64 + 16 + 8 + 8);
+        assert!(image.level(4).is_none());
+
+        let image = TextureImage::volume_texture(FixedTextureFormat::A1, 9, 2, 5, 0).unwrap();
+        assert_eq!(image.levels(), 4);
+        assert_eq!(*image.level(0).unwrap(), TextureImageLevel { width: 9, height: 2, depth: 5, row_pitch: 2, slice_pitch: 4 });
+        assert_eq!(*image.level(3).unwrap(), TextureImageLevel { width: 1, height: 1, depth: 1, row_pitch: 1, slice_pitch: 1 });
+        assert_eq!(image.surface(0, 0).unwrap().len(), 20);
+        assert!(image.surface(1, 0).is_none() && image.texture_mips().is_none() && image.cube_texture_mips().is_none());
+
+        let mut image = TextureImage::cube_texture(FixedTextureFormat::A8R8G8B8, 4, 2).unwrap();
+        assert_eq!((image.faces(), image.data().len()), (6, 6 * (64 + 16)));
+        image.surface_mut(5, 1).unwrap().fill(0xAA);
+        assert_eq!(image.cube_texture_mips().unwrap()[1].neg_z.data, &[0xAA; 16]);
+        assert_eq!(image.data()[5 * 80 + 64 ..], [0xAA; 16]);
+        assert_eq!(image.data().iter().filter(|&&b| b != 0).count(), 16);
+    }
+
+    #[test] fn from_mips() {
+        let data = (0 .. 256).map(|i| i as u8).collect::<Vec<_>>();
+        let mips = [TextureMipRef { data: &data, stride: 20 }, TextureMipRef { data: &data[200 ..], stride: 8 }, TextureMipRef { data: &data[254 ..], stride: 0 }];
+        let image = TextureImage::from_texture_mips(FixedTextureFormat::L16, 4, 3, &mips).unwrap();
+        let read = image.texture_mips().unwrap();
+        assert_eq!(read.len(), 3);
+        assert_eq!((read[0].stride, read[0].data.len()), (8, 24));
+        assert_eq!(read[0].data[8 .. 16], data[20 .. 28]);
+        assert_eq!(read[1].data, &data[200 .. 204]);
+        assert_eq!(read[2].data.len(), 2);
+
+        let mips = [VolumeTextureMipRef { data: &data, stride_row: 2, stride_slice: 100 }, VolumeTextureMipRef { data: &data[50 ..], stride_row: 1, stride_slice: 1 }];
+        let image = TextureImage::from_volume_texture_mips(FixedTextureFormat::L8, 2, 2, 2, &mips).unwrap();
+        let read = image.volume_texture_mips().unwrap();
+        assert_eq!((read[0].data, read[0].stride_row, read[0].stride_slice), (&[0, 1, 2, 3, 100, 101, 102, 103][..], 2, 4));
+        assert_eq!(read[1].data, &[50]);
+
+        let face = TextureMipRef { data: &data[..4], stride: 4 };
+        let mips = [CubeTextureMipRef { pos_x: face, neg_x: face, pos_y: face, neg_y: face, pos_z: face, neg_z: TextureMipRef { data: &data[4 ..], stride: 4 } }];
+        let image = TextureImage::from_cube_texture_mips(FixedTextureFormat::A8R8G8B8, 1, &mips).unwrap();
+        assert_eq!(image.surface(5, 0).unwrap(), &data[4 .. 8]);
+    }
+
+    #[test] fn errors() {
+        let format = FixedTextureFormat::A8R8G8B8;
+        let mip = TextureMipRef { data: &[0; 16], stride: 8 };
+        assert_eq!(TextureImage::texture(format, 0, 1, 1).unwrap_err().kind(), D3DERR::INVALIDCALL);
+        assert_eq!(TextureImage::texture(format, 4, 4, 4).unwrap_err().kind(), D3DERR::INVALIDCALL); // too many levels
+        assert_eq!(TextureImage::from_data(TextureKind::CubeTexture, format, 2, 1, 1, 1, vec![0; 48]).unwrap_err().kind(), D3DERR::INVALIDCALL); // not square
+        assert_eq!(TextureImage::from_data(TextureKind::Texture, format, 2, 1, 2, 1, vec![0; 16]).unwrap_err().kind(), D3DERR::INVALIDCALL); // 2D with depth
+        assert_eq!(TextureImage::from_data(TextureKind::Texture, format, 2, 2, 1, 2, vec![0; 19]).unwrap_err().kind(), D3DERR::INVALIDCALL); // size
+        assert!(TextureImage::from_data(TextureKind::Texture, format, 2, 2, 1, 2, vec![0; 20]).is_ok());
+        assert_eq!(TextureImage::from_texture_mips(format, 2, 2, &[]).unwrap_err().kind(), D3DERR::INVALIDCALL);
+        assert_eq!(TextureImage::from_texture_mips(format, 2, 2, &[mip, mip, mip]).unwrap_err().kind(), D3DERR::INVALIDCALL); // too many mips
+        assert_eq!(TextureImage::from_texture_mips(format, 2, 3, &[mip]).unwrap_err().kind(), D3DERR::INVALIDCALL); // too few rows
+        assert_eq!(TextureImage::from_texture_mips(format, 3, 2, &[mip]).unwrap_err().kind(), D3DERR::INVALIDCALL); // stride < row
+        assert_eq!(TextureImage::texture(FixedTextureFormat::A32B32G32R32F, u32::MAX, u32::MAX, 1).unwrap_err().kind(), THINERR::ALLOC_OVERFLOW);
+        assert_eq!(TextureImage::volume_texture(FixedTextureFormat::A8, 1 << 24, 1 << 24, 1 << 24, 1).unwrap_err().kind(), THINERR::ALLOC_OVERFLOW);
+
+        // mips are checked before allocating the (otherwise valid) layout:  96 TiB and 256 TiB respectively
+        let cube = CubeTextureMipRef { pos_x: mip, neg_x: mip, pos_y: mip, neg_y: mip, pos_z: mip, neg_z: mip };
+        assert_eq!(TextureImage::from_cube_texture_mips(FixedTextureFormat::A32B32G32R32F, 1 << 20, &[cube]).unwrap_err().kind(), D3DERR::INVALIDCALL);
+        let volume = VolumeTextureMipRef { data: &[0; 16], stride_row: 8, stride_slice: 16 };
+        assert_eq!(TextureImage::from_volume_texture_mips(FixedTextureFormat::A8, 1 << 16, 1 << 16, 1 << 16, &[volume]).unwrap_err().kind(), D3DERR::INVALIDCALL);
+    }
+}
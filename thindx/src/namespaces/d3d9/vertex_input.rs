@@ -0,0 +1,413 @@
+use crate::*;
+use crate::d3d9::*;
+
+use std::fmt::{self, Display, Formatter};
+
+
+
+/// An input register a vertex shader reads, as declared by SM1-3 `dcl_*` instructions or a SM4+ `ISGN` input signature.
+///
+/// ### See Also
+/// *   [check_vertex_input] / [check_fvf_input]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VertexShaderInput {
+    /// The HLSL semantic without index (e.g. `"POSITION"`, `"TEXCOORD"`, `"BLENDWEIGHT"`.)
+    pub semantic_name:  String,
+
+    /// The semantic index (e.g. `1` for `TEXCOORD1`.)
+    pub semantic_index: u32,
+
+    /// The `v#` register the input is read from.
+    pub register:       u32,
+
+    /// The components the shader declares (`0b0001` = `x`, ..., `0b1111` = `xyzw`.)
+    pub mask:           u8,
+
+    /// The type the shader expects.  Always [d3d::RegisterComponentType::Float32] for SM1-3.
+    pub component_type: d3d::RegisterComponentType,
+}
+
+impl VertexShaderInput {
+    /// Read the inputs of a vertex shader from its bytecode:
+    /// *   SM1-3 (`vs_1_1`, `vs_2_0`, `vs_3_0`, ...) token streams are scanned for `dcl_*` instructions
+    /// *   SM4+ DXBC is read via [d3d11::ReflectionData::parse] (no `d3dcompiler_*.dll` required)
+    ///
+    /// `SV_VertexID`, `SV_InstanceID`, and `SV_PrimitiveID` are generated by the runtime, not by vertex buffers, and are omitted.
+    ///
+    /// ### Errors
+    /// *   [THINERR::INVALID_BYTECODE] - if `bytecode` isn't a vertex shader, or is truncated/corrupt
+    ///
+    /// ### Examples
+    /// ```rust
+    /// # use thindx::{*, d3d::*};
+    /// # let d3dc = Compiler::load_system(47).unwrap();
+    /// let vs = d3dc.compile_from_file(r"test\data\basic.hlsl", None, None, "vs_main", "vs_3_0", Compile::Debug, CompileEffect::None).unwrap();
+    /// let inputs = d3d9::VertexShaderInput::from_bytecode(&vs).unwrap();
+    /// assert_eq!(inputs[0].semantic_name, "POSITION");
+    /// assert_eq!(inputs[1].semantic_name, "COLOR");
+    /// ```
+    pub fn from_bytecode(bytecode: &d3d::Bytecode) -> Result<Vec<Self>, Error> {
+        fn_context!(d3d9::VertexShaderInput::from_bytecode);
+        let bytes = bytecode.as_bytes();
+        if bytes.get(0..4) == Some(b"DXBC") {
+            let data = d3d11::ReflectionData::parse(bytecode)?;
+            if data.desc.version.ty() != d3d11::ShVer::VertexShader { return Err(fn_param_error!(bytecode, THINERR::INVALID_BYTECODE)) }
+            Ok(Self::from_signature(&data.inputs))
+        } else {
+            sm1_inputs(bytes).ok_or_else(|| fn_param_error!(bytecode, THINERR::INVALID_BYTECODE))
+        }
+    }
+
+    /// Convert a SM4+ input signature (e.g. [d3d11::ReflectionData::inputs]), omitting `SV_VertexID`, `SV_InstanceID`, and `SV_PrimitiveID`.
+    pub fn from_signature(signature: &[d3d11::ReflectionParameter]) -> Vec<Self> {
+        signature.iter()
+            .filter(|p| !matches!(p.system_value_type, d3d::Name::VertexId | d3d::Name::InstanceId | d3d::Name::PrimitiveId))
+            .map(|p| Self {
+                semantic_name:  p.semantic_name.clone(),
+                semantic_index: p.semantic_index,
+                register:       p.register,
+                mask:           p.mask & 0xF,
+                component_type: p.component_type,
+            })
+            .collect()
+    }
+
+    /// Check if `element` feeds this input (e.g. [DeclUsage8::TexCoord] index 1 feeds `TEXCOORD1`.)
+    ///
+    /// Semantics are case insensitive, and `SV_Position` is treated as `POSITION`.
+    pub fn is_fed_by(&self, element: &VertexElement) -> bool {
+        let name = if self.semantic_name.eq_ignore_ascii_case("SV_Position") { "POSITION" } else { self.semantic_name.as_str() };
+        self.semantic_index == u32::from(element.usage_index) && element.usage.semantic().map_or(false, |s| s.eq_ignore_ascii_case(name))
+    }
+}
+
+
+
+impl DeclUsage8 {
+    /// The HLSL semantic (without index) corresponding to this usage (e.g. [DeclUsage8::TexCoord] → `"TEXCOORD"`.)
+    pub fn semantic(self) -> Option<&'static str> {
+        Some(match self {
+            DeclUsage8::Position        => "POSITION",
+            DeclUsage8::BlendWeight     => "BLENDWEIGHT",
+            DeclUsage8::BlendIndices    => "BLENDINDICES",
+            DeclUsage8::Normal          => "NORMAL",
+            DeclUsage8::PSize           => "PSIZE",
+            DeclUsage8::TexCoord        => "TEXCOORD",
+            DeclUsage8::Tangent         => "TANGENT",
+            DeclUsage8::Binormal        => "BINORMAL",
+            DeclUsage8::TessFactor      => "TESSFACTOR",
+            DeclUsage8::PositionT       => "POSITIONT",
+            DeclUsage8::Color           => "COLOR",
+            DeclUsage8::Fog             => "FOG",
+            DeclUsage8::Depth           => "DEPTH",
+            DeclUsage8::Sample          => "SAMPLE",
+            _                           => return None,
+        })
+    }
+
+    /// The usage corresponding to an HLSL semantic (without index), case insensitive (e.g. `"TEXCOORD"` → [DeclUsage8::TexCoord].)
+    pub fn from_semantic(semantic: &str) -> Option<Self> {
+        (0 ..= 13).map(DeclUsage8::from_unchecked).find(|u| u.semantic().map_or(false, |s| s.eq_ignore_ascii_case(semantic)))
+    }
+}
+
+impl DeclType8 {
+    /// The number of components this type provides to a vertex shader, or 0 for [DeclType8::Unused].
+    ///
+    /// Components a vertex shader reads, but which aren't provided by the vertex element, default to `(0, 0, 0, 1)`.
+    pub fn components(self) -> u32 {
+        match self {
+            DeclType8::Float1                                                                           => 1,
+            DeclType8::Float2 | DeclType8::Short2 | DeclType8::Short2N | DeclType8::UShort2N | DeclType8::Float16_2 => 2,
+            DeclType8::Float3 | DeclType8::UDec3 | DeclType8::Dec3N                                     => 3,
+            DeclType8::Float4 | DeclType8::Color | DeclType8::UByte4 | DeclType8::Short4 | DeclType8::UByte4N |
+            DeclType8::Short4N | DeclType8::UShort4N | DeclType8::Float16_4                             => 4,
+            _                                                                                           => 0,
+        }
+    }
+
+    /// The size of this type in a vertex buffer, in bytes, or 0 for [DeclType8::Unused].
+    pub fn size(self) -> u16 {
+        match self {
+            DeclType8::Float1 | DeclType8::Color | DeclType8::UByte4 | DeclType8::Short2 | DeclType8::UByte4N |
+            DeclType8::Short2N | DeclType8::UShort2N | DeclType8::UDec3 | DeclType8::Dec3N | DeclType8::Float16_2 => 4,
+            DeclType8::Float2 | DeclType8::Short4 | DeclType8::Short4N | DeclType8::UShort4N | DeclType8::Float16_4 => 8,
+            DeclType8::Float3                                                                           => 12,
+            DeclType8::Float4                                                                           => 16,
+            _                                                                                           => 0,
+        }
+    }
+}
+
+impl VertexElement {
+    /// Expand a flexible vertex format into equivalent vertex elements (like `D3DXDeclaratorFromFVF`, but without the trailing [VertexElement::END].)
+    ///
+    /// ### Examples
+    /// ```rust
+    /// # use thindx::{*, d3d9::*};
+    /// let elements = VertexElement::from_fvf(FVF::XYZ | FVF::Diffuse | FVF::Tex1);
+    /// assert_eq!(elements, [
+    ///     VertexElement::new(0,  0, DeclType8::Float3, DeclMethod8::Default, DeclUsage8::Position, 0),
+    ///     VertexElement::new(0, 12, DeclType8::Color,  DeclMethod8::Default, DeclUsage8::Color,    0),
+    ///     VertexElement::new(0, 16, DeclType8::Float2, DeclMethod8::Default, DeclUsage8::TexCoord, 0),
+    /// ]);
+    /// ```
+    pub fn from_fvf(fvf: FVF) -> Vec<VertexElement> {
+        let fvf = *fvf;
+        let mut elements = Vec::new();
+        let mut offset = 0;
+        let mut push = |ty: DeclType8, usage: DeclUsage8, usage_index: u8| {
+            elements.push(VertexElement::new(0, offset, ty, DeclMethod8::Default, usage, usage_index));
+            offset += ty.size();
+        };
+        let floats = |n: u32| [DeclType8::Float1, DeclType8::Float2, DeclType8::Float3, DeclType8::Float4][(n - 1) as usize];
+
+        let betas = match fvf & *FVF::PositionMask {
+            p if p == *FVF::XYZRHW  => { push(DeclType8::Float4, DeclUsage8::PositionT, 0); 0 },
+            p if p == *FVF::XYZW    => { push(DeclType8::Float4, DeclUsage8::Position,  0); 0 },
+            p if p == *FVF::XYZ     => { push(DeclType8::Float3, DeclUsage8::Position,  0); 0 },
+            p if p == *FVF::XYZB1   => { push(DeclType8::Float3, DeclUsage8::Position,  0); 1 },
+            p if p == *FVF::XYZB2   => { push(DeclType8::Float3, DeclUsage8::Position,  0); 2 },
+            p if p == *FVF::XYZB3   => { push(DeclType8::Float3, DeclUsage8::Position,  0); 3 },
+            p if p == *FVF::XYZB4   => { push(DeclType8::Float3, DeclUsage8::Position,  0); 4 },
+            p if p == *FVF::XYZB5   => { push(DeclType8::Float3, DeclUsage8::Position,  0); 5 },
+            _                       => 0,
+        };
+        let last_beta = if fvf & *FVF::LastBetaUByte4 != 0 { Some(DeclType8::UByte4) } else if fvf & *FVF::LastBetaD3DColor != 0 { Some(DeclType8::Color) } else { None };
+        match last_beta {
+            Some(indices) if betas > 0  => {
+                if betas > 1 { push(floats(betas - 1), DeclUsage8::BlendWeight, 0); }
+                push(indices, DeclUsage8::BlendIndices, 0);
+            },
+            _ if betas > 0              => push(floats(betas.min(4)), DeclUsage8::BlendWeight, 0),
+            _                           => {},
+        }
+
+        if fvf & *FVF::Normal   != 0 { push(DeclType8::Float3, DeclUsage8::Normal, 0); }
+        if fvf & *FVF::PSize    != 0 { push(DeclType8::Float1, DeclUsage8::PSize,  0); }
+        if fvf & *FVF::Diffuse  != 0 { push(DeclType8::Color,  DeclUsage8::Color,  0); }
+        if fvf & *FVF::Specular != 0 { push(DeclType8::Color,  DeclUsage8::Color,  1); }
+
+        let textures = ((fvf & *FVF::TexCountMask) >> FVF::TexCountShift).min(8);
+        for i in 0 .. textures {
+            let ty = match (fvf >> (16 + 2 * i)) & 3 {
+                0 => DeclType8::Float2, // D3DFVF_TEXTUREFORMAT2 (default)
+                1 => DeclType8::Float3, // D3DFVF_TEXTUREFORMAT3
+                2 => DeclType8::Float4, // D3DFVF_TEXTUREFORMAT4
+                _ => DeclType8::Float1, // D3DFVF_TEXTUREFORMAT1
+            };
+            push(ty, DeclUsage8::TexCoord, i as u8);
+        }
+
+        elements
+    }
+}
+
+
+
+/// Check that `elements` provides every input `inputs` reads, with enough components of the right type.
+///
+/// Direct3D 9 doesn't validate this:  a vertex shader reading an input that the vertex declaration doesn't provide
+/// reads garbage (or defaults), silently.  This catches such mismatches before [IDirect3DDevice9Ext::create_vertex_declaration] / `draw_*`.
+///
+/// Vertex elements the shader doesn't read are fine, as are [VertexElement::END] terminators.
+/// D3D9 fills components the shader reads but the element doesn't provide with `(0, 0, 0, 1)` - since a missing `w` is the idiomatic
+/// way to promote a `float3` position to `float4`, only missing `x`/`y`/`z` components are reported as problems.
+///
+/// ### Errors
+/// *   [VertexInputProblem::Missing]           - if no element feeds an input
+/// *   [VertexInputProblem::Duplicate]         - if several elements have the same usage and usage index
+/// *   [VertexInputProblem::ComponentType]     - if the shader expects integer components (D3D9 vertex elements always provide floats)
+/// *   [VertexInputProblem::ComponentCount]    - if the shader reads `x`/`y`/`z` components the element doesn't provide
+///
+/// ### Examples
+/// ```rust
+/// # use thindx::{*, d3d::*, d3d9::*};
+/// # let d3dc = Compiler::load_system(47).unwrap();
+/// let vs = d3dc.compile_from_file(r"test\data\basic.hlsl", None, None, "vs_main", "vs_3_0", Compile::Debug, CompileEffect::None).unwrap();
+/// let inputs = VertexShaderInput::from_bytecode(&vs).unwrap();
+///
+/// check_fvf_input(FVF::XYZ | FVF::Diffuse, &inputs).unwrap();
+///
+/// let err = check_fvf_input(FVF::XYZ, &inputs).unwrap_err();
+/// assert!(matches!(err.problems[..], [VertexInputProblem::Missing { .. }]), "{}", err);
+/// ```
+pub fn check_vertex_input(elements: &[VertexElement], inputs: &[VertexShaderInput]) -> Result<(), VertexInputMismatch> {
+    let elements = elements.iter().take_while(|e| **e != VertexElement::END).collect::<Vec<_>>();
+    let mut problems = Vec::new();
+
+    for (i, e) in elements.iter().enumerate() {
+        if elements[..i].iter().any(|prev| prev.usage == e.usage && prev.usage_index == e.usage_index) && !problems.iter().any(|p| matches!(p, VertexInputProblem::Duplicate { usage, usage_index } if *usage == e.usage && *usage_index == e.usage_index)) {
+            problems.push(VertexInputProblem::Duplicate { usage: e.usage, usage_index: e.usage_index });
+        }
+    }
+
+    for input in inputs.iter() {
+        let Some(element) = elements.iter().find(|e| input.is_fed_by(e)) else {
+            problems.push(VertexInputProblem::Missing { semantic: semantic(input) });
+            continue;
+        };
+        if matches!(input.component_type, d3d::RegisterComponentType::UInt32 | d3d::RegisterComponentType::SInt32) {
+            problems.push(VertexInputProblem::ComponentType { semantic: semantic(input), ty: element.ty, expected: input.component_type });
+        }
+        let provided = element.ty.components();
+        let read = 4 - (input.mask & 0xF).leading_zeros().saturating_sub(4); // highest component read + 1
+        if read.min(3) > provided {
+            problems.push(VertexInputProblem::ComponentCount { semantic: semantic(input), ty: element.ty, provided, read });
+        }
+    }
+
+    if problems.is_empty() { Ok(()) } else { Err(VertexInputMismatch { problems }) }
+}
+
+/// Check that a flexible vertex format provides every input `inputs` reads.  See [check_vertex_input] for details.
+pub fn check_fvf_input(fvf: FVF, inputs: &[VertexShaderInput]) -> Result<(), VertexInputMismatch> {
+    check_vertex_input(&VertexElement::from_fvf(fvf), inputs)
+}
+
+fn semantic(input: &VertexShaderInput) -> String { format!("{}{}", input.semantic_name, input.semantic_index) }
+
+
+
+/// The [VertexElement]s / [FVF] passed to [check_vertex_input] / [check_fvf_input] don't match a vertex shader's inputs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VertexInputMismatch {
+    /// Every problem found (always at least one.)
+    pub problems:   Vec<VertexInputProblem>,
+}
+
+/// A single [VertexInputMismatch] problem.
+#[allow(missing_docs)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VertexInputProblem {
+    /// The shader reads `semantic`, but no vertex element provides it
+    Missing         { semantic: String },
+
+    /// Several vertex elements share the same usage and usage index
+    Duplicate       { usage: DeclUsage8, usage_index: u8 },
+
+    /// The shader expects integer components, but D3D9 vertex elements always provide floats
+    ComponentType   { semantic: String, ty: DeclType8, expected: d3d::RegisterComponentType },
+
+    /// The shader reads `read` components, but the vertex element only provides `provided`
+    ComponentCount  { semantic: String, ty: DeclType8, provided: u32, read: u32 },
+}
+
+impl std::error::Error for VertexInputMismatch {}
+
+impl Display for VertexInputMismatch {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "vertex elements don't match vertex shader inputs:")?;
+        for problem in self.problems.iter() { write!(fmt, "\n    {}", problem)?; }
+        Ok(())
+    }
+}
+
+impl Display for VertexInputProblem {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Missing { semantic }                              => write!(fmt, "{}: no vertex element provides this input", semantic),
+            Self::Duplicate { usage, usage_index }                  => write!(fmt, "{:?} {}: multiple vertex elements have this usage", usage, usage_index),
+            Self::ComponentType { semantic, ty, expected }          => write!(fmt, "{}: shader expects {:?} components, but {:?} provides floats", semantic, expected, ty),
+            Self::ComponentCount { semantic, ty, provided, read }   => write!(fmt, "{}: shader reads {} components, but {:?} only provides {}", semantic, read, ty, provided),
+        }
+    }
+}
+
+
+
+/// Scan a SM1-3 vertex shader token stream for `dcl_* v#` instructions
+fn sm1_inputs(b: &[u8]) -> Option<Vec<VertexShaderInput>> {
+    let token = |i: usize| -> Option<u32> { Some(u32::from_le_bytes(b.get(4*i .. 4*i+4)?.try_into().ok()?)) };
+    let version = token(0)?;
+    if version >> 16 != 0xFFFE { return None } // not a vertex shader
+    let major = (version >> 8) & 0xFF;
+
+    let mut inputs = Vec::new();
+    let mut i = 1;
+    loop {
+        let instruction = token(i)?;
+        let opcode = instruction & 0xFFFF;
+        match opcode {
+            0xFFFF => return Some(inputs), // D3DSIO_END
+            0xFFFE => { i += 1 + ((instruction >> 16) & 0x7FFF) as usize; continue }, // D3DSIO_COMMENT
+            0x1F   => { // D3DSIO_DCL
+                let (dcl, dst) = (token(i + 1)?, token(i + 2)?);
+                let register_type = ((dst >> 28) & 0x7) | ((dst >> 8) & 0x18);
+                if register_type == 1 { // D3DSPR_INPUT
+                    let usage = DeclUsage8::from_unchecked(dcl & 0x1F);
+                    inputs.push(VertexShaderInput {
+                        semantic_name:  usage.semantic()?.into(),
+                        semantic_index: (dcl >> 16) & 0xF,
+                        register:       dst & 0x7FF,
+                        mask:           ((dst >> 16) & 0xF) as u8,
+                        component_type: d3d::RegisterComponentType::Float32,
+                    });
+                }
+            },
+            _ => {},
+        }
+
+        i += 1 + if major >= 2 {
+            ((instruction >> 24) & 0xF) as usize // D3DSI_INSTLENGTH_MASK
+        } else if opcode == 0x51 {
+            5 // D3DSIO_DEF: dst + 4 raw floats (which might not have bit 31 set)
+        } else {
+            // SM1 doesn't encode instruction lengths, but all parameter tokens have bit 31 set
+            let mut n = 0;
+            while token(i + 1 + n)? & 0x8000_0000 != 0 { n += 1 }
+            n
+        };
+    }
+}
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    fn input(semantic_name: &str, semantic_index: u32, mask: u8) -> VertexShaderInput {
+        VertexShaderInput { semantic_name: semantic_name.into(), semantic_index, register: 0, mask, component_type: d3d::RegisterComponentType::Float32 }
+    }
+
+    #[test] fn fvf() {
+        let e = VertexElement::from_fvf(FVF::XYZB3 | FVF::LastBetaUByte4 | FVF::Normal | FVF::Tex2 | FVF::from_unchecked(1 << 18));
+        let summary = e.iter().map(|e| (e.offset, e.ty, e.usage, e.usage_index)).collect::<Vec<_>>();
+        assert_eq!(summary, [
+            ( 0, DeclType8::Float3, DeclUsage8::Position,       0),
+            (12, DeclType8::Float2, DeclUsage8::BlendWeight,    0),
+            (20, DeclType8::UByte4, DeclUsage8::BlendIndices,   0),
+            (24, DeclType8::Float3, DeclUsage8::Normal,         0),
+            (36, DeclType8::Float2, DeclUsage8::TexCoord,       0),
+            (44, DeclType8::Float3, DeclUsage8::TexCoord,       1),
+        ]);
+    }
+
+    #[test] fn check() {
+        let inputs = [input("POSITION", 0, 0xF), input("TEXCOORD", 1, 0x7), input("COLOR", 0, 0xF)];
+        assert_eq!(check_fvf_input(FVF::XYZ | FVF::Diffuse | FVF::Tex2 | FVF::from_unchecked(1 << 18), &inputs), Ok(()));
+
+        let err = check_fvf_input(FVF::XYZ | FVF::Tex2, &inputs).unwrap_err();
+        assert_eq!(err.problems, [
+            VertexInputProblem::ComponentCount { semantic: "TEXCOORD1".into(), ty: DeclType8::Float2, provided: 2, read: 3 },
+            VertexInputProblem::Missing { semantic: "COLOR0".into() },
+        ]);
+    }
+
+    #[test] fn sm1() {
+        let tokens : &[u32] = &[
+            0xFFFE0101,                                 // vs_1_1
+            0x0000001F, 0x80000000, 0x900F0000,         // dcl_position v0
+            0x0000001F, 0x80010005, 0x90030001,         // dcl_texcoord1 v1.xy
+            0x00000051, 0xA00F0000, 0x3F800000, 0, 0, 0,// def c0, 1, 0, 0, 0
+            0x00000001, 0xC00F0000, 0x90E40000,         // mov oPos, v0
+            0x0000FFFF,
+        ];
+        let bytes = tokens.iter().flat_map(|t| t.to_le_bytes()).collect::<Vec<_>>();
+        let inputs = sm1_inputs(&bytes).unwrap();
+        assert_eq!(inputs, [
+            input("POSITION", 0, 0xF),
+            VertexShaderInput { register: 1, ..input("TEXCOORD", 1, 0x3) },
+        ]);
+    }
+}
@@ -0,0 +1,31 @@
+//! Hand assembled bytecode shared by the unit tests of multiple modules.
+
+/// A DXBC container (with a zeroed checksum) holding `chunks` in order
+pub(crate) fn dxbc<D: AsRef<[u8]>>(chunks: &[(&[u8; 4], D)]) -> Vec<u8> {
+    let mut b = Vec::new();
+    b.extend_from_slice(b"DXBC");
+    b.extend_from_slice(&[0; 16]);                          // checksum
+    b.extend_from_slice(&1u32.to_le_bytes());               // version
+    b.extend_from_slice(&0u32.to_le_bytes());               // total size (patched below)
+    b.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+    let mut off = 32 + 4 * chunks.len();
+    for (_, data) in chunks { b.extend_from_slice(&(off as u32).to_le_bytes()); off += 8 + data.as_ref().len(); }
+    for (fourcc, data) in chunks {
+        let data = data.as_ref();
+        b.extend_from_slice(&fourcc[..]);
+        b.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        b.extend_from_slice(data);
+    }
+    let len = b.len() as u32;
+    b[24..28].copy_from_slice(&len.to_le_bytes());
+    b
+}
+
+/// Little endian `d`
+pub(crate) fn dwords(d: &[u32]) -> Vec<u8> { d.iter().flat_map(|d| d.to_le_bytes()).collect() }
+
+/// `header` followed by each of `strings`, `\0` terminated
+pub(crate) fn with_strings(mut header: Vec<u8>, strings: &[&str]) -> Vec<u8> {
+    for s in strings { header.extend_from_slice(s.as_bytes()); header.push(0); }
+    header
+}